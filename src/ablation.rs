@@ -0,0 +1,124 @@
+// Switches off each evaluation term in turn, and reports how much it changes
+// static evaluations and fixed-depth searches of the test positions, as CSV.
+// Terms that change little are candidates for removal, and can then be
+// checked in a match with `--a-disable` / `--b-disable`.
+
+use std::io::Write;
+
+use crate::{
+    board::{
+        evaluation::{parameters::Parameters, to_centipawns},
+        Board,
+    },
+    chessmove::Move,
+    definitions::{Depth, WHITE},
+    searchinfo::SearchInfo,
+    testpositions::TEST_POSITIONS,
+    uci::OutputFormat,
+};
+
+const DEFAULT_DEPTH: i32 = 8;
+
+/// What the engine made of each test position with one set of parameters.
+struct Results {
+    /// Static evaluations in centipawns, from white's point of view.
+    evals: Vec<i32>,
+    best_moves: Vec<Move>,
+    nodes: u64,
+}
+
+fn measure(pos: &mut Board, params: &Parameters, depth: i32) -> Result<Results, String> {
+    pos.set_eval_params(params.clone());
+    let mut results = Results {
+        evals: Vec::new(),
+        best_moves: Vec::new(),
+        nodes: 0,
+    };
+    for position in &TEST_POSITIONS {
+        pos.set_from_fen(position.fen)
+            .map_err(|e| format!("{}: {e}", position.name))?;
+        let eval = to_centipawns(pos.evaluate().raw());
+        results
+            .evals
+            .push(if pos.turn() == WHITE { eval } else { -eval });
+        let mut info = SearchInfo {
+            depth: Depth::new(depth),
+            output_format: OutputFormat::Silent,
+            ..SearchInfo::default()
+        };
+        // each position is searched from scratch, so that its result doesn't depend on the ones before it.
+        pos.clear_tt();
+        pos.reset_tables();
+        let (_, best_move) = pos.search_position(&mut info);
+        results.best_moves.push(best_move);
+        results.nodes += info.nodes;
+    }
+    Ok(results)
+}
+
+/// Runs the ablation study, with the arguments after `ablation`:
+/// `--depth N` for the search depth, and `--terms A,B,...` to try only some terms.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut depth = DEFAULT_DEPTH;
+    let mut terms = Parameters::term_names();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("no value given after {flag}"))
+        };
+        match flag.as_str() {
+            "--depth" => {
+                depth = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --depth: {e}"))?;
+            }
+            "--terms" => {
+                let names = value()?;
+                let known = Parameters::term_names();
+                terms = names
+                    .split(',')
+                    .map(|name| {
+                        known
+                            .iter()
+                            .find(|&&known| known == name)
+                            .copied()
+                            .ok_or_else(|| format!("unknown evaluation term {name}"))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(format!("unknown flag {flag}")),
+        }
+    }
+
+    let mut pos = Board::new();
+    pos.reset_tables();
+    let baseline = measure(&mut pos, &Parameters::default(), depth)?;
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "term,mean_eval_change,max_eval_change,nodes,best_moves_changed")
+        .map_err(|e| e.to_string())?;
+    writeln!(out, "none,0,0,{},0", baseline.nodes).map_err(|e| e.to_string())?;
+    for term in terms {
+        let mut params = Parameters::default();
+        params.disable(term)?;
+        let results = measure(&mut pos, &params, depth)?;
+        let changes = results
+            .evals
+            .iter()
+            .zip(&baseline.evals)
+            .map(|(eval, base)| (eval - base).abs())
+            .collect::<Vec<_>>();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let mean = changes.iter().sum::<i32>() / changes.len() as i32;
+        let max = changes.iter().copied().max().unwrap_or(0);
+        let moves_changed = results
+            .best_moves
+            .iter()
+            .zip(&baseline.best_moves)
+            .filter(|(m, base)| m != base)
+            .count();
+        writeln!(out, "{term},{mean},{max},{},{moves_changed}", results.nodes)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,832 @@
+// A simple engine-vs-engine match runner, so that changes can be
+// sanity-checked without setting up an external tool like cutechess.
+
+use rand::prelude::SliceRandom;
+
+use crate::{
+    board::{
+        evaluation::{parameters::Parameters, to_centipawns},
+        Board,
+    },
+    definitions::{MAX_DEPTH, WHITE},
+    search,
+    searchinfo::{SearchInfo, Verdict},
+    uci::{self, OutputFormat},
+};
+
+/// The built-in openings, given as UCI move sequences from the starting position.
+const OPENINGS: [&str; 12] = [
+    "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6",
+    "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5",
+    "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6",
+    "e2e4 c7c5 b1c3 b8c6 g2g3 g7g6",
+    "e2e4 e7e6 d2d4 d7d5 b1c3 g8f6",
+    "e2e4 c7c6 d2d4 d7d5 e4e5 c8f5",
+    "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6",
+    "d2d4 d7d5 c2c4 c7c6 g1f3 g8f6 b1c3 d5c4",
+    "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6",
+    "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4",
+    "c2c4 e7e5 b1c3 g8f6 g1f3 b8c6",
+    "g1f3 d7d5 g2g3 g8f6 f1g2 e7e6",
+];
+
+/// A starting position for a pair of games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Opening {
+    /// A sequence of UCI moves from the starting position.
+    Moves(String),
+    /// A position in FEN.
+    Fen(String),
+}
+
+impl Opening {
+    pub fn builtin() -> Vec<Self> {
+        OPENINGS
+            .iter()
+            .map(|&moves| Self::Moves(moves.to_string()))
+            .collect()
+    }
+
+    /// Sets up `board` in this opening.
+    pub fn set_up(&self, board: &mut Board) -> Result<(), String> {
+        match self {
+            Self::Moves(moves) => {
+                board.set_startpos();
+                for san in moves.split_ascii_whitespace() {
+                    let m = board
+                        .parse_san(san)
+                        .map_err(|e| format!("bad opening \"{moves}\": {e}"))?;
+                    board.make_move(m);
+                    board.zero_height();
+                }
+            }
+            Self::Fen(fen) => board
+                .set_from_fen(fen)
+                .map_err(|e| format!("bad opening \"{fen}\": {e}"))?,
+        }
+        Ok(())
+    }
+}
+
+/// Reads a file of starting positions, one FEN or EPD per line.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn load_book(path: &str) -> Result<Vec<Opening>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {path}: {e}"))?;
+    let book = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            epd_to_fen(line)
+                .map(Opening::Fen)
+                .ok_or_else(|| format!("bad line in {path}: {line}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if book.is_empty() {
+        return Err(format!("{path} contains no positions"));
+    }
+    Ok(book)
+}
+
+/// Converts an EPD line to a FEN, dropping any operations. If the line is already
+/// a full FEN, the move counters are kept, otherwise they're set to "0 1".
+pub fn epd_to_fen(line: &str) -> Option<String> {
+    let fields = line.split_ascii_whitespace().collect::<Vec<_>>();
+    if fields.len() < 4 {
+        return None;
+    }
+    let counters = match fields.get(4..6) {
+        Some([halfmove, fullmove])
+            if halfmove.parse::<u8>().is_ok() && fullmove.parse::<usize>().is_ok() =>
+        {
+            format!("{halfmove} {fullmove}")
+        }
+        _ => "0 1".to_string(),
+    };
+    Some(format!("{} {counters}", fields[..4].join(" ")))
+}
+
+/// A game is won once the score has been at least `score` centipawns, in favour
+/// of the same side, for `plies` consecutive plies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinRule {
+    pub score: i32,
+    pub plies: usize,
+}
+
+/// A game is drawn once the score has been at most `score` centipawns
+/// for `plies` consecutive plies, after `min_ply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRule {
+    pub score: i32,
+    pub plies: usize,
+    pub min_ply: usize,
+}
+
+/// Rules for ending games early when the result is clear.
+#[derive(Debug, Clone)]
+pub struct Adjudication {
+    pub win: Option<WinRule>,
+    pub draw: Option<DrawRule>,
+    /// A game is drawn if it reaches this length.
+    pub max_plies: usize,
+}
+
+impl Adjudication {
+    /// Plays every game out to the end, or to `max_plies`.
+    pub const fn none(max_plies: usize) -> Self {
+        Self {
+            win: None,
+            draw: None,
+            max_plies,
+        }
+    }
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            win: Some(WinRule {
+                score: 1000,
+                plies: 6,
+            }),
+            draw: Some(DrawRule {
+                score: 10,
+                plies: 10,
+                min_ply: 80,
+            }),
+            max_plies: 400,
+        }
+    }
+}
+
+/// The settings for one side of a match.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerConfig {
+    pub eval_params: Parameters,
+    pub search_config: search::Config,
+    /// Options applied with [`uci::set_option`] before the match starts.
+    pub options: Vec<(String, String)>,
+}
+
+/// The hypotheses and error rates for a sequential probability ratio test.
+/// The test is between the Elo difference being `elo0` (H0) and being `elo1` (H1),
+/// with false positive rate `alpha` and false negative rate `beta`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// The outcome of checking an SPRT against a set of results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtOutcome {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    pub const fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// The log-likelihood ratio bounds at which H0 and H1 are accepted, respectively.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log-likelihood ratio of H1 against H0, using the normal approximation
+    /// to the trinomial (win/draw/loss) distribution of game results.
+    pub fn llr(&self, results: &MatchResults) -> f64 {
+        #![allow(clippy::cast_precision_loss)]
+        let Some((mu, variance)) = results.score_and_variance() else {
+            return 0.0;
+        };
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        results.games() as f64 * (s1 - s0) * mu.mul_add(2.0, -s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn outcome(&self, results: &MatchResults) -> SprtOutcome {
+        let llr = self.llr(results);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtOutcome::AcceptH0
+        } else if llr >= upper {
+            SprtOutcome::AcceptH1
+        } else {
+            SprtOutcome::Continue
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// The number of games to play, rounded up to a multiple of two.
+    /// If unset, each opening is played once with each colour.
+    /// When running an SPRT, this is the most games that will be played.
+    pub games: Option<usize>,
+    /// The depth to search to on each move, if `movetime` isn't set.
+    pub depth: i32,
+    /// The time to search for on each move, in milliseconds.
+    pub movetime: Option<u64>,
+    pub adjudication: Adjudication,
+    /// If set, the match stops as soon as the test accepts either hypothesis.
+    pub sprt: Option<Sprt>,
+    /// Each opening is played twice, with the players swapping colours.
+    pub openings: Vec<Opening>,
+    /// Play the openings in a random order, rather than the order given.
+    pub shuffle_openings: bool,
+    pub players: [PlayerConfig; 2],
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            games: None,
+            depth: 6,
+            movetime: None,
+            adjudication: Adjudication::default(),
+            sprt: None,
+            openings: Opening::builtin(),
+            shuffle_openings: false,
+            players: [PlayerConfig::default(), PlayerConfig::default()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+}
+
+impl GameResult {
+    const fn win_for(side: u8) -> Self {
+        if side == WHITE {
+            Self::WhiteWin
+        } else {
+            Self::BlackWin
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::WhiteWin => "1-0",
+            Self::BlackWin => "0-1",
+            Self::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// The results of a match, from the point of view of the first player.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchResults {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+}
+
+impl MatchResults {
+    fn record(&mut self, result: GameResult, first_player_side: u8) {
+        match result {
+            GameResult::Draw => self.draws += 1,
+            r if r == GameResult::win_for(first_player_side) => self.wins += 1,
+            _ => self.losses += 1,
+        }
+    }
+
+    pub const fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The mean score per game, and the variance of the score of a single game.
+    fn score_and_variance(&self) -> Option<(f64, f64)> {
+        #![allow(clippy::cast_precision_loss)]
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return None;
+        }
+        let (w, d, l) = (
+            self.wins as f64 / n,
+            self.draws as f64 / n,
+            self.losses as f64 / n,
+        );
+        let mu = w + d / 2.0;
+        let variance = l.mul_add(
+            mu.powi(2),
+            w.mul_add((1.0 - mu).powi(2), d * (0.5 - mu).powi(2)),
+        );
+        Some((mu, variance))
+    }
+
+    /// The Elo difference implied by the results, with the half-width
+    /// of its 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64) {
+        #![allow(clippy::cast_precision_loss)]
+        let Some((mu, variance)) = self.score_and_variance() else {
+            return (0.0, f64::INFINITY);
+        };
+        let n = self.games() as f64;
+        let std_error = (variance / n).sqrt();
+        let lower = elo_difference(1.96f64.mul_add(-std_error, mu).max(0.0));
+        let upper = elo_difference(1.96f64.mul_add(std_error, mu).min(1.0));
+        (elo_difference(mu), (upper - lower) / 2.0)
+    }
+}
+
+/// Converts an Elo difference into an expected score.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Converts an expected score into an Elo difference.
+/// Scores of exactly zero or one map to negative or positive infinity.
+fn elo_difference(score: f64) -> f64 {
+    let elo = -400.0 * (1.0 / score - 1.0).log10();
+    // adding zero normalises -0.0, so an even score doesn't print as "-0.0".
+    elo + 0.0
+}
+
+struct Player {
+    board: Board,
+    info: SearchInfo,
+}
+
+impl Player {
+    fn new(config: &PlayerConfig) -> Result<Self, String> {
+        let mut board = Board::new();
+        let mut info = SearchInfo::default();
+        let mut search_config = config.search_config.clone();
+        for (name, value) in &config.options {
+            uci::set_option(name, value, &mut info, &mut search_config)
+                .map_err(|e| format!("bad option {name}={value}: {e}"))?;
+        }
+        board.set_large_pages(info.large_pages);
+        if let Some(megabytes) = info.hash_megabytes {
+            let allocated = board.resize_tt(megabytes);
+            if allocated < megabytes {
+                eprintln!(
+                    "Warning: couldn't allocate {megabytes} MB of hash, using {allocated} MB"
+                );
+            } else if allocated > megabytes {
+                eprintln!(
+                    "Warning: {megabytes} MB of hash is too little for every table, using {allocated} MB"
+                );
+            }
+        }
+        info.output_format = OutputFormat::Silent;
+        board.set_search_config(search_config);
+        board.set_eval_params(config.eval_params.clone());
+        Ok(Self { board, info })
+    }
+}
+
+/// Plays out a match between the two players in `config`, printing the result of each game
+/// and a final summary, and returns the results from the point of view of the first player.
+pub fn run_match(config: &MatchConfig) -> Result<MatchResults, String> {
+    let mut players = vec![
+        Player::new(&config.players[0])?,
+        Player::new(&config.players[1])?,
+    ];
+    if config.openings.is_empty() {
+        return Err("no openings to play".to_string());
+    }
+    let mut openings = config.openings.clone();
+    if config.shuffle_openings {
+        openings.shuffle(&mut rand::thread_rng());
+    }
+    let mut results = MatchResults::default();
+    let pairs = config
+        .games
+        .map_or(openings.len(), |games| games.div_ceil(2));
+
+    for game in 0..pairs * 2 {
+        let opening = &openings[(game / 2) % openings.len()];
+        // the first player is white in even games, and black in odd ones.
+        let first_player_side = if game % 2 == 0 { WHITE } else { WHITE ^ 1 };
+        let (result, reason) = play_game(&mut players, opening, first_player_side, config)?;
+        results.record(result, first_player_side);
+        let (white, black) = if first_player_side == WHITE {
+            ("A", "B")
+        } else {
+            ("B", "A")
+        };
+        println!(
+            "game {} / {}: {white} vs {black} {} ({reason}), score of A vs B: {} - {} - {}",
+            game + 1,
+            pairs * 2,
+            result.as_str(),
+            results.wins,
+            results.losses,
+            results.draws,
+        );
+        if let Some(sprt) = &config.sprt {
+            let (lower, upper) = sprt.bounds();
+            println!("llr: {:.2} ({lower:.2}, {upper:.2})", sprt.llr(&results));
+            match sprt.outcome(&results) {
+                SprtOutcome::AcceptH0 => {
+                    println!("sprt: H0 accepted");
+                    break;
+                }
+                SprtOutcome::AcceptH1 => {
+                    println!("sprt: H1 accepted");
+                    break;
+                }
+                SprtOutcome::Continue => {}
+            }
+        }
+    }
+
+    let (elo, error) = results.elo();
+    println!(
+        "finished: {} - {} - {} (W - L - D), Elo difference: {elo:.1} +/- {error:.1}",
+        results.wins, results.losses, results.draws
+    );
+
+    Ok(results)
+}
+
+/// Plays a single game from `opening`, returning the result and the reason for it.
+fn play_game(
+    players: &mut [Player],
+    opening: &Opening,
+    first_player_side: u8,
+    config: &MatchConfig,
+) -> Result<(GameResult, &'static str), String> {
+    let adjudication = &config.adjudication;
+    let mut referee = Board::new();
+    referee.reset_tables();
+    opening.set_up(&mut referee)?;
+    for player in players.iter_mut() {
+        opening.set_up(&mut player.board)?;
+        player.board.clear_tt();
+        player.board.reset_tables();
+        player.info.verdicts.new_game();
+    }
+
+    let mut keys = vec![referee.hashkey()];
+    let mut win_streak = 0;
+    let mut draw_streak = 0;
+    let mut last_winner = WHITE;
+
+    loop {
+        if referee.get_first_legal_move().is_none() {
+            return Ok(if referee.in_check::<{ Board::US }>() {
+                (GameResult::win_for(referee.turn() ^ 1), "checkmate")
+            } else {
+                (GameResult::Draw, "stalemate")
+            });
+        }
+        if referee.fifty_move_counter() >= 100 {
+            return Ok((GameResult::Draw, "fifty-move rule"));
+        }
+        if keys.iter().filter(|&&k| k == referee.hashkey()).count() >= 3 {
+            return Ok((GameResult::Draw, "threefold repetition"));
+        }
+        if referee.is_drawn_by_material() {
+            return Ok((GameResult::Draw, "insufficient material"));
+        }
+        if referee.ply() >= adjudication.max_plies {
+            return Ok((GameResult::Draw, "maximum game length"));
+        }
+
+        let mover = usize::from((referee.turn() == WHITE) != (first_player_side == WHITE));
+        let player = &mut players[mover];
+        player.info.depth = config
+            .movetime
+            .map_or_else(|| config.depth.into(), |_| MAX_DEPTH);
+        player.info.time_set = config.movetime.is_some();
+        player.info.set_time_window(config.movetime.unwrap_or(0));
+        let (score, m) = player.board.search_position(&mut player.info);
+        let cp = to_centipawns(score.raw());
+
+        // the players' own verdicts come before the referee's adjudication.
+        match player.info.verdicts.last() {
+            Some(Verdict::Resign) => {
+                return Ok((GameResult::win_for(referee.turn() ^ 1), "resignation"));
+            }
+            Some(Verdict::AcceptDraw)
+                if players[mover ^ 1].info.verdicts.last() == Some(Verdict::AcceptDraw) =>
+            {
+                return Ok((GameResult::Draw, "draw agreed"));
+            }
+            _ => (),
+        }
+
+        // score is from white's point of view.
+        if let Some(rule) = adjudication.win {
+            if cp.abs() >= rule.score {
+                let winner = if cp > 0 { WHITE } else { WHITE ^ 1 };
+                win_streak = if winner == last_winner {
+                    win_streak + 1
+                } else {
+                    1
+                };
+                last_winner = winner;
+                if win_streak >= rule.plies {
+                    return Ok((GameResult::win_for(winner), "adjudicated win"));
+                }
+            } else {
+                win_streak = 0;
+            }
+        }
+        if let Some(rule) = adjudication.draw {
+            if referee.ply() >= rule.min_ply && cp.abs() <= rule.score {
+                draw_streak += 1;
+                if draw_streak >= rule.plies {
+                    return Ok((GameResult::Draw, "adjudicated draw"));
+                }
+            } else {
+                draw_streak = 0;
+            }
+        }
+
+        if m.is_null() || !referee.make_move(m) {
+            return Err(format!("engine played an illegal move {m}"));
+        }
+        referee.zero_height();
+        for player in players.iter_mut() {
+            player.board.make_move(m);
+            player.board.zero_height();
+        }
+        keys.push(referee.hashkey());
+    }
+}
+
+/// Parses the arguments following `match` on the command line, and runs the match.
+#[allow(clippy::too_many_lines)]
+pub fn match_cli(args: &[String]) -> Result<(), String> {
+    let mut config = MatchConfig::default();
+    // terms are disabled once all parameters are loaded, whatever the order of the flags.
+    let mut disabled_terms: [Vec<&str>; 2] = [Vec::new(), Vec::new()];
+    // and the SPRT's error rates are set once it is known there is one.
+    let mut error_rates: [Option<f64>; 2] = [None, None];
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("no value given after {flag}"))
+        };
+        match flag.as_str() {
+            "--games" => {
+                config.games = Some(value()?.parse().map_err(|e| format!("bad --games: {e}"))?);
+            }
+            "--depth" => {
+                config.depth = value()?.parse().map_err(|e| format!("bad --depth: {e}"))?;
+            }
+            "--movetime" => {
+                config.movetime = Some(
+                    value()?
+                        .parse()
+                        .map_err(|e| format!("bad --movetime: {e}"))?,
+                );
+            }
+            "--sprt" => {
+                let mut elo = || -> Result<f64, String> {
+                    value()?
+                        .parse()
+                        .map_err(|e| format!("bad --sprt bound: {e}"))
+                };
+                let (elo0, elo1) = (elo()?, elo()?);
+                config.sprt = Some(Sprt::new(elo0, elo1));
+            }
+            "--alpha" | "--beta" => {
+                let rate: f64 = value()?.parse().map_err(|e| format!("bad {flag}: {e}"))?;
+                if !(0.0 < rate && rate < 0.5) {
+                    return Err(format!("{flag} must be between 0 and 0.5, got {rate}"));
+                }
+                error_rates[usize::from(flag == "--beta")] = Some(rate);
+            }
+            "--win-adjudication" => {
+                let mut number = || -> Result<usize, String> {
+                    value()?.parse().map_err(|e| format!("bad {flag}: {e}"))
+                };
+                let score = i32::try_from(number()?).map_err(|e| format!("bad {flag}: {e}"))?;
+                config.adjudication.win = Some(WinRule {
+                    score,
+                    plies: number()?,
+                });
+            }
+            "--draw-adjudication" => {
+                let mut number = || -> Result<usize, String> {
+                    value()?.parse().map_err(|e| format!("bad {flag}: {e}"))
+                };
+                let score = i32::try_from(number()?).map_err(|e| format!("bad {flag}: {e}"))?;
+                let (plies, min_ply) = (number()?, number()?);
+                config.adjudication.draw = Some(DrawRule {
+                    score,
+                    plies,
+                    min_ply,
+                });
+            }
+            "--max-plies" => {
+                config.adjudication.max_plies = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --max-plies: {e}"))?;
+            }
+            "--no-adjudication" => {
+                config.adjudication = Adjudication::none(config.adjudication.max_plies);
+            }
+            "--book" => config.openings = load_book(value()?)?,
+            "--shuffle" => config.shuffle_openings = true,
+            "--a-params" | "--b-params" => {
+                let player = usize::from(flag == "--b-params");
+                let path = value()?;
+                config.players[player].eval_params = Parameters::from_file(path)
+                    .map_err(|e| format!("couldn't load parameters from {path}: {e}"))?;
+            }
+            "--a-search-params" | "--b-search-params" => {
+                let player = usize::from(flag == "--b-search-params");
+                let path = value()?;
+                config.players[player].search_config = search::Config::from_file(path)
+                    .map_err(|e| format!("couldn't load search parameters from {path}: {e}"))?;
+            }
+            "--a-option" | "--b-option" => {
+                let player = usize::from(flag == "--b-option");
+                let option = value()?;
+                let (name, value) = option
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected NAME=VALUE after {flag}, got {option}"))?;
+                config.players[player]
+                    .options
+                    .push((name.to_string(), value.to_string()));
+            }
+            "--a-disable" | "--b-disable" => {
+                let player = usize::from(flag == "--b-disable");
+                disabled_terms[player].push(value()?);
+            }
+            unknown => return Err(format!("unknown argument to match: {unknown}")),
+        }
+    }
+    for (player, terms) in config.players.iter_mut().zip(disabled_terms) {
+        for term in terms {
+            player.eval_params.disable(term)?;
+        }
+    }
+    match (&mut config.sprt, error_rates) {
+        (Some(sprt), [alpha, beta]) => {
+            sprt.alpha = alpha.unwrap_or(sprt.alpha);
+            sprt.beta = beta.unwrap_or(sprt.beta);
+        }
+        (None, [None, None]) => (),
+        (None, _) => return Err("--alpha and --beta need --sprt".to_string()),
+    }
+    run_match(&config).map(|_| ())
+}
+
+mod tests {
+    #[test]
+    fn openings_are_legal() {
+        use super::{Opening, OPENINGS};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        for opening in OPENINGS {
+            board.set_startpos();
+            for san in opening.split_ascii_whitespace() {
+                let m = board
+                    .parse_san(san)
+                    .unwrap_or_else(|e| panic!("{opening}: {e}"));
+                assert!(board.make_move(m), "{opening}: {san} is illegal");
+                board.zero_height();
+            }
+        }
+        for opening in Opening::builtin() {
+            opening.set_up(&mut board).unwrap();
+        }
+    }
+
+    #[test]
+    fn epd_lines_become_fens() {
+        use super::epd_to_fen;
+
+        assert_eq!(
+            epd_to_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 id \"e4\";")
+                .as_deref(),
+            Some("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+        );
+        assert_eq!(
+            epd_to_fen("8/8/8/8/8/8/1k6/R2K4 b - - 12 40").as_deref(),
+            Some("8/8/8/8/8/8/1k6/R2K4 b - - 12 40")
+        );
+        assert_eq!(epd_to_fen("8/8/8/8 w"), None);
+    }
+
+    #[test]
+    fn sprt_decisions() {
+        use super::{MatchResults, Sprt, SprtOutcome};
+
+        let sprt = Sprt::new(0.0, 10.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3);
+        assert!((upper - 2.944).abs() < 1e-3);
+
+        assert!(sprt.llr(&MatchResults::default()).abs() < f64::EPSILON);
+        let few = MatchResults {
+            wins: 3,
+            draws: 4,
+            losses: 2,
+        };
+        assert_eq!(sprt.outcome(&few), SprtOutcome::Continue);
+        let strong = MatchResults {
+            wins: 400,
+            draws: 300,
+            losses: 300,
+        };
+        assert_eq!(sprt.outcome(&strong), SprtOutcome::AcceptH1);
+        let weak = MatchResults {
+            wins: 300,
+            draws: 300,
+            losses: 400,
+        };
+        assert_eq!(sprt.outcome(&weak), SprtOutcome::AcceptH0);
+    }
+
+    #[test]
+    fn elo_estimates() {
+        use super::MatchResults;
+
+        let even = MatchResults {
+            wins: 10,
+            draws: 20,
+            losses: 10,
+        };
+        let (elo, error) = even.elo();
+        assert!(elo.abs() < 1e-9);
+        assert!(error > 0.0 && error.is_finite());
+
+        let winning = MatchResults {
+            wins: 30,
+            draws: 10,
+            losses: 10,
+        };
+        let (elo, _) = winning.elo();
+        // a 70% score is about +147 Elo.
+        assert!((elo - 147.2).abs() < 0.5, "elo: {elo}");
+
+        let more_games = MatchResults {
+            wins: 300,
+            draws: 100,
+            losses: 100,
+        };
+        assert!(more_games.elo().1 < winning.elo().1);
+    }
+
+    #[test]
+    fn error_rates_need_an_sprt() {
+        use super::match_cli;
+
+        let args = ["--games", "2", "--alpha", "0.1"].map(String::from);
+        assert_eq!(match_cli(&args), Err("--alpha and --beta need --sprt".to_string()));
+        let args = ["--beta", "0.7", "--sprt", "0", "5"].map(String::from);
+        assert!(match_cli(&args).unwrap_err().starts_with("--beta must be between"));
+    }
+
+    #[test]
+    fn verdicts_need_a_sustained_score() {
+        use crate::searchinfo::{SearchInfo, Verdict};
+
+        let mut verdicts = SearchInfo::default().verdicts;
+        // both rules are off by default.
+        assert!((0..10).all(|_| verdicts.update(-5000, 60) == Verdict::Play));
+        assert!((0..10).all(|_| verdicts.update(0, 60) == Verdict::Play));
+
+        verdicts.new_game();
+        (verdicts.resign_moves, verdicts.draw_moves) = (3, 2);
+        assert_eq!(verdicts.update(-1200, 20), Verdict::Play);
+        assert_eq!(verdicts.update(-1200, 21), Verdict::Play);
+        // a single better score breaks the streak.
+        assert_eq!(verdicts.update(-300, 22), Verdict::Play);
+        assert_eq!(verdicts.update(-1000, 23), Verdict::Play);
+        assert_eq!(verdicts.update(-1000, 24), Verdict::Play);
+        assert_eq!(verdicts.update(-1000, 25), Verdict::Resign);
+        assert_eq!(verdicts.last(), Some(Verdict::Resign));
+
+        // draws are only accepted late enough in the game.
+        verdicts.new_game();
+        assert_eq!(verdicts.last(), None);
+        assert_eq!(verdicts.update(5, 38), Verdict::Play);
+        assert_eq!(verdicts.update(-5, 39), Verdict::Play);
+        assert_eq!(verdicts.update(5, 40), Verdict::Play);
+        assert_eq!(verdicts.update(0, 41), Verdict::AcceptDraw);
+    }
+}
@@ -0,0 +1,412 @@
+// A binary protocol for programs that drive the engine at high volume, such as
+// data generation pipelines, where formatting and parsing UCI text for every
+// position would cost more than a short search. It runs over stdin and stdout,
+// or over a Unix socket with `--socket PATH`.
+//
+// Every message in both directions is a frame: the length of the payload as a
+// little-endian u32, then the payload. Integers are little-endian throughout.
+//
+// Requests start with a command byte:
+// - 0x01 position: a packed position (see `PackedPosition`).
+// - 0x02 search: the node limit as a u64 and the depth limit as a u8, where 0 is no limit.
+//   At least one of the two must be set.
+// - 0x03 newgame: clears the transposition table.
+// - 0x04 quit: ends the session, with no reply.
+//
+// Replies start with a status byte, 0 for success and 1 for an error, which is
+// followed by a UTF-8 message. A successful search is followed by a packed
+// `SearchResult`, and other successful commands by nothing.
+
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::{
+    board::{evaluation::to_centipawns, Board},
+    chessmove::Move,
+    definitions::{Depth, Score, MAX_DEPTH, WHITE},
+    searchinfo::SearchInfo,
+    uci::OutputFormat,
+};
+
+const POSITION: u8 = 0x01;
+const SEARCH: u8 = 0x02;
+const NEW_GAME: u8 = 0x03;
+const QUIT: u8 = 0x04;
+
+const OK: u8 = 0x00;
+const ERROR: u8 = 0x01;
+
+/// The largest request payload, which stops a corrupt length from allocating without bound.
+const MAX_FRAME_LENGTH: u32 = 1 << 16;
+
+/// A position, packed into 38 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedPosition {
+    /// The pieces, four bits to a square from a1 to h8, the lower half of
+    /// each byte first, in the engine's numbering: 0 for empty, 1 to 6 for the
+    /// white pawn to king, and 7 to 12 for the black pawn to king.
+    pub squares: [u8; 32],
+    /// 0 for white, 1 for black.
+    pub side: u8,
+    /// 1 for white kingside, 2 for white queenside, 4 for black kingside, and 8 for black queenside.
+    pub castling: u8,
+    /// The en passant square, from 0 for a1 to 63 for h8, or 64 for none.
+    pub ep_sq: u8,
+    pub halfmove: u8,
+    pub fullmove: u16,
+}
+
+impl PackedPosition {
+    pub const SIZE: usize = 38;
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; Self::SIZE] = bytes.try_into().ok()?;
+        Some(Self {
+            squares: bytes[..32].try_into().ok()?,
+            side: bytes[32],
+            castling: bytes[33],
+            ep_sq: bytes[34],
+            halfmove: bytes[35],
+            fullmove: u16::from_le_bytes([bytes[36], bytes[37]]),
+        })
+    }
+
+    #[allow(dead_code)] // the driver's side of the encoding.
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..32].copy_from_slice(&self.squares);
+        bytes[32] = self.side;
+        bytes[33] = self.castling;
+        bytes[34] = self.ep_sq;
+        bytes[35] = self.halfmove;
+        bytes[36..].copy_from_slice(&self.fullmove.to_le_bytes());
+        bytes
+    }
+
+    /// Packs the piece on each square, from a1 to h8.
+    #[allow(dead_code)] // the driver's side of the encoding.
+    pub fn pack_squares(pieces: &[u8; 64]) -> [u8; 32] {
+        let mut squares = [0; 32];
+        for (packed, pair) in squares.iter_mut().zip(pieces.chunks_exact(2)) {
+            *packed = pair[0] | pair[1] << 4;
+        }
+        squares
+    }
+
+    fn set_up(&self, board: &mut Board) -> Result<(), String> {
+        let mut pieces = [0; 64];
+        for (pair, &packed) in pieces.chunks_exact_mut(2).zip(&self.squares) {
+            pair[0] = packed & 0xF;
+            pair[1] = packed >> 4;
+        }
+        board.set_from_parts(
+            &pieces,
+            self.side,
+            self.castling,
+            self.ep_sq,
+            self.halfmove.into(),
+            self.fullmove.into(),
+        )
+    }
+}
+
+/// The result of a search, packed into 16 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The source square, the target square shifted left by 6, and the piece type
+    /// promoted to (2 for a knight to 5 for a queen) shifted left by 12, or 0 if
+    /// there is no legal move.
+    pub best_move: u16,
+    /// The score in centipawns, for the side to move.
+    pub score: i32,
+    /// The number of moves to mate, negative if the side to move is mated, or 0.
+    pub mate: i16,
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    pub const SIZE: usize = 16;
+
+    fn new(m: Move, score: Score, nodes: u64) -> Self {
+        let best_move = if m.is_null() {
+            0
+        } else {
+            u16::from(m.from()) | u16::from(m.to()) << 6 | u16::from(m.promotion()) << 12
+        };
+        let (score, mate) = if score.is_mate() {
+            let moves = i16::try_from((score.plies_to_mate() + 1) / 2).unwrap_or(i16::MAX);
+            (0, if score > Score::DRAW { moves } else { -moves })
+        } else {
+            (to_centipawns(score.raw()), 0)
+        };
+        Self { best_move, score, mate, nodes }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..2].copy_from_slice(&self.best_move.to_le_bytes());
+        bytes[2..6].copy_from_slice(&self.score.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.mate.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.nodes.to_le_bytes());
+        bytes
+    }
+
+    #[allow(dead_code)] // the driver's side of the encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; Self::SIZE] = bytes.try_into().ok()?;
+        Some(Self {
+            best_move: u16::from_le_bytes([bytes[0], bytes[1]]),
+            score: i32::from_le_bytes(bytes[2..6].try_into().ok()?),
+            mate: i16::from_le_bytes([bytes[6], bytes[7]]),
+            nodes: u64::from_le_bytes(bytes[8..].try_into().ok()?),
+        })
+    }
+}
+
+/// Reads a frame, returning `None` at the end of the input.
+fn read_frame(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match input.read_exact(&mut length) {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_le_bytes(length);
+    if length > MAX_FRAME_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {length} bytes is too long"),
+        ));
+    }
+    let mut payload = vec![0; length as usize];
+    input.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+fn write_frame(output: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too long"))?;
+    output.write_all(&length.to_le_bytes())?;
+    output.write_all(payload)?;
+    output.flush()
+}
+
+/// Carries out a request other than quit, returning the payload of a successful reply.
+fn handle(board: &mut Board, request: &[u8]) -> Result<Vec<u8>, String> {
+    match request {
+        [POSITION, packed @ ..] => {
+            let position = PackedPosition::from_bytes(packed).ok_or_else(|| {
+                format!(
+                    "a position is {} bytes, not {}",
+                    PackedPosition::SIZE,
+                    packed.len()
+                )
+            })?;
+            position.set_up(board)?;
+            Ok(Vec::new())
+        }
+        [SEARCH, limits @ ..] => {
+            let [n0, n1, n2, n3, n4, n5, n6, n7, depth] = *limits else {
+                return Err(format!("search limits are 9 bytes, not {}", limits.len()));
+            };
+            let nodes = u64::from_le_bytes([n0, n1, n2, n3, n4, n5, n6, n7]);
+            if nodes == 0 && depth == 0 {
+                return Err("a search needs a node or depth limit".to_string());
+            }
+            let mut info = SearchInfo {
+                depth: if depth == 0 { MAX_DEPTH } else { Depth::new(depth.into()) },
+                node_limit: nodes,
+                output_format: OutputFormat::Silent,
+                ..SearchInfo::default()
+            };
+            let (score, m) = board.search_position(&mut info);
+            // the search scores from white's side, and the protocol from the side to move.
+            let score = if board.turn() == WHITE { score } else { -score };
+            Ok(SearchResult::new(m, score, info.nodes).to_bytes().to_vec())
+        }
+        [NEW_GAME] => {
+            board.clear_tt();
+            Ok(Vec::new())
+        }
+        [command, ..] => Err(format!("unknown command {command:#04x}")),
+        [] => Err("empty request".to_string()),
+    }
+}
+
+/// Answers requests from `input` on `output` until quit or the end of the input.
+fn serve(board: &mut Board, mut input: impl Read, mut output: impl Write) -> io::Result<()> {
+    while let Some(request) = read_frame(&mut input)? {
+        if request == [QUIT] {
+            break;
+        }
+        let reply = match handle(board, &request) {
+            Ok(payload) => [&[OK], payload.as_slice()].concat(),
+            Err(e) => [&[ERROR], e.as_bytes()].concat(),
+        };
+        write_frame(&mut output, &reply)?;
+    }
+    Ok(())
+}
+
+/// Runs the binary protocol, with the arguments after `binary`: over stdin
+/// and stdout, or with `--socket PATH`, over each connection to a Unix socket in turn.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut board = Board::new();
+    board.reset_tables();
+    board.set_startpos();
+    match args {
+        [] => serve(
+            &mut board,
+            BufReader::new(io::stdin().lock()),
+            BufWriter::new(io::stdout().lock()),
+        )
+        .map_err(|e| e.to_string()),
+        #[cfg(unix)]
+        [flag, path] if flag == "--socket" => {
+            let listener = std::os::unix::net::UnixListener::bind(path)
+                .map_err(|e| format!("couldn't bind {path}: {e}"))?;
+            for stream in listener.incoming() {
+                let stream = stream.map_err(|e| e.to_string())?;
+                let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+                if let Err(e) = serve(&mut board, reader, BufWriter::new(stream)) {
+                    eprintln!("Warning: connection closed: {e}");
+                }
+            }
+            Ok(())
+        }
+        _ => Err("usage: binary [--socket PATH]".to_string()),
+    }
+}
+
+mod tests {
+    #[test]
+    fn positions_are_packed() {
+        use super::PackedPosition;
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        let source = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3").unwrap();
+        let pieces = std::array::from_fn(|sq| source.piece_at(sq.try_into().unwrap()));
+        let position = PackedPosition {
+            squares: PackedPosition::pack_squares(&pieces),
+            side: 1,
+            castling: 0b1001,
+            ep_sq: 20,
+            halfmove: 0,
+            fullmove: 3,
+        };
+        assert_eq!(PackedPosition::from_bytes(&position.to_bytes()), Some(position));
+        position.set_up(&mut board).unwrap();
+        assert_eq!(board.fen(), source.fen());
+        assert_eq!(board.hashkey(), source.hashkey());
+
+        let no_kings = PackedPosition { squares: [0; 32], ..position };
+        assert!(no_kings.set_up(&mut board).is_err());
+    }
+
+    #[test]
+    fn requests_are_answered() {
+        use super::{serve, PackedPosition, SearchResult, NEW_GAME, POSITION, QUIT, SEARCH};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        let frame = |payload: &[u8]| {
+            let mut frame = u32::try_from(payload.len()).unwrap().to_le_bytes().to_vec();
+            frame.extend_from_slice(payload);
+            frame
+        };
+        // white to move and mate in one with Ra8.
+        let mut pieces = [0; 64];
+        pieces[0] = 4; // white rook on a1.
+        pieces[6] = 6; // white king on g1.
+        pieces[62] = 12; // black king on g8.
+        pieces[53] = 7; // black pawns on f7,
+        pieces[54] = 7; // g7,
+        pieces[55] = 7; // and h7.
+        let position = PackedPosition {
+            squares: PackedPosition::pack_squares(&pieces),
+            side: 0,
+            castling: 0,
+            ep_sq: 64,
+            halfmove: 0,
+            fullmove: 1,
+        };
+        let mut input = Vec::new();
+        input.extend(frame(&[[POSITION].as_slice(), &position.to_bytes()].concat()));
+        input.extend(frame(&[SEARCH, 0, 0, 0, 0, 0, 0, 0, 0, 3]));
+        input.extend(frame(&[SEARCH, 0, 0, 0, 0, 0, 0, 0, 0, 0]));
+        input.extend(frame(&[NEW_GAME]));
+        input.extend(frame(&[QUIT]));
+        input.extend(frame(&[NEW_GAME]));
+        let mut output = Vec::new();
+        serve(&mut board, input.as_slice(), &mut output).unwrap();
+
+        let mut replies = Vec::new();
+        let mut rest = output.as_slice();
+        while let [l0, l1, l2, l3, tail @ ..] = rest {
+            let length = u32::from_le_bytes([*l0, *l1, *l2, *l3]) as usize;
+            replies.push(&tail[..length]);
+            rest = &tail[length..];
+        }
+        // nothing is answered after quit.
+        assert_eq!(replies.len(), 4);
+        assert_eq!(replies[0], [0]);
+        assert_eq!(replies[1][0], 0);
+        let result = SearchResult::from_bytes(&replies[1][1..]).unwrap();
+        // a1 to a8.
+        assert_eq!(result.best_move, 56 << 6);
+        assert_eq!(result.mate, 1);
+        assert!(result.nodes > 0);
+        assert_eq!(replies[2][0], 1);
+        assert_eq!(replies[3], [0]);
+    }
+
+    #[test]
+    fn scores_are_from_the_side_to_move() {
+        use super::{serve, PackedPosition, SearchResult, POSITION, QUIT, SEARCH};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        let frame = |payload: &[u8]| {
+            let mut frame = u32::try_from(payload.len()).unwrap().to_le_bytes().to_vec();
+            frame.extend_from_slice(payload);
+            frame
+        };
+        // black to move and mate in one with Ra1.
+        let mut pieces = [0; 64];
+        pieces[56] = 10; // black rook on a8.
+        pieces[62] = 12; // black king on g8.
+        pieces[6] = 6; // white king on g1.
+        pieces[13] = 1; // white pawns on f2,
+        pieces[14] = 1; // g2,
+        pieces[15] = 1; // and h2.
+        let position = PackedPosition {
+            squares: PackedPosition::pack_squares(&pieces),
+            side: 1,
+            castling: 0,
+            ep_sq: 64,
+            halfmove: 0,
+            fullmove: 1,
+        };
+        let mut input = Vec::new();
+        input.extend(frame(&[[POSITION].as_slice(), &position.to_bytes()].concat()));
+        input.extend(frame(&[SEARCH, 0, 0, 0, 0, 0, 0, 0, 0, 3]));
+        input.extend(frame(&[QUIT]));
+        let mut output = Vec::new();
+        serve(&mut board, input.as_slice(), &mut output).unwrap();
+
+        // the search reply follows the four-byte length of the position reply, its
+        // payload, the search reply's length and its status byte.
+        let result = SearchResult::from_bytes(&output[10..]).unwrap();
+        // a8 to a1.
+        assert_eq!(result.best_move, 56);
+        assert_eq!(result.mate, 1);
+        let decoded = SearchResult::from_bytes(&result.to_bytes()).unwrap();
+        assert_eq!(decoded, result);
+    }
+}
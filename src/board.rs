@@ -6,58 +6,139 @@
 
 pub mod evaluation;
 pub mod movegen;
+pub mod variant;
 mod history;
+mod notation;
 
 use std::{
     collections::HashSet,
     fmt::{Debug, Display, Formatter, Write},
+    sync::Arc,
 };
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::{
     board::{
-        evaluation::ONE_PAWN,
         movegen::{
             bitboards::{
                 self, north_east_one, north_west_one, south_east_one, south_west_one, BitLoop,
-                BB_NONE,
+                BB_NONE, BB_RANK_1, BB_RANK_8,
             },
             MoveList,
         },
     },
     chessmove::Move,
     definitions::{
-        colour_of, square_name, type_of, Colour, Depth, File,
+        colour_of, type_of, Colour, Depth, File,
         Rank::{self, RANK_3, RANK_6},
+        Score,
         Square::{A1, A8, C1, C8, D1, D8, F1, F8, G1, G8, H1, H8, NO_SQUARE},
-        Undo, BB, BISHOP, BK, BKCA, BLACK, BN, BOARD_N_SQUARES, BP, BQ, BQCA, BR, INFINITY, KING,
-        KNIGHT, MAX_DEPTH, PIECE_EMPTY, ROOK, WB, WHITE, WK, WKCA, WN, WP, WQ, WQCA, WR,
+        Undo, BB, BISHOP, BK, BKCA, BLACK, BN, BOARD_N_SQUARES, BP, BQ, BQCA, BR, KING, KNIGHT,
+        MAX_DEPTH, PAWN, PIECE_EMPTY, ROOK, WB, WHITE, WK, WKCA, WN, WP, WQ, WQCA, WR,
     },
-    errors::{FenParseError, MoveParseError, PositionValidityError},
+    errors::{FenParseError, MoveParseError, ValidationError},
     lookups::{
         filerank_to_square, piece_char, rank, PIECE_BIG, PIECE_MAJ, PIECE_MIN, PROMO_CHAR_LOOKUP,
         SQUARE_NAMES,
     },
     macros,
-    makemove::{hash_castling, hash_ep, hash_piece, hash_side, CASTLE_PERM_MASKS},
+    makemove::{hash_castling, hash_checks, hash_ep, hash_piece, hash_side, CASTLE_PERM_MASKS},
     piecelist::PieceList,
     piecesquaretable::pst_value,
     search,
-    searchinfo::SearchInfo,
-    transpositiontable::{DefaultTT, HFlag, ProbeResult},
-    uci::format_score,
+    searchinfo::{RootMove, SearchControl, SearchInfo},
+    transpositiontable::{
+        DefaultTT, HFlag, ProbeResult, TTHit, DEFAULT_TABLE_MEGABYTES, MEGABYTE,
+    },
+    uci::{self, BlunderCheck, SearchReport},
     validate::{piece_type_valid, piece_valid, side_valid, square_on_board}, historytable::{DoubleHistoryTable, HistoryTable, MoveTable},
 };
 
-use self::{evaluation::score::S, movegen::bitboards::BitBoard};
+use self::{
+    evaluation::{score::S, PIECE_PHASE, TOTAL_PHASE},
+    movegen::bitboards::BitBoard,
+    variant::Variant,
+};
+
+/// The fifty-move counter at and beyond which TT scores aren't trusted.
+const FIFTY_MOVE_TT_LIMIT: u16 = 90;
+/// What history scores are divided by between searches of the same game.
+const CONTINUATION_HISTORY_AGE: i32 = 2;
+/// What history scores are divided by before a search of any other position.
+const HISTORY_AGE: i32 = 4;
+
+/// The memory taken up by the tables other than the TT, once they are in use.
+const FIXED_TABLE_BYTES: usize =
+    HistoryTable::SIZE_IN_BYTES + DoubleHistoryTable::SIZE_IN_BYTES + MoveTable::SIZE_IN_BYTES;
+/// The Hash setting that the tables fit in as they are before it is set.
+pub const DEFAULT_HASH_MEGABYTES: usize =
+    DEFAULT_TABLE_MEGABYTES + FIXED_TABLE_BYTES.div_ceil(MEGABYTE);
+
+/// The memory, in bytes, allocated for each of the engine's tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub tt: usize,
+    pub history: usize,
+    pub followup_history: usize,
+    pub counter_moves: usize,
+    /// The board itself, with its killer tables and the moves of the game so far.
+    pub board: usize,
+}
+
+impl MemoryUsage {
+    pub const fn total(&self) -> usize {
+        self.tt + self.history + self.followup_history + self.counter_moves + self.board
+    }
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kilobytes = |bytes: usize| bytes.div_ceil(1024);
+        write!(
+            f,
+            "tt {} KB history {} KB followup {} KB countermoves {} KB board {} KB total {} KB",
+            kilobytes(self.tt),
+            kilobytes(self.history),
+            kilobytes(self.followup_history),
+            kilobytes(self.counter_moves),
+            kilobytes(self.board),
+            kilobytes(self.total())
+        )
+    }
+}
+
+/// Everything about a position that setting up a new one overwrites,
+/// kept so that a position which fails to set up can be put back.
+struct SavedPosition {
+    pieces: BitBoard,
+    piece_array: [u8; 64],
+    side: u8,
+    ep_sq: u8,
+    fifty_move_counter: u16,
+    height: usize,
+    ply: usize,
+    key: u64,
+    big_piece_counts: [u8; 2],
+    major_piece_counts: [u8; 2],
+    minor_piece_counts: [u8; 2],
+    material: [S; 2],
+    phase: i32,
+    castle_perm: u8,
+    checks: [u8; 2],
+    history: Vec<Undo>,
+    piece_lists: [PieceList; 13],
+    pst_vals: S,
+}
 
 pub struct Board {
     /// The bitboards of all the pieces on the board.
     pieces: BitBoard,
-    /// An array to accelerate piece_at().
+    /// An array to accelerate `piece_at()`.
     piece_array: [u8; 64],
     side: u8,
     ep_sq: u8,
-    fifty_move_counter: u8,
+    fifty_move_counter: u16,
     height: usize,
     ply: usize,
     key: u64,
@@ -65,15 +146,28 @@ pub struct Board {
     major_piece_counts: [u8; 2],
     minor_piece_counts: [u8; 2],
     material: [S; 2],
+    /// The phase of the game, as returned by `phase()`.
+    phase: i32,
     castle_perm: u8,
+    /// The rules the game is played under.
+    variant: Variant,
+    /// The number of checks each side has given, only counted in three-check.
+    checks: [u8; 2],
+    /// One entry per move made, growing as needed, so there is no limit on game length.
     history: Vec<Undo>,
-    repetition_cache: HashSet<u64>,
     piece_lists: [PieceList; 13],
 
     principal_variation: Vec<Move>,
 
     history_table: HistoryTable,
     killer_move_table: [[Move; 2]; MAX_DEPTH.ply_to_horizon()],
+    /// The last quiet move at each height that cut off with a mate score.
+    mate_killer_table: [Move; MAX_DEPTH.ply_to_horizon()],
+    /// The move excluded from the search at each height, used
+    /// when verifying that a TT move is singular.
+    pub excluded: [Move; MAX_DEPTH.ply_to_horizon()],
+    /// The number of double extensions on the current line.
+    pub double_extensions: usize,
     counter_move_table: MoveTable,
     followup_history: DoubleHistoryTable,
     tt: DefaultTT,
@@ -85,6 +179,9 @@ pub struct Board {
     pub lmr_table: search::LMRTable,
 
     movegen_ready: bool,
+    /// Whether the position is the last one set up plus two moves, so the
+    /// next search can keep more of what the one before it learned.
+    continues_game: bool,
 }
 
 impl Debug for Board {
@@ -101,6 +198,7 @@ impl Debug for Board {
             .field("major_piece_counts", &self.major_piece_counts)
             .field("minor_piece_counts", &self.minor_piece_counts)
             .field("material", &self.material)
+            .field("phase", &self.phase)
             .field("castle_perm", &self.castle_perm)
             .field("pst_vals", &self.pst_vals)
             .finish_non_exhaustive()
@@ -110,9 +208,9 @@ impl Debug for Board {
 impl PartialEq for Board {
     fn eq(&self, other: &Self) -> bool {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
         #[cfg(debug_assertions)]
-        other.check_validity().unwrap();
+        other.validate().unwrap();
         self.pieces == other.pieces
             && self.side == other.side
             && self.ep_sq == other.ep_sq
@@ -140,13 +238,18 @@ impl Board {
             major_piece_counts: [0; 2],
             minor_piece_counts: [0; 2],
             material: [S(0, 0); 2],
+            phase: TOTAL_PHASE,
             castle_perm: 0,
+            variant: Variant::Standard,
+            checks: [0; 2],
             history: Vec::new(),
-            repetition_cache: HashSet::new(),
             piece_lists: [PieceList::new(); 13],
             principal_variation: Vec::new(),
             history_table: HistoryTable::new(),
             killer_move_table: [[Move::NULL; 2]; MAX_DEPTH.ply_to_horizon()],
+            mate_killer_table: [Move::NULL; MAX_DEPTH.ply_to_horizon()],
+            excluded: [Move::NULL; MAX_DEPTH.ply_to_horizon()],
+            double_extensions: 0,
             counter_move_table: MoveTable::new(),
             followup_history: DoubleHistoryTable::new(),
             pst_vals: S(0, 0),
@@ -155,6 +258,7 @@ impl Board {
             search_params: search::Config::default(),
             lmr_table: search::LMRTable::new(&search::Config::default()),
             movegen_ready: false,
+            continues_game: false,
         };
         out.reset();
         out
@@ -165,21 +269,66 @@ impl Board {
         self.lmr_table = search::LMRTable::new(&self.search_params);
     }
 
-    pub fn tt_store(&mut self, best_move: Move, score: i32, flag: HFlag, depth: Depth) {
+    pub fn tt_store(&mut self, best_move: Move, score: Score, flag: HFlag, depth: Depth) {
         self.tt
             .store(self.key, self.height, best_move, score, flag, depth);
     }
 
-    pub fn tt_probe(&mut self, alpha: i32, beta: i32, depth: Depth) -> ProbeResult {
+    pub fn tt_probe(&self, alpha: Score, beta: Score, depth: Depth) -> ProbeResult {
+        // the hash key doesn't include the fifty-move counter, so close to
+        // a fifty-move draw, a stored score may be from a transposition that
+        // had many more moves left to make progress in. In this case, we only
+        // use the TT for move ordering, by asking for an unreachable depth.
+        let depth = if self.fifty_move_counter >= FIFTY_MOVE_TT_LIMIT { MAX_DEPTH } else { depth };
         self.tt.probe(self.key, self.height, alpha, beta, depth)
     }
 
+    pub fn tt_probe_for_singularity(&self) -> Option<TTHit> {
+        self.tt.probe_for_singularity(self.key, self.height)
+    }
+
     /// Nuke the transposition table.
     /// This wipes all entries in the table, don't call it during a search.
     pub fn clear_tt(&mut self) {
         self.tt.clear();
     }
 
+    /// Replaces the transposition table with an empty one, sized so that it and the
+    /// other tables fit in about `megabytes` in total, as the Hash option promises.
+    /// The other tables have a fixed size, so they come out of the budget first, and
+    /// the TT gets what is left, but at least a megabyte. It may get less if that
+    /// much memory isn't available. Returns the megabytes the tables use in total.
+    pub fn resize_tt(&mut self, megabytes: usize) -> usize {
+        let fixed = FIXED_TABLE_BYTES.div_ceil(MEGABYTE);
+        match self.tt.resize(megabytes.saturating_sub(fixed)) {
+            0 => 0,
+            allocated => allocated + fixed,
+        }
+    }
+
+    /// The memory each of the tables takes up.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            tt: self.tt.size_in_bytes(),
+            history: self.history_table.size_in_bytes(),
+            followup_history: self.followup_history.size_in_bytes(),
+            counter_moves: self.counter_move_table.size_in_bytes(),
+            board: std::mem::size_of::<Self>()
+                + self.history.capacity() * std::mem::size_of::<Undo>()
+                + self.principal_variation.capacity() * std::mem::size_of::<Move>(),
+        }
+    }
+
+    /// Sets whether the transposition table is backed by huge pages, where the OS
+    /// supports them. If the table is already allocated, it is allocated again, empty.
+    pub fn set_large_pages(&mut self, large_pages: bool) {
+        self.tt.set_large_pages(large_pages);
+    }
+
+    pub const fn tt_size_in_bytes(&self) -> usize {
+        self.tt.size_in_bytes()
+    }
+
     pub fn king_sq(&self, side: u8) -> u8 {
         debug_assert!(side == WHITE || side == BLACK);
         debug_assert_eq!(self.pieces.king::<true>().count_ones(), 1);
@@ -209,7 +358,22 @@ impl Board {
         }
     }
 
-    pub fn zero_height(&mut self) {
+    /// Notes whether the position is the one with hash `previous_key` after two
+    /// more moves, our move and the reply, as when a GUI sends the whole game before
+    /// each search. If so, the next search keeps more of what the last one learned.
+    pub fn detect_game_continuation(&mut self, previous_key: u64) {
+        self.continues_game = self
+            .history
+            .len()
+            .checked_sub(2)
+            .is_some_and(|i| self.history[i].key == previous_key);
+    }
+
+    pub const fn continues_game(&self) -> bool {
+        self.continues_game
+    }
+
+    pub const fn zero_height(&mut self) {
         self.height = 0;
     }
 
@@ -221,6 +385,19 @@ impl Board {
         self.side
     }
 
+    pub const fn hashkey(&self) -> u64 {
+        self.key
+    }
+
+    /// The number of plies played in the game so far.
+    pub const fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub const fn fifty_move_counter(&self) -> u16 {
+        self.fifty_move_counter
+    }
+
     pub fn generate_pos_key(&self) -> u64 {
         #![allow(clippy::cast_possible_truncation)]
         let mut key = 0;
@@ -237,13 +414,17 @@ impl Board {
 
         if self.ep_sq != NO_SQUARE {
             debug_assert!(self.ep_sq < BOARD_N_SQUARES.try_into().unwrap());
-            hash_ep(&mut key, self.ep_sq as u8);
+            hash_ep(&mut key, self.ep_sq);
         }
 
         debug_assert!(self.castle_perm <= 15);
 
         hash_castling(&mut key, self.castle_perm);
 
+        for side in [WHITE, BLACK] {
+            hash_checks(&mut key, side, self.checks[side as usize]);
+        }
+
         key
     }
 
@@ -254,6 +435,7 @@ impl Board {
         self.major_piece_counts.fill(0);
         self.minor_piece_counts.fill(0);
         self.material.fill(S(0, 0));
+        self.phase = TOTAL_PHASE;
         self.piece_lists.iter_mut().for_each(PieceList::clear);
         self.side = Colour::Both as u8;
         self.ep_sq = NO_SQUARE;
@@ -261,15 +443,81 @@ impl Board {
         self.height = 0;
         self.ply = 0;
         self.castle_perm = 0;
+        self.checks = [0; 2];
         self.key = 0;
         self.pst_vals = S(0, 0);
         self.history.clear();
-        self.repetition_cache.clear();
     }
 
+    /// Runs `set_up`, putting the previous position back if it fails, so that
+    /// a rejected position is never left on the board.
+    fn set_up_or_restore(
+        &mut self,
+        set_up: impl FnOnce(&mut Self) -> Result<(), FenParseError>,
+    ) -> Result<(), FenParseError> {
+        let saved = SavedPosition {
+            pieces: self.pieces.clone(),
+            piece_array: self.piece_array,
+            side: self.side,
+            ep_sq: self.ep_sq,
+            fifty_move_counter: self.fifty_move_counter,
+            height: self.height,
+            ply: self.ply,
+            key: self.key,
+            big_piece_counts: self.big_piece_counts,
+            major_piece_counts: self.major_piece_counts,
+            minor_piece_counts: self.minor_piece_counts,
+            material: self.material,
+            phase: self.phase,
+            castle_perm: self.castle_perm,
+            checks: self.checks,
+            history: std::mem::take(&mut self.history),
+            piece_lists: self.piece_lists,
+            pst_vals: self.pst_vals,
+        };
+        let result = set_up(self);
+        if result.is_err() {
+            self.pieces = saved.pieces;
+            self.piece_array = saved.piece_array;
+            self.side = saved.side;
+            self.ep_sq = saved.ep_sq;
+            self.fifty_move_counter = saved.fifty_move_counter;
+            self.height = saved.height;
+            self.ply = saved.ply;
+            self.key = saved.key;
+            self.big_piece_counts = saved.big_piece_counts;
+            self.major_piece_counts = saved.major_piece_counts;
+            self.minor_piece_counts = saved.minor_piece_counts;
+            self.material = saved.material;
+            self.phase = saved.phase;
+            self.castle_perm = saved.castle_perm;
+            self.checks = saved.checks;
+            self.history = saved.history;
+            self.piece_lists = saved.piece_lists;
+            self.pst_vals = saved.pst_vals;
+        }
+        result
+    }
+
+    /// Sets up the board from a FEN, keeping the previous position
+    /// if the FEN is malformed or the position it describes is illegal.
     pub fn set_from_fen(&mut self, fen: &str) -> Result<(), FenParseError> {
+        self.set_up_or_restore(|board| {
+            board.parse_fen(fen)?;
+            board.validate().map_err(|errors| {
+                let reasons = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                format!(
+                    "FEN string is an illegal position ({}): {fen}",
+                    reasons.join(", ")
+                )
+            })
+        })
+    }
+
+    /// Sets up the board from a FEN without checking that the position is legal.
+    fn parse_fen(&mut self, fen: &str) -> Result<(), FenParseError> {
         if !fen.is_ascii() {
-            return Err(format!("FEN string is not ASCII: {}", fen));
+            return Err(format!("FEN string is not ASCII: {fen}"));
         }
 
         let mut rank = Rank::RANK_8;
@@ -281,7 +529,7 @@ impl Board {
         let split_idx = fen_chars
             .iter()
             .position(|&c| c == b' ')
-            .ok_or_else(|| format!("FEN string is missing space: {}", fen))?;
+            .ok_or_else(|| format!("FEN string is missing space: {fen}"))?;
         let (board_part, info_part) = fen_chars.split_at(split_idx);
 
         for &c in board_part {
@@ -335,7 +583,15 @@ impl Board {
 
         self.set_ep(info_parts.next())?;
 
-        self.set_halfmove(info_parts.next())?;
+        let mut halfmove_part = info_parts.next();
+        if self.variant == Variant::ThreeCheck
+            && halfmove_part.is_some_and(|part| part.contains(&b'+'))
+        {
+            self.set_checks(halfmove_part)?;
+            halfmove_part = info_parts.next();
+        }
+
+        self.set_halfmove(halfmove_part)?;
 
         self.set_fullmove(info_parts.next())?;
 
@@ -344,6 +600,53 @@ impl Board {
         Ok(())
     }
 
+    /// Sets up the board from its parts rather than a FEN: the piece on each square
+    /// from a1 to h8, the side to move, the castling rights as in `castle_perm`,
+    /// the en passant square or `NO_SQUARE`, and the move counters. An illegal
+    /// position is rejected, and the previous one kept.
+    pub fn set_from_parts(
+        &mut self,
+        squares: &[u8; 64],
+        side: u8,
+        castling: u8,
+        ep_sq: u8,
+        halfmove: u16,
+        fullmove: usize,
+    ) -> Result<(), FenParseError> {
+        if side != WHITE && side != BLACK {
+            return Err(format!("invalid side to move {side}"));
+        }
+        if castling > (WKCA | WQCA | BKCA | BQCA) {
+            return Err(format!("invalid castling rights {castling:#06b}"));
+        }
+        if ep_sq > NO_SQUARE {
+            return Err(format!("invalid en passant square {ep_sq}"));
+        }
+        if let Some(&piece) = squares.iter().find(|&&piece| piece > BK) {
+            return Err(format!("invalid piece {piece}"));
+        }
+
+        self.set_up_or_restore(|board| {
+            board.reset();
+            for (sq, &piece) in (0..).zip(squares) {
+                if piece != PIECE_EMPTY {
+                    board.add_piece(sq, piece);
+                }
+            }
+            board.side = side;
+            board.castle_perm = castling;
+            board.ep_sq = ep_sq;
+            board.fifty_move_counter = halfmove;
+            board.ply = fullmove.saturating_sub(1) * 2 + usize::from(side == BLACK);
+            board.key = board.generate_pos_key();
+
+            board.validate().map_err(|errors| {
+                let reasons = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                format!("illegal position ({})", reasons.join(", "))
+            })
+        })
+    }
+
     pub fn set_startpos(&mut self) {
         self.set_from_fen(Self::STARTING_FEN)
             .expect("for some reason, STARTING_FEN is now broken.");
@@ -389,7 +692,7 @@ impl Board {
                         write!(fen, "{counter}").unwrap();
                     }
                     counter = 0;
-                    fen.push(piece_char(piece).unwrap() as char);
+                    fen.push(piece_char(piece).unwrap());
                 } else {
                     counter += 1;
                 }
@@ -425,6 +728,10 @@ impl Board {
         } else {
             fen.push('-');
         }
+        if self.variant == Variant::ThreeCheck {
+            let remaining = |side: u8| 3_u8.saturating_sub(self.checks[side as usize]);
+            write!(fen, " {}+{}", remaining(WHITE), remaining(BLACK)).unwrap();
+        }
         write!(fen, " {}", self.fifty_move_counter).unwrap();
         write!(fen, " {}", self.ply / 2 + 1).unwrap();
 
@@ -474,8 +781,8 @@ impl Board {
                 if ep_sq.len() != 2 {
                     return Err(format!("FEN string is invalid, expected en passant part to be of the form 'a1', got \"{}\"", std::str::from_utf8(ep_sq).unwrap_or("<invalid utf8>")));
                 }
-                let file = ep_sq[0] as u8 - b'a';
-                let rank = ep_sq[1] as u8 - b'1';
+                let file = ep_sq[0] - b'a';
+                let rank = ep_sq[1] - b'1';
                 if !((File::FILE_A..=File::FILE_H).contains(&file)
                     && (Rank::RANK_1..=Rank::RANK_8).contains(&rank))
                 {
@@ -488,6 +795,19 @@ impl Board {
         Ok(())
     }
 
+    /// Reads the checks each side has left to give in three-check, written as `W+B`.
+    fn set_checks(&mut self, checks_part: Option<&[u8]>) -> Result<(), FenParseError> {
+        let parsed = checks_part.and_then(|part| match part {
+            [white @ b'0'..=b'3', b'+', black @ b'0'..=b'3'] => Some([3 - (white - b'0'), 3 - (black - b'0')]),
+            _ => None,
+        });
+        let Some(checks) = parsed else {
+            return Err("FEN string is invalid, expected checks part to be of the form '3+3'".into());
+        };
+        self.checks = checks;
+        Ok(())
+    }
+
     fn set_halfmove(&mut self, halfmove_part: Option<&[u8]>) -> Result<(), FenParseError> {
         match halfmove_part {
             None => return Err("FEN string is invalid, expected halfmove clock part.".to_string()),
@@ -496,7 +816,7 @@ impl Board {
                     .map_err(|_| {
                         "FEN string is invalid, expected halfmove clock part to be valid UTF-8"
                     })?
-                    .parse::<u8>()
+                    .parse::<u16>()
                     .map_err(|_| {
                         "FEN string is invalid, expected halfmove clock part to be a number"
                     })?;
@@ -518,7 +838,8 @@ impl Board {
                     .map_err(|_| {
                         "FEN string is invalid, expected fullmove number part to be a number"
                     })?;
-                self.ply = (fullmove_number - 1) * 2;
+                // some tools write a fullmove number of 0, so treat it as 1.
+                self.ply = fullmove_number.saturating_sub(1) * 2;
                 if self.side == BLACK {
                     self.ply += 1;
                 }
@@ -528,10 +849,33 @@ impl Board {
         Ok(())
     }
 
-    #[allow(clippy::cognitive_complexity, clippy::too_many_lines, dead_code)]
-    pub fn check_validity(&self) -> Result<(), PositionValidityError> {
+    /// Checks that the position could arise in a game, and that the board's
+    /// internal state agrees with the pieces on it, returning every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let errors = self.validation_errors::<false>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Like `validate`, but allows the side that just moved to be in check,
+    /// as they are after an illegal pseudo-legal move has been made.
+    #[allow(dead_code)]
+    pub fn validate_pseudo_legal(&self) -> Result<(), Vec<ValidationError>> {
+        let errors = self.validation_errors::<true>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[allow(clippy::cognitive_complexity, clippy::too_many_lines)]
+    fn validation_errors<const PSEUDO_LEGAL: bool>(&self) -> Vec<ValidationError> {
         #![allow(clippy::similar_names, clippy::cast_possible_truncation)]
-        use Colour::{Black, White};
+        let mut errors = Vec::new();
         let mut piece_num = [0u8; 13];
         let mut big_pce = [0, 0];
         let mut maj_pce = [0, 0];
@@ -542,23 +886,18 @@ impl Board {
         for piece in WP..=BK {
             for &sq in self.piece_lists[piece as usize].iter() {
                 if self.piece_at(sq) != piece {
-                    return Err(format!(
-                        "piece list corrupt: expected square {} to be '{}' but was '{}'",
-                        square_name(sq).unwrap_or(&format!("offboard: {}", sq)),
-                        piece_char(piece)
-                            .map(|c| c.to_string())
-                            .unwrap_or(format!("unknown piece: {}", piece)),
-                        piece_char(self.piece_at(sq))
-                            .map(|c| c.to_string())
-                            .unwrap_or(format!("unknown piece: {}", self.piece_at(sq)))
-                    ));
+                    errors.push(ValidationError::PieceListCorrupt {
+                        sq,
+                        expected: piece,
+                        found: self.piece_at(sq),
+                    });
                 }
             }
         }
 
         // check turn
         if self.side != WHITE && self.side != BLACK {
-            return Err(format!("invalid side: {}", self.side));
+            errors.push(ValidationError::InvalidSide(self.side));
         }
 
         // check piece count and other counters
@@ -581,144 +920,176 @@ impl Board {
             material[colour as usize] += self.eval_params.piece_values[piece as usize];
         }
 
-        if piece_num[1..].to_vec()
-            != self.piece_lists[1..]
-                .iter()
-                .map(PieceList::len)
-                .collect::<Vec<_>>()
-        {
-            return Err(format!(
-                "piece counts are corrupt: expected {:?}, got {:?}",
-                &piece_num[1..],
-                &self.piece_lists[1..]
-                    .iter()
-                    .map(PieceList::len)
-                    .collect::<Vec<_>>()
-            ));
+        let list_lengths = self.piece_lists[1..]
+            .iter()
+            .map(PieceList::len)
+            .collect::<Vec<_>>();
+        if piece_num[1..] != list_lengths[..] {
+            errors.push(ValidationError::PieceCountsCorrupt {
+                expected: piece_num[1..].to_vec(),
+                found: list_lengths,
+            });
         }
 
         // check bitboard / piece array coherency
         for piece in WP..=BK {
-            let bb = self.pieces.piece_bb(piece);
-            for sq in BitLoop::new(bb) {
+            for sq in self.pieces_of(piece) {
                 if self.piece_at(sq) != piece {
-                    return Err(format!(
-                        "bitboard / piece array coherency corrupt: expected square {} to be '{}' but was '{}'",
-                        square_name(sq).unwrap_or(&format!("offboard: {}", sq)),
-                        piece_char(piece).map(|c| c.to_string()).unwrap_or(format!("unknown piece: {}", piece)),
-                        piece_char(self.piece_at(sq)).map(|c| c.to_string()).unwrap_or(format!("unknown piece: {}", self.piece_at(sq)))
-                    ));
+                    errors.push(ValidationError::BitboardCorrupt {
+                        sq,
+                        expected: piece,
+                        found: self.piece_at(sq),
+                    });
+                }
+            }
+        }
+        for colour in [WHITE, BLACK] {
+            for sq in self.all_pieces(colour) {
+                let found = self.piece_at(sq);
+                if found == PIECE_EMPTY || colour_of(found) != colour {
+                    errors.push(ValidationError::ColourBitboardCorrupt { sq, colour, found });
                 }
             }
         }
 
-        if material[White as usize].0 != self.material[White as usize].0 {
-            return Err(format!(
-                "white midgame material is corrupt: expected {:?}, got {:?}",
-                material[White as usize].0, self.material[White as usize].0
-            ));
-        }
-        if material[White as usize].1 != self.material[White as usize].1 {
-            return Err(format!(
-                "white endgame material is corrupt: expected {:?}, got {:?}",
-                material[White as usize].1, self.material[White as usize].1
-            ));
-        }
-        if material[Black as usize].0 != self.material[Black as usize].0 {
-            return Err(format!(
-                "black midgame material is corrupt: expected {:?}, got {:?}",
-                material[Black as usize].0, self.material[Black as usize].0
-            ));
-        }
-        if material[Black as usize].1 != self.material[Black as usize].1 {
-            return Err(format!(
-                "black endgame material is corrupt: expected {:?}, got {:?}",
-                material[Black as usize].1, self.material[Black as usize].1
-            ));
-        }
-        if min_pce[White as usize] != self.minor_piece_counts[White as usize] {
-            return Err(format!(
-                "white minor piece count is corrupt: expected {:?}, got {:?}",
-                min_pce[White as usize], self.minor_piece_counts[White as usize]
-            ));
-        }
-        if min_pce[Black as usize] != self.minor_piece_counts[Black as usize] {
-            return Err(format!(
-                "black minor piece count is corrupt: expected {:?}, got {:?}",
-                min_pce[Black as usize], self.minor_piece_counts[Black as usize]
-            ));
-        }
-        if maj_pce[White as usize] != self.major_piece_counts[White as usize] {
-            return Err(format!(
-                "white major piece count is corrupt: expected {:?}, got {:?}",
-                maj_pce[White as usize], self.major_piece_counts[White as usize]
-            ));
-        }
-        if maj_pce[Black as usize] != self.major_piece_counts[Black as usize] {
-            return Err(format!(
-                "black major piece count is corrupt: expected {:?}, got {:?}",
-                maj_pce[Black as usize], self.major_piece_counts[Black as usize]
-            ));
-        }
-        if big_pce[White as usize] != self.big_piece_counts[White as usize] {
-            return Err(format!(
-                "white big piece count is corrupt: expected {:?}, got {:?}",
-                big_pce[White as usize], self.big_piece_counts[White as usize]
-            ));
-        }
-        if big_pce[Black as usize] != self.big_piece_counts[Black as usize] {
-            return Err(format!(
-                "black big piece count is corrupt: expected {:?}, got {:?}",
-                big_pce[Black as usize], self.big_piece_counts[Black as usize]
-            ));
-        }
-
-        if !(self.side == WHITE || self.side == BLACK) {
-            return Err(format!(
-                "side is corrupt: expected WHITE or BLACK, got {:?}",
-                self.side
-            ));
+        for colour in [WHITE, BLACK] {
+            let c = colour as usize;
+            if material[c] != self.material[c] {
+                errors.push(ValidationError::MaterialCorrupt {
+                    colour,
+                    expected: (material[c].0, material[c].1),
+                    found: (self.material[c].0, self.material[c].1),
+                });
+            }
+            for (kind, expected, found) in [
+                ("minor", min_pce[c], self.minor_piece_counts[c]),
+                ("major", maj_pce[c], self.major_piece_counts[c]),
+                ("big", big_pce[c], self.big_piece_counts[c]),
+            ] {
+                if expected != found {
+                    errors.push(ValidationError::PieceCountCorrupt {
+                        kind,
+                        colour,
+                        expected,
+                        found,
+                    });
+                }
+            }
         }
+
+        let n = |white: u8, black: u8| piece_num[white as usize] + piece_num[black as usize];
+        let phase = evaluation::game_phase(n(WP, BP), n(WN, BN), n(WB, BB), n(WR, BR), n(WQ, BQ));
+        if phase != self.phase {
+            errors.push(ValidationError::PhaseCorrupt {
+                expected: phase,
+                found: self.phase,
+            });
+        }
+
         if self.generate_pos_key() != self.key {
-            return Err(format!(
-                "key is corrupt: expected {:?}, got {:?}",
-                self.generate_pos_key(),
-                self.key
-            ));
+            errors.push(ValidationError::KeyCorrupt {
+                expected: self.generate_pos_key(),
+                found: self.key,
+            });
         }
 
         if !(self.ep_sq == NO_SQUARE
             || (rank(self.ep_sq) == RANK_6 && self.side == WHITE)
             || (rank(self.ep_sq) == RANK_3 && self.side == BLACK))
         {
-            return Err(format!("en passant square is corrupt: expected square to be {} (NoSquare) or to be on ranks 6 or 3, got {} (Rank {})", NO_SQUARE, self.ep_sq, rank(self.ep_sq)));
+            errors.push(ValidationError::InvalidEnPassant(self.ep_sq));
         }
 
-        if self.fifty_move_counter >= 100 {
-            return Err(format!(
-                "fifty move counter is corrupt: expected 0-99, got {}",
-                self.fifty_move_counter
-            ));
+        let back_rank_pawns =
+            (self.pieces.piece_bb(WP) | self.pieces.piece_bb(BP)) & (BB_RANK_1 | BB_RANK_8);
+        for sq in BitLoop::new(back_rank_pawns) {
+            errors.push(ValidationError::PawnOnBackRank(sq));
         }
 
-        // check there are the correct number of kings for each side
-        assert_eq!(self.num(WK), 1);
-        assert_eq!(self.num(BK), 1);
+        // check there are the correct number of kings for each side,
+        // and only look at where they are if there are.
+        for (colour, king) in [(WHITE, WK), (BLACK, BK)] {
+            let count = self.num(king);
+            if count != 1 || self.pieces.piece_bb(king).count_ones() != 1 {
+                errors.push(ValidationError::KingCount { colour, count });
+            }
+        }
+        if errors
+            .iter()
+            .any(|e| matches!(e, ValidationError::KingCount { .. }))
+        {
+            return errors;
+        }
 
-        if self.piece_at(self.king_sq(WHITE)) != WK {
-            return Err(format!(
-                "white king square is corrupt: expected white king, got {:?}",
-                self.piece_at(self.king_sq(WHITE))
-            ));
+        for (colour, king) in [(WHITE, WK), (BLACK, BK)] {
+            let found = self.piece_at(*self.piece_lists[king as usize].first().unwrap());
+            if found != king {
+                errors.push(ValidationError::KingSquareCorrupt { colour, found });
+            }
         }
-        if self.piece_at(self.king_sq(BLACK)) != BK {
-            return Err(format!(
-                "black king square is corrupt: expected black king, got {:?}",
-                self.piece_at(self.king_sq(BLACK))
-            ));
+
+        if !PSEUDO_LEGAL && errors.is_empty() {
+            let white_king = self.king_sq(WHITE);
+            let black_king = self.king_sq(BLACK);
+            if (white_king % 8).abs_diff(black_king % 8) <= 1
+                && (white_king / 8).abs_diff(black_king / 8) <= 1
+            {
+                errors.push(ValidationError::KingsAdjacent);
+            } else if self.in_check::<{ Self::THEM }>() {
+                errors.push(ValidationError::OpponentInCheck);
+            }
         }
 
-        Ok(())
+        errors
+    }
+
+    /// The pieces of `side` that are pinned to their king by an enemy slider.
+    /// Pinned pieces of the other colour, which can't be on the line, aren't included.
+    pub fn pinned(&self, side: u8) -> u64 {
+        debug_assert!(side_valid(side));
+        self.king_blockers(side, side)
+    }
+
+    /// The pieces of the side to move that stand between one of its own sliders
+    /// and the enemy king, and so would give check by moving off that line.
+    pub fn discovered_check_candidates(&self) -> u64 {
+        self.king_blockers(self.side ^ 1, self.side)
+    }
+
+    /// The pieces of `blocker_side` that are the only piece between the king of
+    /// `king_side` and an enemy slider that would otherwise attack it.
+    fn king_blockers(&self, king_side: u8, blocker_side: u8) -> u64 {
+        let king_sq = self.king_sq(king_side);
+        let occupied = self.pieces.occupied();
+        let (orthogonal, diagonal) = if king_side == WHITE {
+            (
+                self.pieces.rookqueen::<false>(),
+                self.pieces.bishopqueen::<false>(),
+            )
+        } else {
+            (
+                self.pieces.rookqueen::<true>(),
+                self.pieces.bishopqueen::<true>(),
+            )
+        };
+        let blocker_candidates = if blocker_side == WHITE {
+            self.pieces.their_pieces::<false>()
+        } else {
+            self.pieces.their_pieces::<true>()
+        };
+
+        // a slider and the king both attack the square between them if,
+        // and only if, it is the only piece on the line between them.
+        let king_orthogonal = bitboards::attacks::<ROOK>(king_sq, occupied);
+        let king_diagonal = bitboards::attacks::<BISHOP>(king_sq, occupied);
+        let mut blockers = 0;
+        for sniper in BitLoop::new(bitboards::attacks::<ROOK>(king_sq, BB_NONE) & orthogonal) {
+            blockers |= bitboards::attacks::<ROOK>(sniper, occupied) & king_orthogonal;
+        }
+        for sniper in BitLoop::new(bitboards::attacks::<BISHOP>(king_sq, BB_NONE) & diagonal) {
+            blockers |= bitboards::attacks::<BISHOP>(sniper, occupied) & king_diagonal;
+        }
+        blockers & blocker_candidates
     }
 
     /// Determines if `sq` is attacked by `side`
@@ -726,7 +1097,7 @@ impl Board {
         debug_assert!(side_valid(side));
         debug_assert!(square_on_board(sq));
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate_pseudo_legal().unwrap();
 
         // pawns
         if side == WHITE {
@@ -796,6 +1167,61 @@ impl Board {
         false
     }
 
+    /// Determines whether the side to move has a rook or queen that is
+    /// attacked by a piece of lower value, or that is attacked and undefended.
+    /// Such a piece will likely be lost if we pass or play a quiet move, so we
+    /// shouldn't trust pruning methods that assume our position is stable.
+    pub fn has_threatened_major_piece(&self) -> bool {
+        let (us, them) = if self.side == WHITE { (WHITE, BLACK) } else { (BLACK, WHITE) };
+        let (our_rooks, our_queens) = if us == WHITE {
+            (self.pieces.rooks::<true>(), self.pieces.queens::<true>())
+        } else {
+            (self.pieces.rooks::<false>(), self.pieces.queens::<false>())
+        };
+        let our_majors = our_rooks | our_queens;
+        if our_majors == BB_NONE {
+            return false;
+        }
+
+        let (their_pawn_attacks, their_knights, their_bishops, their_rooks) = if them == WHITE {
+            (
+                self.pieces.pawn_attacks::<true>(),
+                self.pieces.knights::<true>(),
+                self.pieces.bishops::<true>(),
+                self.pieces.rooks::<true>(),
+            )
+        } else {
+            (
+                self.pieces.pawn_attacks::<false>(),
+                self.pieces.knights::<false>(),
+                self.pieces.bishops::<false>(),
+                self.pieces.rooks::<false>(),
+            )
+        };
+
+        let occupied = self.pieces.occupied();
+        let mut minor_attacks = their_pawn_attacks;
+        for sq in BitLoop::new(their_knights) {
+            minor_attacks |= bitboards::attacks::<KNIGHT>(sq, BB_NONE);
+        }
+        for sq in BitLoop::new(their_bishops) {
+            minor_attacks |= bitboards::attacks::<BISHOP>(sq, occupied);
+        }
+        if our_majors & minor_attacks != BB_NONE {
+            return true;
+        }
+
+        let mut rook_attacks = BB_NONE;
+        for sq in BitLoop::new(their_rooks) {
+            rook_attacks |= bitboards::attacks::<ROOK>(sq, occupied);
+        }
+        if our_queens & rook_attacks != BB_NONE {
+            return true;
+        }
+
+        BitLoop::new(our_majors).any(|sq| self.sq_attacked(sq, them) && !self.sq_attacked(sq, us))
+    }
+
     /// Checks if a move is legal in the current position.
     /// Because moves must be played and unplayed, this method
     /// requires a mutable reference to the position.
@@ -803,20 +1229,11 @@ impl Board {
     /// object to be the same before and after calling [`Board::is_legal`].
     #[allow(clippy::wrong_self_convention)]
     pub fn is_legal(&mut self, move_to_check: Move) -> bool {
-        let mut list = MoveList::new();
-        self.generate_moves(&mut list);
-
-        for m in list {
-            if !self.make_move(m) {
-                continue;
-            }
-            self.unmake_move();
-            if m == move_to_check {
-                return true;
-            }
+        if !self.is_pseudo_legal(move_to_check) || !self.make_move(move_to_check) {
+            return false;
         }
-
-        false
+        self.unmake_move();
+        true
     }
 
     fn clear_piece(&mut self, sq: u8) {
@@ -834,6 +1251,7 @@ impl Board {
 
         *self.piece_at_mut(sq) = PIECE_EMPTY;
         self.material[colour as usize] -= self.eval_params.piece_values[piece as usize];
+        self.phase += PIECE_PHASE[piece as usize];
         self.pst_vals -= pst_value(piece, sq, &self.eval_params.piece_square_tables);
 
         if PIECE_BIG[piece as usize] {
@@ -848,6 +1266,24 @@ impl Board {
         self.piece_lists[piece as usize].remove(sq);
     }
 
+    /// Recomputes the material, piece-square and phase scores, which are kept
+    /// incrementally, from the pieces on the board. Nothing else about the
+    /// position changes, so the moves that led to it are kept.
+    fn refresh_incremental_scores(&mut self) {
+        self.material.fill(S(0, 0));
+        self.phase = TOTAL_PHASE;
+        self.pst_vals = S(0, 0);
+        for sq in 0..64 {
+            let piece = self.piece_at(sq);
+            if piece == PIECE_EMPTY {
+                continue;
+            }
+            self.material[colour_of(piece) as usize] += self.eval_params.piece_values[piece as usize];
+            self.phase -= PIECE_PHASE[piece as usize];
+            self.pst_vals += pst_value(piece, sq, &self.eval_params.piece_square_tables);
+        }
+    }
+
     fn add_piece(&mut self, sq: u8, piece: u8) {
         debug_assert!(piece_valid(piece));
         debug_assert!(square_on_board(sq));
@@ -860,6 +1296,7 @@ impl Board {
 
         *self.piece_at_mut(sq) = piece;
         self.material[colour as usize] += self.eval_params.piece_values[piece as usize];
+        self.phase -= PIECE_PHASE[piece as usize];
         self.pst_vals += pst_value(piece, sq, &self.eval_params.piece_square_tables);
 
         if PIECE_BIG[piece as usize] {
@@ -918,6 +1355,36 @@ impl Board {
         unsafe { *self.piece_array.get_unchecked(m.from() as usize) }
     }
 
+    /// The piece that `m` captures, if it were made in this position.
+    pub fn captured_piece(&self, m: Move) -> u8 {
+        if m.is_ep() {
+            if self.side == WHITE {
+                BP
+            } else {
+                WP
+            }
+        } else {
+            self.piece_at(m.to())
+        }
+    }
+
+    /// Whether `m` is a capture, if it were made in this position.
+    pub fn is_capture(&self, m: Move) -> bool {
+        self.captured_piece(m) != PIECE_EMPTY
+    }
+
+    /// Whether `m` moves a piece other than a pawn onto a square an enemy pawn attacks.
+    /// This is a cheap stand-in for a static exchange evaluation of a quiet move:
+    /// losing a piece for a pawn is almost always bad, whatever else defends the square.
+    pub fn hangs_to_pawn(&self, m: Move) -> bool {
+        let enemy_pawn_attacks = if self.side == WHITE {
+            self.pieces.pawn_attacks::<false>()
+        } else {
+            self.pieces.pawn_attacks::<true>()
+        };
+        type_of(self.moved_piece(m)) != PAWN && enemy_pawn_attacks & (1 << m.to()) != 0
+    }
+
     /// Gets the piece at the given square.
     pub fn piece_at(&self, sq: u8) -> u8 {
         debug_assert!((sq as usize) < BOARD_N_SQUARES);
@@ -933,7 +1400,7 @@ impl Board {
     #[allow(clippy::cognitive_complexity)]
     pub fn make_move(&mut self, m: Move) -> bool {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
 
         let from = m.from();
         let to = m.to();
@@ -943,9 +1410,11 @@ impl Board {
         debug_assert!(square_on_board(from));
         debug_assert!(square_on_board(to));
         debug_assert!(side_valid(side));
-        debug_assert!(piece_valid(piece), "piece: {:?}", piece);
+        debug_assert!(piece_valid(piece), "piece: {piece:?}");
+        debug_assert!(self.is_pseudo_legal(m), "{m} is not pseudo-legal in {}", self.fen());
 
         let saved_key = self.key;
+        let captured = self.captured_piece(m);
 
         if m.is_ep() {
             if side == WHITE {
@@ -974,11 +1443,13 @@ impl Board {
 
         self.history.push(Undo {
             m,
+            capture: captured,
             castle_perm: self.castle_perm,
             ep_square: self.ep_sq,
             fifty_move_counter: self.fifty_move_counter,
+            key: saved_key,
+            checks: self.checks,
         });
-        self.repetition_cache.insert(saved_key);
 
         self.castle_perm &= unsafe { *CASTLE_PERM_MASKS.get_unchecked(from as usize) };
         self.castle_perm &= unsafe { *CASTLE_PERM_MASKS.get_unchecked(to as usize) };
@@ -987,10 +1458,9 @@ impl Board {
         // reinsert the castling rights
         hash_castling(&mut self.key, self.castle_perm);
 
-        let captured = m.capture();
         self.fifty_move_counter += 1;
 
-        if captured != PIECE_EMPTY {
+        if captured != PIECE_EMPTY && !m.is_ep() {
             debug_assert!(piece_valid(captured));
             self.clear_piece(to);
             self.fifty_move_counter = 0;
@@ -1001,7 +1471,7 @@ impl Board {
 
         if piece == WP || piece == BP {
             self.fifty_move_counter = 0;
-            if m.is_pawn_start() {
+            if from.abs_diff(to) == 16 {
                 if side == WHITE {
                     self.ep_sq = from + 8;
                     debug_assert!(rank(self.ep_sq) == RANK_3);
@@ -1015,9 +1485,12 @@ impl Board {
 
         self.move_piece(from, to);
 
-        let promoted_piece = m.promotion();
-
-        if promoted_piece != PIECE_EMPTY {
+        if m.is_promo() {
+            let promoted_piece = if side == WHITE {
+                m.promotion()
+            } else {
+                m.promotion() + BP - WP
+            };
             debug_assert!(piece_valid(promoted_piece));
             debug_assert!(promoted_piece != WP && promoted_piece != BP);
             self.clear_piece(to);
@@ -1028,7 +1501,7 @@ impl Board {
         hash_side(&mut self.key);
 
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate_pseudo_legal().unwrap();
 
         // reversed in_check fn, as we have now swapped sides
         if self.in_check::<{ Self::THEM }>() {
@@ -1036,19 +1509,29 @@ impl Board {
             return false;
         }
 
+        if self.variant == Variant::ThreeCheck && self.in_check::<{ Self::US }>() {
+            let checks = &mut self.checks[side as usize];
+            hash_checks(&mut self.key, side, *checks);
+            *checks = (*checks + 1).min(3);
+            hash_checks(&mut self.key, side, *checks);
+        }
+
         true
     }
 
     pub fn make_nullmove(&mut self) {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
         debug_assert!(!self.in_check::<{ Self::US }>());
 
         self.history.push(Undo {
             m: Move::NULL,
+            capture: PIECE_EMPTY,
             castle_perm: self.castle_perm,
             ep_square: self.ep_sq,
             fifty_move_counter: self.fifty_move_counter,
+            key: self.key,
+            checks: self.checks,
         });
 
         if self.ep_sq != NO_SQUARE {
@@ -1063,21 +1546,24 @@ impl Board {
         hash_side(&mut self.key);
 
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
     }
 
     pub fn unmake_move(&mut self) {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate_pseudo_legal().unwrap();
 
         self.height -= 1;
         self.ply -= 1;
 
         let Undo {
             m,
+            capture,
             castle_perm,
             ep_square,
             fifty_move_counter,
+            key: _,
+            checks,
         } = self.history.pop().expect("No move to unmake!");
 
         let from = m.from();
@@ -1098,6 +1584,15 @@ impl Board {
             hash_ep(&mut self.key, self.ep_sq);
         }
 
+        for side in [WHITE, BLACK] {
+            let i = side as usize;
+            if self.checks[i] != checks[i] {
+                hash_checks(&mut self.key, side, self.checks[i]);
+                hash_checks(&mut self.key, side, checks[i]);
+            }
+        }
+        self.checks = checks;
+
         // reinsert the castling rights
         hash_castling(&mut self.key, self.castle_perm);
 
@@ -1124,36 +1619,23 @@ impl Board {
 
         self.move_piece(to, from);
 
-        let captured = m.capture();
-        if captured != PIECE_EMPTY {
-            debug_assert!(piece_valid(captured));
-            self.add_piece(to, captured);
+        if capture != PIECE_EMPTY && !m.is_ep() {
+            debug_assert!(piece_valid(capture));
+            self.add_piece(to, capture);
         }
 
-        if m.promotion() != PIECE_EMPTY {
-            debug_assert!(piece_valid(m.promotion()));
-            debug_assert!(m.promotion() != WP && m.promotion() != BP);
+        if m.is_promo() {
             self.clear_piece(from);
-            self.add_piece(
-                from,
-                if colour_of(m.promotion()) == WHITE {
-                    WP
-                } else {
-                    BP
-                },
-            );
+            self.add_piece(from, if self.side == WHITE { WP } else { BP });
         }
 
-        let something_removed = self.repetition_cache.remove(&self.key);
-        debug_assert!(something_removed);
-
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
     }
 
     pub fn unmake_nullmove(&mut self) {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
 
         self.height -= 1;
         self.ply -= 1;
@@ -1166,9 +1648,12 @@ impl Board {
 
         let Undo {
             m: _,
+            capture: _,
             castle_perm,
             ep_square,
             fifty_move_counter,
+            key: _,
+            checks: _,
         } = self.history.pop().expect("No move to unmake!");
 
         self.castle_perm = castle_perm;
@@ -1183,7 +1668,7 @@ impl Board {
         hash_side(&mut self.key);
 
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
     }
 
     /// Parses Standard Algebraic Notation (SAN) and returns a move or a reason why it couldn't be parsed.
@@ -1229,40 +1714,120 @@ impl Board {
             .ok_or_else(|| IllegalMove(san.to_string()))
     }
 
-    /// Has the current position occurred before in the current game?
-    pub fn is_repetition(&self) -> bool {
-        self.repetition_cache.contains(&self.key)
+    /// The number of plies ago that the current position occurred before, most recent first.
+    /// Only positions since the last capture, pawn move or null move can repeat.
+    fn repetitions(&self) -> impl Iterator<Item = usize> + '_ {
+        let reversible = self
+            .history
+            .iter()
+            .rev()
+            .take(usize::from(self.fifty_move_counter))
+            .take_while(|undo| !undo.m.is_null());
+        reversible
+            .enumerate()
+            .skip(1)
+            .step_by(2)
+            .filter(|(_, undo)| undo.key == self.key)
+            .map(|(i, _)| i + 1)
+    }
+
+    /// Whether the current position counts as drawn by repetition in search.
+    /// Repeating a position from since the root is a draw, as the side that
+    /// can avoid it would have done so. A position from before the root
+    /// only draws once it is reached for the third time, as the real game does.
+    fn is_repetition_draw(&self) -> bool {
+        let mut earlier_in_game = 0;
+        for plies_ago in self.repetitions() {
+            if plies_ago <= self.height {
+                return true;
+            }
+            earlier_in_game += 1;
+            if earlier_in_game == 2 {
+                return true;
+            }
+        }
+        false
     }
 
     /// Should we consider the current position a draw?
     pub fn is_draw(&self) -> bool {
-        (self.fifty_move_counter >= 100 || self.is_repetition()) && self.height != 0
+        (self.fifty_move_counter >= 100 || self.is_repetition_draw()) && self.height != 0
     }
 
     pub const fn num(&self, piece: u8) -> u8 {
         self.piece_lists[piece as usize].len()
     }
 
+    /// The squares of every `piece` on the board, from a1 to h8.
+    pub const fn pieces_of(&self, piece: u8) -> BitLoop {
+        BitLoop::new(self.pieces.piece_bb(piece))
+    }
+
+    /// The squares of every piece belonging to `side`, from a1 to h8.
+    pub const fn all_pieces(&self, side: u8) -> BitLoop {
+        BitLoop::new(self.pieces.colour_bb(side))
+    }
+
     pub fn reset_tables(&mut self) {
         self.history_table.clear();
         self.killer_move_table.fill([Move::NULL; 2]);
+        self.mate_killer_table.fill(Move::NULL);
         self.counter_move_table.clear();
         self.followup_history.clear();
+        self.prepare_for_search();
+    }
+
+    /// Gets the tables ready for a new search, keeping some of what the last one
+    /// learned. History scores are divided down, so they still help move ordering
+    /// without growing forever. If the position is two plies further into the same
+    /// game, they are halved, counter moves are kept, and killers move up two plies,
+    /// to the heights they belong at now. Otherwise they are quartered, and the
+    /// other tables are cleared.
+    fn age_tables(&mut self, continues_game: bool) {
+        if continues_game {
+            self.history_table.age(CONTINUATION_HISTORY_AGE);
+            self.followup_history.age(CONTINUATION_HISTORY_AGE);
+            self.killer_move_table.copy_within(2.., 0);
+            self.killer_move_table[MAX_DEPTH.ply_to_horizon() - 2..].fill([Move::NULL; 2]);
+            self.mate_killer_table.copy_within(2.., 0);
+            self.mate_killer_table[MAX_DEPTH.ply_to_horizon() - 2..].fill(Move::NULL);
+        } else {
+            self.history_table.age(HISTORY_AGE);
+            self.followup_history.age(HISTORY_AGE);
+            self.killer_move_table.fill([Move::NULL; 2]);
+            self.mate_killer_table.fill(Move::NULL);
+            self.counter_move_table.clear();
+        }
+        self.prepare_for_search();
+    }
+
+    fn prepare_for_search(&mut self) {
+        self.excluded.fill(Move::NULL);
+        self.double_extensions = 0;
         self.height = 0;
         self.tt.clear_for_search();
         self.movegen_ready = true;
     }
 
+    /// Follows the best moves stored in the TT from the root, for at most `depth` moves.
+    /// The walk stops at a move that isn't legal here, which a hash collision can store,
+    /// at a draw, and on coming back to a position it has already passed through.
     fn regenerate_pv_line(&mut self, depth: i32) {
         self.principal_variation.clear();
+        let mut seen = HashSet::from([self.key]);
 
-        while let ProbeResult::BestMove(pv_move) = self.tt_probe(-INFINITY, INFINITY, MAX_DEPTH) {
+        while let ProbeResult::BestMove(pv_move) =
+            self.tt_probe(-Score::INFINITY, Score::INFINITY, MAX_DEPTH)
+        {
             if self.principal_variation.len() < depth as usize
                 && self.is_legal(pv_move)
                 && !self.is_draw()
             {
                 self.make_move(pv_move);
                 self.principal_variation.push(pv_move);
+                if !seen.insert(self.key) {
+                    break;
+                }
             } else {
                 break;
             }
@@ -1277,105 +1842,353 @@ impl Board {
         &self.principal_variation
     }
 
-    fn print_pv(&self) {
-        for &m in self.get_pv_line() {
-            print!("{m} ");
-        }
-        println!();
+    /// Prints the line from the root to the node currently being searched.
+    pub fn print_currline(&self, info: &SearchInfo) {
+        let line = &self.history[self.history.len() - self.height..];
+        uci::print_currline(info.output_format, line.iter().map(|undo| undo.m));
+    }
+
+    /// Re-searches the current node, which failed high against a zero window,
+    /// with a full window, and reports the line if it no longer beats `alpha`.
+    pub fn verify_scout(
+        &mut self,
+        info: &mut SearchInfo,
+        depth: Depth,
+        alpha: Score,
+        scout_score: Score,
+    ) {
+        // the verification search mustn't verify its own scouts, or it would never finish.
+        info.verify_scout = false;
+        let score = -self.alpha_beta::<true>(info, depth, -Score::INFINITY, Score::INFINITY, false);
+        info.verify_scout = true;
+        if info.stopped || score > alpha {
+            return;
+        }
+        let line = &self.history[self.history.len() - self.height..];
+        uci::print_scout_discrepancy(info.output_format, line.iter().map(|undo| undo.m), alpha, scout_score, score);
     }
 
-    /// Performs the root search. Returns the score of the position, from white's perspective.
-    pub fn search_position(&mut self, info: &mut SearchInfo) -> i32 {
-        self.reset_tables();
+    /// Prints the progress of the search, with the PV last generated by
+    /// [`Board::regenerate_pv_line`], cut down to the `MaxPVLength` option.
+    fn print_search_info(
+        &self,
+        info: &SearchInfo,
+        depth: i32,
+        score: Score,
+        bound: Option<&'static str>,
+    ) {
+        let report = SearchReport {
+            score,
+            bound,
+            depth,
+            seldepth: info.seldepth.ply_to_horizon(),
+            nodes: info.nodes,
+            time: info.start_time.elapsed().as_millis(),
+            pv: match (self.get_pv_line(), info.max_pv_length) {
+                (pv, 0) => pv,
+                (pv, max) => &pv[..pv.len().min(max)],
+            },
+        };
+        uci::print_search_report(info.output_format, self.turn(), &report);
+    }
+
+    /// Announces a new best move found at the root partway through an
+    /// iteration, so that GUIs don't have to wait for the iteration to end.
+    /// The line after the move is whatever the TT holds for it, as the root's
+    /// own entry isn't stored until the iteration ends.
+    pub fn print_new_best_root_move(
+        &mut self,
+        info: &SearchInfo,
+        depth: Depth,
+        m: Move,
+        score: Score,
+        beta: Score,
+    ) {
+        debug_assert_eq!(self.height, 0);
+        if info.output_format == uci::OutputFormat::Silent {
+            return;
+        }
+        // the root search has just made and unmade the move, so it is legal.
+        let legal = self.make_move(m);
+        debug_assert!(legal, "{m} was legal a moment ago");
+        self.regenerate_pv_line(depth.round() - 1);
+        self.unmake_move();
+        self.principal_variation.insert(0, m);
+        let bound = if score >= beta { Some("lowerbound") } else { None };
+        self.print_search_info(info, depth.round(), score, bound);
+    }
+
+    /// Performs the root search. Returns the score of the position, from white's perspective,
+    /// and the best move found.
+    pub fn search_position(&mut self, info: &mut SearchInfo) -> (Score, Move) {
+        let continues_game = std::mem::take(&mut self.continues_game);
+        self.age_tables(continues_game);
+        self.iterative_deepening(info)
+    }
+
+    /// The root search, with the tables already made ready for it.
+    fn iterative_deepening(&mut self, info: &mut SearchInfo) -> (Score, Move) {
+        #![allow(clippy::too_many_lines)]
         info.clear_for_search();
 
+        if let Some(moves) = info.mate_moves {
+            if let Some(line) = self.prove_mate(info, moves) {
+                info.wait_for_ponder_end();
+                let ponder_move = line.get(1).copied().filter(|_| info.ponder);
+                uci::print_bestmove(info.output_format, line[0], ponder_move);
+                let score = Score::mate_in(line.len());
+                return (if self.side == WHITE { score } else { -score }, line[0]);
+            }
+            if info.output_format == uci::OutputFormat::Uci && !info.stopped {
+                macros::send!("info string no mate in {moves} found");
+            }
+        }
+
         let first_legal = self.get_first_legal_move().unwrap_or(Move::NULL);
 
         let mut most_recent_move = first_legal;
-        let mut most_recent_score = 0;
+        let mut most_recent_score = Score::DRAW;
+        let mut best_line = Vec::new();
+        let mut root_moves = Vec::new();
         let mut best_depth = 1;
-        let (mut alpha, mut beta) = (-INFINITY, INFINITY);
-        let max_depth = std::cmp::min(info.depth, MAX_DEPTH - 1).round();
+        let mut final_bound = None;
+        let (mut alpha, mut beta) = (-Score::INFINITY, Score::INFINITY);
+        let mut max_depth = std::cmp::min(info.depth, MAX_DEPTH - 1).round();
+        if info.time_set && self.legal_move_count() == 1 {
+            // there's no choice to spend the clock on, so the move is played
+            // at once, after a depth 1 search to have a score to report.
+            max_depth = max_depth.min(1);
+        }
+        // the first iteration is too quick to be worth interrupting,
+        // and without it there would only be an arbitrary move to play.
+        info.limits_deferred = true;
         for i_depth in 0..=max_depth {
             let depth = Depth::from(i_depth);
             // main search
             assert!(self.height == 0, "height != 0 before aspiration search");
-            let mut score = Self::alpha_beta::<true>(self, info, depth, alpha, beta);
-
-            info.check_up();
-            if info.stopped {
-                break;
-            }
+            let mut score = Self::alpha_beta::<true>(self, info, depth, alpha, beta, false);
 
-            if score <= alpha || score >= beta {
-                let score_string = format_score(score, self.turn());
+            if !info.stopped && (score <= alpha || score >= beta) {
                 let boundstr = ["lowerbound", "upperbound"][usize::from(score <= alpha)];
-                print!(
-                    "info score {} {} depth {} seldepth {} nodes {} time {} pv ",
-                    score_string,
-                    boundstr,
-                    i_depth,
-                    info.seldepth.ply_to_horizon(),
-                    info.nodes,
-                    info.start_time.elapsed().as_millis()
-                );
                 self.regenerate_pv_line(best_depth);
-                self.print_pv();
+                self.print_search_info(info, i_depth, score, Some(boundstr));
                 // recalculate the score with a full window, as we failed either low or high.
                 assert!(self.height == 0, "height != 0 before fullwindow search");
-                score = Self::alpha_beta::<true>(self, info, depth, -INFINITY, INFINITY);
-                info.check_up();
-                if info.stopped {
-                    break;
+                score =
+                    Self::alpha_beta::<true>(self, info, depth, -Score::INFINITY, Score::INFINITY, false);
+            }
+
+            if info.stopped {
+                if let Some(hit) = self.unfinished_iteration_result(best_depth) {
+                    most_recent_move = hit.m;
+                    most_recent_score = hit.value;
+                    final_bound = Some("lowerbound");
+                    self.regenerate_pv_line(i_depth);
+                    best_line.clone_from(&self.principal_variation);
                 }
+                break;
             }
 
             most_recent_score = score;
             best_depth = i_depth;
-            if !evaluation::is_mate_score(score) && i_depth > 4 {
-                alpha = score - ONE_PAWN / 4;
-                beta = score + ONE_PAWN / 4;
+            root_moves.clone_from(&info.root_moves);
+            if !score.is_mate() && depth >= self.search_params.aspiration_depth {
+                alpha = score - self.search_params.aspiration_window;
+                beta = score + self.search_params.aspiration_window;
             } else {
-                alpha = -INFINITY;
-                beta = INFINITY;
+                alpha = -Score::INFINITY;
+                beta = Score::INFINITY;
             }
             self.regenerate_pv_line(best_depth);
+            best_line.clone_from(&self.principal_variation);
             most_recent_move = *self
                 .principal_variation
                 .first()
                 .unwrap_or(&most_recent_move);
 
-            let score_string = format_score(most_recent_score, self.turn());
-            print!(
-                "info score {} depth {} seldepth {} nodes {} time {} pv ",
-                score_string,
-                i_depth,
-                info.seldepth.ply_to_horizon(),
-                info.nodes,
-                info.start_time.elapsed().as_millis()
+            self.print_search_info(info, i_depth, most_recent_score, None);
+
+            info.limits_deferred = i_depth < 1;
+            info.check_up();
+            if info.stopped {
+                break;
+            }
+        }
+        info.limits_deferred = false;
+        self.principal_variation = best_line;
+        self.print_search_info(info, best_depth, most_recent_score, final_bound);
+        if info.debug && info.output_format == uci::OutputFormat::Uci {
+            macros::send!(
+                "info string ordering {:.2} lmp {} history {}",
+                info.failhigh_first / info.failhigh.max(1.0),
+                info.lmp_prunes,
+                info.history_prunes
             );
-            self.print_pv();
-        }
-        let score_string = format_score(most_recent_score, self.turn());
-        print!(
-            "info score {} depth {} seldepth {} nodes {} time {} pv ",
-            score_string,
-            best_depth,
-            info.seldepth.ply_to_horizon(),
-            info.nodes,
-            info.start_time.elapsed().as_millis()
-        );
-        self.regenerate_pv_line(best_depth);
-        self.print_pv();
-        println!("bestmove {}", most_recent_move);
-        if self.side == WHITE {
+        }
+        if info.root_report {
+            uci::print_root_report(info.output_format, self.turn(), &mut root_moves);
+        }
+        if !most_recent_score.is_mate() {
+            most_recent_move = self.sample_root_move(info, &root_moves, most_recent_move);
+        }
+        let verdict = info
+            .verdicts
+            .update(evaluation::to_centipawns(most_recent_score.raw()), self.ply() / 2 + 1);
+        uci::print_verdict(info.output_format, verdict);
+        info.wait_for_ponder_end();
+        let ponder_move = match self.principal_variation[..] {
+            [m, ponder, ..] if info.ponder && m == most_recent_move => Some(ponder),
+            _ => None,
+        };
+        uci::print_bestmove(info.output_format, most_recent_move, ponder_move);
+        let score = if self.side == WHITE {
             most_recent_score
         } else {
             -most_recent_score
+        };
+        (score, most_recent_move)
+    }
+
+    /// Searches the position after our move `m`, with the opponent to move,
+    /// until `control` stops it, so that the TT already holds the lines the
+    /// opponent might play into when the next `go` arrives. Nothing is printed,
+    /// and the position is left as it was. The tables aren't aged, as this is
+    /// a continuation of the search just finished rather than a new one, and
+    /// whether the game continues is left for the next search to find out.
+    pub fn analyse_after(&mut self, m: Move, control: Option<Arc<SearchControl>>) {
+        if m.is_null() || !self.make_root_move(m) {
+            return;
+        }
+        let mut info = SearchInfo {
+            output_format: uci::OutputFormat::Silent,
+            control,
+            ..SearchInfo::default()
+        };
+        self.prepare_for_search();
+        self.iterative_deepening(&mut info);
+        self.unmake_root_move();
+    }
+
+    /// Searches the position, and the position after the proposed move `m`,
+    /// to `depth`, to find out how much worse `m` is than the best move.
+    /// Returns `None` if `m` is illegal. The position is left as it was.
+    pub fn blunder_check(&mut self, m: Move, depth: Depth) -> Option<BlunderCheck> {
+        if m.is_null() || !self.is_legal(m) {
+            return None;
+        }
+        let new_info = || SearchInfo {
+            depth,
+            output_format: uci::OutputFormat::Silent,
+            ..SearchInfo::default()
+        };
+        let turn = self.side;
+        let from_our_side = |score: Score| if turn == WHITE { score } else { -score };
+
+        let (best_score, best_move) = self.search_position(&mut new_info());
+        assert!(self.make_root_move(m));
+        let (move_score, _) = self.search_position(&mut new_info());
+        let refutation = self.principal_variation.clone();
+        self.unmake_root_move();
+
+        let best_score = from_our_side(best_score);
+        // mates after the move are a ply further from the position it was played in.
+        let move_score = match from_our_side(move_score) {
+            score if score.is_mate() && score > Score::DRAW => score - 1,
+            score if score.is_mate() => score + 1,
+            score => score,
+        };
+        let loss = if m == best_move {
+            Some(0)
+        } else if best_score.is_mate() || move_score.is_mate() {
+            None
+        } else {
+            Some(evaluation::to_centipawns((best_score - move_score).max(0)))
+        };
+        Some(BlunderCheck { m, move_score, best_move, best_score, loss, refutation })
+    }
+
+    /// Plays `m` and makes the new position the root of the search, as
+    /// `position ... moves` does. Returns false, leaving the position alone,
+    /// if `m` is illegal. Undone by [`Self::unmake_root_move`].
+    fn make_root_move(&mut self, m: Move) -> bool {
+        debug_assert_eq!(self.height, 0);
+        if !self.make_move(m) {
+            return false;
         }
+        self.zero_height();
+        true
     }
 
-    fn get_first_legal_move(&mut self) -> Option<Move> {
+    fn unmake_root_move(&mut self) {
+        self.height = 1;
+        self.unmake_move();
+    }
+
+    /// The best move of an iteration that was stopped before it finished,
+    /// if it's known to be better than the best move of the last iteration that
+    /// finished at `best_depth`. This is the case when it beat the first move
+    /// searched, or failed high, as both leave a lower bound on the root in the TT.
+    fn unfinished_iteration_result(&mut self, best_depth: i32) -> Option<TTHit> {
+        debug_assert_eq!(self.height, 0);
+        self.tt_probe_for_singularity().filter(|hit| {
+            hit.bound == HFlag::Beta && hit.depth > Depth::from(best_depth) && self.is_legal(hit.m)
+        })
+    }
+
+    /// Whether the root move will be picked at random, which needs
+    /// the exact score of every root move from the main search.
+    pub const fn samples_root_move(&self, info: &SearchInfo) -> bool {
+        info.root_temperature != 0 && self.ply / 2 < info.root_temperature_moves
+    }
+
+    /// Whether the main search needs the exact score of every root move,
+    /// for the root report or to pick the root move at random.
+    pub const fn scores_root_moves(&self, info: &SearchInfo) -> bool {
+        info.root_report || self.samples_root_move(info)
+    }
+
+    /// In the first few moves of the game, picks the root move at random from
+    /// those that scored within the margin of the best in the last finished
+    /// iteration, weighted by a softmax over their scores, so that games
+    /// without an opening book don't all follow the same line.
+    fn sample_root_move(&self, info: &SearchInfo, root_moves: &[RootMove], best_move: Move) -> Move {
+        if !self.samples_root_move(info) || best_move.is_null() || info.quit {
+            return best_move;
+        }
+        let Some(best_score) = root_moves.iter().map(|rm| rm.score).max() else {
+            return best_move;
+        };
+        let floor = best_score - evaluation::centipawns(info.root_temperature_margin);
+        let candidates = root_moves
+            .iter()
+            .filter(|rm| rm.score > floor)
+            .map(|rm| (rm.m, rm.score))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return best_move;
+        }
+
+        let temperature = f64::from(evaluation::centipawns(info.root_temperature));
+        let weights = candidates
+            .iter()
+            .map(|&(_, s)| (f64::from(s - best_score) / temperature).exp())
+            .collect::<Vec<_>>();
+        let mut rng = if info.deterministic {
+            StdRng::seed_from_u64(self.hashkey())
+        } else {
+            StdRng::from_entropy()
+        };
+        let mut target = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (&(m, _), w) in candidates.iter().zip(weights) {
+            if target < w {
+                return m;
+            }
+            target -= w;
+        }
+        best_move
+    }
+
+    pub fn get_first_legal_move(&mut self) -> Option<Move> {
         let mut move_list = MoveList::new();
         self.generate_moves(&mut move_list);
         let mut first_legal = None;
@@ -1387,6 +2200,22 @@ impl Board {
         }
         first_legal
     }
+
+    /// The number of legal moves in the position.
+    pub fn legal_move_count(&mut self) -> usize {
+        let mut move_list = MoveList::new();
+        self.generate_moves(&mut move_list);
+        move_list
+            .into_iter()
+            .filter(|&m| {
+                let legal = self.make_move(m);
+                if legal {
+                    self.unmake_move();
+                }
+                legal
+            })
+            .count()
+    }
 }
 
 impl Default for Board {
@@ -1406,7 +2235,7 @@ impl Display for Board {
             for file in (File::FILE_A)..=(File::FILE_H) {
                 let sq = filerank_to_square(file, rank);
                 let piece = self.piece_at(sq);
-                write!(f, "{} ", piece_char(piece).unwrap() as char)?;
+                write!(f, "{} ", piece_char(piece).unwrap())?;
             }
             writeln!(f)?;
         }
@@ -1429,14 +2258,528 @@ mod tests {
         board_1
             .set_from_fen(Board::STARTING_FEN)
             .expect("setfen failed.");
-        board_1.check_validity().unwrap();
+        board_1.validate().unwrap();
 
         let board_2 = Board::from_fen(Board::STARTING_FEN).expect("setfen failed.");
-        board_2.check_validity().unwrap();
+        board_2.validate().unwrap();
 
         assert_eq!(board_1, board_2);
     }
 
+    #[test]
+    fn illegal_fens_are_rejected() {
+        use super::Board;
+        use crate::errors::ValidationError;
+
+        crate::magic::initialise();
+
+        let errors_of = |fen: &str| {
+            let mut board = Board::new();
+            assert!(board.set_from_fen(fen).is_err(), "accepted {fen}");
+            board.parse_fen(fen).unwrap();
+            board.validate().unwrap_err()
+        };
+
+        let no_black_king = ValidationError::KingCount {
+            colour: 1,
+            count: 0,
+        };
+        assert!(errors_of("8/8/8/8/8/8/8/4K3 w - - 0 1").contains(&no_black_king));
+        let two_black_kings = ValidationError::KingCount {
+            colour: 1,
+            count: 2,
+        };
+        assert!(errors_of("k3k3/8/8/8/8/8/8/4K3 w - - 0 1").contains(&two_black_kings));
+        assert_eq!(
+            errors_of("8/8/8/8/8/3k4/3K4/8 w - - 0 1"),
+            [ValidationError::KingsAdjacent]
+        );
+        assert_eq!(
+            errors_of("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            [
+                ValidationError::PawnOnBackRank(7),
+                ValidationError::PawnOnBackRank(56)
+            ]
+        );
+        assert_eq!(
+            errors_of("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            [ValidationError::OpponentInCheck]
+        );
+        assert_eq!(
+            errors_of("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            [ValidationError::InvalidEnPassant(20)]
+        );
+    }
+
+    #[test]
+    fn rejected_positions_keep_the_old_one() {
+        use super::Board;
+        use crate::{
+            definitions::{Depth, Square::NO_SQUARE, WHITE, WK},
+            searchinfo::SearchInfo,
+            uci::OutputFormat,
+        };
+
+        crate::magic::initialise();
+
+        let mut board = Board::default();
+        board.reset_tables();
+        for san in ["e2e4", "e7e5"] {
+            let m = board.parse_san(san).unwrap();
+            assert!(board.make_move(m));
+            board.zero_height();
+        }
+        let fen = board.fen();
+
+        assert!(board.set_from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(board.set_from_fen("8/8/8/8/8/8/8/4K3").is_err());
+        let mut squares = [0; 64];
+        squares[4] = WK;
+        assert!(board.set_from_parts(&squares, WHITE, 0, NO_SQUARE, 0, 1).is_err());
+        board.validate().unwrap();
+        assert_eq!(board.fen(), fen);
+        assert_eq!(board.ply(), 2);
+
+        let mut info = SearchInfo {
+            depth: Depth::new(3),
+            output_format: OutputFormat::Silent,
+            ..SearchInfo::default()
+        };
+        let (_, best_move) = board.search_position(&mut info);
+        assert!(board.make_move(best_move));
+    }
+
+    #[test]
+    fn long_game_replay() {
+        use super::Board;
+
+        crate::magic::initialise();
+
+        // 300 moves of shuffling knights, far past the fifty-move rule.
+        let mut board = Board::from_fen(Board::STARTING_FEN).unwrap();
+        board.reset_tables();
+        for san in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(600) {
+            let m = board.parse_san(san).unwrap();
+            assert!(board.make_move(m));
+            board.zero_height();
+        }
+        assert_eq!(board.ply(), 600);
+        assert_eq!(board.fifty_move_counter(), 600);
+        let fen = board.fen();
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 600 301"
+        );
+        let replayed = Board::from_fen(&fen).unwrap();
+        assert_eq!(replayed.ply(), 600);
+        assert_eq!(replayed.fifty_move_counter(), 600);
+    }
+
+    #[test]
+    fn repetitions_from_the_game_history() {
+        use super::Board;
+
+        crate::magic::initialise();
+
+        let replay = |moves: &[&str]| {
+            let mut board = Board::from_fen(Board::STARTING_FEN).unwrap();
+            board.reset_tables();
+            for san in moves {
+                board.zero_height();
+                let m = board.parse_san(san).unwrap();
+                assert!(board.make_move(m));
+            }
+            board.zero_height();
+            board
+        };
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        // the start position has occurred twice in the game, which is no draw yet.
+        let mut board = replay(&shuffle);
+        for san in &shuffle[..3] {
+            let m = board.parse_san(san).unwrap();
+            assert!(board.make_move(m));
+            assert!(!board.is_draw());
+        }
+        // reaching it within the search counts as a draw...
+        let m = board.parse_san("f6g8").unwrap();
+        assert!(board.make_move(m));
+        assert!(board.is_draw());
+
+        // ...as does a third occurrence, once the root is past the second.
+        let mut board = replay(&[&shuffle[..], &shuffle[..3]].concat());
+        let m = board.parse_san("f6g8").unwrap();
+        assert!(board.make_move(m));
+        assert!(board.is_draw());
+
+        // a position that only repeats from before the root is not a draw.
+        let mut board = replay(&shuffle[..2]);
+        for san in ["f3g1", "f6g8"] {
+            let m = board.parse_san(san).unwrap();
+            assert!(board.make_move(m));
+        }
+        board.zero_height();
+        let m = board.parse_san("g1f3").unwrap();
+        assert!(board.make_move(m));
+        assert!(!board.is_draw());
+    }
+
+    #[test]
+    fn corrupt_state_is_reported() {
+        use super::{Board, S};
+        use crate::{definitions::WHITE, errors::ValidationError};
+
+        crate::magic::initialise();
+
+        let start = || Board::from_fen(Board::STARTING_FEN).unwrap();
+
+        let mut board = start();
+        board.side = 2;
+        assert!(board
+            .validate()
+            .unwrap_err()
+            .contains(&ValidationError::InvalidSide(2)));
+
+        let mut board = start();
+        let key = board.key;
+        board.key ^= 1;
+        assert_eq!(
+            board.validate(),
+            Err(vec![ValidationError::KeyCorrupt {
+                expected: key,
+                found: key ^ 1
+            }])
+        );
+
+        let mut board = start();
+        board.material[WHITE as usize] = S(0, 0);
+        assert!(matches!(
+            board.validate().unwrap_err()[..],
+            [ValidationError::MaterialCorrupt { colour: 0, .. }]
+        ));
+
+        let mut board = start();
+        board.minor_piece_counts[WHITE as usize] += 1;
+        assert_eq!(
+            board.validate(),
+            Err(vec![ValidationError::PieceCountCorrupt {
+                kind: "minor",
+                colour: 0,
+                expected: 4,
+                found: 5
+            }])
+        );
+
+        let mut board = start();
+        board.phase += 1;
+        assert_eq!(
+            board.validate(),
+            Err(vec![ValidationError::PhaseCorrupt {
+                expected: 0,
+                found: 1
+            }])
+        );
+
+        // a knight that only the piece array knows has gone.
+        let mut board = start();
+        board.piece_array[1] = 0;
+        let errors = board.validate().unwrap_err();
+        assert!(errors.contains(&ValidationError::PieceListCorrupt {
+            sq: 1,
+            expected: 2,
+            found: 0
+        }));
+        assert!(errors.contains(&ValidationError::BitboardCorrupt {
+            sq: 1,
+            expected: 2,
+            found: 0
+        }));
+        assert!(errors.contains(&ValidationError::ColourBitboardCorrupt {
+            sq: 1,
+            colour: WHITE,
+            found: 0
+        }));
+        assert!(errors
+            .iter()
+            .any(|e| matches!(e, ValidationError::PieceCountsCorrupt { .. })));
+    }
+
+    #[test]
+    fn piece_iterators() {
+        use super::Board;
+        use crate::definitions::{
+            Square::{B1, G1, G8},
+            BLACK, BN, WHITE, WN, WQ,
+        };
+
+        crate::magic::initialise();
+
+        let board =
+            Board::from_fen("rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+        assert_eq!(board.pieces_of(WN).collect::<Vec<_>>(), [B1, G1]);
+        assert_eq!(board.pieces_of(WQ).count(), 0);
+        assert!(board.pieces_of(BN).all(|sq| sq != G8));
+        assert_eq!(board.all_pieces(WHITE).count(), 15);
+        assert_eq!(board.all_pieces(BLACK).count(), 16);
+        assert!(board
+            .all_pieces(BLACK)
+            .all(|sq| crate::definitions::colour_of(board.piece_at(sq)) == BLACK));
+    }
+
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn sq_attacked_matches_reference() {
+        use super::Board;
+        use crate::{
+            board::movegen::MoveList,
+            definitions::{
+                colour_of, type_of, BISHOP, BLACK, KING, KNIGHT, PAWN, PIECE_EMPTY, QUEEN, ROOK,
+                WHITE,
+            },
+            rng::XorShiftState,
+        };
+
+        /// Looks for attackers by walking out from the square one step at a time.
+        fn attacked_slowly(board: &Board, sq: u8, side: u8) -> bool {
+            let (file, rank) = (i32::from(sq % 8), i32::from(sq / 8));
+            let piece_on = |df: i32, dr: i32| {
+                let (f, r) = (file + df, rank + dr);
+                #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                ((0..8).contains(&f) && (0..8).contains(&r))
+                    .then(|| board.piece_at((r * 8 + f) as u8))
+            };
+            let is = |piece: Option<u8>, types: &[u8]| {
+                piece.is_some_and(|p| {
+                    p != PIECE_EMPTY && colour_of(p) == side && types.contains(&type_of(p))
+                })
+            };
+            // a pawn attacks from the rank behind the square, as seen by its own side.
+            let behind = if side == WHITE { -1 } else { 1 };
+            if is(piece_on(-1, behind), &[PAWN]) || is(piece_on(1, behind), &[PAWN]) {
+                return true;
+            }
+            let knight_jumps = [
+                (1, 2),
+                (2, 1),
+                (2, -1),
+                (1, -2),
+                (-1, -2),
+                (-2, -1),
+                (-2, 1),
+                (-1, 2),
+            ];
+            if knight_jumps
+                .iter()
+                .any(|&(df, dr)| is(piece_on(df, dr), &[KNIGHT]))
+            {
+                return true;
+            }
+            let orthogonals = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+            let diagonals = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+            if orthogonals
+                .iter()
+                .chain(&diagonals)
+                .any(|&(df, dr)| is(piece_on(df, dr), &[KING]))
+            {
+                return true;
+            }
+            for (directions, sliders) in
+                [(orthogonals, [ROOK, QUEEN]), (diagonals, [BISHOP, QUEEN])]
+            {
+                for (df, dr) in directions {
+                    for step in 1..8 {
+                        match piece_on(df * step, dr * step) {
+                            None => break,
+                            Some(PIECE_EMPTY) => {}
+                            piece => {
+                                if is(piece, &sliders) {
+                                    return true;
+                                }
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            false
+        }
+
+        crate::magic::initialise();
+
+        let fens = [
+            Board::STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "7k/8/8/8/8/8/8/K7 w - - 0 1",
+        ];
+        let mut rng = XorShiftState::new();
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+            board.reset_tables();
+            // a random walk from each position, checking every square for both sides on the way.
+            for _ in 0..80 {
+                for sq in 0..64 {
+                    for side in [WHITE, BLACK] {
+                        assert_eq!(
+                            board.sq_attacked(sq, side),
+                            attacked_slowly(&board, sq, side),
+                            "square {sq} attacked by side {side} in {}",
+                            board.fen()
+                        );
+                    }
+                }
+                let mut list = MoveList::new();
+                board.generate_moves(&mut list);
+                let mut moves = list.into_iter().collect::<Vec<_>>();
+                let mut made = false;
+                while !moves.is_empty() && !made {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let m = moves.swap_remove(rng.next() as usize % moves.len());
+                    made = board.make_move(m);
+                }
+                if !made {
+                    break;
+                }
+                board.zero_height();
+            }
+        }
+    }
+
+    #[test]
+    fn zobrist_keys_are_consistent() {
+        use super::{movegen::MoveList, Board};
+        use crate::rng::XorShiftState;
+        use std::collections::HashMap;
+
+        crate::magic::initialise();
+
+        let fens = [
+            Board::STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+        // the same position with and without an en passant square or a castling right is different.
+        for (a, b) in [
+            (
+                fens[3],
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3",
+            ),
+            (
+                fens[1],
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQk - 0 1",
+            ),
+        ] {
+            let a = Board::from_fen(a).unwrap().hashkey();
+            assert_ne!(a, Board::from_fen(b).unwrap().hashkey());
+        }
+
+        // every position reached in random playouts has a key that matches one computed from
+        // scratch, and positions that differ in anything but the move counters never share a key.
+        let mut seen = HashMap::new();
+        let mut rng = XorShiftState::new();
+        let mut board = Board::new();
+        board.reset_tables();
+        for fen in fens {
+            for _ in 0..20 {
+                board.set_from_fen(fen).unwrap();
+                let start_key = board.hashkey();
+                let mut plies = 0;
+                for _ in 0..60 {
+                    let mut list = MoveList::new();
+                    board.generate_moves(&mut list);
+                    let mut moves = list.into_iter().collect::<Vec<_>>();
+                    let mut made = false;
+                    while !moves.is_empty() && !made {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let m = moves.swap_remove(rng.next() as usize % moves.len());
+                        made = board.make_move(m);
+                    }
+                    if !made {
+                        break;
+                    }
+                    plies += 1;
+                    assert_eq!(board.hashkey(), board.generate_pos_key(), "{}", board.fen());
+                    let fen = board.fen();
+                    let position = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+                    let previous = seen
+                        .entry(board.hashkey())
+                        .or_insert_with(|| position.clone());
+                    assert_eq!(*previous, position, "key collision");
+                }
+                for _ in 0..plies {
+                    board.unmake_move();
+                }
+                assert_eq!(board.hashkey(), start_key);
+            }
+        }
+    }
+
+    #[test]
+    fn pins_and_discovered_checks() {
+        use super::Board;
+        use crate::definitions::{
+            Square::{C3, D2, E2, E4, G3},
+            BLACK, WHITE,
+        };
+
+        crate::magic::initialise();
+
+        let bb = |squares: &[u8]| squares.iter().fold(0u64, |bb, &sq| bb | 1 << sq);
+
+        // the knight on e4 is pinned by the rook, and the black knight on g3
+        // would uncover a check from the queen.
+        let board = Board::from_fen("4k3/8/4r3/8/4N2q/6n1/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.pinned(WHITE), bb(&[E4]));
+        assert_eq!(board.pinned(BLACK), 0);
+        assert_eq!(board.discovered_check_candidates(), bb(&[G3]));
+
+        // two pieces on the line mean neither is pinned, and the
+        // black pawn on e5 doesn't count as a white pin.
+        let board = Board::from_fen("4k3/8/4r3/4p3/4N3/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pinned(WHITE), 0);
+        let board = Board::from_fen("4k3/8/4r3/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pinned(WHITE), bb(&[E2]));
+
+        // a diagonal pin, and a discovered check from white's bishop.
+        let board = Board::from_fen("8/8/8/7k/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pinned(WHITE), bb(&[D2]));
+        let board = Board::from_fen("7k/8/8/8/8/2N5/8/B3K3 w - - 0 1").unwrap();
+        assert_eq!(board.discovered_check_candidates(), bb(&[C3]));
+        assert_eq!(board.pinned(BLACK), 0);
+    }
+
+    #[test]
+    fn threatened_major_pieces() {
+        use super::Board;
+
+        crate::magic::initialise();
+
+        let start = Board::from_fen(Board::STARTING_FEN).unwrap();
+        assert!(!start.has_threatened_major_piece());
+
+        // a rook attacked by a knight, though it is defended.
+        let knight_fork = Board::from_fen("4k3/8/8/8/8/1n6/8/R3K3 w - - 0 1").unwrap();
+        assert!(knight_fork.has_threatened_major_piece());
+
+        // a queen attacked by a rook, though it is defended.
+        let queen_attacked = Board::from_fen("3rk3/8/8/8/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        assert!(queen_attacked.has_threatened_major_piece());
+
+        // a rook attacked by a rook, but defended.
+        let rooks_traded = Board::from_fen("3rk3/8/8/8/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        assert!(!rooks_traded.has_threatened_major_piece());
+
+        // a rook attacked by a rook, and undefended.
+        let rook_hanging = Board::from_fen("3rk3/8/8/8/8/8/3R4/7K w - - 0 1").unwrap();
+        assert!(rook_hanging.has_threatened_major_piece());
+
+        // the threatened rook belongs to the side not to move.
+        let their_rook = Board::from_fen("4k3/8/8/8/8/1n6/8/R3K3 b - - 0 1").unwrap();
+        assert!(!their_rook.has_threatened_major_piece());
+    }
+
     #[test]
     fn fen_round_trip() {
         use crate::board::Board;
@@ -1456,4 +2799,51 @@ mod tests {
             assert_eq!(fen, fen_2);
         }
     }
+
+    #[test]
+    fn pv_walk_stops_at_cycles() {
+        use super::Board;
+        use crate::{
+            definitions::{Depth, Score},
+            transpositiontable::HFlag,
+        };
+
+        crate::magic::initialise();
+        let mut board = Board::default();
+        board.reset_tables();
+        // the knights go out and back, so the stored line leads round in a circle.
+        let line = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for san in line {
+            let m = board.parse_san(san).unwrap();
+            board.tt_store(m, Score::DRAW, HFlag::Exact, Depth::new(10));
+            board.make_move(m);
+        }
+        for _ in line {
+            board.unmake_move();
+        }
+        board.zero_height();
+        board.regenerate_pv_line(100);
+        assert_eq!(board.principal_variation.len(), line.len());
+        board.regenerate_pv_line(2);
+        assert_eq!(board.principal_variation.len(), 2);
+    }
+
+    #[test]
+    fn tables_fit_in_the_hash_budget() {
+        use super::Board;
+        use crate::transpositiontable::MEGABYTE;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        assert_eq!(board.resize_tt(16), 16);
+        board.reset_tables();
+        let usage = board.memory_usage();
+        assert!(usage.history > 0 && usage.followup_history > 0 && usage.counter_moves > 0);
+        let tables = usage.tt + usage.history + usage.followup_history + usage.counter_moves;
+        assert!(tables <= 16 * MEGABYTE);
+        assert!(tables > 15 * MEGABYTE);
+        assert!(usage.to_string().starts_with(&format!("tt {} KB", usage.tt.div_ceil(1024))));
+        // the fixed tables don't fit in a megabyte, so more than that is used.
+        assert!(board.resize_tt(1) > 1);
+    }
 }
@@ -1,4 +1,6 @@
-// The granularity of evaluation in this engine is in centipawns.
+// The granularity of evaluation in this engine is set by `ONE_PAWN`.
+// Weights, margins and thresholds are all written in centipawns and
+// converted with `centipawns`, so they follow it if it changes.
 
 pub mod parameters;
 pub mod score;
@@ -9,13 +11,19 @@ use score::S;
 use crate::{
     board::Board,
     definitions::{
-        BB, BISHOP, BLACK, BN, BP, BQ, BR, KNIGHT, MAX_DEPTH, QUEEN, ROOK, WB, WHITE, WN, WP, WQ,
-        WR,
+        square_distance, Score, BB, BISHOP, BK, BLACK, BN, BP, BQ, BR, KING, KNIGHT, QUEEN, ROOK,
+        WB, WHITE, WK, WN, WP, WQ, WR,
     },
-    lookups::{file, init_eval_masks, init_passed_isolated_bb, rank},
+    lookups::{file, init_eval_masks, init_passed_isolated_bb, init_pawn_attack_spans, rank},
 };
 
-use super::movegen::{bitboards::attacks, BitLoop, BB_NONE};
+use super::{
+    movegen::{
+        bitboards::{attacks, BB_LIGHT_SQUARES, BB_RANK_3, BB_RANK_4, BB_RANK_5, BB_RANK_6},
+        BitLoop, BB_NONE,
+    },
+    variant::Variant,
+};
 
 pub const PAWN_VALUE: S = S(93, 121);
 pub const KNIGHT_VALUE: S = S(358, 308);
@@ -23,20 +31,25 @@ pub const BISHOP_VALUE: S = S(342, 301);
 pub const ROOK_VALUE: S = S(482, 511);
 pub const QUEEN_VALUE: S = S(1052, 957);
 
+/// The value of a pawn in the units the evaluation and the search work in.
 pub const ONE_PAWN: i32 = 100;
 
-/// The value of checkmate.
-/// To recover depth-to-mate, we subtract depth (ply) from this value.
-/// e.g. if white has a mate in two ply, the output from a depth-5 search will be
-/// `3_000_000 - 2 = 2_999_998`.
-pub const MATE_SCORE: i32 = 3_000_000;
+/// Converts a value in centipawns into evaluation units.
+pub const fn centipawns(cp: i32) -> i32 {
+    cp * ONE_PAWN / 100
+}
 
-/// A threshold over which scores must be mate.
-#[allow(clippy::cast_possible_truncation)]
-pub const IS_MATE_SCORE: i32 = MATE_SCORE - MAX_DEPTH.ply_to_horizon() as i32;
+/// Converts a score in evaluation units into centipawns, for output.
+pub const fn to_centipawns(score: i32) -> i32 {
+    score * 100 / ONE_PAWN
+}
 
-/// The value of a draw.
-pub const DRAW_SCORE: i32 = 0;
+/// If the score after material and piece-square tables is this far
+/// outside of the window, evaluation stops there.
+pub const LAZY_THRESHOLD_1: i32 = 7 * ONE_PAWN;
+/// If the score after pawn structure is this far outside of the window,
+/// evaluation stops there.
+pub const LAZY_THRESHOLD_2: i32 = 5 * ONE_PAWN;
 
 #[rustfmt::skip]
 pub static PIECE_VALUES: [S; 13] = [
@@ -54,6 +67,21 @@ pub const DOUBLED_PAWN_MALUS: S = S(29, 9);
 /// The bonus granted for having two bishops.
 pub const BISHOP_PAIR_BONUS: S = S(43, 74);
 
+/// The bonus for a knight on an outpost: a square in the middle of the board,
+/// defended by a pawn, that no enemy pawn can ever attack.
+pub const KNIGHT_OUTPOST_BONUS: S = S(20, 10);
+/// The malus for each pawn of a bishop's own colour, on its colour of square, that is
+/// blocked by an enemy pawn.
+pub const BAD_BISHOP_MALUS: S = S(4, 6);
+/// The bonus for each knight, and the malus for each bishop, per pawn on the board
+/// beyond eight. Knights do better in closed positions, and bishops in open ones.
+pub const KNIGHT_CLOSED_POSITION_BONUS: S = S(2, 2);
+
+/// In pawn endgames, the bonus per step the king is closer to the centre than the enemy king.
+pub const KING_CENTRALISATION_BONUS: S = S(10, 10);
+/// In pawn endgames, the bonus for a passed pawn that the enemy king can't catch.
+pub const UNSTOPPABLE_PASSER_BONUS: S = S(400, 400);
+
 /// The bonus for having a rook on an open file.
 pub const ROOK_OPEN_FILE_BONUS: S = S(51, 0);
 /// The bonus for having a rook on a semi-open file.
@@ -63,6 +91,24 @@ pub const QUEEN_OPEN_FILE_BONUS: S = S(-1, 0);
 /// The bonus for having a queen on a semi-open file.
 pub const QUEEN_HALF_OPEN_FILE_BONUS: S = S(7, 0);
 
+/// The bonus for each pair of rooks or queens that defend each other along a file.
+pub const ROOK_BATTERY_BONUS: S = S(12, 8);
+/// The bonus for each bishop or queen lined up behind another on a diagonal
+/// that runs on into the enemy king's zone.
+pub const DIAGONAL_BATTERY_BONUS: S = S(18, 4);
+/// The bonus for each enemy king, queen or rook (attacked by a bishop) that a
+/// sliding piece attacks through an enemy piece, as in a pin or a skewer.
+pub const XRAY_PRESSURE_BONUS: S = S(10, 6);
+
+/// With the kings castled on opposite wings, the bonus per rank that each pawn
+/// has advanced on the files around the enemy king.
+pub const OPPOSITE_CASTLING_STORM_BONUS: S = S(9, 0);
+/// With the kings castled on opposite wings, the bonus for each piece attacking the enemy king's zone.
+pub const OPPOSITE_CASTLING_ATTACKER_BONUS: S = S(14, 0);
+/// With the kings castled on opposite wings, the malus for each pawn on the d- and e-files,
+/// as the race on the wings matters more than the centre.
+pub const OPPOSITE_CASTLING_CENTRE_PAWN_MALUS: S = S(8, 0);
+
 // nonlinear mobility eval tables.
 #[rustfmt::skip]
 const KNIGHT_MOBILITY_BONUS: [S; 9] = [S(-103, -120), S(-37, -26), S(3, -24), S(13, 24), S(1, 48), S(-8, 54), S(9, 57), S(25, 62), S(38, 65)];
@@ -83,14 +129,40 @@ pub static PASSED_PAWN_BONUS: [S; 6] = [
     S(113, 134),
 ];
 
+/// Non-linear interactions between the pieces on the board, each weighted by a feature
+/// that is a product of piece counts, taken for white minus the same for black:
+/// - knights, times own pawns beyond five, as knights gain value with more pawns.
+/// - rooks, times own pawns beyond five, as rooks lose value with more pawns.
+/// - pairs of rooks, as a second rook adds less than the first.
+/// - queens, times enemy minor pieces, as a queen is at its worst against many minors.
+pub const MATERIAL_IMBALANCE: [S; 4] = [S(6, 6), S(-12, -12), S(-16, -24), S(-4, -8)];
+
 const PAWN_PHASE: i32 = 1;
 const KNIGHT_PHASE: i32 = 10;
 const BISHOP_PHASE: i32 = 10;
 const ROOK_PHASE: i32 = 20;
 const QUEEN_PHASE: i32 = 40;
-const TOTAL_PHASE: i32 =
+pub const TOTAL_PHASE: i32 =
     16 * PAWN_PHASE + 4 * KNIGHT_PHASE + 4 * BISHOP_PHASE + 4 * ROOK_PHASE + 2 * QUEEN_PHASE;
 
+/// How much each piece moves the game towards the endgame when it leaves the board.
+/// The board keeps `TOTAL_PHASE` minus the sum of these, updating it in make-unmake.
+pub static PIECE_PHASE: [i32; 13] = [
+    0,
+    PAWN_PHASE,
+    KNIGHT_PHASE,
+    BISHOP_PHASE,
+    ROOK_PHASE,
+    QUEEN_PHASE,
+    0,
+    PAWN_PHASE,
+    KNIGHT_PHASE,
+    BISHOP_PHASE,
+    ROOK_PHASE,
+    QUEEN_PHASE,
+    0,
+];
+
 #[allow(dead_code)]
 pub static RANK_BB: [u64; 8] = init_eval_masks().0;
 pub static FILE_BB: [u64; 8] = init_eval_masks().1;
@@ -100,6 +172,9 @@ pub static BLACK_PASSED_BB: [u64; 64] = init_passed_isolated_bb().1;
 
 pub static ISOLATED_BB: [u64; 64] = init_passed_isolated_bb().2;
 
+pub static WHITE_ATTACK_SPAN_BB: [u64; 64] = init_pawn_attack_spans().0;
+pub static BLACK_ATTACK_SPAN_BB: [u64; 64] = init_pawn_attack_spans().1;
+
 /// `game_phase` computes a number between 0 and 256, which is the phase of the game.
 /// 0 is the opening, 256 is the endgame.
 #[allow(clippy::many_single_char_names)]
@@ -121,49 +196,94 @@ pub fn lerp(mg: i32, eg: i32, t: i32) -> i32 {
     mg * (256 - t) / 256 + eg * t / 256
 }
 
-pub const fn is_mate_score(score: i32) -> bool {
-    score.abs() >= IS_MATE_SCORE
-}
-
 impl Board {
+    /// Switches to new evaluation parameters, rescoring the current position with them.
     pub fn set_eval_params(&mut self, params: Parameters) {
         self.eval_params = params;
+        self.refresh_incremental_scores();
+    }
+
+    pub const fn eval_params(&self) -> &Parameters {
+        &self.eval_params
     }
 
     /// Computes a score for the position, from the point of view of the side to move.
     /// This function should strive to be as cheap to call as possible, relying on
     /// incremental updates in make-unmake to avoid recomputation.
-    pub fn evaluate(&mut self) -> i32 {
-        #![allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn evaluate(&self) -> Score {
+        // with an infinite window, the lazy evaluation never exits early.
+        self.lazy_evaluate(-Score::INFINITY, Score::INFINITY)
+    }
 
-        if !self.pieces.any_pawns() && self.is_material_draw() {
+    /// Computes a score for the position, from the point of view of the side to move,
+    /// in stages of increasing cost. If the score so far is far enough outside of
+    /// the window `(alpha, beta)` that the remaining terms are unlikely to bring it
+    /// back inside, the partial score is returned without computing them.
+    pub fn lazy_evaluate(&self, alpha: Score, beta: Score) -> Score {
+        if self.is_drawn_by_material() {
             return if self.side == WHITE {
-                DRAW_SCORE
+                Score::DRAW
             } else {
-                -DRAW_SCORE
+                -Score::DRAW
             };
         }
+        let (alpha, beta) = (alpha.raw(), beta.raw());
+        let phase = self.phase();
+
+        // stage one: material, piece-square tables, the bishop pair, and material imbalance.
         let material = self.material[WHITE as usize] - self.material[BLACK as usize];
         let pst = self.pst_vals;
+        let bishop_pair_val = self.bishop_pair_term();
+
+        let imbalance_val = self.imbalance_term();
 
         let mut score = material + pst;
+        score += bishop_pair_val;
+        score += imbalance_val;
+        score += self.variant_term();
 
+        let partial = self.finalise_score(score.value(phase));
+        if partial - LAZY_THRESHOLD_1 >= beta || partial + LAZY_THRESHOLD_1 <= alpha {
+            return Score::new(partial);
+        }
+
+        // stage two: pawn structure, and king activity in pawn endgames.
         let pawn_val = self.pawn_structure_term(); // INCREMENTAL UPDATE.
-        let bishop_pair_val = self.bishop_pair_term();
+        let pawn_endgame_val = self.pawn_endgame_term();
+
+        score += pawn_val;
+        score += pawn_endgame_val;
+
+        let partial = self.finalise_score(score.value(phase));
+        if partial - LAZY_THRESHOLD_2 >= beta || partial + LAZY_THRESHOLD_2 <= alpha {
+            return Score::new(partial);
+        }
+
+        // stage three: piece activity, which requires generating attacks.
         let mobility_val = self.mobility();
         let rook_open_file_val = self.rook_open_file_term();
         let queen_open_file_val = self.queen_open_file_term();
+        let minor_piece_val = self.minor_piece_term();
+        let coordination_val = self.coordination_term();
+        let opposite_castling_val = self.opposite_castling_term();
 
-        score += pawn_val;
-        score += bishop_pair_val;
         score += mobility_val;
         score += rook_open_file_val;
         score += queen_open_file_val;
+        score += minor_piece_val;
+        score += coordination_val;
+        score += opposite_castling_val;
 
-        let score = score.value(self.phase());
+        Score::new(self.finalise_score(score.value(phase)))
+    }
 
+    /// Converts a white-relative score into the final evaluation from the
+    /// point of view of the side to move, applying draw and fifty-move scaling.
+    fn finalise_score(&self, score: i32) -> i32 {
         let score = self.clamp_score(score);
 
+        let score = self.fifty_move_scale(score);
+
         if self.side == WHITE {
             score
         } else {
@@ -177,29 +297,25 @@ impl Board {
             "unwinnable_for called with invalid side"
         );
 
-        if SIDE == WHITE {
-            if self.major_piece_counts[WHITE as usize] != 0 {
-                return false;
-            }
-            if self.minor_piece_counts[WHITE as usize] > 1 {
-                return false;
-            }
-            if self.num(WP) != 0 {
-                return false;
-            }
-            true
-        } else {
-            if self.major_piece_counts[BLACK as usize] != 0 {
-                return false;
-            }
-            if self.minor_piece_counts[BLACK as usize] > 1 {
-                return false;
-            }
-            if self.num(BP) != 0 {
-                return false;
-            }
-            true
+        let (side, pawn) = if SIDE == WHITE { (WHITE, WP) } else { (BLACK, BP) };
+        if self.major_piece_counts[side as usize] != 0 {
+            return false;
+        }
+        if self.minor_piece_counts[side as usize] > 1 {
+            return false;
         }
+        if self.num(pawn) != 0 {
+            return false;
+        }
+        true
+    }
+
+    /// Whether neither side has enough material to force a win.
+    /// In the variants, a king alone can still win, so no material is drawn.
+    pub const fn is_drawn_by_material(&self) -> bool {
+        matches!(self.variant, Variant::Standard)
+            && !self.pieces.any_pawns()
+            && self.is_material_draw()
     }
 
     const fn is_material_draw(&self) -> bool {
@@ -237,9 +353,11 @@ impl Board {
         false
     }
 
-    fn clamp_score(&mut self, score: i32) -> i32 {
+    fn clamp_score(&self, score: i32) -> i32 {
         // if we can't win with our material, we clamp the eval to zero.
-        if score > 0 && self.unwinnable_for::<{ WHITE }>()
+        if self.variant != Variant::Standard {
+            score
+        } else if score > 0 && self.unwinnable_for::<{ WHITE }>()
             || score < 0 && self.unwinnable_for::<{ BLACK }>()
         {
             0
@@ -248,6 +366,15 @@ impl Board {
         }
     }
 
+    /// Scales the score towards zero as the fifty-move counter increases,
+    /// so that if we're winning, we prefer lines that make progress
+    /// (pawn moves and captures) rather than shuffling pieces around.
+    /// At the point of a fifty-move draw, the score is halved.
+    const fn fifty_move_scale(&self, score: i32) -> i32 {
+        let halfmoves = if self.fifty_move_counter > 100 { 100 } else { self.fifty_move_counter as i32 };
+        score * (200 - halfmoves) / 200
+    }
+
     pub const fn zugzwang_unlikely(&self) -> bool {
         const ENDGAME_PHASE: i32 = game_phase(3, 0, 0, 2, 0);
         self.big_piece_counts[self.side as usize] > 0 && self.phase() < ENDGAME_PHASE
@@ -268,13 +395,34 @@ impl Board {
         S(0, 0)
     }
 
+    fn imbalance_term(&self) -> S {
+        let count = |piece| i32::from(self.num(piece));
+        let features = |pawn, knight, rook, queen, enemy_minors: i32| {
+            let pawns_beyond_five = count(pawn) - 5;
+            let rooks = count(rook);
+            [
+                count(knight) * pawns_beyond_five,
+                rooks * pawns_beyond_five,
+                rooks * (rooks - 1) / 2,
+                count(queen) * enemy_minors,
+            ]
+        };
+        let white = features(WP, WN, WR, WQ, count(BN) + count(BB));
+        let black = features(BP, BN, BR, BQ, count(WN) + count(WB));
+        let mut score = S(0, 0);
+        for ((w, b), &weight) in white.into_iter().zip(black).zip(&self.eval_params.material_imbalance) {
+            score += weight * (w - b);
+        }
+        score
+    }
+
     fn pawn_structure_term(&self) -> S {
         /// not a tunable parameter, just how "number of pawns in a file" is mapped to "amount of doubled pawn-ness"
         static DOUBLED_PAWN_MAPPING: [i32; 7] = [0, 0, 1, 2, 3, 4, 5];
         let mut w_score = S(0, 0);
         let (white_pawns, black_pawns) =
             (self.pieces.pawns::<true>(), self.pieces.pawns::<false>());
-        for &white_pawn_loc in self.piece_lists[WP as usize].iter() {
+        for white_pawn_loc in self.pieces_of(WP) {
             if ISOLATED_BB[white_pawn_loc as usize] & white_pawns == 0 {
                 w_score -= self.eval_params.isolated_pawn_malus;
             }
@@ -286,7 +434,7 @@ impl Board {
         }
 
         let mut b_score = S(0, 0);
-        for &black_pawn_loc in self.piece_lists[BP as usize].iter() {
+        for black_pawn_loc in self.pieces_of(BP) {
             if ISOLATED_BB[black_pawn_loc as usize] & black_pawns == 0 {
                 b_score -= self.eval_params.isolated_pawn_malus;
             }
@@ -309,6 +457,77 @@ impl Board {
         w_score - b_score
     }
 
+    /// King centralisation and pawn races, for positions with only kings and pawns.
+    fn pawn_endgame_term(&self) -> S {
+        /// How far a square is from the four central squares, in files plus ranks.
+        const fn centre_distance(sq: u8) -> i32 {
+            let file = file(sq) as i32;
+            let rank = rank(sq) as i32;
+            let file_distance = if file < 4 { 3 - file } else { file - 4 };
+            let rank_distance = if rank < 4 { 3 - rank } else { rank - 4 };
+            file_distance + rank_distance
+        }
+
+        if self.big_piece_counts != [0, 0] {
+            return S(0, 0);
+        }
+        let white_king = self.king_sq(WHITE);
+        let black_king = self.king_sq(BLACK);
+        let mut score = self.eval_params.king_centralisation_bonus
+            * (centre_distance(black_king) - centre_distance(white_king));
+
+        let white_race = self.fastest_unstoppable_passer::<WHITE>(black_king);
+        let black_race = self.fastest_unstoppable_passer::<BLACK>(white_king);
+        // if both sides have a pawn that can't be caught, both will queen.
+        match (white_race, black_race) {
+            (Some(_), None) => score += self.eval_params.unstoppable_passer_bonus,
+            (None, Some(_)) => score -= self.eval_params.unstoppable_passer_bonus,
+            _ => (),
+        }
+        score
+    }
+
+    /// The number of moves the fastest passed pawn of `SIDE` needs to queen, if the
+    /// enemy king is outside its square (the rule of the square) and nothing is in its way.
+    fn fastest_unstoppable_passer<const SIDE: u8>(&self, enemy_king: u8) -> Option<u8> {
+        let (our_pawns, their_pawns, passed_bb) = if SIDE == WHITE {
+            (
+                self.pieces.pawns::<true>(),
+                self.pieces.pawns::<false>(),
+                &WHITE_PASSED_BB,
+            )
+        } else {
+            (
+                self.pieces.pawns::<false>(),
+                self.pieces.pawns::<true>(),
+                &BLACK_PASSED_BB,
+            )
+        };
+        let mut fastest = None;
+        for pawn_sq in BitLoop::new(our_pawns) {
+            let span = passed_bb[pawn_sq as usize];
+            let path = span & FILE_BB[file(pawn_sq) as usize];
+            if span & their_pawns != 0 || path & self.pieces.occupied() != 0 {
+                continue;
+            }
+            let (promotion_sq, ranks_to_go) = if SIDE == WHITE {
+                (file(pawn_sq) + 56, 7 - rank(pawn_sq))
+            } else {
+                (file(pawn_sq), rank(pawn_sq))
+            };
+            // a pawn on its starting rank can move two squares at once.
+            let moves_to_queen = ranks_to_go.min(5);
+            let mut king_moves = square_distance(enemy_king, promotion_sq);
+            if self.side != SIDE {
+                king_moves = king_moves.saturating_sub(1);
+            }
+            if moves_to_queen < king_moves {
+                fastest = Some(fastest.map_or(moves_to_queen, |f: u8| f.min(moves_to_queen)));
+            }
+        }
+        fastest
+    }
+
     fn is_file_open(&self, file: u8) -> bool {
         let mask = FILE_BB[file as usize];
         let pawns = self.pieces.pawns::<true>() | self.pieces.pawns::<false>();
@@ -327,7 +546,7 @@ impl Board {
 
     fn rook_open_file_term(&self) -> S {
         let mut score = S(0, 0);
-        for &rook_sq in self.piece_lists[WR as usize].iter() {
+        for rook_sq in self.pieces_of(WR) {
             let file = file(rook_sq);
             if self.is_file_open(file) {
                 score += self.eval_params.rook_open_file_bonus;
@@ -335,7 +554,7 @@ impl Board {
                 score += self.eval_params.rook_half_open_file_bonus;
             }
         }
-        for &rook_sq in self.piece_lists[BR as usize].iter() {
+        for rook_sq in self.pieces_of(BR) {
             let file = file(rook_sq);
             if self.is_file_open(file) {
                 score -= self.eval_params.rook_open_file_bonus;
@@ -348,7 +567,7 @@ impl Board {
 
     fn queen_open_file_term(&self) -> S {
         let mut score = S(0, 0);
-        for &queen_sq in self.piece_lists[WQ as usize].iter() {
+        for queen_sq in self.pieces_of(WQ) {
             let file = file(queen_sq);
             if self.is_file_open(file) {
                 score += self.eval_params.queen_open_file_bonus;
@@ -356,7 +575,7 @@ impl Board {
                 score += self.eval_params.queen_half_open_file_bonus;
             }
         }
-        for &queen_sq in self.piece_lists[BQ as usize].iter() {
+        for queen_sq in self.pieces_of(BQ) {
             let file = file(queen_sq);
             if self.is_file_open(file) {
                 score -= self.eval_params.queen_open_file_bonus;
@@ -367,18 +586,160 @@ impl Board {
         score
     }
 
-    /// `phase` computes a number between 0 and 256, which is the phase of the game. 0 is the opening, 256 is the endgame.
+    /// Knight outposts, bad bishops, and how well the minor pieces suit the pawn structure.
+    fn minor_piece_term(&self) -> S {
+        let mut score = S(0, 0);
+        let (white_pawns, black_pawns) =
+            (self.pieces.pawns::<true>(), self.pieces.pawns::<false>());
+
+        let white_outposts =
+            (BB_RANK_4 | BB_RANK_5 | BB_RANK_6) & self.pieces.pawn_attacks::<true>();
+        for knight_sq in BitLoop::new(self.pieces.knights::<true>() & white_outposts) {
+            if WHITE_ATTACK_SPAN_BB[knight_sq as usize] & black_pawns == 0 {
+                score += self.eval_params.knight_outpost_bonus;
+            }
+        }
+        let black_outposts =
+            (BB_RANK_3 | BB_RANK_4 | BB_RANK_5) & self.pieces.pawn_attacks::<false>();
+        for knight_sq in BitLoop::new(self.pieces.knights::<false>() & black_outposts) {
+            if BLACK_ATTACK_SPAN_BB[knight_sq as usize] & white_pawns == 0 {
+                score -= self.eval_params.knight_outpost_bonus;
+            }
+        }
+
+        // pawns that are blocked by an enemy pawn can't get out of their bishop's way.
+        let white_fixed = white_pawns & (black_pawns >> 8);
+        let black_fixed = black_pawns & (white_pawns << 8);
+        let same_colour = |sq: u8| {
+            if BB_LIGHT_SQUARES & (1 << sq) == 0 {
+                !BB_LIGHT_SQUARES
+            } else {
+                BB_LIGHT_SQUARES
+            }
+        };
+        for bishop_sq in BitLoop::new(self.pieces.bishops::<true>()) {
+            let fixed = (white_fixed & same_colour(bishop_sq)).count_ones() as i32;
+            score -= self.eval_params.bad_bishop_malus * fixed;
+        }
+        for bishop_sq in BitLoop::new(self.pieces.bishops::<false>()) {
+            let fixed = (black_fixed & same_colour(bishop_sq)).count_ones() as i32;
+            score += self.eval_params.bad_bishop_malus * fixed;
+        }
+
+        let closedness = (white_pawns | black_pawns).count_ones() as i32 - 8;
+        let white_balance = i32::from(self.num(WN)) - i32::from(self.num(WB));
+        let black_balance = i32::from(self.num(BN)) - i32::from(self.num(BB));
+        score += self.eval_params.knight_closed_position_bonus
+            * (closedness * (white_balance - black_balance));
+
+        score
+    }
+
+    /// Batteries of sliding pieces, and pressure through enemy pieces onto more valuable ones.
+    fn coordination_term(&self) -> S {
+        let (white_files, white_diagonals, white_xrays) = self.coordination_counts::<true>();
+        let (black_files, black_diagonals, black_xrays) = self.coordination_counts::<false>();
+        self.eval_params.rook_battery_bonus * (white_files - black_files)
+            + self.eval_params.diagonal_battery_bonus * (white_diagonals - black_diagonals)
+            + self.eval_params.xray_pressure_bonus * (white_xrays - black_xrays)
+    }
+
+    /// The number of file batteries, diagonal batteries aimed at the enemy king,
+    /// and x-ray attacks that `IS_WHITE`'s pieces make.
+    fn coordination_counts<const IS_WHITE: bool>(&self) -> (i32, i32, i32) {
+        let occupied = self.pieces.occupied();
+        let them = self.pieces.their_pieces::<IS_WHITE>();
+        let (king, queen, rook) = if IS_WHITE { (BK, BQ, BR) } else { (WK, WQ, WR) };
+        let their_king = self.pieces.piece_bb(king);
+        let their_queens = self.pieces.piece_bb(queen);
+        let their_rooks = self.pieces.piece_bb(rook);
+        let king_zone = BitLoop::new(their_king)
+            .next()
+            .map_or(0, |sq| their_king | attacks::<KING>(sq, BB_NONE));
+
+        let rookqueens = self.pieces.rookqueen::<IS_WHITE>();
+        let mut file_partners = 0;
+        let mut xrays = 0;
+        for sq in BitLoop::new(rookqueens) {
+            let seen = attacks::<ROOK>(sq, occupied);
+            file_partners += (seen & rookqueens & FILE_BB[file(sq) as usize]).count_ones();
+            let beyond = attacks::<ROOK>(sq, occupied ^ (seen & them)) & !seen;
+            xrays += (beyond & (their_king | their_queens)).count_ones();
+        }
+
+        let bishopqueens = self.pieces.bishopqueen::<IS_WHITE>();
+        let mut diagonal_batteries = 0;
+        for sq in BitLoop::new(bishopqueens) {
+            let seen = attacks::<BISHOP>(sq, occupied);
+            for front in BitLoop::new(seen & bishopqueens) {
+                let beyond = attacks::<BISHOP>(sq, occupied ^ (1 << front)) & !seen;
+                diagonal_batteries += u32::from(beyond & king_zone != 0);
+            }
+            let beyond = attacks::<BISHOP>(sq, occupied ^ (seen & them)) & !seen;
+            xrays += (beyond & (their_king | their_queens | their_rooks)).count_ones();
+        }
+
+        // each file battery is seen from both ends.
+        (file_partners as i32 / 2, diagonal_batteries as i32, xrays as i32)
+    }
+
+    /// Whether the kings are on their back two ranks, on opposite wings of the board.
+    fn castled_opposite_sides(&self) -> bool {
+        let (white_king, black_king) = (self.king_sq(WHITE), self.king_sq(BLACK));
+        if rank(white_king) > 1 || rank(black_king) < 6 {
+            return false;
+        }
+        let (white_file, black_file) = (file(white_king), file(black_king));
+        (white_file <= 2 && black_file >= 5) || (white_file >= 5 && black_file <= 2)
+    }
+
+    /// Pawn storms and attacks on the king, and the centre pawns, when the kings have
+    /// castled on opposite wings. Both sides then race to open lines on the other's king.
+    fn opposite_castling_term(&self) -> S {
+        if !self.castled_opposite_sides() {
+            return S(0, 0);
+        }
+        let (white_pawns, black_pawns) =
+            (self.pieces.pawns::<true>(), self.pieces.pawns::<false>());
+        let wing = |king_sq: u8| {
+            let king_file = file(king_sq);
+            (king_file.saturating_sub(1)..=(king_file + 1).min(7))
+                .fold(0, |acc, f| acc | FILE_BB[f as usize])
+        };
+        let king_zone = |king_sq: u8| (1 << king_sq) | attacks::<KING>(king_sq, BB_NONE);
+        let attackers = |side: u8, zone: u64| {
+            let pieces = self.pieces.colour_bb(side)
+                & !(self.pieces.piece_bb(WP) | self.pieces.piece_bb(BP))
+                & !(self.pieces.piece_bb(WK) | self.pieces.piece_bb(BK));
+            BitLoop::new(pieces)
+                .filter(|&sq| self.piece_attacks(sq) & zone != 0)
+                .fold(0, |count, _| count + 1)
+        };
+
+        let (white_king, black_king) = (self.king_sq(WHITE), self.king_sq(BLACK));
+        let white_storm: i32 = BitLoop::new(white_pawns & wing(black_king))
+            .map(|sq| i32::from(rank(sq)) - 1)
+            .sum();
+        let black_storm: i32 = BitLoop::new(black_pawns & wing(white_king))
+            .map(|sq| 6 - i32::from(rank(sq)))
+            .sum();
+        let white_attackers = attackers(WHITE, king_zone(black_king));
+        let black_attackers = attackers(BLACK, king_zone(white_king));
+        let centre = FILE_BB[3] | FILE_BB[4];
+        let white_centre = (white_pawns & centre).count_ones() as i32;
+        let black_centre = (black_pawns & centre).count_ones() as i32;
+
+        self.eval_params.opposite_castling_storm_bonus * (white_storm - black_storm)
+            + self.eval_params.opposite_castling_attacker_bonus * (white_attackers - black_attackers)
+            - self.eval_params.opposite_castling_centre_pawn_malus * (white_centre - black_centre)
+    }
+
+    /// `phase` returns a number between 0 and 256, which is the phase of the game. 0 is the opening, 256 is the endgame.
     pub const fn phase(&self) -> i32 {
-        // todo: this can be incrementally updated.
-        let pawns = self.num(WP) + self.num(BP);
-        let knights = self.num(WN) + self.num(BN);
-        let bishops = self.num(WB) + self.num(BB);
-        let rooks = self.num(WR) + self.num(BR);
-        let queens = self.num(WQ) + self.num(BQ);
-        game_phase(pawns, knights, bishops, rooks, queens)
+        self.phase
     }
 
-    fn mobility(&mut self) -> S {
+    fn mobility(&self) -> S {
         let mut mob_score = S(0, 0);
         let safe_white_moves = !self.pieces.pawn_attacks::<false>();
         let safe_black_moves = !self.pieces.pawn_attacks::<true>();
@@ -440,12 +801,13 @@ mod tests {
 
     #[test]
     fn unwinnable() {
+        use crate::definitions::Score;
         const FEN: &str = "8/8/8/8/2K2k2/2n2P2/8/8 b - - 1 1";
         crate::magic::initialise();
-        let mut board = super::Board::from_fen(FEN).unwrap();
+        let board = super::Board::from_fen(FEN).unwrap();
         let eval = board.evaluate();
         assert!(
-            eval.abs() == 0,
+            eval == Score::DRAW,
             "eval is not a draw score ({eval}cp != 0cp) in a position unwinnable for both sides."
         );
     }
@@ -455,8 +817,8 @@ mod tests {
         const FEN1: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
         const FEN2: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1";
         crate::magic::initialise();
-        let mut board1 = super::Board::from_fen(FEN1).unwrap();
-        let mut board2 = super::Board::from_fen(FEN2).unwrap();
+        let board1 = super::Board::from_fen(FEN1).unwrap();
+        let board2 = super::Board::from_fen(FEN2).unwrap();
         let eval1 = board1.evaluate();
         let eval2 = board2.evaluate();
         assert_eq!(eval1, -eval2);
@@ -466,15 +828,140 @@ mod tests {
     fn startpos_mobility_equality() {
         use crate::board::evaluation::S;
         crate::magic::initialise();
-        let mut board = super::Board::default();
+        let board = super::Board::default();
         assert_eq!(board.mobility(), S(0, 0));
     }
 
+    #[test]
+    fn fifty_move_scaling() {
+        use crate::definitions::Score;
+        const FRESH: &str = "4k3/8/8/8/8/8/3Q4/4K3 w - - 0 1";
+        const STALE: &str = "4k3/8/8/8/8/8/3Q4/4K3 w - - 80 1";
+        crate::magic::initialise();
+        let fresh = super::Board::from_fen(FRESH).unwrap().evaluate();
+        let stale = super::Board::from_fen(STALE).unwrap().evaluate();
+        assert!(fresh > stale, "fresh eval {fresh}cp should exceed stale eval {stale}cp");
+        assert!(stale > Score::DRAW);
+    }
+
+    #[test]
+    fn lazy_eval_fences() {
+        const FEN: &str = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1";
+        use crate::board::evaluation::LAZY_THRESHOLD_1;
+        crate::magic::initialise();
+        let board = super::Board::from_fen(FEN).unwrap();
+        let full = board.evaluate();
+        // a window around the real score must compute the full evaluation.
+        assert_eq!(board.lazy_evaluate(full - 1, full + 1), full);
+        // a window far from the real score may exit early, but must stay on the same side of it.
+        let far = 4 * LAZY_THRESHOLD_1;
+        assert!(board.lazy_evaluate(full + far, full + far + 1) <= full + far);
+        assert!(board.lazy_evaluate(full - far - 1, full - far) >= full - far);
+    }
+
+    #[test]
+    fn minor_piece_terms() {
+        // a knight on d5, defended by a pawn, that no black pawn can chase away.
+        const OUTPOST: &str = "4k3/pp4pp/8/3N4/4P3/8/PP4PP/4K3 w - - 0 1";
+        const OUTPOST_FLIPPED: &str = "4k3/pp4pp/8/4p3/3n4/8/PP4PP/4K3 b - - 0 1";
+        // a bishop on the same colour as its own pawn, which is blocked.
+        const BAD_BISHOP: &str = "4k3/8/8/3p4/3P4/2B5/8/4K3 w - - 0 1";
+        crate::magic::initialise();
+        let board = super::Board::from_fen(OUTPOST).unwrap();
+        let params = &board.eval_params;
+        let outpost = board.minor_piece_term();
+        assert_eq!(
+            outpost,
+            params.knight_outpost_bonus + params.knight_closed_position_bonus
+        );
+        let flipped = super::Board::from_fen(OUTPOST_FLIPPED).unwrap();
+        assert_eq!(flipped.minor_piece_term(), -outpost);
+
+        let board = super::Board::from_fen(BAD_BISHOP).unwrap();
+        let params = &board.eval_params;
+        // two pawns on the board is an open position, which suits the bishop.
+        assert_eq!(
+            board.minor_piece_term(),
+            params.knight_closed_position_bonus * 6 - params.bad_bishop_malus
+        );
+    }
+
+    #[test]
+    fn battery_and_xray_terms() {
+        // doubled rooks on the a-file.
+        const ROOK_BATTERY: &str = "4k3/8/8/8/8/8/R7/R3K3 w - - 0 1";
+        // a bishop behind a queen, aimed through h7 at the castled king.
+        const DIAGONAL_BATTERY: &str = "6k1/8/8/8/8/3Q4/2B5/4K3 w - - 0 1";
+        // a rook pinning a knight to the king.
+        const PIN: &str = "4k3/8/8/4n3/8/8/8/4RK2 w - - 0 1";
+        const PIN_FLIPPED: &str = "4rk2/8/8/8/4N3/8/8/4K3 b - - 0 1";
+        crate::magic::initialise();
+        let board = super::Board::from_fen(ROOK_BATTERY).unwrap();
+        assert_eq!(board.coordination_term(), board.eval_params.rook_battery_bonus);
+        let board = super::Board::from_fen(DIAGONAL_BATTERY).unwrap();
+        assert_eq!(board.coordination_term(), board.eval_params.diagonal_battery_bonus);
+        let board = super::Board::from_fen(PIN).unwrap();
+        assert_eq!(board.coordination_term(), board.eval_params.xray_pressure_bonus);
+        let flipped = super::Board::from_fen(PIN_FLIPPED).unwrap();
+        assert_eq!(flipped.coordination_term(), -board.coordination_term());
+    }
+
+    #[test]
+    fn opposite_castling_terms() {
+        // white has castled long and black short, and white's g- and h-pawns are storming.
+        const OPPOSITE: &str = "6k1/5ppp/8/8/6PP/8/PPP5/2K5 w - - 0 1";
+        const OPPOSITE_FLIPPED: &str = "2k5/ppp5/8/6pp/8/8/5PPP/6K1 b - - 0 1";
+        const SAME_SIDE: &str = "6k1/5ppp/8/8/6PP/8/PPP5/6K1 w - - 0 1";
+        crate::magic::initialise();
+        let board = super::Board::from_fen(OPPOSITE).unwrap();
+        assert!(board.castled_opposite_sides());
+        let storm = board.opposite_castling_term();
+        assert_eq!(storm, board.eval_params.opposite_castling_storm_bonus * 4);
+        let flipped = super::Board::from_fen(OPPOSITE_FLIPPED).unwrap();
+        assert_eq!(flipped.opposite_castling_term(), -storm);
+
+        let board = super::Board::from_fen(SAME_SIDE).unwrap();
+        assert!(!board.castled_opposite_sides());
+        assert_eq!(board.opposite_castling_term(), super::S(0, 0));
+    }
+
+    #[test]
+    fn rule_of_the_square() {
+        use crate::definitions::{BLACK, WHITE};
+        crate::magic::initialise();
+        let race = |fen: &str| {
+            let board = super::Board::from_fen(fen).unwrap();
+            (
+                board.fastest_unstoppable_passer::<{ WHITE }>(board.king_sq(BLACK)),
+                board.fastest_unstoppable_passer::<{ BLACK }>(board.king_sq(WHITE)),
+            )
+        };
+        // the h-pawn needs three moves, and the king is four moves from h8.
+        assert_eq!(race("8/8/8/3k3P/8/8/8/K7 w - - 0 1"), (Some(3), None));
+        // with black to move, the king steps into the square in time.
+        assert_eq!(race("8/8/8/3k3P/8/8/8/K7 b - - 0 1"), (None, None));
+        assert_eq!(race("8/8/8/4k2P/8/8/8/K7 w - - 0 1"), (None, None));
+        // pawns on their starting rank can move two squares at once.
+        assert_eq!(race("7K/8/8/8/8/8/P7/7k w - - 0 1"), (Some(5), None));
+        assert_eq!(race("k7/7p/8/8/8/8/8/K7 b - - 0 1"), (None, Some(5)));
+        // a pawn blocked by its own king isn't going anywhere.
+        assert_eq!(race("8/7K/8/7P/8/8/8/k7 w - - 0 1"), (None, None));
+
+        let board = super::Board::from_fen("8/8/8/3k3P/8/8/8/K7 w - - 0 1").unwrap();
+        let params = &board.eval_params;
+        // the black king is in the centre, and the white king is in the corner.
+        assert_eq!(
+            board.pawn_endgame_term(),
+            params.unstoppable_passer_bonus - params.king_centralisation_bonus * 6
+        );
+    }
+
     #[test]
     fn startpos_eval_equality() {
+        use crate::definitions::Score;
         crate::magic::initialise();
-        let mut board = super::Board::default();
-        assert_eq!(board.evaluate(), 0);
+        let board = super::Board::default();
+        assert_eq!(board.evaluate(), Score::DRAW);
     }
 
     #[test]
@@ -483,7 +970,7 @@ mod tests {
 
         crate::magic::initialise();
 
-        let mut board = super::Board::default();
+        let board = super::Board::default();
 
         let material = board.material[crate::definitions::WHITE as usize] - board.material[crate::definitions::BLACK as usize];
         let pst = board.pst_vals;
@@ -559,8 +1046,8 @@ mod tests {
     fn passers_should_be_pushed() {
         use super::Board;
 
-        let mut starting_rank_passer = Board::from_fen("8/k7/8/8/8/8/K6P/8 w - - 0 1").unwrap();
-        let mut end_rank_passer = Board::from_fen("8/k6P/8/8/8/8/K7/8 w - - 0 1").unwrap();
+        let starting_rank_passer = Board::from_fen("8/k7/8/8/8/8/K6P/8 w - - 0 1").unwrap();
+        let end_rank_passer = Board::from_fen("8/k6P/8/8/8/8/K7/8 w - - 0 1").unwrap();
 
         let starting_rank_eval = starting_rank_passer.evaluate();
         let end_rank_eval = end_rank_passer.evaluate();
@@ -571,4 +1058,47 @@ mod tests {
             "end_rank_eval: {end_rank_eval}, starting_rank_eval: {starting_rank_eval}"
         );
     }
+
+    #[test]
+    fn imbalance_features() {
+        use super::{Board, S};
+
+        crate::magic::initialise();
+        let startpos = Board::default();
+        assert_eq!(startpos.imbalance_term(), S(0, 0));
+
+        // a queen against three minor pieces, with five pawns each.
+        let queen_vs_minors =
+            Board::from_fen("1nb1kb2/ppp2pp1/8/8/8/8/PPP2PP1/3QK3 w - - 0 1").unwrap();
+        let weights = queen_vs_minors.eval_params.material_imbalance;
+        assert_eq!(queen_vs_minors.imbalance_term(), weights[3] * 3);
+
+        // doubled rooks, with eight pawns each.
+        let rook_pair = Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w - - 0 1").unwrap();
+        assert_eq!(rook_pair.imbalance_term(), weights[1] * 6 + weights[2]);
+    }
+
+    #[test]
+    fn new_params_rescore_the_position() {
+        use super::{parameters::Parameters, Board, S};
+        use crate::definitions::WN;
+
+        crate::magic::initialise();
+        let mut board = Board::default();
+        board.reset_tables();
+        for san in ["g1f3", "g8f6", "f3g1"] {
+            let m = board.parse_san(san).unwrap();
+            assert!(board.make_move(m));
+        }
+        let mut params = Parameters::default();
+        params.piece_values[WN as usize] += S(100, 100);
+        board.set_eval_params(params);
+        // the material is counted with the new knight value...
+        board.validate().unwrap();
+        // ...and the game so far is kept, so the next move repeats the start position.
+        assert_eq!(board.ply(), 3);
+        let m = board.parse_san("f6g8").unwrap();
+        assert!(board.make_move(m));
+        assert!(board.is_draw());
+    }
 }
@@ -1,17 +1,30 @@
 use std::{error::Error, fmt::Display};
 
 use crate::{
-    definitions::{flip_file, flip_rank, BP, KING, KNIGHT, WK, WN, WP},
+    definitions::{flip_file, flip_rank, BP, KING, KNIGHT, PAWN, WK, WN, WP},
     lookups::file,
 };
 
 use super::{
-    score::S, BISHOP_MOBILITY_BONUS, BISHOP_PAIR_BONUS, DOUBLED_PAWN_MALUS, ISOLATED_PAWN_MALUS,
-    KNIGHT_MOBILITY_BONUS, PASSED_PAWN_BONUS, PIECE_VALUES, QUEEN_HALF_OPEN_FILE_BONUS,
-    QUEEN_MOBILITY_BONUS, QUEEN_OPEN_FILE_BONUS, ROOK_HALF_OPEN_FILE_BONUS, ROOK_MOBILITY_BONUS,
-    ROOK_OPEN_FILE_BONUS,
+    score::S, BAD_BISHOP_MALUS, BISHOP_MOBILITY_BONUS, BISHOP_PAIR_BONUS, DOUBLED_PAWN_MALUS,
+    ISOLATED_PAWN_MALUS, KING_CENTRALISATION_BONUS, MATERIAL_IMBALANCE, KNIGHT_CLOSED_POSITION_BONUS,
+    KNIGHT_MOBILITY_BONUS, KNIGHT_OUTPOST_BONUS, PASSED_PAWN_BONUS, PIECE_VALUES,
+    QUEEN_HALF_OPEN_FILE_BONUS, QUEEN_MOBILITY_BONUS, QUEEN_OPEN_FILE_BONUS,
+    ROOK_HALF_OPEN_FILE_BONUS, ROOK_MOBILITY_BONUS, ROOK_OPEN_FILE_BONUS, UNSTOPPABLE_PASSER_BONUS,
+    ROOK_BATTERY_BONUS, DIAGONAL_BATTERY_BONUS, XRAY_PRESSURE_BONUS, OPPOSITE_CASTLING_STORM_BONUS,
+    OPPOSITE_CASTLING_ATTACKER_BONUS, OPPOSITE_CASTLING_CENTRE_PAWN_MALUS,
 };
 
+/// The names of the piece-square tables in a TOML parameter file, from pawn to king.
+const PST_NAMES: [&str; 6] = [
+    "pawn_pst",
+    "knight_pst",
+    "bishop_pst",
+    "rook_pst",
+    "queen_pst",
+    "king_pst",
+];
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Parameters {
     pub piece_values: [S; 13],
@@ -22,16 +35,29 @@ pub struct Parameters {
     pub rook_half_open_file_bonus: S,
     pub queen_open_file_bonus: S,
     pub queen_half_open_file_bonus: S,
+    pub knight_outpost_bonus: S,
+    pub bad_bishop_malus: S,
+    pub knight_closed_position_bonus: S,
+    pub king_centralisation_bonus: S,
+    pub unstoppable_passer_bonus: S,
+    pub rook_battery_bonus: S,
+    pub diagonal_battery_bonus: S,
+    pub xray_pressure_bonus: S,
+    pub opposite_castling_storm_bonus: S,
+    pub opposite_castling_attacker_bonus: S,
+    pub opposite_castling_centre_pawn_malus: S,
     pub knight_mobility_bonus: [S; 9],
     pub bishop_mobility_bonus: [S; 14],
     pub rook_mobility_bonus: [S; 15],
     pub queen_mobility_bonus: [S; 28],
     pub passed_pawn_bonus: [S; 6],
+    pub material_imbalance: [S; 4],
     pub piece_square_tables: [[S; 64]; 13],
 }
 
 impl Default for Parameters {
     fn default() -> Self {
+        // the built-in values are written in centipawns.
         Self {
             piece_values: PIECE_VALUES,
             isolated_pawn_malus: ISOLATED_PAWN_MALUS,
@@ -41,13 +67,26 @@ impl Default for Parameters {
             rook_half_open_file_bonus: ROOK_HALF_OPEN_FILE_BONUS,
             queen_open_file_bonus: QUEEN_OPEN_FILE_BONUS,
             queen_half_open_file_bonus: QUEEN_HALF_OPEN_FILE_BONUS,
+            knight_outpost_bonus: KNIGHT_OUTPOST_BONUS,
+            bad_bishop_malus: BAD_BISHOP_MALUS,
+            knight_closed_position_bonus: KNIGHT_CLOSED_POSITION_BONUS,
+            king_centralisation_bonus: KING_CENTRALISATION_BONUS,
+            unstoppable_passer_bonus: UNSTOPPABLE_PASSER_BONUS,
+            rook_battery_bonus: ROOK_BATTERY_BONUS,
+            diagonal_battery_bonus: DIAGONAL_BATTERY_BONUS,
+            xray_pressure_bonus: XRAY_PRESSURE_BONUS,
+            opposite_castling_storm_bonus: OPPOSITE_CASTLING_STORM_BONUS,
+            opposite_castling_attacker_bonus: OPPOSITE_CASTLING_ATTACKER_BONUS,
+            opposite_castling_centre_pawn_malus: OPPOSITE_CASTLING_CENTRE_PAWN_MALUS,
             knight_mobility_bonus: KNIGHT_MOBILITY_BONUS,
             bishop_mobility_bonus: BISHOP_MOBILITY_BONUS,
             rook_mobility_bonus: ROOK_MOBILITY_BONUS,
             queen_mobility_bonus: QUEEN_MOBILITY_BONUS,
             passed_pawn_bonus: PASSED_PAWN_BONUS,
+            material_imbalance: MATERIAL_IMBALANCE,
             piece_square_tables: crate::piecesquaretable::tables::construct_piece_square_table(),
         }
+        .map_terms(S::in_eval_units)
     }
 }
 
@@ -82,6 +121,33 @@ impl Display for Parameters {
             "    queen_half_open_file_bonus: {:?},",
             self.queen_half_open_file_bonus
         )?;
+        writeln!(
+            f,
+            "    knight_outpost_bonus: {:?},",
+            self.knight_outpost_bonus
+        )?;
+        writeln!(f, "    bad_bishop_malus: {:?},", self.bad_bishop_malus)?;
+        writeln!(
+            f,
+            "    knight_closed_position_bonus: {:?},",
+            self.knight_closed_position_bonus
+        )?;
+        writeln!(
+            f,
+            "    king_centralisation_bonus: {:?},",
+            self.king_centralisation_bonus
+        )?;
+        writeln!(
+            f,
+            "    unstoppable_passer_bonus: {:?},",
+            self.unstoppable_passer_bonus
+        )?;
+        writeln!(f, "    rook_battery_bonus: {:?},", self.rook_battery_bonus)?;
+        writeln!(f, "    diagonal_battery_bonus: {:?},", self.diagonal_battery_bonus)?;
+        writeln!(f, "    xray_pressure_bonus: {:?},", self.xray_pressure_bonus)?;
+        writeln!(f, "    opposite_castling_storm_bonus: {:?},", self.opposite_castling_storm_bonus)?;
+        writeln!(f, "    opposite_castling_attacker_bonus: {:?},", self.opposite_castling_attacker_bonus)?;
+        writeln!(f, "    opposite_castling_centre_pawn_malus: {:?},", self.opposite_castling_centre_pawn_malus)?;
         writeln!(
             f,
             "    knight_mobility_bonus: {:?},",
@@ -103,6 +169,7 @@ impl Display for Parameters {
             self.queen_mobility_bonus
         )?;
         writeln!(f, "    passed_pawn_bonus: {:?},", self.passed_pawn_bonus)?;
+        writeln!(f, "    material_imbalance: {:?},", self.material_imbalance)?;
         writeln!(
             f,
             "    piece_square_tables: {:?},",
@@ -123,11 +190,23 @@ impl Parameters {
         rook_half_open_file_bonus: S::NULL,
         queen_open_file_bonus: S::NULL,
         queen_half_open_file_bonus: S::NULL,
+        knight_outpost_bonus: S::NULL,
+        bad_bishop_malus: S::NULL,
+        knight_closed_position_bonus: S::NULL,
+        king_centralisation_bonus: S::NULL,
+        unstoppable_passer_bonus: S::NULL,
+        rook_battery_bonus: S::NULL,
+        diagonal_battery_bonus: S::NULL,
+        xray_pressure_bonus: S::NULL,
+        opposite_castling_storm_bonus: S::NULL,
+        opposite_castling_attacker_bonus: S::NULL,
+        opposite_castling_centre_pawn_malus: S::NULL,
         knight_mobility_bonus: [S::NULL; 9],
         bishop_mobility_bonus: [S::NULL; 14],
         rook_mobility_bonus: [S::NULL; 15],
         queen_mobility_bonus: [S::NULL; 28],
         passed_pawn_bonus: [S::NULL; 6],
+        material_imbalance: [S::NULL; 4],
         piece_square_tables: [[S::NULL; 64]; 13],
     };
 
@@ -146,11 +225,23 @@ impl Parameters {
                 self.queen_open_file_bonus.0,
                 self.queen_half_open_file_bonus.0,
             )))
-            .chain(self.knight_mobility_bonus.into_iter())
-            .chain(self.bishop_mobility_bonus.into_iter())
-            .chain(self.rook_mobility_bonus.into_iter())
-            .chain(self.queen_mobility_bonus.into_iter())
-            .chain(self.passed_pawn_bonus.into_iter())
+            .chain(Some(self.knight_outpost_bonus))
+            .chain(Some(self.bad_bishop_malus))
+            .chain(Some(self.knight_closed_position_bonus))
+            .chain(Some(self.king_centralisation_bonus))
+            .chain(Some(self.unstoppable_passer_bonus))
+            .chain(Some(self.rook_battery_bonus))
+            .chain(Some(self.diagonal_battery_bonus))
+            .chain(Some(self.xray_pressure_bonus))
+            .chain(Some(self.opposite_castling_storm_bonus))
+            .chain(Some(self.opposite_castling_attacker_bonus))
+            .chain(Some(self.opposite_castling_centre_pawn_malus))
+            .chain(self.knight_mobility_bonus)
+            .chain(self.bishop_mobility_bonus)
+            .chain(self.rook_mobility_bonus)
+            .chain(self.queen_mobility_bonus)
+            .chain(self.passed_pawn_bonus)
+            .chain(self.material_imbalance)
             // take the left halves of the white piece square tables, except for the pawn table.
             .chain(self.piece_square_tables[WP as usize].iter().copied())
             .chain(
@@ -162,6 +253,7 @@ impl Parameters {
     }
 
     pub fn devectorise(data: &[i32]) -> Self {
+        #![allow(clippy::too_many_lines)]
         let mut out = Self::NULL;
         let mut data = data.chunks(2).map(|x| S(x[0], x[1]));
         for p in 1..6 {
@@ -190,6 +282,39 @@ impl Parameters {
             .expect("failed to read queen_file_bonus term from vector");
         out.queen_open_file_bonus = S(queen_file_bonus.0, 0);
         out.queen_half_open_file_bonus = S(queen_file_bonus.1, 0);
+        out.knight_outpost_bonus = data
+            .next()
+            .expect("failed to read knight_outpost_bonus term from vector");
+        out.bad_bishop_malus = data
+            .next()
+            .expect("failed to read bad_bishop_malus term from vector");
+        out.knight_closed_position_bonus = data
+            .next()
+            .expect("failed to read knight_closed_position_bonus term from vector");
+        out.king_centralisation_bonus = data
+            .next()
+            .expect("failed to read king_centralisation_bonus term from vector");
+        out.unstoppable_passer_bonus = data
+            .next()
+            .expect("failed to read unstoppable_passer_bonus term from vector");
+        out.rook_battery_bonus = data
+            .next()
+            .expect("failed to read rook_battery_bonus term from vector");
+        out.diagonal_battery_bonus = data
+            .next()
+            .expect("failed to read diagonal_battery_bonus term from vector");
+        out.xray_pressure_bonus = data
+            .next()
+            .expect("failed to read xray_pressure_bonus term from vector");
+        out.opposite_castling_storm_bonus = data
+            .next()
+            .expect("failed to read opposite_castling_storm_bonus term from vector");
+        out.opposite_castling_attacker_bonus = data
+            .next()
+            .expect("failed to read opposite_castling_attacker_bonus term from vector");
+        out.opposite_castling_centre_pawn_malus = data
+            .next()
+            .expect("failed to read opposite_castling_centre_pawn_malus term from vector");
         for knight_mobility_bonus in &mut out.knight_mobility_bonus {
             *knight_mobility_bonus = data
                 .next()
@@ -215,6 +340,11 @@ impl Parameters {
                 .next()
                 .expect("failed to read passed_pawn_bonus term from vector");
         }
+        for material_imbalance in &mut out.material_imbalance {
+            *material_imbalance = data
+                .next()
+                .expect("failed to read material_imbalance term from vector");
+        }
         // load in the pawn table
         for sq in 0..64 {
             let val = data
@@ -272,8 +402,317 @@ impl Parameters {
         Ok(params)
     }
 
+    /// Loads parameters from a file. Files ending in `.toml` are read with
+    /// [`Self::from_toml`], anything else is read as a comma-separated parameter vector.
     pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        if std::path::Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+        {
+            let text = std::fs::read_to_string(path)?;
+            return Ok(Self::from_toml(&text)?);
+        }
         let vec = Self::load_param_vec(path)?;
         Ok(Self::devectorise(&vec))
     }
+
+    /// A hash of every parameter, so that it can be told which parameters a binary
+    /// or a file holds without comparing them term by term.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+        self.vectorise()
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    /// The terms that are stored as-is, by name.
+    const fn named_terms(&mut self) -> [(&'static str, &mut [S]); 24] {
+        [
+            (
+                "isolated_pawn_malus",
+                std::slice::from_mut(&mut self.isolated_pawn_malus),
+            ),
+            (
+                "doubled_pawn_malus",
+                std::slice::from_mut(&mut self.doubled_pawn_malus),
+            ),
+            (
+                "bishop_pair_bonus",
+                std::slice::from_mut(&mut self.bishop_pair_bonus),
+            ),
+            (
+                "rook_open_file_bonus",
+                std::slice::from_mut(&mut self.rook_open_file_bonus),
+            ),
+            (
+                "rook_half_open_file_bonus",
+                std::slice::from_mut(&mut self.rook_half_open_file_bonus),
+            ),
+            (
+                "queen_open_file_bonus",
+                std::slice::from_mut(&mut self.queen_open_file_bonus),
+            ),
+            (
+                "queen_half_open_file_bonus",
+                std::slice::from_mut(&mut self.queen_half_open_file_bonus),
+            ),
+            (
+                "knight_outpost_bonus",
+                std::slice::from_mut(&mut self.knight_outpost_bonus),
+            ),
+            (
+                "bad_bishop_malus",
+                std::slice::from_mut(&mut self.bad_bishop_malus),
+            ),
+            (
+                "knight_closed_position_bonus",
+                std::slice::from_mut(&mut self.knight_closed_position_bonus),
+            ),
+            (
+                "king_centralisation_bonus",
+                std::slice::from_mut(&mut self.king_centralisation_bonus),
+            ),
+            (
+                "unstoppable_passer_bonus",
+                std::slice::from_mut(&mut self.unstoppable_passer_bonus),
+            ),
+            (
+                "rook_battery_bonus",
+                std::slice::from_mut(&mut self.rook_battery_bonus),
+            ),
+            (
+                "diagonal_battery_bonus",
+                std::slice::from_mut(&mut self.diagonal_battery_bonus),
+            ),
+            (
+                "xray_pressure_bonus",
+                std::slice::from_mut(&mut self.xray_pressure_bonus),
+            ),
+            (
+                "opposite_castling_storm_bonus",
+                std::slice::from_mut(&mut self.opposite_castling_storm_bonus),
+            ),
+            (
+                "opposite_castling_attacker_bonus",
+                std::slice::from_mut(&mut self.opposite_castling_attacker_bonus),
+            ),
+            (
+                "opposite_castling_centre_pawn_malus",
+                std::slice::from_mut(&mut self.opposite_castling_centre_pawn_malus),
+            ),
+            ("knight_mobility_bonus", &mut self.knight_mobility_bonus),
+            ("bishop_mobility_bonus", &mut self.bishop_mobility_bonus),
+            ("rook_mobility_bonus", &mut self.rook_mobility_bonus),
+            ("queen_mobility_bonus", &mut self.queen_mobility_bonus),
+            ("passed_pawn_bonus", &mut self.passed_pawn_bonus),
+            ("material_imbalance", &mut self.material_imbalance),
+        ]
+    }
+
+    /// The names of the terms that [`Self::disable`] can switch off.
+    pub fn term_names() -> Vec<&'static str> {
+        Self::default().named_terms().map(|(name, _)| name).to_vec()
+    }
+
+    /// Switches off the term called `name` by zeroing it, for finding out what it is worth.
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        let (_, term) = self
+            .named_terms()
+            .into_iter()
+            .find(|(term_name, _)| *term_name == name)
+            .ok_or_else(|| format!("unknown evaluation term {name}"))?;
+        term.fill(S(0, 0));
+        Ok(())
+    }
+
+    /// Applies `f` to every term.
+    fn map_terms(mut self, f: fn(S) -> S) -> Self {
+        for s in &mut self.piece_values {
+            *s = f(*s);
+        }
+        for (_, ss) in self.named_terms() {
+            for s in ss {
+                *s = f(*s);
+            }
+        }
+        for s in self.piece_square_tables.iter_mut().flatten() {
+            *s = f(*s);
+        }
+        self
+    }
+
+    /// Writes out the parameters as a TOML file that [`Self::from_toml`] can read.
+    pub fn to_toml(&self) -> String {
+        use std::fmt::Write;
+        fn list(ss: &[S]) -> String {
+            ss.iter()
+                .copied()
+                .map(S::to_centipawns)
+                .map(|s| format!("[{}, {}]", s.0, s.1))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        let mut out = String::new();
+        out.push_str("# virtue evaluation parameters.\n");
+        out.push_str(
+            "# every term is [midgame, endgame] in centipawns, from white's point of view.\n",
+        );
+        out.push_str("# terms missing from the file keep their built-in values.\n\n");
+        out.push_str("# pawn, knight, bishop, rook, queen\n");
+        writeln!(out, "piece_values = [{}]", list(&self.piece_values[1..6])).unwrap();
+        for (name, ss) in self.clone().named_terms() {
+            if ss.len() == 1 {
+                writeln!(out, "{name} = {}", list(ss)).unwrap();
+            } else {
+                writeln!(out, "{name} = [{}]", list(ss)).unwrap();
+            }
+        }
+        out.push_str("\n# piece-square tables for white, indexed from a1 to h8.\n");
+        for (piece, name) in (PAWN..=KING).zip(PST_NAMES) {
+            writeln!(out, "{name} = [").unwrap();
+            for rank in self.piece_square_tables[piece as usize].chunks(8) {
+                writeln!(out, "    {},", list(rank)).unwrap();
+            }
+            out.push_str("]\n");
+        }
+        out
+    }
+
+    /// Reads parameters from the TOML format written by [`Self::to_toml`].
+    /// Terms missing from the text keep their built-in values.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let mut out = Self::default();
+        let mut lines = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim());
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected \"name = value\", got \"{line}\""))?;
+            let (key, mut value) = (key.trim(), value.trim().to_string());
+            // arrays can span several lines, so keep reading until the brackets balance.
+            while value.matches('[').count() > value.matches(']').count() {
+                let next = lines
+                    .next()
+                    .ok_or_else(|| format!("unterminated array for {key}"))?;
+                value.push_str(next);
+            }
+            let ss = parse_terms(&value).map_err(|e| format!("bad value for {key}: {e}"))?;
+            out.set_term(key, &ss)?;
+        }
+        Ok(out)
+    }
+
+    fn set_term(&mut self, key: &str, ss: &[S]) -> Result<(), String> {
+        let expect_len = |expected: usize| {
+            if ss.len() == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected {expected} terms for {key}, got {}",
+                    ss.len()
+                ))
+            }
+        };
+        if key == "piece_values" {
+            expect_len(5)?;
+            for (p, &val) in (1..6).zip(ss) {
+                self.piece_values[p] = val;
+                self.piece_values[p + 6] = val;
+            }
+            return Ok(());
+        }
+        if let Some(piece) = PST_NAMES.iter().position(|&name| name == key) {
+            expect_len(64)?;
+            let piece = piece + 1;
+            for (sq, &val) in (0..64).zip(ss) {
+                self.piece_square_tables[piece][sq as usize] = val;
+                self.piece_square_tables[piece + 6][flip_rank(sq) as usize] = -val;
+            }
+            return Ok(());
+        }
+        let (_, term) = self
+            .named_terms()
+            .into_iter()
+            .find(|(name, _)| *name == key)
+            .ok_or_else(|| format!("unknown parameter {key}"))?;
+        expect_len(term.len())?;
+        term.copy_from_slice(ss);
+        Ok(())
+    }
+}
+
+/// Parses either a single `[mg, eg]` term, or an array of them.
+fn parse_terms(value: &str) -> Result<Vec<S>, String> {
+    if !value.starts_with('[') || !value.ends_with(']') {
+        return Err(format!("expected an array, got \"{value}\""));
+    }
+    let numbers = value
+        .split(|c: char| c == '[' || c == ']' || c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse::<i32>().map_err(|e| format!("{n}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbers.len() % 2 != 0 {
+        return Err("every term needs a midgame and an endgame value".to_string());
+    }
+    Ok(numbers
+        .chunks(2)
+        .map(|x| S(x[0], x[1]).in_eval_units())
+        .collect())
+}
+
+mod tests {
+    #[test]
+    fn toml_round_trip() {
+        use super::Parameters;
+
+        let params = Parameters::default();
+        assert_eq!(Parameters::from_toml(&params.to_toml()), Ok(params));
+    }
+
+    #[test]
+    fn toml_partial_and_invalid() {
+        use super::{Parameters, S};
+
+        let params = Parameters::from_toml("# comment\nbishop_pair_bonus = [10, 20]\n").unwrap();
+        assert_eq!(params.bishop_pair_bonus, S(10, 20));
+        assert_eq!(params.piece_values, Parameters::default().piece_values);
+
+        assert!(Parameters::from_toml("bishop_pair_bonus = [10]").is_err());
+        assert!(Parameters::from_toml("passed_pawn_bonus = [[1, 2]]").is_err());
+        assert!(Parameters::from_toml("king_safety = [1, 2]").is_err());
+        assert!(Parameters::from_toml("pawn_pst = [\n[1, 2],").is_err());
+    }
+
+    #[test]
+    fn terms_can_be_disabled() {
+        use super::{Parameters, S};
+
+        let mut params = Parameters::default();
+        for name in Parameters::term_names() {
+            assert!(params.disable(name).is_ok());
+        }
+        assert_eq!(params.bishop_pair_bonus, S(0, 0));
+        assert!(params.passed_pawn_bonus.iter().all(|&s| s == S(0, 0)));
+        assert_eq!(params.piece_values, Parameters::default().piece_values);
+        assert!(params.disable("piece_values").is_err());
+    }
+
+    #[test]
+    fn fingerprints_tell_parameters_apart() {
+        use super::Parameters;
+
+        let params = Parameters::default();
+        assert_eq!(params.fingerprint(), Parameters::default().fingerprint());
+        let mut changed = params.clone();
+        changed.bishop_pair_bonus.1 += 1;
+        assert_ne!(params.fingerprint(), changed.fingerprint());
+    }
 }
@@ -1,4 +1,4 @@
-use super::lerp;
+use super::{centipawns, lerp, to_centipawns};
 
 use std::{
     fmt::Display,
@@ -71,4 +71,14 @@ impl S {
     pub fn value(self, phase: i32) -> i32 {
         lerp(self.0, self.1, phase)
     }
+
+    /// Converts a term written in centipawns into evaluation units.
+    pub const fn in_eval_units(self) -> Self {
+        Self(centipawns(self.0), centipawns(self.1))
+    }
+
+    /// Converts a term in evaluation units into centipawns.
+    pub const fn to_centipawns(self) -> Self {
+        Self(to_centipawns(self.0), to_centipawns(self.1))
+    }
 }
@@ -1,4 +1,10 @@
-use crate::{chessmove::Move, definitions::{MAX_DEPTH, PIECE_EMPTY}};
+use std::io::{self, Write};
+
+use crate::{
+    chessmove::Move,
+    definitions::{MAX_DEPTH, PIECE_EMPTY},
+    historytable::HeatmapFormat,
+};
 
 use super::Board;
 
@@ -11,7 +17,7 @@ impl Board {
     }
 
     /// Get the history score for a move.
-    pub(super) fn history_score(&self, m: Move) -> i32 {
+    pub(crate) fn history_score(&self, m: Move) -> i32 {
         let piece_moved = self.moved_piece(m);
         let to = m.to();
         self.history_table.get(piece_moved, to)
@@ -53,9 +59,8 @@ impl Board {
     /// Add a move to the follow-up history table.
     pub fn add_followup_history(&mut self, m: Move, score: i32) {
         debug_assert!(self.height < MAX_DEPTH.ply_to_horizon());
-        let two_ply_ago = match self.history.len().checked_sub(2) {
-            Some(idx) => idx,
-            None => return,
+        let Some(two_ply_ago) = self.history.len().checked_sub(2) else {
+            return;
         };
         let move_to_follow_up = self.history[two_ply_ago].m;
         let prev_move = self.history[two_ply_ago + 1].m;
@@ -68,7 +73,7 @@ impl Board {
         // meaning that the piece on the target square of the move 
         // two ply ago may have been captured.
         let tpa_piece = {
-            let capture = prev_move.capture();
+            let capture = self.history[two_ply_ago + 1].capture;
             // determine where to find the piece_t info:
             // we don't need to worry about ep-captures because
             // we just blanket filter them out with the null checks.
@@ -88,10 +93,9 @@ impl Board {
     }
 
     /// Get the follow-up history score for a move.
-    pub(super) fn followup_history_score(&self, m: Move) -> i32 {
-        let two_ply_ago = match self.history.len().checked_sub(2) {
-            Some(idx) => idx,
-            None => return 0,
+    pub(crate) fn followup_history_score(&self, m: Move) -> i32 {
+        let Some(two_ply_ago) = self.history.len().checked_sub(2) else {
+            return 0;
         };
         let move_to_follow_up = self.history[two_ply_ago].m;
         let prev_move = self.history[two_ply_ago + 1].m;
@@ -104,7 +108,7 @@ impl Board {
         // meaning that the piece on the target square of the move 
         // two ply ago may have been captured.
         let tpa_piece = {
-            let capture = prev_move.capture();
+            let capture = self.history[two_ply_ago + 1].capture;
             // determine where to find the piece_t info:
             // we don't need to worry about ep-captures because
             // we just blanket filter them out with the null checks.
@@ -123,6 +127,20 @@ impl Board {
             .get(tpa_piece, tpa_to, piece, to)
     }
 
+    /// Writes the main and follow-up history tables as per-piece heatmaps,
+    /// so that the tables left by a search can be compared between runs.
+    pub fn write_history_heatmaps(
+        &self,
+        out: &mut impl Write,
+        format: HeatmapFormat,
+    ) -> io::Result<()> {
+        if format == HeatmapFormat::Csv {
+            writeln!(out, "table,piece,square,value")?;
+        }
+        self.history_table.write_heatmaps(out, format)?;
+        self.followup_history.write_heatmaps(out, format)
+    }
+
     /// Add a killer move.
     pub fn insert_killer(&mut self, m: Move) {
         debug_assert!(self.height < MAX_DEPTH.ply_to_horizon());
@@ -131,9 +149,22 @@ impl Board {
         entry[0] = m;
     }
 
+    /// Add a mate killer, a quiet move that cut off with a mate score.
+    /// Mate killers are kept apart from the normal killers, so that a
+    /// mating move doesn't push out the killers for the ordinary lines.
+    pub fn insert_mate_killer(&mut self, m: Move) {
+        debug_assert!(self.height < MAX_DEPTH.ply_to_horizon());
+        self.mate_killer_table[self.height] = m;
+    }
+
+    /// Determine if a move is the mate killer for this height.
+    pub(super) fn is_mate_killer(&self, m: Move) -> bool {
+        self.mate_killer_table[self.height] == m
+    }
+
     /// Determine if a move is a third-order killer move.
     /// The third-order killer is the first killer from the previous move (two ply ago)
     pub(super) fn is_third_order_killer(&self, m: Move) -> bool {
-        self.height > 2 && self.killer_move_table[self.height - 2][0] == m
+        self.height >= 2 && self.killer_move_table[self.height - 2][0] == m
     }
 }
\ No newline at end of file
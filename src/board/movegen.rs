@@ -1,7 +1,8 @@
 pub mod bitboards;
 
 use self::bitboards::{
-    lsb, north_east_one, north_west_one, south_east_one, south_west_one, BB_RANK_2, BB_RANK_7,
+    lsb, north_east_one, north_west_one, south_east_one, south_west_one, BB_RANK_1, BB_RANK_2,
+    BB_RANK_3, BB_RANK_6, BB_RANK_7, BB_RANK_8,
 };
 pub use self::bitboards::{BitLoop, BB_NONE};
 
@@ -16,19 +17,29 @@ use crate::{
     chessmove::Move,
     definitions::{
         Square::{B1, B8, C1, C8, D1, D8, E1, E8, F1, F8, G1, G8, NO_SQUARE},
-        BB, BISHOP, BKCA, BLACK, BN, BQ, BQCA, BR, KING, KNIGHT, PIECE_EMPTY, ROOK, WB, WHITE,
-        WKCA, WN, WQ, WQCA, WR,
+        colour_of, type_of, BISHOP, BKCA, BLACK, BQCA, KING, KNIGHT, PAWN, PIECE_EMPTY, QUEEN,
+        ROOK, WHITE, WKCA, WQCA,
     },
     lookups::MVV_LVA_SCORE,
     macros,
-    validate::{piece_valid, square_on_board}, magic::MAGICS_READY,
+    validate::{piece_valid, side_valid, square_on_board}, magic::MAGICS_READY,
 };
 
+// Moves are ordered in stages, by the score they are given:
+// 1. the TT move,
+// 2. captures, by MVV-LVA,
+// 3. the mate killer, then the two killers for this height,
+// 4. the countermove, then the killer from two plies ago,
+// 5. the remaining quiet moves, by history,
+// 6. underpromotions.
 pub const TT_MOVE_SCORE: i32 = 20_000_000;
+const MATE_KILLER_SCORE: i32 = 9_500_000;
 const FIRST_ORDER_KILLER_SCORE: i32 = 9_000_000;
 const SECOND_ORDER_KILLER_SCORE: i32 = 8_000_000;
 const COUNTER_MOVE_SCORE: i32 = 2_000_000;
 const THIRD_ORDER_KILLER_SCORE: i32 = 1_000_000;
+/// Underpromotions are almost never best, so they are tried after every other move.
+const UNDERPROMOTION_SCORE: i32 = -1_000_000_000;
 
 const MAX_POSITION_MOVES: usize = 256;
 
@@ -53,7 +64,7 @@ pub struct MoveListIter {
 impl MoveList {
     pub const fn new() -> Self {
         const DEFAULT: MoveListEntry = MoveListEntry {
-            entry: Move { data: 0 },
+            entry: Move::NULL,
             score: 0,
         };
         Self {
@@ -103,10 +114,10 @@ impl Iterator for MoveListIter {
         if self.index == self.count {
             return None;
         }
-        let mut best_score = 0;
+        let mut best_score = unsafe { self.moves.get_unchecked(self.index).score };
         let mut best_num = self.index;
 
-        for index in self.index..self.count {
+        for index in self.index + 1..self.count {
             let score = unsafe { self.moves.get_unchecked(index).score };
             if score > best_score {
                 best_score = score;
@@ -157,6 +168,7 @@ impl Display for MoveList {
     }
 }
 
+#[allow(dead_code)]
 pub struct MoveVecWrapper(pub Vec<Move>);
 impl Display for MoveVecWrapper {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
@@ -165,21 +177,42 @@ impl Display for MoveVecWrapper {
         }
         writeln!(f, "MoveList: ({}) [", self.0.len())?;
         for m in &self.0[..self.0.len() - 1] {
-            writeln!(f, "  {},", m)?;
+            writeln!(f, "  {m},")?;
         }
         writeln!(f, "  {}", self.0.last().unwrap())?;
         write!(f, "]")
     }
 }
 
+pub const fn is_underpromotion(m: Move) -> bool {
+    m.is_promo() && m.promotion() != QUEEN
+}
+
 impl Board {
+    /// Whether a move has been excluded from the search at the current height,
+    /// in which case it is never added to the move list.
+    fn is_excluded(&self, m: Move) -> bool {
+        self.excluded[self.height] == m
+    }
+
     fn add_quiet_move(&self, m: Move, move_list: &mut MoveList) {
         debug_assert!(square_on_board(m.from()));
         debug_assert!(square_on_board(m.to()));
 
+        if self.is_excluded(m) {
+            return;
+        }
+
+        if is_underpromotion(m) {
+            move_list.push(m, UNDERPROMOTION_SCORE);
+            return;
+        }
+
         let killer_entry = self.killer_move_table[self.height];
 
-        let score = if killer_entry[0] == m {
+        let score = if self.is_mate_killer(m) {
+            MATE_KILLER_SCORE
+        } else if killer_entry[0] == m {
             FIRST_ORDER_KILLER_SCORE
         } else if killer_entry[1] == m {
             SECOND_ORDER_KILLER_SCORE
@@ -199,9 +232,18 @@ impl Board {
     fn add_capture_move(&self, m: Move, move_list: &mut MoveList) {
         debug_assert!(square_on_board(m.from()));
         debug_assert!(square_on_board(m.to()));
-        debug_assert!(piece_valid(m.capture()), "piece: {}", m);
+        debug_assert!(piece_valid(self.piece_at(m.to())), "piece: {m}");
 
-        let capture = m.capture() as usize;
+        if self.is_excluded(m) {
+            return;
+        }
+
+        if is_underpromotion(m) {
+            move_list.push(m, UNDERPROMOTION_SCORE);
+            return;
+        }
+
+        let capture = self.piece_at(m.to()) as usize;
         let piece_moved = self.piece_at(m.from()) as usize;
         let mmvlva = unsafe {
             *MVV_LVA_SCORE
@@ -213,7 +255,10 @@ impl Board {
         move_list.push(m, score);
     }
 
-    fn add_ep_move(m: Move, move_list: &mut MoveList) {
+    fn add_ep_move(&self, m: Move, move_list: &mut MoveList) {
+        if self.is_excluded(m) {
+            return;
+        }
         move_list.push(m, 1050 + 10_000_000);
     }
 
@@ -243,42 +288,26 @@ impl Board {
         let promo_rank = if SIDE == WHITE { BB_RANK_7 } else { BB_RANK_2 };
         for from in BitLoop::new(attacks_west & !promo_rank) {
             let to = if SIDE == WHITE { from + 7 } else { from - 9 };
-            let cap = self.piece_at(to);
-            debug_assert!(piece_valid(cap));
-            self.add_capture_move(Move::new(from, to, cap, PIECE_EMPTY, 0), move_list);
+            debug_assert!(piece_valid(self.piece_at(to)));
+            self.add_capture_move(Move::new(from, to, PIECE_EMPTY, 0), move_list);
         }
         for from in BitLoop::new(attacks_east & !promo_rank) {
             let to = if SIDE == WHITE { from + 9 } else { from - 7 };
-            let cap = self.piece_at(to);
-            debug_assert!(piece_valid(cap));
-            self.add_capture_move(Move::new(from, to, cap, PIECE_EMPTY, 0), move_list);
+            debug_assert!(piece_valid(self.piece_at(to)));
+            self.add_capture_move(Move::new(from, to, PIECE_EMPTY, 0), move_list);
         }
         for from in BitLoop::new(attacks_west & promo_rank) {
             let to = if SIDE == WHITE { from + 7 } else { from - 9 };
-            let cap = self.piece_at(to);
-            debug_assert!(piece_valid(cap));
-            if SIDE == WHITE {
-                for &promo in &[WQ, WN, WR, WB] {
-                    self.add_capture_move(Move::new(from, to, cap, promo, 0), move_list);
-                }
-            } else {
-                for &promo in &[BQ, BN, BR, BB] {
-                    self.add_capture_move(Move::new(from, to, cap, promo, 0), move_list);
-                }
+            debug_assert!(piece_valid(self.piece_at(to)));
+            for &promo in &[QUEEN, KNIGHT, ROOK, BISHOP] {
+                self.add_capture_move(Move::new(from, to, promo, 0), move_list);
             }
         }
         for from in BitLoop::new(attacks_east & promo_rank) {
             let to = if SIDE == WHITE { from + 9 } else { from - 7 };
-            let cap = self.piece_at(to);
-            debug_assert!(piece_valid(cap));
-            if SIDE == WHITE {
-                for &promo in &[WQ, WN, WR, WB] {
-                    self.add_capture_move(Move::new(from, to, cap, promo, 0), move_list);
-                }
-            } else {
-                for &promo in &[BQ, BN, BR, BB] {
-                    self.add_capture_move(Move::new(from, to, cap, promo, 0), move_list);
-                }
+            debug_assert!(piece_valid(self.piece_at(to)));
+            for &promo in &[QUEEN, KNIGHT, ROOK, BISHOP] {
+                self.add_capture_move(Move::new(from, to, promo, 0), move_list);
             }
         }
     }
@@ -307,15 +336,15 @@ impl Board {
 
         if attacks_west != 0 {
             let from_sq = lsb(attacks_west) as u8;
-            Self::add_ep_move(
-                Move::new(from_sq, self.ep_sq, PIECE_EMPTY, PIECE_EMPTY, Move::EP_MASK),
+            self.add_ep_move(
+                Move::new(from_sq, self.ep_sq, PIECE_EMPTY, Move::EP_FLAG),
                 move_list,
             );
         }
         if attacks_east != 0 {
             let from_sq = lsb(attacks_east) as u8;
-            Self::add_ep_move(
-                Move::new(from_sq, self.ep_sq, PIECE_EMPTY, PIECE_EMPTY, Move::EP_MASK),
+            self.add_ep_move(
+                Move::new(from_sq, self.ep_sq, PIECE_EMPTY, Move::EP_FLAG),
                 move_list,
             );
         }
@@ -344,27 +373,90 @@ impl Board {
         let promoting_pawns = pushable_pawns & promo_rank;
         for sq in BitLoop::new(pushable_pawns & !promoting_pawns) {
             let to = if SIDE == WHITE { sq + 8 } else { sq - 8 };
-            self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, 0), move_list);
+            self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
         }
         for sq in BitLoop::new(double_pushable_pawns) {
             let to = if SIDE == WHITE { sq + 16 } else { sq - 16 };
-            self.add_quiet_move(
-                Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, Move::PAWN_START_MASK),
-                move_list,
-            );
+            self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
         }
         for sq in BitLoop::new(promoting_pawns) {
             let to = if SIDE == WHITE { sq + 8 } else { sq - 8 };
-            if SIDE == WHITE {
-                for &promo in &[WQ, WN, WR, WB] {
-                    self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, promo, 0), move_list);
-                }
+            for &promo in &[QUEEN, KNIGHT, ROOK, BISHOP] {
+                self.add_quiet_move(Move::new(sq, to, promo, 0), move_list);
+            }
+        }
+    }
+
+    /// Checks if a move could be generated in the current position, ignoring
+    /// whether it leaves the king in check. Moves from the transposition table
+    /// may belong to a different position with a colliding key, so this is
+    /// cheap enough to run on any move before it is made.
+    pub fn is_pseudo_legal(&self, m: Move) -> bool {
+        let from = m.from();
+        let to = m.to();
+        let flags = if m.is_ep() {
+            Move::EP_FLAG
+        } else if m.is_castle() {
+            Move::CASTLE_FLAG
+        } else {
+            0
+        };
+        // reject stray promotion bits, which no generated move carries.
+        if from == to || m != Move::new(from, to, m.promotion(), flags) {
+            return false;
+        }
+
+        let piece = self.piece_at(from);
+        if piece == PIECE_EMPTY || colour_of(piece) != self.side {
+            return false;
+        }
+        let captured = self.piece_at(to);
+        if captured != PIECE_EMPTY && colour_of(captured) == self.side {
+            return false;
+        }
+
+        if m.is_castle() {
+            let mut list = MoveList::new();
+            if self.side == WHITE {
+                self.generate_castling_moves::<WHITE>(&mut list);
             } else {
-                for &promo in &[BQ, BN, BR, BB] {
-                    self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, promo, 0), move_list);
-                }
+                self.generate_castling_moves::<BLACK>(&mut list);
+            }
+            return list.into_iter().any(|e| e == m);
+        }
+
+        let from_bb = 1 << from;
+        let to_bb = 1 << to;
+        let piece_type = type_of(piece);
+        if piece_type == PAWN {
+            let (attacks, single_push, double_push) = if self.side == WHITE {
+                let single_push = (from_bb << 8) & self.pieces.empty();
+                let double_push = ((single_push & BB_RANK_3) << 8) & self.pieces.empty();
+                (north_east_one(from_bb) | north_west_one(from_bb), single_push, double_push)
+            } else {
+                let single_push = (from_bb >> 8) & self.pieces.empty();
+                let double_push = ((single_push & BB_RANK_6) >> 8) & self.pieces.empty();
+                (south_east_one(from_bb) | south_west_one(from_bb), single_push, double_push)
+            };
+            if m.is_ep() {
+                return to == self.ep_sq && attacks & to_bb != 0;
             }
+            let promotes = to_bb & (BB_RANK_1 | BB_RANK_8) != 0;
+            let targets = single_push | double_push | attacks & self.pieces.colour_bb(self.side ^ 1);
+            return promotes == m.is_promo() && targets & to_bb != 0;
         }
+        if m.is_ep() || m.is_promo() {
+            return false;
+        }
+        let blockers = self.pieces.occupied();
+        let attacks = match piece_type {
+            KNIGHT => bitboards::attacks::<KNIGHT>(from, blockers),
+            BISHOP => bitboards::attacks::<BISHOP>(from, blockers),
+            ROOK => bitboards::attacks::<ROOK>(from, blockers),
+            QUEEN => bitboards::attacks::<QUEEN>(from, blockers),
+            _ => bitboards::attacks::<KING>(from, blockers),
+        };
+        attacks & to_bb != 0
     }
 
     pub fn generate_moves(&self, move_list: &mut MoveList) {
@@ -381,7 +473,7 @@ impl Board {
     #[inline(never)]
     pub fn generate_moves_for<const SIDE: u8>(&self, move_list: &mut MoveList) {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
 
         if SIDE != WHITE && SIDE != BLACK {
             unsafe {
@@ -414,13 +506,10 @@ impl Board {
         for sq in BitLoop::new(our_knights) {
             let moves = bitboards::attacks::<KNIGHT>(sq, BB_NONE);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
             for to in BitLoop::new(moves & freespace) {
-                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, 0), move_list);
+                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -439,13 +528,10 @@ impl Board {
         for sq in BitLoop::new(our_king) {
             let moves = bitboards::attacks::<KING>(sq, BB_NONE);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
             for to in BitLoop::new(moves & freespace) {
-                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, 0), move_list);
+                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -465,13 +551,10 @@ impl Board {
         for sq in BitLoop::new(our_diagonal_sliders) {
             let moves = bitboards::attacks::<BISHOP>(sq, blockers);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
             for to in BitLoop::new(moves & freespace) {
-                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, 0), move_list);
+                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -491,13 +574,10 @@ impl Board {
         for sq in BitLoop::new(our_orthogonal_sliders) {
             let moves = bitboards::attacks::<ROOK>(sq, blockers);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
             for to in BitLoop::new(moves & freespace) {
-                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, PIECE_EMPTY, 0), move_list);
+                self.add_quiet_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -515,7 +595,7 @@ impl Board {
     #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
     pub fn generate_captures_comptime<const SIDE: u8>(&self, move_list: &mut MoveList) {
         #[cfg(debug_assertions)]
-        self.check_validity().unwrap();
+        self.validate().unwrap();
 
         if SIDE != WHITE && SIDE != BLACK {
             unsafe {
@@ -546,10 +626,7 @@ impl Board {
         for sq in BitLoop::new(our_knights) {
             let moves = bitboards::attacks::<KNIGHT>(sq, BB_NONE);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -567,10 +644,7 @@ impl Board {
         for sq in BitLoop::new(our_king) {
             let moves = bitboards::attacks::<KING>(sq, BB_NONE);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -589,10 +663,7 @@ impl Board {
         for sq in BitLoop::new(our_diagonal_sliders) {
             let moves = bitboards::attacks::<BISHOP>(sq, blockers);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
 
@@ -611,10 +682,7 @@ impl Board {
         for sq in BitLoop::new(our_orthogonal_sliders) {
             let moves = bitboards::attacks::<ROOK>(sq, blockers);
             for to in BitLoop::new(moves & their_pieces) {
-                self.add_capture_move(
-                    Move::new(sq, to, self.piece_at(to), PIECE_EMPTY, 0),
-                    move_list,
-                );
+                self.add_capture_move(Move::new(sq, to, PIECE_EMPTY, 0), move_list);
             }
         }
     }
@@ -627,10 +695,7 @@ impl Board {
                 && !self.sq_attacked(E1, BLACK)
                 && !self.sq_attacked(F1, BLACK)
             {
-                self.add_quiet_move(
-                    Move::new(E1, G1, PIECE_EMPTY, PIECE_EMPTY, Move::CASTLE_MASK),
-                    move_list,
-                );
+                self.add_quiet_move(Move::new(E1, G1, PIECE_EMPTY, Move::CASTLE_FLAG), move_list);
             }
 
             if (self.castle_perm & WQCA) != 0
@@ -640,10 +705,7 @@ impl Board {
                 && !self.sq_attacked(E1, BLACK)
                 && !self.sq_attacked(D1, BLACK)
             {
-                self.add_quiet_move(
-                    Move::new(E1, C1, PIECE_EMPTY, PIECE_EMPTY, Move::CASTLE_MASK),
-                    move_list,
-                );
+                self.add_quiet_move(Move::new(E1, C1, PIECE_EMPTY, Move::CASTLE_FLAG), move_list);
             }
         } else {
             if (self.castle_perm & BKCA) != 0
@@ -652,10 +714,7 @@ impl Board {
                 && !self.sq_attacked(E8, WHITE)
                 && !self.sq_attacked(F8, WHITE)
             {
-                self.add_quiet_move(
-                    Move::new(E8, G8, PIECE_EMPTY, PIECE_EMPTY, Move::CASTLE_MASK),
-                    move_list,
-                );
+                self.add_quiet_move(Move::new(E8, G8, PIECE_EMPTY, Move::CASTLE_FLAG), move_list);
             }
 
             if (self.castle_perm & BQCA) != 0
@@ -665,10 +724,7 @@ impl Board {
                 && !self.sq_attacked(E8, WHITE)
                 && !self.sq_attacked(D8, WHITE)
             {
-                self.add_quiet_move(
-                    Move::new(E8, C8, PIECE_EMPTY, PIECE_EMPTY, Move::CASTLE_MASK),
-                    move_list,
-                );
+                self.add_quiet_move(Move::new(E8, C8, PIECE_EMPTY, Move::CASTLE_FLAG), move_list);
             }
         }
     }
@@ -727,4 +783,195 @@ impl Board {
 
         attackers
     }
+
+    /// The squares attacked by the piece on `sq`, with sliding pieces stopped by the
+    /// current occupancy. Pawns attack only their capture squares. An empty square attacks nothing.
+    pub fn piece_attacks(&self, sq: u8) -> u64 {
+        debug_assert!(square_on_board(sq));
+        let piece = self.piece_at(sq);
+        if piece == PIECE_EMPTY {
+            return 0;
+        }
+        let occupied = self.pieces.occupied();
+        match type_of(piece) {
+            PAWN if colour_of(piece) == WHITE => {
+                north_west_one(1 << sq) | north_east_one(1 << sq)
+            }
+            PAWN => south_west_one(1 << sq) | south_east_one(1 << sq),
+            KNIGHT => bitboards::attacks::<KNIGHT>(sq, occupied),
+            BISHOP => bitboards::attacks::<BISHOP>(sq, occupied),
+            ROOK => bitboards::attacks::<ROOK>(sq, occupied),
+            QUEEN => bitboards::attacks::<QUEEN>(sq, occupied),
+            KING => bitboards::attacks::<KING>(sq, occupied),
+            _ => unsafe { macros::inconceivable!() },
+        }
+    }
+
+    /// Every square attacked by at least one of `side`'s pieces, for highlighting
+    /// threats or building features without generating moves.
+    /// Squares occupied by `side`'s own pieces are included when they are defended.
+    #[allow(dead_code)] // for use outside the engine.
+    pub fn attack_map(&self, side: u8) -> u64 {
+        debug_assert!(side_valid(side));
+        BitLoop::new(self.pieces.colour_bb(side))
+            .fold(0, |map, sq| map | self.piece_attacks(sq))
+    }
+}
+
+mod tests {
+    #[test]
+    fn attack_maps() {
+        use super::bitboards::{BB_RANK_1, BB_RANK_2, BB_RANK_3, BB_RANK_6, BB_RANK_7, BB_RANK_8};
+        use crate::{
+            board::Board,
+            definitions::{
+                Square::{A1, A8, D5, E2, E4, F3, F5, G1, H1, H3, H8},
+                BLACK, WHITE,
+            },
+        };
+
+        crate::magic::initialise();
+
+        let mut board = Board::default();
+        assert_eq!(board.piece_attacks(G1), 1 << E2 | 1 << F3 | 1 << H3);
+        assert_eq!(board.piece_attacks(E4), 0);
+        // only the corner rooks are undefended.
+        assert_eq!(
+            board.attack_map(WHITE),
+            BB_RANK_1 & !(1 << A1 | 1 << H1) | BB_RANK_2 | BB_RANK_3
+        );
+        assert_eq!(
+            board.attack_map(BLACK),
+            BB_RANK_8 & !(1 << A8 | 1 << H8) | BB_RANK_7 | BB_RANK_6
+        );
+
+        board.set_from_fen("4k3/8/8/8/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.piece_attacks(E4), 1 << D5 | 1 << F5);
+    }
+
+    #[test]
+    fn killers_are_ordered_in_stages() {
+        use super::MoveList;
+        use crate::board::Board;
+
+        crate::magic::initialise();
+
+        let mut board = Board::default();
+        board.reset_tables();
+        let parse = |board: &Board, san| board.parse_san(san).unwrap();
+        let (nf3, nc3, e4, d4) = (
+            parse(&board, "g1f3"),
+            parse(&board, "b1c3"),
+            parse(&board, "e2e4"),
+            parse(&board, "d2d4"),
+        );
+        board.insert_killer(nf3);
+        board.insert_killer(nc3);
+        board.insert_mate_killer(e4);
+        let mut list = MoveList::new();
+        board.generate_moves(&mut list);
+        let moves = list.into_iter().take(3).collect::<Vec<_>>();
+        assert_eq!(moves, [e4, nc3, nf3]);
+
+        // two plies later, the first killer from the root is the third-order killer.
+        board.insert_killer(d4);
+        assert!(board.make_move(nf3));
+        assert!(board.make_move(parse(&board, "g8f6")));
+        let mut list = MoveList::new();
+        board.generate_moves(&mut list);
+        assert_eq!(list.into_iter().next(), Some(d4));
+    }
+
+    #[test]
+    fn underpromotions_come_last() {
+        use super::{is_underpromotion, MoveList};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+
+        let mut board = Board::from_fen("8/P7/8/8/8/8/8/k6K w - - 0 1").unwrap();
+        board.reset_tables();
+        let mut list = MoveList::new();
+        board.generate_moves(&mut list);
+        let moves = list.into_iter().collect::<Vec<_>>();
+        assert_eq!(moves.len(), 7);
+        assert!(moves[..4].iter().all(|&m| !is_underpromotion(m)));
+        assert!(moves[4..].iter().all(|&m| is_underpromotion(m)));
+    }
+
+    #[test]
+    fn pawn_captures_for_both_colours() {
+        use super::MoveList;
+        use crate::{
+            board::Board,
+            definitions::{type_of, PAWN},
+        };
+
+        crate::magic::initialise();
+
+        // the black position is the white one flipped, so each side has the same captures.
+        for (fen, expected) in [
+            (
+                "n1n1k3/1P6/8/3p4/4P3/8/8/4K3 w - - 0 1",
+                "b7a8b b7a8n b7a8q b7a8r b7c8b b7c8n b7c8q b7c8r e4d5",
+            ),
+            (
+                "4k3/8/8/4p3/3P4/8/1p6/N1N1K3 b - - 0 1",
+                "b2a1b b2a1n b2a1q b2a1r b2c1b b2c1n b2c1q b2c1r e5d4",
+            ),
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            board.reset_tables();
+            for captures_only in [false, true] {
+                let mut list = MoveList::new();
+                if captures_only {
+                    board.generate_captures(&mut list);
+                } else {
+                    board.generate_moves(&mut list);
+                }
+                let mut pawn_captures = list
+                    .into_iter()
+                    .filter(|&m| type_of(board.piece_at(m.from())) == PAWN && board.is_capture(m))
+                    .map(|m| m.to_string())
+                    .collect::<Vec<_>>();
+                pawn_captures.sort();
+                assert_eq!(pawn_captures.join(" "), expected, "{fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn pseudo_legality_matches_movegen() {
+        use super::MoveList;
+        use crate::{board::Board, chessmove::Move};
+        use std::{
+            fs::File,
+            io::{BufRead, BufReader},
+        };
+
+        crate::magic::initialise();
+
+        // every possible bit pattern is checked, so as to cover moves from other positions.
+        let fens = BufReader::new(File::open("perftsuite.epd").unwrap())
+            .lines()
+            .map(|l| l.unwrap().split_once(';').unwrap().0.trim().to_owned())
+            .step_by(12)
+            .take(10)
+            .collect::<Vec<_>>();
+        for fen in fens {
+            let mut board = Board::from_fen(&fen).unwrap();
+            board.reset_tables();
+            let mut list = MoveList::new();
+            board.generate_moves(&mut list);
+            let generated = list.into_iter().collect::<Vec<_>>();
+            for data in 0..=u16::MAX {
+                let m = Move { data };
+                assert_eq!(
+                    board.is_pseudo_legal(m),
+                    generated.contains(&m),
+                    "{m} in {fen}"
+                );
+            }
+        }
+    }
 }
@@ -1,19 +1,19 @@
 use crate::{
     definitions::{
-        colour_of, type_of, BB, BISHOP, BK, BN, BP, BQ, BR, KING, KNIGHT, PAWN, QUEEN, ROOK, WB,
-        WHITE, WK, WN, WP, WQ, WR,
+        colour_of, type_of, BB, BISHOP, BK, BLACK, BN, BP, BQ, BR, KING, KNIGHT, PAWN, QUEEN, ROOK,
+        WB, WHITE, WK, WN, WP, WQ, WR,
     },
     lookups, macros, magic,
 };
 
-// pub const BB_RANK_1: u64 = 0x0000_0000_0000_00FF;
+pub const BB_RANK_1: u64 = 0x0000_0000_0000_00FF;
 pub const BB_RANK_2: u64 = 0x0000_0000_0000_FF00;
-// pub const BB_RANK_3: u64 = 0x0000_0000_00FF_0000;
-// pub const BB_RANK_4: u64 = 0x0000_0000_FF00_0000;
-// pub const BB_RANK_5: u64 = 0x0000_00FF_0000_0000;
-// pub const BB_RANK_6: u64 = 0x0000_FF00_0000_0000;
+pub const BB_RANK_3: u64 = 0x0000_0000_00FF_0000;
+pub const BB_RANK_4: u64 = 0x0000_0000_FF00_0000;
+pub const BB_RANK_5: u64 = 0x0000_00FF_0000_0000;
+pub const BB_RANK_6: u64 = 0x0000_FF00_0000_0000;
 pub const BB_RANK_7: u64 = 0x00FF_0000_0000_0000;
-// pub const BB_RANK_8: u64 = 0xFF00_0000_0000_0000;
+pub const BB_RANK_8: u64 = 0xFF00_0000_0000_0000;
 pub const BB_FILE_A: u64 = 0x0101_0101_0101_0101;
 // pub const BB_FILE_B: u64 = 0x0202_0202_0202_0202;
 // pub const BB_FILE_C: u64 = 0x0404_0404_0404_0404;
@@ -23,6 +23,7 @@ pub const BB_FILE_A: u64 = 0x0101_0101_0101_0101;
 // pub const BB_FILE_G: u64 = 0x4040_4040_4040_4040;
 pub const BB_FILE_H: u64 = 0x8080_8080_8080_8080;
 pub const BB_NONE: u64 = 0x0000_0000_0000_0000;
+pub const BB_LIGHT_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
 // pub const BB_ALL: u64 = 0xFFFF_FFFF_FFFF_FFFF;
 
 /// least significant bit of a u64
@@ -161,6 +162,14 @@ impl BitBoard {
         self.occupied
     }
 
+    pub const fn colour_bb(&self, side: u8) -> u64 {
+        match side {
+            WHITE => self.white,
+            BLACK => self.black,
+            _ => unsafe { macros::inconceivable!() },
+        }
+    }
+
     #[rustfmt::skip]
     pub const fn knights<const IS_WHITE: bool>(&self) -> u64 {
         if IS_WHITE { self.w_knights } else { self.b_knights }
@@ -181,7 +190,7 @@ impl BitBoard {
         if IS_WHITE { self.w_queens } else { self.b_queens }
     }
 
-    pub fn reset(&mut self) {
+    pub const fn reset(&mut self) {
         *self = Self::NULL;
     }
 
@@ -329,11 +338,7 @@ pub fn print_bb(bb: u64) {
             let sq = crate::lookups::filerank_to_square(file, rank);
             assert!(
                 sq < 64,
-                "sq64: {}, sq: {}, file: {}, rank: {}",
-                sq,
-                sq,
-                file,
-                rank
+                "sq64: {sq}, sq: {sq}, file: {file}, rank: {rank}"
             );
             if bb & (1 << sq) != 0 {
                 print!(" X");
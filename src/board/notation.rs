@@ -0,0 +1,206 @@
+use crate::{
+    chessmove::Move,
+    definitions::{square_name, type_of, BISHOP, KING, KNIGHT, PAWN, PIECE_EMPTY, QUEEN, ROOK},
+    errors::MoveParseError,
+    lookups::{file, rank},
+};
+
+use super::{movegen::MoveList, Board};
+
+const fn san_piece_type(c: char) -> Option<u8> {
+    match c {
+        'N' => Some(KNIGHT),
+        'B' => Some(BISHOP),
+        'R' => Some(ROOK),
+        'Q' => Some(QUEEN),
+        'K' => Some(KING),
+        _ => None,
+    }
+}
+
+/// Splits SAN without check marks or annotations into the piece moved, the
+/// disambiguation, the destination square and the promotion piece, with capture
+/// marks dropped. Returns `None` if it isn't shaped like a move at all.
+fn split_san(san: &str) -> Option<(u8, String, &str, u8)> {
+    let (moved, san) = san
+        .chars()
+        .next()
+        .and_then(san_piece_type)
+        .map_or((PAWN, san), |piece| (piece, &san[1..]));
+    // promotions are usually written "e8=Q", but sometimes "e8Q".
+    let (san, promotion) = match san.char_indices().last() {
+        Some((i, c)) if moved == PAWN && c.is_ascii_uppercase() => {
+            (san[..i].trim_end_matches('='), san_piece_type(c)?)
+        }
+        _ => (san, PIECE_EMPTY),
+    };
+    let (disambiguation, to) = san.split_at_checked(san.len().checked_sub(2)?)?;
+    let disambiguation = disambiguation.replace(['x', ':', '-'], "");
+    let is_square = |s: &str| matches!(s.as_bytes(), [b'a'..=b'h', b'1'..=b'8']);
+    let disambiguates = disambiguation.len() <= 2
+        && disambiguation.bytes().all(|c| matches!(c, b'a'..=b'h' | b'1'..=b'8'));
+    (is_square(to) && disambiguates).then_some((moved, disambiguation, to, promotion))
+}
+
+impl Board {
+    /// Whether `m` is the move written in SAN as `san`, without check marks or annotations.
+    fn matches_san(&self, m: Move, san: &str) -> bool {
+        match san {
+            "O-O" | "0-0" => return m.is_castle() && file(m.to()) == 6,
+            "O-O-O" | "0-0-0" => return m.is_castle() && file(m.to()) == 2,
+            _ if m.is_castle() => return false,
+            _ => (),
+        }
+        let Some((moved, disambiguation, to, promotion)) = split_san(san) else {
+            return false;
+        };
+        type_of(self.piece_at(m.from())) == moved
+            && m.promotion() == promotion
+            && square_name(m.to()) == Some(to)
+            && disambiguation.bytes().all(|c| match c {
+                b'a'..=b'h' => file(m.from()) == c - b'a',
+                _ => rank(m.from()) == c - b'1',
+            })
+    }
+
+    /// Parses a move written in Standard Algebraic Notation, like "Nbd7" or "exd8=Q+".
+    /// Unlike [`Board::parse_san`], which despite its name reads UCI moves like "e2e4",
+    /// this finds the one legal move that fits, and fails if there are none or several.
+    pub fn parse_algebraic(&mut self, san: &str) -> Result<Move, MoveParseError> {
+        let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+        let castles = matches!(trimmed, "O-O" | "0-0" | "O-O-O" | "0-0-0");
+        if !castles && split_san(trimmed).is_none() {
+            return Err(MoveParseError::MalformedMove(san.to_string()));
+        }
+        let mut list = MoveList::new();
+        self.generate_moves(&mut list);
+        let mut found = None;
+        for m in list {
+            if self.matches_san(m, trimmed) && self.is_legal(m) {
+                if found.is_some() {
+                    return Err(MoveParseError::AmbiguousMove(san.to_string()));
+                }
+                found = Some(m);
+            }
+        }
+        found.ok_or_else(|| MoveParseError::IllegalMove(san.to_string()))
+    }
+
+    /// Makes each of the whitespace-separated moves in `moves`, given as
+    /// a function from the board to each move, saying which one failed if any did.
+    /// The moves before a failed one are left made.
+    fn make_moves(
+        &mut self,
+        moves: &str,
+        mut parse: impl FnMut(&mut Self, &str) -> Result<Move, MoveParseError>,
+    ) -> Result<(), MoveParseError> {
+        for (index, token) in moves.split_ascii_whitespace().enumerate() {
+            let made = parse(self, token).and_then(|m| {
+                if self.make_move(m) {
+                    Ok(())
+                } else {
+                    Err(MoveParseError::IllegalMove(token.to_string()))
+                }
+            });
+            if let Err(reason) = made {
+                return Err(MoveParseError::InMoveList {
+                    index,
+                    token: token.to_string(),
+                    reason: Box::new(reason),
+                });
+            }
+            self.zero_height();
+        }
+        Ok(())
+    }
+
+    /// Makes the moves in a list of UCI moves like "e2e4 e7e5 g1f3".
+    /// If one can't be made, the error says which, and the moves before it are left made.
+    pub fn make_uci_moves(&mut self, moves: &str) -> Result<(), MoveParseError> {
+        self.make_moves(moves, |board, token| board.parse_san(token))
+    }
+
+    /// Makes the moves in a list of SAN moves like "e4 e5 Nf3", skipping any
+    /// move numbers like "1." or "12...". If one can't be made, the error
+    /// says which, and the moves before it are left made.
+    #[allow(dead_code)] // for library consumers, as UCI only uses coordinates.
+    pub fn make_san_moves(&mut self, moves: &str) -> Result<(), MoveParseError> {
+        let is_move_number = |token: &str| {
+            token.ends_with('.') && token.trim_end_matches('.').bytes().all(|c| c.is_ascii_digit())
+        };
+        let moves = moves
+            .split_ascii_whitespace()
+            .filter(|token| !is_move_number(token))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.make_moves(&moves, Self::parse_algebraic)
+    }
+}
+
+mod tests {
+    #[test]
+    fn algebraic_moves_are_parsed() {
+        use crate::{board::Board, errors::MoveParseError};
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        board
+            .set_from_fen("r3k2r/1P6/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1")
+            .unwrap();
+        let parse = |board: &mut Board, san| board.parse_algebraic(san).map(|m| m.to_string());
+        assert_eq!(parse(&mut board, "O-O").as_deref(), Ok("e1g1"));
+        assert_eq!(parse(&mut board, "O-O-O").as_deref(), Ok("e1c1"));
+        assert_eq!(parse(&mut board, "Nce4").as_deref(), Ok("c3e4"));
+        assert_eq!(
+            parse(&mut board, "Ne4"),
+            Err(MoveParseError::AmbiguousMove("Ne4".to_string()))
+        );
+        assert_eq!(parse(&mut board, "bxa8=Q+").as_deref(), Ok("b7a8q"));
+        assert_eq!(parse(&mut board, "b8N").as_deref(), Ok("b7b8n"));
+        assert_eq!(parse(&mut board, "Rxa8").as_deref(), Ok("a1a8"));
+        assert_eq!(
+            parse(&mut board, "e4"),
+            Err(MoveParseError::IllegalMove("e4".to_string()))
+        );
+        assert_eq!(
+            parse(&mut board, "Nz9"),
+            Err(MoveParseError::MalformedMove("Nz9".to_string()))
+        );
+    }
+
+    #[test]
+    fn move_lists_are_made() {
+        use crate::{board::Board, errors::MoveParseError};
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        board.set_startpos();
+        assert!(board.make_uci_moves("e2e4 e7e5 g1f3").is_ok());
+        assert!(board.make_san_moves("2... Nc6 3. Bb5").is_ok());
+        assert!(board
+            .fen()
+            .starts_with("r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq"));
+
+        board.set_startpos();
+        // the king can't move two squares forward.
+        let error = board.make_uci_moves("e2e4 e7e5 e1e3").unwrap_err();
+        assert_eq!(
+            error,
+            MoveParseError::InMoveList {
+                index: 2,
+                token: "e1e3".to_string(),
+                reason: Box::new(MoveParseError::IllegalMove("e1e3".to_string())),
+            }
+        );
+        // the moves before it are left made.
+        assert!(board
+            .fen()
+            .starts_with("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq"));
+        assert!(matches!(
+            board.make_san_moves("Nf3 Nc6 Bb5 Nf6 Q-xx"),
+            Err(MoveParseError::InMoveList { index: 4, .. })
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+// The chess variants that only change how the game is won, so that the
+// board, move generation and search work for them unchanged:
+// - King of the Hill: a king that reaches one of the four centre squares wins.
+// - Three-check: giving a third check wins.
+
+use crate::{
+    board::Board,
+    definitions::{BLACK, WHITE},
+    lookups::{file, rank},
+};
+
+use super::evaluation::score::S;
+
+/// The value of having a king this many king moves from the centre, in King of the Hill.
+const KING_OF_THE_HILL_DISTANCE: [S; 4] = [S(0, 0), S(120, 160), S(40, 60), S(10, 20)];
+/// The value of having given this many checks, in three-check.
+const CHECKS_GIVEN: [S; 3] = [S(0, 0), S(60, 60), S(200, 200)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    KingOfTheHill,
+    ThreeCheck,
+}
+
+impl Variant {
+    /// The names of the variants, as given to `UCI_Variant`.
+    pub const NAMES: [&'static str; 3] = ["chess", "kingofthehill", "3check"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chess" => Some(Self::Standard),
+            "kingofthehill" => Some(Self::KingOfTheHill),
+            "3check" => Some(Self::ThreeCheck),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Standard => "chess",
+            Self::KingOfTheHill => "kingofthehill",
+            Self::ThreeCheck => "3check",
+        }
+    }
+}
+
+/// How many king moves `sq` is from the nearest of d4, e4, d5 and e5.
+const fn distance_to_centre(sq: u8) -> u8 {
+    let (file, rank) = (file(sq), rank(sq));
+    let files = if file < 3 { 3 - file } else { file.saturating_sub(4) };
+    let ranks = if rank < 3 { 3 - rank } else { rank.saturating_sub(4) };
+    if files > ranks {
+        files
+    } else {
+        ranks
+    }
+}
+
+impl Board {
+    pub const fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Switches to the rules of `variant`. The position should be set again afterwards.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.checks = [0; 2];
+        self.key = self.generate_pos_key();
+    }
+
+    /// The number of checks `side` has given, which is only counted in three-check.
+    pub const fn checks_given(&self, side: u8) -> u8 {
+        self.checks[side as usize]
+    }
+
+    /// Whether the side to move has already lost under the rules of the variant,
+    /// as the previous move won the game without mating.
+    pub fn is_variant_loss(&self) -> bool {
+        match self.variant {
+            Variant::Standard => false,
+            Variant::KingOfTheHill => distance_to_centre(self.king_sq(self.side ^ 1)) == 0,
+            Variant::ThreeCheck => self.checks[(self.side ^ 1) as usize] >= 3,
+        }
+    }
+
+    /// The evaluation of progress towards the variant's win condition, from white's point of view.
+    pub(super) fn variant_term(&self) -> S {
+        match self.variant {
+            Variant::Standard => S(0, 0),
+            Variant::KingOfTheHill => {
+                let value = |side| {
+                    let distance = distance_to_centre(self.king_sq(side)) as usize;
+                    KING_OF_THE_HILL_DISTANCE.get(distance).copied().unwrap_or(S(0, 0))
+                };
+                value(WHITE) - value(BLACK)
+            }
+            Variant::ThreeCheck => {
+                let value = |side: u8| CHECKS_GIVEN[usize::from(self.checks_given(side).min(2))];
+                value(WHITE) - value(BLACK)
+            }
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn centre_distances() {
+        use super::distance_to_centre;
+        use crate::definitions::Square::{A1, C3, D4, E5, F6, H8};
+
+        assert_eq!(distance_to_centre(D4), 0);
+        assert_eq!(distance_to_centre(E5), 0);
+        assert_eq!(distance_to_centre(C3), 1);
+        assert_eq!(distance_to_centre(F6), 1);
+        assert_eq!(distance_to_centre(A1), 3);
+        assert_eq!(distance_to_centre(H8), 3);
+    }
+
+    #[test]
+    fn three_check_counts_and_hashes_checks() {
+        use super::Variant;
+        use crate::{board::Board, definitions::WHITE};
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.set_variant(Variant::ThreeCheck);
+        board.set_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 2+3 0 1").unwrap();
+        board.reset_tables();
+        assert_eq!(board.checks_given(WHITE), 1);
+        let standard_key = {
+            let mut standard = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+            standard.reset_tables();
+            standard.key
+        };
+        assert_ne!(board.key, standard_key);
+
+        let check = board.parse_san("a1a8").unwrap();
+        assert!(board.make_move(check));
+        assert_eq!(board.checks_given(WHITE), 2);
+        assert_eq!(board.key, board.generate_pos_key());
+        assert_eq!(board.fen(), "R3k3/8/8/8/8/8/8/4K3 b - - 1+3 1 1");
+        board.unmake_move();
+        assert_eq!(board.checks_given(WHITE), 1);
+        assert_eq!(board.key, board.generate_pos_key());
+        assert!(!board.is_variant_loss());
+    }
+
+    #[test]
+    fn variant_wins_are_found() {
+        use super::Variant;
+        use crate::{
+            board::Board, definitions::Depth, searchinfo::SearchInfo, uci::OutputFormat,
+        };
+
+        crate::magic::initialise();
+        for (variant, fen, best) in [
+            (Variant::KingOfTheHill, "k7/8/8/8/8/2K5/8/8 w - - 0 1", "c3d4"),
+            (Variant::ThreeCheck, "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1", "a1a8"),
+        ] {
+            let mut board = Board::new();
+            board.set_variant(variant);
+            board.set_from_fen(fen).unwrap();
+            let mut info = SearchInfo {
+                depth: Depth::new(3),
+                output_format: OutputFormat::Silent,
+                ..SearchInfo::default()
+            };
+            let (score, m) = board.search_position(&mut info);
+            assert_eq!(m.to_string(), best, "{}", variant.name());
+            assert!(score.is_mate(), "{}", variant.name());
+        }
+    }
+}
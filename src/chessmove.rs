@@ -1,32 +1,42 @@
 use std::fmt::{Debug, Display, Formatter};
 
-use crate::{definitions::square_name, lookups::PROMO_CHAR_LOOKUP};
-
+use crate::{
+    definitions::{square_name, KNIGHT, PIECE_EMPTY, QUEEN},
+    lookups::PROMO_CHAR_LOOKUP,
+};
+
+/// A move, packed into 16 bits as the from-square, the to-square, the type
+/// of piece promoted to, and a flag for special moves. The piece captured
+/// isn't stored, as it can be found on the board before the move is made.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Move {
-    pub data: u32,
+    pub data: u16,
 }
 
 impl Move {
-    const FROM_MASK: u32 = 0b0111_1111;
-    const TO_MASK: u32 = 0b0011_1111_1000_0000;
-    const CAPTURE_MASK: u32 = 0b0011_1100_0000_0000_0000;
-    const PROMO_MASK: u32 = 0b1111_0000_0000_0000_0000_0000;
-    pub const EP_MASK: u32 = 0b0100_0000_0000_0000_0000;
-    pub const PAWN_START_MASK: u32 = 0b1000_0000_0000_0000_0000;
-    pub const CASTLE_MASK: u32 = 0b0001_0000_0000_0000_0000_0000_0000;
+    const FROM_MASK: u16 = 0b0000_0000_0011_1111;
+    const TO_MASK: u16 = 0b0000_1111_1100_0000;
+    const PROMO_TYPE_MASK: u16 = 0b0011_0000_0000_0000;
+    const FLAG_MASK: u16 = 0b1100_0000_0000_0000;
+    const PROMO_FLAG: u16 = 0b0100_0000_0000_0000;
+    pub const EP_FLAG: u16 = 0b1000_0000_0000_0000;
+    pub const CASTLE_FLAG: u16 = 0b1100_0000_0000_0000;
     pub const NULL: Self = Self { data: 0 };
 
-    pub fn new(from: u8, to: u8, capture: u8, promotion: u8, flags: u32) -> Self {
-        debug_assert!(
-            (flags & (Self::EP_MASK | Self::PAWN_START_MASK | Self::CASTLE_MASK)) == flags
-        );
+    /// Creates a move. `promotion` is the type of piece promoted to, or
+    /// `PIECE_EMPTY`, and `flags` is one of `EP_FLAG`, `CASTLE_FLAG`, or 0.
+    pub fn new(from: u8, to: u8, promotion: u8, flags: u16) -> Self {
+        debug_assert!(from < 64 && to < 64);
+        debug_assert!(flags == 0 || flags == Self::EP_FLAG || flags == Self::CASTLE_FLAG);
+        debug_assert!(promotion == PIECE_EMPTY || (KNIGHT..=QUEEN).contains(&promotion));
+        debug_assert!(promotion == PIECE_EMPTY || flags == 0);
+        let promotion_bits = if promotion == PIECE_EMPTY {
+            0
+        } else {
+            (u16::from(promotion - KNIGHT) << 12) | Self::PROMO_FLAG
+        };
         Self {
-            data: u32::from(from)
-                | (u32::from(to) << 7)
-                | (u32::from(capture) << 14)
-                | (u32::from(promotion) << 20)
-                | flags,
+            data: u16::from(from) | (u16::from(to) << 6) | promotion_bits | flags,
         }
     }
 
@@ -35,35 +45,28 @@ impl Move {
     }
 
     pub const fn to(self) -> u8 {
-        (((self.data & Self::TO_MASK) >> 7) & 0x7F) as u8
-    }
-
-    pub const fn capture(self) -> u8 {
-        (((self.data & Self::CAPTURE_MASK) >> 14) & 0xF) as u8
+        ((self.data & Self::TO_MASK) >> 6) as u8
     }
 
+    /// The type of piece promoted to, or `PIECE_EMPTY` if this isn't a promotion.
     pub const fn promotion(self) -> u8 {
-        (((self.data & Self::PROMO_MASK) >> 20) & 0xF) as u8
+        if self.is_promo() {
+            ((self.data & Self::PROMO_TYPE_MASK) >> 12) as u8 + KNIGHT
+        } else {
+            PIECE_EMPTY
+        }
     }
 
     pub const fn is_promo(self) -> bool {
-        ((self.data & Self::PROMO_MASK) != 0) as bool
+        (self.data & Self::FLAG_MASK) == Self::PROMO_FLAG
     }
 
     pub const fn is_ep(self) -> bool {
-        (self.data & Self::EP_MASK) != 0
-    }
-
-    pub const fn is_pawn_start(self) -> bool {
-        (self.data & Self::PAWN_START_MASK) != 0
+        (self.data & Self::FLAG_MASK) == Self::EP_FLAG
     }
 
     pub const fn is_castle(self) -> bool {
-        (self.data & Self::CASTLE_MASK) != 0
-    }
-
-    pub const fn is_capture(self) -> bool {
-        (self.data & Self::CAPTURE_MASK) != 0
+        (self.data & Self::FLAG_MASK) == Self::CASTLE_FLAG
     }
 
     pub const fn is_null(self) -> bool {
@@ -86,7 +89,7 @@ impl Display for Move {
             let pchar = PROMO_CHAR_LOOKUP[self.promotion() as usize];
             write!(f, "{}{}{}", from_square, to_square, pchar as char)?;
         } else {
-            write!(f, "{}{}", from_square, to_square)?;
+            write!(f, "{from_square}{to_square}")?;
         }
 
         Ok(())
@@ -97,16 +100,14 @@ impl Debug for Move {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
         write!(
             f,
-            "move from {} ({}) to {} ({}), capture {}, promo {}, ispromo {}, ep {}, pawn start {}, castle {}",
+            "move from {} ({}) to {} ({}), promo {}, ispromo {}, ep {}, castle {}",
             self.from(),
-            square_name(self.from()).unwrap_or("NONE"), 
+            square_name(self.from()).unwrap_or("NONE"),
             self.to(),
             square_name(self.to()).unwrap_or("NONE"),
-            self.capture(),
             self.promotion(),
             self.is_promo(),
             self.is_ep(),
-            self.is_pawn_start(),
             self.is_castle()
         )
     }
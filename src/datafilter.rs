@@ -0,0 +1,240 @@
+// Cleans up the files of FENs and game results that the Texel tuner reads.
+// Duplicate positions are removed, along with positions that say little about
+// the evaluation: those in check, those straight out of the opening, and those
+// where one side is so far ahead that the result was never in doubt.
+// The numbers of wins, draws and losses can then be evened out.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use crate::{
+    board::{evaluation::to_centipawns, Board},
+    definitions::Score,
+    searchinfo::SearchInfo,
+};
+
+pub struct FilterConfig {
+    /// Positions before this many plies into the game are dropped.
+    pub min_ply: usize,
+    /// Positions whose quiescent score is further than this from zero, in centipawns, are dropped.
+    pub max_score: i32,
+    /// Whether to keep positions where the side to move is in check.
+    pub keep_checks: bool,
+    /// Whether to cut every result class down to the size of the smallest.
+    pub rebalance: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            min_ply: 16,
+            max_score: 1500,
+            keep_checks: false,
+            rebalance: true,
+        }
+    }
+}
+
+/// How many positions were read, and why the ones that were dropped were dropped.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FilterStats {
+    pub read: usize,
+    pub duplicates: usize,
+    pub early: usize,
+    pub in_check: usize,
+    pub lopsided: usize,
+    pub rebalanced: usize,
+    pub written: usize,
+}
+
+/// Which of loss, draw or win a game result is, from white's point of view.
+fn result_class(outcome: f64) -> usize {
+    if outcome < 0.25 {
+        0
+    } else if outcome < 0.75 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Filters lines of the form `FEN RESULT`, returning the lines to keep in their original order.
+/// When rebalancing, the first positions of each result class are the ones kept.
+pub fn filter<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    config: &FilterConfig,
+) -> Result<(Vec<&'a str>, FilterStats), String> {
+    let mut stats = FilterStats::default();
+    let mut pos = Board::default();
+    let mut info = SearchInfo {
+        lazy_eval: false,
+        ..SearchInfo::default()
+    };
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    for (number, line) in lines.into_iter().enumerate() {
+        let line_error = |e: String| format!("line {}: {e}", number + 1);
+        let (fen, outcome) = line
+            .trim()
+            .rsplit_once(' ')
+            .ok_or_else(|| line_error(format!("expected a FEN and a result, got {line}")))?;
+        let outcome = outcome
+            .parse::<f64>()
+            .map_err(|e| line_error(format!("bad result {outcome}: {e}")))?;
+        pos.set_from_fen(fen).map_err(line_error)?;
+        stats.read += 1;
+
+        if !seen.insert(pos.hashkey()) {
+            stats.duplicates += 1;
+            continue;
+        }
+        if pos.ply() < config.min_ply {
+            stats.early += 1;
+            continue;
+        }
+        if !config.keep_checks && pos.in_check::<{ Board::US }>() {
+            stats.in_check += 1;
+            continue;
+        }
+        let score = Board::quiescence(&mut pos, &mut info, -Score::INFINITY, Score::INFINITY);
+        if to_centipawns(score.raw()).abs() > config.max_score {
+            stats.lopsided += 1;
+            continue;
+        }
+        kept.push((line, result_class(outcome)));
+    }
+
+    let mut quota = if config.rebalance {
+        let mut counts = [0; 3];
+        for &(_, class) in &kept {
+            counts[class] += 1;
+        }
+        // a class that never appears can't be balanced against, so it's left out.
+        let smallest = counts.into_iter().filter(|&n| n > 0).min();
+        [smallest.unwrap_or(0); 3]
+    } else {
+        [usize::MAX; 3]
+    };
+    let mut output = Vec::new();
+    for (line, class) in kept {
+        if quota[class] == 0 {
+            stats.rebalanced += 1;
+            continue;
+        }
+        quota[class] -= 1;
+        output.push(line);
+    }
+    stats.written = output.len();
+    Ok((output, stats))
+}
+
+/// Parses the arguments following `datafilter` on the command line, and filters the file.
+pub fn datafilter_cli(args: &[String]) -> Result<(), String> {
+    let mut config = FilterConfig::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("no value given after {arg}"))
+        };
+        match arg.as_str() {
+            "--min-ply" => {
+                config.min_ply = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --min-ply: {e}"))?;
+            }
+            "--max-score" => {
+                config.max_score = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --max-score: {e}"))?;
+            }
+            "--keep-checks" => config.keep_checks = true,
+            "--no-rebalance" => config.rebalance = false,
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown argument to datafilter: {flag}"));
+            }
+            path => paths.push(path),
+        }
+    }
+    let [input, output] = paths[..] else {
+        return Err("usage: datafilter INPUT OUTPUT [--min-ply N] [--max-score CP] [--keep-checks] [--no-rebalance]".to_string());
+    };
+
+    let lines =
+        BufReader::new(File::open(input).map_err(|e| format!("couldn't open {input}: {e}"))?)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("couldn't read {input}: {e}"))?;
+    let (kept, stats) = filter(lines.iter().map(String::as_str), &config)?;
+
+    let mut out =
+        BufWriter::new(File::create(output).map_err(|e| format!("couldn't create {output}: {e}"))?);
+    for line in kept {
+        writeln!(out, "{line}").map_err(|e| format!("couldn't write to {output}: {e}"))?;
+    }
+    out.flush()
+        .map_err(|e| format!("couldn't write to {output}: {e}"))?;
+
+    println!("read {} positions", stats.read);
+    println!("dropped {} duplicates", stats.duplicates);
+    println!("dropped {} before ply {}", stats.early, config.min_ply);
+    println!("dropped {} in check", stats.in_check);
+    println!(
+        "dropped {} scored beyond {}cp",
+        stats.lopsided, config.max_score
+    );
+    println!("dropped {} to rebalance results", stats.rebalanced);
+    println!("wrote {} positions to {output}", stats.written);
+    Ok(())
+}
+
+mod tests {
+    #[test]
+    fn positions_are_filtered_and_rebalanced() {
+        use super::{filter, FilterConfig, FilterStats};
+
+        crate::magic::initialise();
+        let lines = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 10 0.5",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 12 0.5",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 0.5",
+            "4k3/8/8/8/8/8/4R3/4K3 b - - 0 30 1.0",
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 30 1.0",
+            "4k3/8/8/8/8/8/3P4/4K3 w - - 0 30 1.0",
+            "4k3/8/8/8/8/8/2P5/4K3 w - - 0 30 1.0",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 30 0.5",
+            "4k3/3p4/8/8/8/8/8/4K3 w - - 0 30 0.0",
+        ];
+        let config = FilterConfig {
+            max_score: 500,
+            ..FilterConfig::default()
+        };
+        let (kept, stats) = filter(lines, &config).unwrap();
+        assert_eq!(kept, [lines[0], lines[5], lines[8]]);
+        assert_eq!(
+            stats,
+            FilterStats {
+                read: 9,
+                duplicates: 1,
+                early: 1,
+                in_check: 1,
+                lopsided: 1,
+                rebalanced: 2,
+                written: 3,
+            }
+        );
+
+        let config = FilterConfig {
+            rebalance: false,
+            ..config
+        };
+        assert_eq!(filter(lines, &config).unwrap().0.len(), 5);
+        assert!(filter(["not a fen 1.0"], &config).is_err());
+        assert!(filter([lines[0].trim_end_matches(" 0.5")], &config).is_err());
+    }
+}
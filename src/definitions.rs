@@ -1,27 +1,40 @@
 use std::{
     fmt::Display,
-    ops::{Add, AddAssign, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, Neg, Sub, SubAssign},
     str::FromStr,
 };
 
 use crate::{
-    board::evaluation::MATE_SCORE,
     chessmove::Move,
     lookups::{file, rank, SQUARE_NAMES},
 };
 
 pub const BOARD_N_SQUARES: usize = 64;
 pub const MAX_DEPTH: Depth = Depth::new(128);
-pub const INFINITY: i32 = MATE_SCORE * 2;
-
+/// The depth of a quiescence node. Main search nodes always have a depth
+/// strictly greater than this, so TT entries stored from quiescence can
+/// never satisfy a depth requirement in the main search.
+pub const ZERO_PLY: Depth = Depth::new(0);
+
+/// A search depth, measured in hundredths of a ply so that extensions and
+/// reductions can be applied in fractional amounts. Arithmetic saturates
+/// rather than overflowing.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Depth(i32);
 
 impl Depth {
     pub const ONE_PLY: i32 = 100;
+    pub const HALF_PLY: Self = Self(Self::ONE_PLY / 2);
+    pub const QUARTER_PLY: Self = Self(Self::ONE_PLY / 4);
 
     pub const fn new(depth: i32) -> Self {
-        Self(depth * Self::ONE_PLY)
+        Self(depth.saturating_mul(Self::ONE_PLY))
+    }
+
+    /// Constructs a depth of `numerator / denominator` plies,
+    /// rounded towards zero to the nearest representable fraction.
+    pub const fn from_fraction(numerator: i32, denominator: i32) -> Self {
+        Self(numerator.saturating_mul(Self::ONE_PLY) / denominator)
     }
 
     pub const fn from_raw(raw: i32) -> Self {
@@ -59,7 +72,7 @@ impl Depth {
 impl Add<Self> for Depth {
     type Output = Self;
     fn add(self, other: Self) -> Self::Output {
-        Self(self.0 + other.0)
+        Self(self.0.saturating_add(other.0))
     }
 }
 impl AddAssign<Self> for Depth {
@@ -70,7 +83,7 @@ impl AddAssign<Self> for Depth {
 impl Add<i32> for Depth {
     type Output = Self;
     fn add(self, other: i32) -> Self::Output {
-        Self(self.0 + other * Self::ONE_PLY)
+        Self(self.0.saturating_add(other.saturating_mul(Self::ONE_PLY)))
     }
 }
 impl AddAssign<i32> for Depth {
@@ -81,7 +94,7 @@ impl AddAssign<i32> for Depth {
 impl Sub<Self> for Depth {
     type Output = Self;
     fn sub(self, other: Self) -> Self::Output {
-        Self(self.0 - other.0)
+        Self(self.0.saturating_sub(other.0))
     }
 }
 impl SubAssign<Self> for Depth {
@@ -92,7 +105,7 @@ impl SubAssign<Self> for Depth {
 impl Sub<i32> for Depth {
     type Output = Self;
     fn sub(self, other: i32) -> Self::Output {
-        Self(self.0 - other * Self::ONE_PLY)
+        Self(self.0.saturating_sub(other.saturating_mul(Self::ONE_PLY)))
     }
 }
 impl SubAssign<i32> for Depth {
@@ -100,6 +113,12 @@ impl SubAssign<i32> for Depth {
         *self = *self - other;
     }
 }
+impl Div<i32> for Depth {
+    type Output = Self;
+    fn div(self, other: i32) -> Self::Output {
+        Self(self.0 / other)
+    }
+}
 impl From<i32> for Depth {
     fn from(depth: i32) -> Self {
         Self::new(depth)
@@ -138,7 +157,7 @@ impl Display for Depth {
         };
         write!(
             f,
-            "{}{}.{}",
+            "{}{}.{:02}",
             sign,
             self.0.abs() / Self::ONE_PLY,
             self.0.abs() % Self::ONE_PLY
@@ -156,13 +175,16 @@ impl FromStr for Depth {
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct CompactDepthStorage(i16);
 impl CompactDepthStorage {
-    pub const NULL: Self = Self(0);
+    /// The depth of an empty TT slot, one ply below quiescence entries,
+    /// so that any real entry will displace it.
+    #[allow(clippy::cast_possible_truncation)]
+    pub const NULL: Self = Self(-Depth::ONE_PLY as i16);
 }
-impl TryFrom<Depth> for CompactDepthStorage {
-    type Error = <i16 as std::convert::TryFrom<i32>>::Error;
-    fn try_from(depth: Depth) -> Result<Self, Self::Error> {
-        let inner = depth.0.try_into()?;
-        Ok(Self(inner))
+impl From<Depth> for CompactDepthStorage {
+    /// Depths outside the range of an `i16` saturate to its bounds.
+    fn from(depth: Depth) -> Self {
+        #![allow(clippy::cast_possible_truncation)]
+        Self(depth.0.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16)
     }
 }
 impl From<CompactDepthStorage> for Depth {
@@ -171,6 +193,120 @@ impl From<CompactDepthStorage> for Depth {
     }
 }
 
+/// A search score in evaluation units, from the point of view of the side to move.
+/// Mate scores count plies from the root of the search, so they are converted
+/// with `to_tt` and `from_tt` on their way in and out of the transposition table.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Score(i32);
+impl Score {
+    /// The value of a draw.
+    pub const DRAW: Self = Self(0);
+    /// The value of checkmate.
+    /// To recover depth-to-mate, we subtract depth (ply) from this value.
+    /// e.g. if white has a mate in two ply, the output from a depth-5 search will be
+    /// `3_000_000 - 2 = 2_999_998`.
+    pub const MATE: Self = Self(3_000_000);
+    /// A threshold over which scores must be mate.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub const IS_MATE: Self = Self(Self::MATE.0 - MAX_DEPTH.ply_to_horizon() as i32);
+    /// A bound beyond every score the search can return.
+    pub const INFINITY: Self = Self(Self::MATE.0 * 2);
+
+    pub const fn new(score: i32) -> Self {
+        Self(score)
+    }
+
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
+
+    /// The score for delivering mate `ply` plies from the root.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub const fn mate_in(ply: usize) -> Self {
+        Self(Self::MATE.0 - ply as i32)
+    }
+
+    /// The score for being mated `ply` plies from the root.
+    pub const fn mated_in(ply: usize) -> Self {
+        Self(-Self::mate_in(ply).0)
+    }
+
+    pub const fn is_mate(self) -> bool {
+        self.0.abs() >= Self::IS_MATE.0
+    }
+
+    /// The number of plies until mate. Only meaningful for mate scores.
+    pub const fn plies_to_mate(self) -> i32 {
+        Self::MATE.0 - self.0.abs()
+    }
+
+    /// Whether this is a mate score that has to be adjusted for the ply it is
+    /// stored or probed at. Bounds past `MATE`, like `INFINITY`, are left alone.
+    const fn is_adjustable_mate(self) -> bool {
+        self.is_mate() && self.0.abs() <= Self::MATE.0
+    }
+
+    /// Converts a mate score from distance-to-root to distance-to-node,
+    /// for storing in the transposition table at `ply`.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub const fn to_tt(self, ply: usize) -> Self {
+        if !self.is_adjustable_mate() {
+            self
+        } else if self.0 > 0 {
+            Self(self.0 + ply as i32)
+        } else {
+            Self(self.0 - ply as i32)
+        }
+    }
+
+    /// Converts a mate score from the transposition table back to distance-to-root,
+    /// for a probe at `ply`.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::wrong_self_convention
+    )]
+    pub const fn from_tt(self, ply: usize) -> Self {
+        if !self.is_adjustable_mate() {
+            self
+        } else if self.0 > 0 {
+            Self(self.0 - ply as i32)
+        } else {
+            Self(self.0 + ply as i32)
+        }
+    }
+}
+impl Neg for Score {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+impl Add<i32> for Score {
+    type Output = Self;
+    fn add(self, other: i32) -> Self::Output {
+        Self(self.0 + other)
+    }
+}
+impl Sub<i32> for Score {
+    type Output = Self;
+    fn sub(self, other: i32) -> Self::Output {
+        Self(self.0 - other)
+    }
+}
+/// The distance between two scores, in evaluation units.
+impl Sub<Self> for Score {
+    type Output = i32;
+    fn sub(self, other: Self) -> Self::Output {
+        self.0 - other.0
+    }
+}
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 #[rustfmt::skip]
@@ -350,9 +486,15 @@ pub const BQCA: u8 = 0b1000;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Undo {
     pub m: Move,
+    /// The piece captured by the move, as moves don't record it.
+    pub capture: u8,
     pub castle_perm: u8,
     pub ep_square: u8,
-    pub fifty_move_counter: u8,
+    pub fifty_move_counter: u16,
+    /// The hash key of the position the move was made from, for spotting repetitions.
+    pub key: u64,
+    /// The checks each side had given before the move, for three-check.
+    pub checks: [u8; 2],
 }
 
 pub fn square_name(sq: u8) -> Option<&'static str> {
@@ -360,7 +502,7 @@ pub fn square_name(sq: u8) -> Option<&'static str> {
 }
 
 pub const fn flip_rank(sq: u8) -> u8 {
-    sq ^ 56
+    sq ^ 0b11_1000
 }
 
 pub const fn flip_file(sq: u8) -> u8 {
@@ -388,4 +530,56 @@ mod tests {
         assert_eq!(flip_file(A8), H8);
         assert_eq!(flip_file(H8), A8);
     }
+
+    #[test]
+    fn fractional_depths() {
+        use super::Depth;
+
+        assert_eq!(Depth::QUARTER_PLY + Depth::QUARTER_PLY, Depth::HALF_PLY);
+        assert_eq!(Depth::from_fraction(3, 4), Depth::new(1) - Depth::QUARTER_PLY);
+        assert_eq!(Depth::from(2.25), Depth::new(2) + Depth::QUARTER_PLY);
+        assert!(Depth::new(3) - Depth::QUARTER_PLY < Depth::new(3));
+        assert!(Depth::new(3) - Depth::QUARTER_PLY > Depth::new(2));
+        assert_eq!((Depth::new(3) - Depth::QUARTER_PLY).round(), 2);
+        assert!(!Depth::HALF_PLY.is_exact_ply());
+        assert_eq!(Depth::from_fraction(1, 20).to_string(), "0.05");
+        assert_eq!((Depth::new(0) - Depth::HALF_PLY).to_string(), "-0.50");
+    }
+
+    #[test]
+    fn depth_saturation() {
+        use super::{CompactDepthStorage, Depth};
+
+        assert_eq!(Depth::from_raw(i32::MAX) + Depth::QUARTER_PLY, Depth::from_raw(i32::MAX));
+        assert_eq!(Depth::from_raw(i32::MIN) - 1, Depth::from_raw(i32::MIN));
+        assert_eq!(Depth::new(i32::MAX), Depth::from_raw(i32::MAX));
+
+        let too_deep = Depth::from(CompactDepthStorage::from(Depth::new(1000)));
+        assert_eq!(too_deep, Depth::from_raw(i32::from(i16::MAX)));
+        let too_shallow = Depth::from(CompactDepthStorage::from(Depth::new(-1000)));
+        assert_eq!(too_shallow, Depth::from_raw(i32::from(i16::MIN)));
+        let in_range = Depth::new(12) + Depth::QUARTER_PLY;
+        assert_eq!(Depth::from(CompactDepthStorage::from(in_range)), in_range);
+    }
+
+    #[test]
+    fn mate_scores() {
+        use super::{Score, MAX_DEPTH};
+
+        let max_ply = MAX_DEPTH.ply_to_horizon();
+        assert_eq!(-Score::mate_in(3), Score::mated_in(3));
+        assert!(Score::mate_in(3) > Score::mate_in(5));
+        assert!(Score::mated_in(3) < Score::mated_in(5));
+        assert!(Score::mate_in(max_ply).is_mate());
+        assert!(Score::mated_in(max_ply).is_mate());
+        assert!(!(Score::mate_in(max_ply) - 1).is_mate());
+        assert!(!Score::DRAW.is_mate());
+        assert_eq!(Score::mate_in(7).plies_to_mate(), 7);
+        assert_eq!(Score::mated_in(7).plies_to_mate(), 7);
+        assert_eq!(Score::mate_in(7).to_tt(4), Score::mate_in(3));
+        assert_eq!(Score::mated_in(7).to_tt(4), Score::mated_in(3));
+        assert_eq!(Score::mate_in(3).from_tt(4), Score::mate_in(7));
+        assert_eq!(Score::new(150).to_tt(4), Score::new(150));
+        assert_eq!(Score::INFINITY.to_tt(4), Score::INFINITY);
+    }
 }
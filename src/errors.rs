@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::{definitions::square_name, lookups::piece_char};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MoveParseError {
     InvalidLength(usize),
@@ -9,6 +11,14 @@ pub enum MoveParseError {
     InvalidToSquareRank(char),
     InvalidPromotionPiece(char),
     IllegalMove(String),
+    AmbiguousMove(String),
+    MalformedMove(String),
+    /// A move in a list couldn't be made, with its index in the list.
+    InMoveList {
+        index: usize,
+        token: String,
+        reason: Box<Self>,
+    },
 }
 impl Display for MoveParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -20,10 +30,157 @@ impl Display for MoveParseError {
             Self::InvalidToSquareRank(rank) => write!(f, "Invalid to-square rank {rank}"),
             Self::InvalidPromotionPiece(piece) => write!(f, "Invalid promotion piece {piece}"),
             Self::IllegalMove(m) => write!(f, "Illegal move {m}"),
+            Self::AmbiguousMove(m) => write!(f, "Ambiguous move {m}"),
+            Self::MalformedMove(m) => write!(f, "Malformed move {m}"),
+            Self::InMoveList {
+                index,
+                token,
+                reason,
+            } => write!(f, "Move {} in the list, {token}, failed: {reason}", index + 1),
+        }
+    }
+}
+
+/// A reason that a position is corrupt or could not arise in a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    PieceListCorrupt {
+        sq: u8,
+        expected: u8,
+        found: u8,
+    },
+    InvalidSide(u8),
+    PieceCountsCorrupt {
+        expected: Vec<u8>,
+        found: Vec<u8>,
+    },
+    BitboardCorrupt {
+        sq: u8,
+        expected: u8,
+        found: u8,
+    },
+    ColourBitboardCorrupt {
+        sq: u8,
+        colour: u8,
+        found: u8,
+    },
+    MaterialCorrupt {
+        colour: u8,
+        expected: (i32, i32),
+        found: (i32, i32),
+    },
+    PieceCountCorrupt {
+        kind: &'static str,
+        colour: u8,
+        expected: u8,
+        found: u8,
+    },
+    PhaseCorrupt {
+        expected: i32,
+        found: i32,
+    },
+    KeyCorrupt {
+        expected: u64,
+        found: u64,
+    },
+    InvalidEnPassant(u8),
+    KingCount {
+        colour: u8,
+        count: u8,
+    },
+    KingSquareCorrupt {
+        colour: u8,
+        found: u8,
+    },
+    KingsAdjacent,
+    PawnOnBackRank(u8),
+    OpponentInCheck,
+}
+
+fn describe_square(sq: u8) -> String {
+    square_name(sq).map_or_else(|| format!("offboard: {sq}"), str::to_string)
+}
+
+fn describe_piece(piece: u8) -> String {
+    piece_char(piece).map_or_else(|| format!("unknown piece: {piece}"), |c| c.to_string())
+}
+
+const fn colour_name(colour: u8) -> &'static str {
+    if colour == 0 {
+        "white"
+    } else {
+        "black"
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::PieceListCorrupt { sq, expected, found } => write!(
+                f,
+                "piece list corrupt: expected square {} to be '{}' but was '{}'",
+                describe_square(*sq),
+                describe_piece(*expected),
+                describe_piece(*found)
+            ),
+            Self::InvalidSide(side) => write!(f, "invalid side: {side}"),
+            Self::PieceCountsCorrupt { expected, found } => write!(
+                f,
+                "piece counts are corrupt: expected {expected:?}, got {found:?}"
+            ),
+            Self::BitboardCorrupt { sq, expected, found } => write!(
+                f,
+                "bitboard / piece array coherency corrupt: expected square {} to be '{}' but was '{}'",
+                describe_square(*sq),
+                describe_piece(*expected),
+                describe_piece(*found)
+            ),
+            Self::ColourBitboardCorrupt { sq, colour, found } => write!(
+                f,
+                "{} pieces bitboard includes square {} but it holds '{}'",
+                colour_name(*colour),
+                describe_square(*sq),
+                describe_piece(*found)
+            ),
+            Self::MaterialCorrupt { colour, expected, found } => write!(
+                f,
+                "{} material is corrupt: expected {expected:?}, got {found:?}",
+                colour_name(*colour)
+            ),
+            Self::PieceCountCorrupt { kind, colour, expected, found } => write!(
+                f,
+                "{} {kind} piece count is corrupt: expected {expected}, got {found}",
+                colour_name(*colour)
+            ),
+            Self::PhaseCorrupt { expected, found } => {
+                write!(f, "game phase is corrupt: expected {expected}, got {found}")
+            }
+            Self::KeyCorrupt { expected, found } => {
+                write!(f, "key is corrupt: expected {expected}, got {found}")
+            }
+            Self::InvalidEnPassant(sq) => write!(
+                f,
+                "en passant square {} is not on the sixth rank for white to move or the third rank for black to move",
+                describe_square(*sq)
+            ),
+            Self::KingCount { colour, count } => write!(
+                f,
+                "{} has {count} kings, expected exactly one",
+                colour_name(*colour)
+            ),
+            Self::KingSquareCorrupt { colour, found } => write!(
+                f,
+                "{} king square is corrupt: expected a king, got '{}'",
+                colour_name(*colour),
+                describe_piece(*found)
+            ),
+            Self::KingsAdjacent => write!(f, "the kings are adjacent"),
+            Self::PawnOnBackRank(sq) => {
+                write!(f, "there is a pawn on the back rank, on {}", describe_square(*sq))
+            }
+            Self::OpponentInCheck => write!(f, "the side not to move is in check"),
         }
     }
 }
 
-#[allow(dead_code)]
-pub type PositionValidityError = String;
 pub type FenParseError = String;
@@ -0,0 +1,79 @@
+// Scores every position in a file, for checking how a change to the
+// evaluation moves the scores of a large set of positions.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+};
+
+use crate::{
+    arena::epd_to_fen,
+    board::{evaluation::to_centipawns, Board},
+    definitions::{Depth, Score, WHITE},
+    searchinfo::SearchInfo,
+    uci::OutputFormat,
+};
+
+/// The Hash budget for the searches. Each one is short and starts from an empty
+/// table, so the default table would only take longer to clear.
+const SEARCH_HASH_MEGABYTES: usize = 16;
+
+/// Formats a score from white's point of view, with mates written as `#N` or `#-N`.
+fn csv_score(score: Score) -> String {
+    if score.is_mate() {
+        let moves_to_mate = (score.plies_to_mate() + 1) / 2;
+        if score > Score::DRAW {
+            format!("#{moves_to_mate}")
+        } else {
+            format!("#-{moves_to_mate}")
+        }
+    } else {
+        to_centipawns(score.raw()).to_string()
+    }
+}
+
+/// Prints a CSV of the static evaluation of each position in the file, and the
+/// score of a search to `depth` if one is given, both from white's point of view.
+/// Each line of the file is a FEN or EPD, and blank lines and lines starting with `#` are skipped.
+pub fn run(path: &str, depth: Option<i32>) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("couldn't open {path}: {e}"))?;
+    let mut pos = Board::new();
+    pos.resize_tt(SEARCH_HASH_MEGABYTES);
+    pos.reset_tables();
+    let mut out = std::io::stdout().lock();
+    let header = if depth.is_some() {
+        "fen,eval,search"
+    } else {
+        "fen,eval"
+    };
+    writeln!(out, "{header}").map_err(|e| e.to_string())?;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("couldn't read {path}: {e}"))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fen =
+            epd_to_fen(line).ok_or_else(|| format!("line {}: not a FEN: {line}", number + 1))?;
+        pos.set_from_fen(&fen)
+            .map_err(|e| format!("line {}: {e}", number + 1))?;
+
+        let eval = pos.evaluate();
+        let eval = if pos.turn() == WHITE { eval } else { -eval };
+        write!(out, "{fen},{}", csv_score(eval)).map_err(|e| e.to_string())?;
+        if let Some(depth) = depth {
+            let mut info = SearchInfo {
+                depth: Depth::new(depth),
+                output_format: OutputFormat::Silent,
+                ..SearchInfo::default()
+            };
+            // each position is searched from scratch, so that its score doesn't depend on the ones before it.
+            pos.clear_tt();
+            pos.reset_tables();
+            let (score, _) = pos.search_position(&mut info);
+            write!(out, ",{}", csv_score(score)).map_err(|e| e.to_string())?;
+        }
+        writeln!(out).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -1,4 +1,11 @@
-use crate::{definitions::BOARD_N_SQUARES, validate::piece_valid, chessmove::Move};
+use std::io::{self, Write};
+
+use crate::{
+    chessmove::Move,
+    definitions::BOARD_N_SQUARES,
+    lookups::{filerank_to_square, piece_char, SQUARE_NAMES},
+    validate::piece_valid,
+};
 
 const DO_COLOUR_DIFFERENTIATION: bool = true;
 
@@ -27,16 +34,73 @@ const fn piece_index(piece: u8) -> u8 {
     }
 }
 
+/// How to lay out a history heatmap dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapFormat {
+    /// An 8x8 grid per piece, with rank 8 at the top, as on a diagram.
+    Grid,
+    /// One `table,piece,square,value` line per square, for diffing between runs.
+    Csv,
+}
+
+/// Writes the scores for one piece slot of a history table.
+fn write_heatmap(
+    out: &mut impl Write,
+    table: &str,
+    slot: usize,
+    values: &[i32; BOARD_N_SQUARES],
+    format: HeatmapFormat,
+) -> io::Result<()> {
+    #![allow(clippy::cast_possible_truncation)]
+    // slots are in piece order, so the first slot is the white pawn.
+    let piece = piece_char(slot as u8 + 1).unwrap();
+    match format {
+        HeatmapFormat::Grid => {
+            writeln!(out, "{table} {piece}")?;
+            for rank in (0..8).rev() {
+                write!(out, "{}", rank + 1)?;
+                for file in 0..8 {
+                    write!(
+                        out,
+                        " {:>8}",
+                        values[filerank_to_square(file, rank) as usize]
+                    )?;
+                }
+                writeln!(out)?;
+            }
+            write!(out, " ")?;
+            for file in 'a'..='h' {
+                write!(out, " {file:>8}")?;
+            }
+            writeln!(out)
+        }
+        HeatmapFormat::Csv => {
+            for (sq, value) in values.iter().enumerate() {
+                writeln!(out, "{table},{piece},{},{value}", SQUARE_NAMES[sq])?;
+            }
+            Ok(())
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct HistoryTable {
     table: Box<[[i32; BOARD_N_SQUARES]]>
 }
 
 impl HistoryTable {
+    /// The memory the table takes up once it is in use.
+    pub const SIZE_IN_BYTES: usize = pslots() * BOARD_N_SQUARES * std::mem::size_of::<i32>();
+
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// The memory allocated for the table, in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(&*self.table)
+    }
+
     pub fn clear(&mut self) {
         if self.table.is_empty() {
             self.table = vec![[0; BOARD_N_SQUARES]; pslots()].into_boxed_slice();
@@ -48,6 +112,16 @@ impl HistoryTable {
         }
     }
 
+    /// Divides every score by `factor`, so that what was learned in the last
+    /// search still counts, but gives way to what is learned in the next.
+    pub fn age(&mut self, factor: i32) {
+        if self.table.is_empty() {
+            self.clear();
+        } else {
+            self.table.iter_mut().flatten().for_each(|x| *x /= factor);
+        }
+    }
+
     #[allow(clippy::only_used_in_recursion)] // wtf??
     pub fn add(&mut self, piece: u8, sq: u8, score: i32) {
         let pt = piece_index(piece);
@@ -59,47 +133,12 @@ impl HistoryTable {
         self.table[pt as usize][sq as usize]
     }
 
-    #[allow(dead_code)]
-    pub fn print_stats(&self) {
-        #![allow(clippy::cast_precision_loss)]
-        let sum = self.table
-            .iter()
-            .flatten()
-            .map(|x| i64::from(*x))
-            .sum::<i64>();
-        let mean = sum as f64 / (BOARD_N_SQUARES as f64 * pslots() as f64);
-        let stdev = self.table
-            .iter()
-            .flatten()
-            .map(|x| i64::from(*x))
-            .map(|x| (x as f64 - mean).powi(2))
-            .sum::<f64>()
-            .sqrt()
-            / (BOARD_N_SQUARES as f64 * pslots() as f64);
-        println!("mean: {}", mean);
-        println!("stdev: {}", stdev);
-        println!("max: {}", self.table.iter().flatten().copied().max().unwrap());
-        let nonzero = self.table
-            .iter()
-            .flatten()
-            .copied()
-            .filter(|x| *x != 0)
-            .collect::<Vec<_>>();
-        println!("nonzero: {}", nonzero.len());
-        let nz_mean = nonzero
-            .iter()
-            .map(|x| i64::from(*x))
-            .sum::<i64>() as f64
-            / (nonzero.len() as f64);
-        let nz_stdev = nonzero
-            .iter()
-            .map(|x| i64::from(*x))
-            .map(|x| (x as f64 - nz_mean).powi(2))
-            .sum::<f64>()
-            .sqrt()
-            / (nonzero.len() as f64);
-        println!("nz mean: {}", nz_mean);
-        println!("nz stdev: {}", nz_stdev);
+    /// Writes one heatmap per piece, of the score for moving it to each square.
+    pub fn write_heatmaps(&self, out: &mut impl Write, format: HeatmapFormat) -> io::Result<()> {
+        for (slot, values) in self.table.iter().enumerate() {
+            write_heatmap(out, "history", slot, values, format)?;
+        }
+        Ok(())
     }
 }
 
@@ -112,11 +151,18 @@ impl DoubleHistoryTable {
     const I1: usize = BOARD_N_SQUARES * pslots() * BOARD_N_SQUARES;
     const I2: usize = BOARD_N_SQUARES * pslots();
     const I3: usize = BOARD_N_SQUARES;
+    /// The memory the table takes up once it is in use.
+    pub const SIZE_IN_BYTES: usize = Self::I1 * pslots() * std::mem::size_of::<i32>();
 
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// The memory allocated for the table, in bytes.
+    pub const fn size_in_bytes(&self) -> usize {
+        self.table.capacity() * std::mem::size_of::<i32>()
+    }
+
     pub fn clear(&mut self) {
         if self.table.is_empty() {
             self.table.resize(BOARD_N_SQUARES * pslots() * BOARD_N_SQUARES * pslots(), 0);
@@ -125,62 +171,49 @@ impl DoubleHistoryTable {
         }
     }
 
-    pub fn add(&mut self, piece_1: u8, sq1: u8, piece_2: u8, sq2: u8, score: i32) {
+    /// Divides every score by `factor`, as `HistoryTable::age` does.
+    pub fn age(&mut self, factor: i32) {
+        if self.table.is_empty() {
+            self.clear();
+        } else {
+            self.table.iter_mut().for_each(|x| *x /= factor);
+        }
+    }
+
+    /// The table is laid out as `[piece_1][sq1][piece_2][sq2]`.
+    const fn index(piece_1: u8, sq1: u8, piece_2: u8, sq2: u8) -> usize {
         let pt_1 = piece_index(piece_1) as usize;
         let pt_2 = piece_index(piece_2) as usize;
         let sq1 = sq1 as usize;
         let sq2 = sq2 as usize;
-        let idx = pt_1 * Self::I1 + pt_2 * Self::I2 + sq1 * Self::I3 + sq2;
-        self.table[idx] += score;
+        pt_1 * Self::I1 + sq1 * Self::I2 + pt_2 * Self::I3 + sq2
+    }
+
+    pub fn add(&mut self, piece_1: u8, sq1: u8, piece_2: u8, sq2: u8, score: i32) {
+        self.table[Self::index(piece_1, sq1, piece_2, sq2)] += score;
     }
 
     pub fn get(&self, piece_1: u8, sq1: u8, piece_2: u8, sq2: u8) -> i32 {
-        let pt_1 = piece_index(piece_1) as usize;
-        let pt_2 = piece_index(piece_2) as usize;
-        let sq1 = sq1 as usize;
-        let sq2 = sq2 as usize;
-        let idx = pt_1 * Self::I1 + pt_2 * Self::I2 + sq1 * Self::I3 + sq2;
-        self.table[idx]
-    }
-
-    #[allow(dead_code)]
-    pub fn print_stats(&self) {
-        #![allow(clippy::cast_precision_loss)]
-        let sum = self.table
-            .iter()
-            .map(|x| i64::from(*x))
-            .sum::<i64>();
-        let mean = sum as f64 / (BOARD_N_SQUARES as f64 * pslots() as f64);
-        let stdev = self.table
-            .iter()
-            .map(|x| i64::from(*x))
-            .map(|x| (x as f64 - mean).powi(2))
-            .sum::<f64>()
-            .sqrt()
-            / (BOARD_N_SQUARES as f64 * pslots() as f64);
-        println!("mean: {}", mean);
-        println!("stdev: {}", stdev);
-        println!("max: {}", self.table.iter().copied().max().unwrap());
-        let nonzero = self.table
-            .iter()
-            .copied()
-            .filter(|x| *x != 0)
-            .collect::<Vec<_>>();
-        println!("nonzero: {}", nonzero.len());
-        let nz_mean = nonzero
-            .iter()
-            .map(|x| i64::from(*x))
-            .sum::<i64>() as f64
-            / (nonzero.len() as f64);
-        let nz_stdev = nonzero
-            .iter()
-            .map(|x| i64::from(*x))
-            .map(|x| (x as f64 - nz_mean).powi(2))
-            .sum::<f64>()
-            .sqrt()
-            / (nonzero.len() as f64);
-        println!("nz mean: {}", nz_mean);
-        println!("nz stdev: {}", nz_stdev);
+        self.table[Self::index(piece_1, sq1, piece_2, sq2)]
+    }
+
+    /// Writes one heatmap per piece, of the score for moving it to each square,
+    /// summed over every move it can follow up.
+    pub fn write_heatmaps(&self, out: &mut impl Write, format: HeatmapFormat) -> io::Result<()> {
+        if self.table.is_empty() {
+            return Ok(());
+        }
+        for slot in 0..pslots() {
+            let mut values = [0; BOARD_N_SQUARES];
+            for (sq, value) in values.iter_mut().enumerate() {
+                *value = (0..self.table.len())
+                    .step_by(Self::I2)
+                    .map(|prev| self.table[prev + slot * Self::I3 + sq])
+                    .sum();
+            }
+            write_heatmap(out, "followup", slot, &values, format)?;
+        }
+        Ok(())
     }
 }
 
@@ -189,12 +222,20 @@ pub struct MoveTable {
 }
 
 impl MoveTable {
+    /// The memory the table takes up once it is in use.
+    pub const SIZE_IN_BYTES: usize = pslots() * BOARD_N_SQUARES * std::mem::size_of::<Move>();
+
     pub const fn new() -> Self {
         Self {
             table: Vec::new()
         }
     }
 
+    /// The memory allocated for the table, in bytes.
+    pub const fn size_in_bytes(&self) -> usize {
+        self.table.capacity() * std::mem::size_of::<Move>()
+    }
+
     pub fn clear(&mut self) {
         if self.table.is_empty() {
             self.table.resize(BOARD_N_SQUARES * pslots(), Move::NULL);
@@ -214,4 +255,56 @@ impl MoveTable {
         let sq = sq as usize;
         self.table[pt * BOARD_N_SQUARES + sq]
     }
-}
\ No newline at end of file
+}
+mod tests {
+    #[test]
+    fn followup_entries_are_distinct() {
+        use super::{DoubleHistoryTable, HeatmapFormat};
+        use crate::definitions::{
+            Square::{A1, A2, E4, E5},
+            WN, WP,
+        };
+
+        let mut table = DoubleHistoryTable::new();
+        table.clear();
+        // these two entries used to share a slot.
+        table.add(WP, A2, WP, E4, 10);
+        table.add(WP, A1, WN, E4, 100);
+        assert_eq!(table.get(WP, A2, WP, E4), 10);
+        assert_eq!(table.get(WP, A1, WN, E4), 100);
+        table.add(WN, E5, WN, E4, 1000);
+
+        let mut out = Vec::new();
+        table.write_heatmaps(&mut out, HeatmapFormat::Csv).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert!(csv.contains("followup,P,e4,10\n"));
+        assert!(csv.contains("followup,N,e4,1100\n"));
+        assert_eq!(csv.lines().count(), 12 * 64);
+    }
+
+    #[test]
+    fn ageing_divides_scores() {
+        use super::{DoubleHistoryTable, HistoryTable};
+        use crate::definitions::{
+            Square::{E4, G1},
+            WN, WP,
+        };
+
+        // an empty table is allocated, as clearing it would.
+        let mut history = HistoryTable::new();
+        history.age(2);
+        history.add(WP, E4, 100);
+        history.add(WN, G1, -7);
+        history.age(2);
+        assert_eq!(history.get(WP, E4), 50);
+        assert_eq!(history.get(WN, G1), -3);
+        history.age(4);
+        assert_eq!(history.get(WP, E4), 12);
+
+        let mut followup = DoubleHistoryTable::new();
+        followup.age(4);
+        followup.add(WP, E4, WN, G1, 100);
+        followup.age(4);
+        assert_eq!(followup.get(WP, E4, WN, G1), 25);
+    }
+}
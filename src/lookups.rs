@@ -23,7 +23,14 @@ macro_rules! cfor {
     }
 }
 
-const fn init_hash_keys() -> ([[u64; 64]; 13], [u64; 16], u64) {
+/// The piece, castling, en passant, side and check keys, in that order.
+type HashKeys = ([[u64; 64]; 13], [u64; 16], [u64; 8], u64, [[u64; 4]; 2]);
+
+/// Generates the zobrist keys: one for each piece on each square, one for each
+/// castling right, combined into a key for each set of rights, one for each
+/// en passant file, one for the side to move, and one for each number of checks
+/// each side can have given, for three-check.
+const fn init_hash_keys() -> HashKeys {
     let mut state = XorShiftState::new();
     let mut piece_keys = [[0; 64]; 13];
     cfor!(let mut index = 0; index < 13; index += 1; {
@@ -33,16 +40,39 @@ const fn init_hash_keys() -> ([[u64; 64]; 13], [u64; 16], u64) {
             piece_keys[index][sq] = key;
         });
     });
+    let mut right_keys = [0; 4];
+    cfor!(let mut right = 0; right < 4; right += 1; {
+        let key;
+        (key, state) = state.next_self();
+        right_keys[right] = key;
+    });
     let mut castle_keys = [0; 16];
     cfor!(let mut index = 0; index < 16; index += 1; {
+        cfor!(let mut right = 0; right < 4; right += 1; {
+            if index & (1 << right) != 0 {
+                castle_keys[index] ^= right_keys[right];
+            }
+        });
+    });
+    let mut ep_keys = [0; 8];
+    cfor!(let mut file = 0; file < 8; file += 1; {
         let key;
         (key, state) = state.next_self();
-        castle_keys[index] = key;
+        ep_keys[file] = key;
     });
     let key;
-    (key, _) = state.next_self();
+    (key, state) = state.next_self();
     let side_key = key;
-    (piece_keys, castle_keys, side_key)
+    // no checks given hashes to zero, so that only three-check games are affected.
+    let mut check_keys = [[0; 4]; 2];
+    cfor!(let mut side = 0; side < 2; side += 1; {
+        cfor!(let mut checks = 1; checks < 4; checks += 1; {
+            let key;
+            (key, state) = state.next_self();
+            check_keys[side][checks] = key;
+        });
+    });
+    (piece_keys, castle_keys, ep_keys, side_key, check_keys)
 }
 
 pub const fn init_eval_masks() -> ([u64; 8], [u64; 8]) {
@@ -126,9 +156,33 @@ pub const fn init_passed_isolated_bb() -> ([u64; 64], [u64; 64], [u64; 64]) {
     (white_passed_bb, black_passed_bb, isolated_bb)
 }
 
+/// The squares on the files either side of each square, ahead of it from white's and
+/// from black's point of view. Only enemy pawns in this span can ever attack a piece
+/// on the square, as pawns can't move backwards.
+pub const fn init_pawn_attack_spans() -> ([u64; 64], [u64; 64]) {
+    #![allow(clippy::cast_possible_truncation)]
+    let (white_passed_bb, black_passed_bb, _) = init_passed_isolated_bb();
+    let file_masks = init_eval_masks().1;
+    let mut white_span = [0; 64];
+    let mut black_span = [0; 64];
+
+    let mut sq = 0;
+    while sq < 64 {
+        let own_file = file_masks[file(sq as u8) as usize];
+        white_span[sq] = white_passed_bb[sq] & !own_file;
+        black_span[sq] = black_passed_bb[sq] & !own_file;
+        sq += 1;
+    }
+
+    (white_span, black_span)
+}
+
 pub static PIECE_KEYS: [[u64; 64]; 13] = init_hash_keys().0;
 pub static CASTLE_KEYS: [u64; 16] = init_hash_keys().1;
-pub const SIDE_KEY: u64 = init_hash_keys().2;
+pub static EP_KEYS: [u64; 8] = init_hash_keys().2;
+pub const SIDE_KEY: u64 = init_hash_keys().3;
+/// Keys for the number of checks each side has given, in three-check.
+pub static CHECK_KEYS: [[u64; 4]; 2] = init_hash_keys().4;
 
 /// knights, bishops, rooks, and queens.
 pub static PIECE_BIG: [bool; 13] = [
@@ -320,6 +374,36 @@ mod tests {
         assert_eq!(len_before, len_after);
     }
 
+    #[test]
+    fn castle_keys_compose_per_right() {
+        use crate::definitions::{BKCA, BQCA, WKCA, WQCA};
+        use crate::lookups::CASTLE_KEYS;
+        assert_eq!(CASTLE_KEYS[0], 0);
+        for perm in 0..16 {
+            let composed = [WKCA, WQCA, BKCA, BQCA]
+                .into_iter()
+                .filter(|&right| perm & right != 0)
+                .fold(0, |key, right| key ^ CASTLE_KEYS[right as usize]);
+            assert_eq!(CASTLE_KEYS[perm as usize], composed);
+        }
+    }
+
+    #[test]
+    fn all_keys_different() {
+        use crate::lookups::{CASTLE_KEYS, CHECK_KEYS, EP_KEYS, PIECE_KEYS, SIDE_KEY};
+        // the keys for single castling rights, as the others are combinations of them.
+        let right_keys = [1, 2, 4, 8].map(|right| CASTLE_KEYS[right]);
+        let mut hashkeys = PIECE_KEYS.iter().flat_map(|&k| k).collect::<Vec<u64>>();
+        hashkeys.extend(right_keys);
+        hashkeys.extend(EP_KEYS);
+        hashkeys.push(SIDE_KEY);
+        hashkeys.extend(CHECK_KEYS.iter().flat_map(|keys| keys[1..].to_vec()));
+        hashkeys.sort_unstable();
+        let len_before = hashkeys.len();
+        hashkeys.dedup();
+        assert_eq!(len_before, hashkeys.len());
+    }
+
     #[test]
     fn python_chess_validation() {
         use crate::definitions::{KING, KNIGHT};
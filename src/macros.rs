@@ -14,3 +14,16 @@ macro_rules! inconceivable {
 }
 
 pub(crate) use inconceivable;
+
+/// Like `println!`, but a line written after whatever reads stdout has closed it is
+/// dropped, rather than panicking. See `uci::write_stdout`.
+macro_rules! send {
+    ($($arg:tt)*) => {
+        $crate::uci::write_stdout(|out| {
+            use std::io::Write as _;
+            writeln!(out, $($arg)*)
+        })
+    };
+}
+
+pub(crate) use send;
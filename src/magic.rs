@@ -230,6 +230,7 @@ const fn rook_attacks_on_the_fly(square: i32, block: u64) -> u64 {
 \**************************************/
 
 fn find_magic(square: i32, relevant_bits: i32, is_bishop: bool) -> u64 {
+    #![allow(clippy::large_stack_arrays)]
     // occupancies array
     let mut occupancies = [0u64; 4096];
 
@@ -315,7 +316,7 @@ pub fn init_magics() {
     println!("static BISHOP_MAGICS: [u64; 64] = [");
     for (square, &relbits) in BISHOP_REL_BITS.iter().enumerate() {
         let magic = find_magic(square as i32, relbits, true);
-        let magic_str = format!("{:016X}", magic);
+        let magic_str = format!("{magic:016X}");
         // split into blocks of four
         let magic_str = magic_str.chars().collect::<Vec<char>>();
         let magic_str = magic_str
@@ -331,7 +332,7 @@ pub fn init_magics() {
     println!("static ROOK_MAGICS: [u64; 64] = [");
     for (square, &relbits) in ROOK_REL_BITS.iter().enumerate() {
         let magic = find_magic(square as i32, relbits, false);
-        let magic_str = format!("{:016X}", magic);
+        let magic_str = format!("{magic:016X}");
         // split into blocks of four
         let magic_str = magic_str.chars().collect::<Vec<char>>();
         let magic_str = magic_str
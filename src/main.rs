@@ -6,19 +6,28 @@ use board::evaluation::parameters::Parameters;
 #[macro_use]
 mod macros;
 
+mod ablation;
+mod arena;
+mod binproto;
 mod board;
 mod chessmove;
+mod datafilter;
 mod definitions;
 mod errors;
+mod evalfile;
 mod lookups;
 mod magic;
 mod makemove;
 mod perft;
 mod piecelist;
 mod piecesquaretable;
+mod puzzles;
 mod rng;
 mod search;
 mod searchinfo;
+mod server;
+mod speedtest;
+mod testpositions;
 mod texel;
 mod transpositiontable;
 mod uci;
@@ -27,6 +36,63 @@ mod historytable;
 
 pub const NAME: &str = "Viridithas 2.2.0";
 
+/// Runs the UCI loop, with parameters loaded from the files given in `args`.
+/// A bare path is taken to be an evaluation parameter file.
+fn run_uci(args: &[String]) {
+    let mut evaluation_parameters = Parameters::default();
+    let mut search_config = search::Config::default();
+    let mut disabled_terms = Vec::new();
+    let mut file_options = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--eval-params" => {
+                let path = args.next().expect("no path given after --eval-params");
+                evaluation_parameters = Parameters::from_file(path).unwrap();
+            }
+            "--search-params" => {
+                let path = args.next().expect("no path given after --search-params");
+                search_config = search::Config::from_file(path).unwrap();
+            }
+            "--disable" => {
+                disabled_terms.push(args.next().expect("no term given after --disable"));
+            }
+            "--no-file-options" => file_options = false,
+            path => evaluation_parameters = Parameters::from_file(path).unwrap(),
+        }
+    }
+    for term in disabled_terms {
+        evaluation_parameters.disable(term).unwrap();
+    }
+    uci::main_loop(evaluation_parameters, search_config, file_options);
+}
+
+/// Prints the position given by the FEN in `args`, with its pins and discovered check candidates.
+fn visualise_board(args: &[String]) {
+    let fen = if args.is_empty() {
+        board::Board::STARTING_FEN.to_string()
+    } else {
+        args.join(" ")
+    };
+    let board = match board::Board::from_fen(&fen) {
+        Ok(board) => board,
+        Err(e) => return eprintln!("Error: {e}"),
+    };
+    print!("{board}");
+    for (name, bb) in [
+        ("white pinned pieces", board.pinned(definitions::WHITE)),
+        ("black pinned pieces", board.pinned(definitions::BLACK)),
+        (
+            "discovered check candidates",
+            board.discovered_check_candidates(),
+        ),
+    ] {
+        println!("{name}:");
+        board::movegen::bitboards::print_bb(bb);
+    }
+}
+
+#[allow(clippy::too_many_lines)]
 fn main() {
     #[cfg(debug_assertions)]
     std::env::set_var("RUST_BACKTRACE", "1");
@@ -37,14 +103,87 @@ fn main() {
     magic::initialise();
 
     match args.get(1).map(String::as_str) {
-        None | Some("uci") => {
-            let evaluation_parameters = args.get(2).map_or_else(Parameters::default, |path| {
-                Parameters::from_file(path).unwrap()
-            });
-            uci::main_loop(evaluation_parameters);
+        None | Some("uci") => run_uci(args.get(2..).unwrap_or_default()),
+        Some(flag) if flag.starts_with("--") => run_uci(&args[1..]),
+        Some("params") => match args.get(2..).unwrap_or_default() {
+            [dump] if dump == "dump" => {
+                print!("{}", Parameters::default().to_toml());
+                println!();
+                print!("{}", search::Config::default().to_toml());
+            }
+            [dump, kind, path @ ..] if dump == "dump" && path.len() <= 1 => match kind.as_str() {
+                "eval" => {
+                    let params = path.first().map_or_else(Parameters::default, |path| {
+                        Parameters::from_file(path).unwrap()
+                    });
+                    print!("{}", params.to_toml());
+                }
+                "search" => {
+                    let config = path.first().map_or_else(search::Config::default, |path| {
+                        search::Config::from_file(path).unwrap()
+                    });
+                    print!("{}", config.to_toml());
+                }
+                _ => println!("usage: params dump [eval|search [PATH]]"),
+            },
+            _ => println!("usage: params dump [eval|search [PATH]]"),
+        },
+        Some("serve") => {
+            if let Err(e) = server::run(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
         }
         Some("perfttest") => perft::gamut(),
-        Some("tune") => texel::tune(),
+        Some("perftcmp") => {
+            if let Err(e) = perft::perftcmp(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("speedtest") => speedtest::run(),
+        Some("positions") => testpositions::list(),
+        Some("match") => {
+            if let Err(e) = arena::match_cli(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("ablation") => {
+            if let Err(e) = ablation::run(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("binary") => {
+            if let Err(e) = binproto::run(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("puzzles") => {
+            if let Err(e) = puzzles::run(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("tune") => {
+            if let Err(e) = texel::tune(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("datafilter") => {
+            if let Err(e) = datafilter::datafilter_cli(&args[2..]) {
+                eprintln!("Error: {e}");
+            }
+        }
+        Some("evalfile") => {
+            let res = match args.get(2..).unwrap_or_default() {
+                [path] => evalfile::run(path, None),
+                [path, flag, depth] if flag == "--depth" => depth
+                    .parse()
+                    .map_err(|e| format!("bad --depth: {e}"))
+                    .and_then(|depth| evalfile::run(path, Some(depth))),
+                _ => Err("usage: evalfile PATH [--depth N]".to_string()),
+            };
+            if let Err(e) = res {
+                eprintln!("Error: {e}");
+            }
+        }
         Some("info") => {
             println!("{NAME}");
             println!(
@@ -73,13 +212,18 @@ fn main() {
             });
             piecesquaretable::render_pst_table(&params.piece_square_tables);
         }
+        Some("visboard") => visualise_board(args.get(2..).unwrap_or_default()),
         Some("gensource") => {
             let path = args.get(2);
             let params = path.map_or_else(Parameters::default, |path| {
                 Parameters::from_file(path).unwrap()
             });
+            // the source only has files A to D for the pieces, so the tables must be mirrored.
+            let mut pst = params.piece_square_tables;
+            piecesquaretable::tables::enforce_constraints(&mut pst, true);
             println!("PSQT source code:");
-            piecesquaretable::tables::printout_pst_source(&params.piece_square_tables);
+            piecesquaretable::tables::printout_pst_source(&pst);
+            piecesquaretable::tables::printout_pst_diff(&Parameters::default().piece_square_tables, &pst);
         }
         Some(unknown) => {
             if unknown != "help" {
@@ -87,12 +231,55 @@ fn main() {
             }
             println!("Available CLI args:");
             println!(" - uci (default) : run the Universal Chess Interface");
+            println!("                   (--eval-params PATH loads evaluation parameters from a .toml or vector file,");
+            println!("                    --search-params PATH loads search parameters from a .toml file,");
+            println!("                    --disable TERM switches off an evaluation term,");
+            println!("                    --no-file-options removes the options that read or write files)");
+            println!(" - binary        : run a length-prefixed binary protocol for programs that drive the engine,");
+            println!("                   over stdin and stdout or a Unix socket (binary [--socket PATH])");
+            println!(" - serve         : serve the Universal Chess Interface over TCP or a Unix socket, with an");
+            println!("                   engine for each connection (serve --listen (HOST:PORT | unix:PATH) [--json]");
+            println!("                    [--max-connections N] [UCI ARGS...]), without the options that read or");
+            println!("                   write files; clients aren't authenticated, so keep HOST to loopback");
             println!(" - perfttest     : run the perft test suite");
-            println!(" - tune          : use texel's tuning method to optimise the evaluation parameters");
+            println!(" - perftcmp      : compare a perft divide with another engine's, and print the root moves");
+            println!("                   whose counts differ (perftcmp --depth N [--fen FEN] [--moves \"MOVE ...\"]");
+            println!("                    [DIVIDE_FILE], reading the other divide from stdin without a file)");
+            println!(" - speedtest     : time movegen, make/unmake, evaluation and search on a fixed workload,");
+            println!("                   for comparing machines or as a profile for PGO builds");
+            println!(" - positions     : list the named test positions, for use with `position test NAME`");
+            println!(" - match         : play two configurations of the engine against each other");
+            println!("                   (--games N, --depth D, --movetime MS, --a-params PATH, --b-params PATH,");
+            println!("                    --a-search-params PATH, --b-search-params PATH,");
+            println!("                    --a-option NAME=VALUE, --b-option NAME=VALUE,");
+            println!("                    --a-disable TERM, --b-disable TERM,");
+            println!("                    --sprt ELO0 ELO1, --alpha A, --beta B,");
+            println!("                    --win-adjudication SCORE PLIES, --draw-adjudication SCORE PLIES MIN_PLY,");
+            println!("                    --max-plies N, --no-adjudication, --book FEN_OR_EPD_FILE, --shuffle)");
+            println!(" - ablation      : switch off each evaluation term in turn, and print how much it changes");
+            println!("                   evaluations and searches of the test positions as CSV");
+            println!("                   (ablation [--depth N] [--terms TERM,TERM,...])");
+            println!(" - puzzles       : find positions with a single winning move, after a mistake, in the games");
+            println!("                   of a PGN file or in self-play, and print them as Lichess puzzle CSV");
+            println!("                   (puzzles (PGN_FILE | --selfplay N) [--depth D] [--verify-depth D]");
+            println!("                    [--win CP] [--margin CP] [--max-moves N])");
+            println!(" - tune          : use texel's tuning method to optimise the evaluation parameters with AdamW");
+            println!("                   (tune [--resume CHECKPOINT] [--asymmetric], where a checkpoint is saved");
+            println!("                    every epoch, and --asymmetric keeps the piece tables unmirrored)");
+            println!(" - datafilter    : deduplicate and filter tuning data, and even out its results");
+            println!("                   (datafilter INPUT OUTPUT [--min-ply N] [--max-score CP]");
+            println!("                    [--keep-checks] [--no-rebalance])");
+            println!(" - evalfile      : print the static evaluation of each FEN in a file as CSV, and the");
+            println!("                   score of a fixed-depth search with --depth N (evalfile PATH [--depth N])");
             println!(" - info          : miscellaneous information about the engine");
             println!(" - visparams     : visualise the evaluation parameters");
+            println!(" - params dump   : print the evaluation and search parameters as TOML, for editing and");
+            println!("                   --eval-params / --search-params (params dump [eval|search [PATH]])");
             println!(" - vispst        : visualise the piece square tables");
-            println!(" - gensource     : generate the source code for the piece square tables");
+            println!(" - visboard      : show a position with its pinned pieces and discovered check");
+            println!("                   candidates (visboard [FEN], defaulting to the starting position)");
+            println!(" - gensource     : generate the source code for the piece square tables, mirrored between");
+            println!("                   files, with a summary of the changes from the built-in tables");
         }
     }
 }
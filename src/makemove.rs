@@ -3,10 +3,7 @@
 // utility functions here that are used in
 // the Board::make_move() function.
 
-use crate::{
-    definitions::PIECE_EMPTY,
-    lookups::{CASTLE_KEYS, PIECE_KEYS, SIDE_KEY},
-};
+use crate::lookups::{file, CASTLE_KEYS, CHECK_KEYS, EP_KEYS, PIECE_KEYS, SIDE_KEY};
 
 pub fn hash_castling(key: &mut u64, castle_perm: u8) {
     debug_assert!((castle_perm as usize) < CASTLE_KEYS.len());
@@ -25,17 +22,20 @@ pub fn hash_piece(key: &mut u64, piece: u8, sq: u8) {
     *key ^= piece_key;
 }
 
-pub fn hash_side(key: &mut u64) {
+pub const fn hash_side(key: &mut u64) {
     *key ^= SIDE_KEY;
 }
 
+/// Hashes in the number of checks `side` has given, for three-check.
+pub fn hash_checks(key: &mut u64, side: u8, checks: u8) {
+    debug_assert!(side < 2 && checks < 4);
+    *key ^= CHECK_KEYS[side as usize][checks as usize];
+}
+
+/// Hashes in the en passant square by its file, as the side to move fixes its rank.
 pub fn hash_ep(key: &mut u64, ep_sq: u8) {
     debug_assert!((ep_sq as usize) < 64);
-    let ep_key = unsafe {
-        *PIECE_KEYS
-            .get_unchecked(PIECE_EMPTY as usize)
-            .get_unchecked(ep_sq as usize)
-    };
+    let ep_key = unsafe { *EP_KEYS.get_unchecked(file(ep_sq) as usize) };
     *key ^= ep_key;
 }
 
@@ -1,14 +1,17 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
 };
 
 use crate::board::{movegen::MoveList, Board};
 
+const USAGE: &str = "usage: perftcmp --depth N [--fen FEN] [--moves \"MOVE MOVE ...\"] [DIVIDE_FILE]";
+
 #[allow(dead_code)]
 pub fn perft(pos: &mut Board, depth: usize) -> u64 {
     #[cfg(debug_assertions)]
-    pos.check_validity().unwrap();
+    pos.validate().unwrap();
 
     if depth == 0 {
         return 1;
@@ -29,6 +32,145 @@ pub fn perft(pos: &mut Board, depth: usize) -> u64 {
     count
 }
 
+/// The perft count below each legal move in the position, by the move in UCI notation.
+pub fn divide(pos: &mut Board, depth: usize) -> BTreeMap<String, u64> {
+    let mut ml = MoveList::new();
+    pos.generate_moves(&mut ml);
+
+    let mut counts = BTreeMap::new();
+    for m in ml {
+        if !pos.make_move(m) {
+            continue;
+        }
+        counts.insert(m.to_string(), perft(pos, depth.saturating_sub(1)));
+        pos.unmake_move();
+    }
+    counts
+}
+
+/// Reads another engine's divide output, taking each line that starts with a UCI move
+/// followed by a count, like `e2e4: 20` or `e2e4 20`. Anything else is skipped, which
+/// takes care of headers and total lines.
+fn parse_divide(text: &str) -> BTreeMap<String, u64> {
+    let is_move = |token: &str| {
+        matches!(
+            token.as_bytes(),
+            [b'a'..=b'h', b'1'..=b'8', b'a'..=b'h', b'1'..=b'8', rest @ ..]
+                if matches!(rest, [] | [b'n' | b'b' | b'r' | b'q'])
+        )
+    };
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line
+                .split(|c: char| c == ':' || c.is_whitespace())
+                .filter(|s| !s.is_empty());
+            let token = parts.next().filter(|token| is_move(token))?;
+            let count = parts.next()?.parse().ok()?;
+            Some((token.to_string(), count))
+        })
+        .collect()
+}
+
+/// A root move whose subtree count isn't the same in both divides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Difference {
+    m: String,
+    ours: Option<u64>,
+    theirs: Option<u64>,
+}
+
+/// Every root move that only one side has, or that has a different count on each.
+fn compare_divides(
+    ours: &BTreeMap<String, u64>,
+    theirs: &BTreeMap<String, u64>,
+) -> Vec<Difference> {
+    let mut moves = ours.keys().chain(theirs.keys()).collect::<Vec<_>>();
+    moves.sort();
+    moves.dedup();
+    moves
+        .into_iter()
+        .map(|m| Difference {
+            m: m.clone(),
+            ours: ours.get(m).copied(),
+            theirs: theirs.get(m).copied(),
+        })
+        .filter(|difference| difference.ours != difference.theirs)
+        .collect()
+}
+
+/// Runs `perftcmp`, with the arguments after it: `--depth N`, then optionally
+/// `--fen FEN` and `--moves "MOVE MOVE ..."` to set up the position, and a file of
+/// another engine's divide output for the same position, which is read from stdin if
+/// no file is given. Prints each root move whose count differs. Rerunning with that
+/// move added to `--moves`, a ply shallower, narrows a movegen bug down to one position.
+pub fn perftcmp(args: &[String]) -> Result<(), String> {
+    let mut depth = None;
+    let mut fen = Board::STARTING_FEN.to_string();
+    let mut moves = String::new();
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("no value given after {arg}"))
+        };
+        match arg.as_str() {
+            "--depth" => {
+                depth = Some(value()?.parse().map_err(|e| format!("bad --depth: {e}"))?);
+            }
+            "--fen" => fen.clone_from(value()?),
+            "--moves" => moves.clone_from(value()?),
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+            _ => path = Some(arg.clone()),
+        }
+    }
+    let Some(depth) = depth.filter(|&depth| depth > 0) else {
+        return Err(USAGE.to_string());
+    };
+
+    let mut pos = Board::new();
+    pos.reset_tables();
+    pos.set_from_fen(&fen).map_err(|e| format!("bad FEN \"{fen}\": {e}"))?;
+    pos.make_uci_moves(&moves).map_err(|e| e.to_string())?;
+    let theirs = if let Some(path) = path {
+        std::fs::read_to_string(&path).map_err(|e| format!("couldn't read {path}: {e}"))?
+    } else {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("couldn't read stdin: {e}"))?;
+        text
+    };
+    let theirs = parse_divide(&theirs);
+    if theirs.is_empty() {
+        return Err("no divide lines like \"e2e4: 20\" in the other engine's output".to_string());
+    }
+
+    let ours = divide(&mut pos, depth);
+    let differences = compare_divides(&ours, &theirs);
+    for Difference { m, ours, theirs } in &differences {
+        match (ours, theirs) {
+            (Some(ours), Some(theirs)) => println!("{m}: ours {ours}, theirs {theirs}"),
+            (Some(ours), None) => println!("{m}: ours {ours}, missing from theirs"),
+            (None, Some(theirs)) => println!("{m}: theirs {theirs}, missing from ours"),
+            (None, None) => unreachable!("every move comes from one of the divides"),
+        }
+    }
+    let total = |divide: &BTreeMap<String, u64>| divide.values().sum::<u64>();
+    if differences.is_empty() {
+        println!("all {} root moves match, {} nodes", ours.len(), total(&ours));
+    } else {
+        println!(
+            "{} of {} root moves differ, {} nodes against {}",
+            differences.len(),
+            ours.len().max(theirs.len()),
+            total(&ours),
+            total(&theirs)
+        );
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub fn gamut() {
     // open perftsuite.epd
@@ -61,6 +203,49 @@ pub fn gamut() {
 }
 
 mod tests {
+    #[test]
+    fn divides_are_compared() {
+        use super::{compare_divides, divide, parse_divide, Difference};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut pos = Board::new();
+        pos.reset_tables();
+        pos.set_startpos();
+        let ours = divide(&mut pos, 2);
+        assert_eq!(ours.len(), 20);
+        assert_eq!(ours.values().sum::<u64>(), 400);
+
+        // as another engine might print it, with one move miscounted and one left out.
+        let mut lines = vec!["info string some header".to_string()];
+        for (m, count) in &ours {
+            match m.as_str() {
+                "e2e4" => lines.push("e2e4: 21".to_string()),
+                "g1f3" => (),
+                _ => lines.push(format!("{m} {count}")),
+            }
+        }
+        lines.push("Nodes searched: 400".to_string());
+        let theirs = parse_divide(&lines.join("\n"));
+        assert_eq!(theirs.len(), 19);
+        assert_eq!(
+            compare_divides(&ours, &theirs),
+            [
+                Difference {
+                    m: "e2e4".to_string(),
+                    ours: Some(20),
+                    theirs: Some(21)
+                },
+                Difference {
+                    m: "g1f3".to_string(),
+                    ours: Some(20),
+                    theirs: None
+                },
+            ]
+        );
+        assert!(compare_divides(&ours, &ours).is_empty());
+    }
+
 
     #[test]
     fn perft_hard_position() {
@@ -114,6 +299,23 @@ mod tests {
         // assert_eq!(perft(&mut pos, 4), 197_281);
     }
 
+    #[test]
+    fn perft_promotions_both_colours() {
+        use super::*;
+        // the same position with the colours swapped, so both sides' pawn
+        // captures and promotions have to be generated correctly.
+        const WHITE_FEN: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        const BLACK_FEN: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+        for fen in [WHITE_FEN, BLACK_FEN] {
+            let mut pos = Board::new();
+            pos.reset_tables();
+            pos.set_from_fen(fen).unwrap();
+            assert_eq!(perft(&mut pos, 1), 6, "{fen}");
+            assert_eq!(perft(&mut pos, 2), 264, "{fen}");
+            assert_eq!(perft(&mut pos, 3), 9_467, "{fen}");
+        }
+    }
+
     #[test]
     fn perft_krk() {
         use super::*;
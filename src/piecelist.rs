@@ -32,23 +32,17 @@ impl PieceList {
             "PieceList is full: [{}]",
             self.data[..self.len as usize]
                 .iter()
-                .map(|&s| square_name(s)
-                    .map(std::string::ToString::to_string)
-                    .unwrap_or(format!("offboard: {}", s)))
+                .map(|&s| square_name(s).map_or_else(|| format!("offboard: {s}"), std::string::ToString::to_string))
                 .collect::<Vec<_>>()
                 .join(", ")
         );
         debug_assert!(
             !self.data[..self.len as usize].contains(&sq),
             "PieceList already contains square {}: [{}]",
-            square_name(sq)
-                .map(std::string::ToString::to_string)
-                .unwrap_or(format!("offboard: {}", sq)),
+            square_name(sq).map_or_else(|| format!("offboard: {sq}"), std::string::ToString::to_string),
             self.data[..self.len as usize]
                 .iter()
-                .map(|&s| square_name(s)
-                    .map(std::string::ToString::to_string)
-                    .unwrap_or(format!("offboard: {}", s)))
+                .map(|&s| square_name(s).map_or_else(|| format!("offboard: {s}"), std::string::ToString::to_string))
                 .collect::<Vec<_>>()
                 .join(", ")
         );
@@ -83,12 +77,10 @@ impl PieceList {
         debug_assert!(
             false,
             "PieceList::remove: piece not found: looking for {} in [{}]",
-            square_name(sq).unwrap_or(&format!("offboard: {}", sq)),
+            square_name(sq).unwrap_or(&format!("offboard: {sq}")),
             self.data[..self.len as usize]
                 .iter()
-                .map(|&s| square_name(s)
-                    .map(std::string::ToString::to_string)
-                    .unwrap_or(format!("offboard: {}", s)))
+                .map(|&s| square_name(s).map_or_else(|| format!("offboard: {s}"), std::string::ToString::to_string))
                 .collect::<Vec<_>>()
                 .join(", ")
         );
@@ -98,7 +90,7 @@ impl PieceList {
         self.len
     }
 
-    pub fn clear(&mut self) {
+    pub const fn clear(&mut self) {
         self.len = 0;
     }
 }
@@ -20,7 +20,7 @@ pub fn render_pst_table(pst: &PieceSquareTable) {
             for col in 0..8 {
                 let sq = row * 8 + col;
                 let pst_val = pst[piece][sq].0;
-                print!("{:>5}", pst_val);
+                print!("{pst_val:>5}");
             }
             println!();
         }
@@ -30,7 +30,7 @@ pub fn render_pst_table(pst: &PieceSquareTable) {
             for col in 0..8 {
                 let sq = row * 8 + col;
                 let pst_val = pst[piece][sq].1;
-                print!("{:>5}", pst_val);
+                print!("{pst_val:>5}");
             }
             println!();
         }
@@ -52,10 +52,33 @@ mod tests {
                     white_pst[sq as usize],
                     -black_pst[crate::definitions::flip_rank(sq) as usize],
                     "pst mirroring failed on square {} for piece {}",
-                    square_name(sq as u8).unwrap(),
+                    square_name(sq).unwrap(),
                     piece_name(white_piece as u8).unwrap()
                 );
             }
         }
     }
+
+    #[test]
+    fn constraints_are_enforced() {
+        use super::{tables, S};
+        use crate::definitions::{flip_rank, Square::{A1, A4, H4}, WN, WP};
+
+        let built_in = tables::construct_piece_square_table();
+        let mut pst = built_in;
+        tables::enforce_constraints(&mut pst, true);
+        assert_eq!(pst, built_in);
+
+        pst[WP as usize][A1 as usize] = S(5, 5);
+        pst[WN as usize][A4 as usize] = S(10, 20);
+        pst[WN as usize][H4 as usize] = S(20, 40);
+        let mut asymmetric = pst;
+        tables::enforce_constraints(&mut asymmetric, false);
+        assert_eq!(asymmetric[WP as usize][A1 as usize], S::NULL);
+        assert_eq!(asymmetric[WN as usize][H4 as usize], S(20, 40));
+        tables::enforce_constraints(&mut pst, true);
+        assert_eq!(pst[WN as usize][A4 as usize], S(15, 30));
+        assert_eq!(pst[WN as usize][H4 as usize], S(15, 30));
+        assert_eq!(pst[WN as usize + 6][flip_rank(H4) as usize], S(-15, -30));
+    }
 }
@@ -1,7 +1,7 @@
 use crate::{
     board::evaluation::score::S,
     definitions::{
-        flip_rank,
+        flip_file, flip_rank, square_name,
         File::{FILE_A, FILE_D, FILE_H},
         Rank::{RANK_1, RANK_2, RANK_7, RANK_8},
         BLACK, WHITE, WK, WN, WP,
@@ -85,6 +85,68 @@ const P_BONUS: [[S; 8]; 8] = [
     [ S::NULL; 8 ],
 ];
 
+/// Puts tuned tables back into the shape the engine assumes: pawns score nothing on
+/// the first and last ranks, the pieces are the same on mirrored files if `mirror_files`
+/// is set, as [`printout_pst_source`] only writes files A to D for them, and the black
+/// tables are the white ones flipped and negated.
+pub fn enforce_constraints(pst: &mut PieceSquareTable, mirror_files: bool) {
+    for file in FILE_A..=FILE_H {
+        pst[WP as usize][filerank_to_square(file, RANK_1) as usize] = S::NULL;
+        pst[WP as usize][filerank_to_square(file, RANK_8) as usize] = S::NULL;
+    }
+    if mirror_files {
+        for table in &mut pst[WN as usize..=WK as usize] {
+            for sq in 0..64 {
+                let mirror = flip_file(sq);
+                if mirror < sq {
+                    continue;
+                }
+                let S(mg_1, eg_1) = table[sq as usize];
+                let S(mg_2, eg_2) = table[mirror as usize];
+                let average = S(i32::midpoint(mg_1, mg_2), i32::midpoint(eg_1, eg_2));
+                table[sq as usize] = average;
+                table[mirror as usize] = average;
+            }
+        }
+    }
+    for piece in WP..=WK {
+        for sq in 0..64 {
+            pst[piece as usize + 6][flip_rank(sq) as usize] = -pst[piece as usize][sq as usize];
+        }
+    }
+}
+
+/// Prints, as comments that can go alongside the source from [`printout_pst_source`],
+/// how far each white table has moved from `old` to `new`.
+pub fn printout_pst_diff(old: &PieceSquareTable, new: &PieceSquareTable) {
+    let names = ["NULL", "Pawn", "Knight", "Bishop", "Rook", "Queen", "King"];
+    println!("// changes from the previous tables:");
+    for piece in WP..=WK {
+        let changes = (0..64)
+            .map(|sq| (sq, new[piece as usize][sq as usize] - old[piece as usize][sq as usize]))
+            .filter(|&(_, change)| change != S::NULL)
+            .collect::<Vec<_>>();
+        let name = names[piece as usize];
+        let Some(&(sq, largest)) = changes
+            .iter()
+            .max_by_key(|(_, S(mg, eg))| mg.abs().max(eg.abs()))
+        else {
+            println!("// {name}: unchanged");
+            continue;
+        };
+        let total = changes
+            .iter()
+            .map(|(_, S(mg, eg))| S(mg.abs(), eg.abs()))
+            .sum::<S>();
+        println!(
+            "// {name}: {} squares changed, by {} in total, most on {} by {largest}",
+            changes.len(),
+            total,
+            square_name(sq).unwrap(),
+        );
+    }
+}
+
 pub fn printout_pst_source(pst: &PieceSquareTable) {
     #[rustfmt::skip]
     println!(
@@ -101,7 +163,7 @@ pub fn printout_pst_source(pst: &PieceSquareTable) {
             for file in FILE_A..=FILE_D {
                 let sq = filerank_to_square(file, rank);
                 let val = pst[piece as usize][sq as usize];
-                print!("{}, ", val);
+                print!("{val}, ");
             }
             println!("],");
         }
@@ -121,7 +183,7 @@ const P_BONUS: [[S; 8]; 8] = [
         for file in FILE_A..=FILE_H {
             let sq = filerank_to_square(file, rank);
             let val = pst[WP as usize][sq as usize];
-            print!("{}, ", val);
+            print!("{val}, ");
         }
         println!("],");
     }
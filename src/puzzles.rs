@@ -0,0 +1,467 @@
+// Finds tactics puzzles in games: positions just after a mistake, where the side
+// to move has exactly one winning move. Games come from a PGN file or are played
+// by the engine against itself, and the puzzles are printed as CSV in the format
+// of the Lichess puzzle database, so they can be loaded by the same tools.
+
+use std::io::Write;
+
+use crate::{
+    arena::Opening,
+    board::{evaluation::centipawns, Board},
+    chessmove::Move,
+    definitions::{Depth, Score},
+    searchinfo::{RootMove, SearchInfo},
+    uci::OutputFormat,
+};
+
+const DEFAULT_DEPTH: i32 = 8;
+/// How much deeper than the scan a candidate puzzle is searched to confirm it.
+const VERIFY_EXTRA_DEPTH: i32 = 4;
+/// The score in centipawns that the solution must reach, and that the
+/// position before the mistake must not have had.
+const DEFAULT_WIN: i32 = 200;
+/// How far in centipawns every other move must score below the solution.
+const DEFAULT_MARGIN: i32 = 300;
+/// The most moves the side solving the puzzle can be asked to find.
+const DEFAULT_MAX_MOVES: usize = 3;
+/// The longest a self-play game may run, in plies.
+const SELF_PLAY_MAX_PLIES: usize = 300;
+/// The root temperature in self-play, so that the games differ and have mistakes to find.
+const SELF_PLAY_TEMPERATURE: i32 = 40;
+
+const USAGE: &str = "usage: puzzles (PGN_FILE | --selfplay N) [--depth D] [--verify-depth D] [--win CP] [--margin CP] [--max-moves N]";
+const CSV_HEADER: &str =
+    "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags";
+
+/// The thresholds a puzzle has to meet.
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    depth: i32,
+    verify_depth: i32,
+    win: i32,
+    margin: i32,
+    max_moves: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            depth: DEFAULT_DEPTH,
+            verify_depth: DEFAULT_DEPTH + VERIFY_EXTRA_DEPTH,
+            win: DEFAULT_WIN,
+            margin: DEFAULT_MARGIN,
+            max_moves: DEFAULT_MAX_MOVES,
+        }
+    }
+}
+
+/// A game to look for puzzles in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Game {
+    /// The starting position, in FEN.
+    fen: String,
+    moves: Vec<Move>,
+    /// Where the game came from, for the `GameUrl` column.
+    url: String,
+}
+
+/// A game as written in a PGN file, before its moves are read.
+#[derive(Debug, Default)]
+struct PgnGame {
+    tags: Vec<(String, String)>,
+    sans: Vec<String>,
+}
+
+impl PgnGame {
+    fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits PGN text into games, with comments, variations, move numbers,
+/// annotations and results removed from the movetext.
+fn read_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut token = String::new();
+    let mut variation_depth = 0;
+    let mut chars = text.chars();
+    let finish_token = |token: &mut String, game: &mut PgnGame, games: &mut Vec<PgnGame>| {
+        let word = std::mem::take(token);
+        match word.as_str() {
+            "" => (),
+            "1-0" | "0-1" | "1/2-1/2" | "*" => games.push(std::mem::take(game)),
+            nag if nag.starts_with('$') => (),
+            _ => {
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !san.is_empty() {
+                    game.sans.push(san.to_string());
+                }
+            }
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                chars.by_ref().find(|&c| c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => (),
+            '[' => {
+                let tag = chars.by_ref().take_while(|&c| c != ']').collect::<String>();
+                // a tag after movetext with no result starts the next game.
+                if !game.sans.is_empty() {
+                    games.push(std::mem::take(&mut game));
+                }
+                if let Some((name, value)) = tag.split_once(char::is_whitespace) {
+                    let value = value.trim().trim_matches('"');
+                    game.tags.push((name.to_string(), value.to_string()));
+                }
+            }
+            c if c.is_whitespace() => finish_token(&mut token, &mut game, &mut games),
+            c => token.push(c),
+        }
+    }
+    finish_token(&mut token, &mut game, &mut games);
+    if !game.sans.is_empty() {
+        games.push(game);
+    }
+    games
+}
+
+impl Game {
+    fn from_pgn(pgn: &PgnGame, board: &mut Board) -> Result<Self, String> {
+        let fen = pgn.tag("FEN").unwrap_or(Board::STARTING_FEN).to_string();
+        board
+            .set_from_fen(&fen)
+            .map_err(|e| format!("bad FEN \"{fen}\": {e}"))?;
+        let mut moves = Vec::new();
+        for san in &pgn.sans {
+            let m = board.parse_algebraic(san).map_err(|e| e.to_string())?;
+            board.make_move(m);
+            board.zero_height();
+            moves.push(m);
+        }
+        let url = pgn
+            .tag("Site")
+            .filter(|site| site.starts_with("http"))
+            .unwrap_or_default()
+            .to_string();
+        Ok(Self { fen, moves, url })
+    }
+
+    /// Sets up `board` in the position after the first `ply` moves of the game.
+    fn set_up(&self, board: &mut Board, ply: usize) {
+        board.set_from_fen(&self.fen).unwrap();
+        for &m in &self.moves[..ply] {
+            board.make_move(m);
+            board.zero_height();
+        }
+    }
+}
+
+/// Plays a game of the engine against itself from `opening`.
+fn play_self_play_game(board: &mut Board, opening: &Opening, depth: i32) -> Result<Game, String> {
+    opening.set_up(board)?;
+    let fen = board.fen();
+    let mut moves = Vec::new();
+    let mut keys = vec![board.hashkey()];
+    while board.get_first_legal_move().is_some()
+        && board.fifty_move_counter() < 100
+        && keys.iter().filter(|&&k| k == board.hashkey()).count() < 3
+        && !board.is_drawn_by_material()
+        && moves.len() < SELF_PLAY_MAX_PLIES
+    {
+        let mut info = SearchInfo {
+            depth: Depth::new(depth),
+            output_format: OutputFormat::Silent,
+            root_temperature: SELF_PLAY_TEMPERATURE,
+            ..SearchInfo::default()
+        };
+        let (_, m) = board.search_position(&mut info);
+        if m.is_null() || !board.make_move(m) {
+            return Err(format!("engine played an illegal move {m}"));
+        }
+        board.zero_height();
+        moves.push(m);
+        keys.push(board.hashkey());
+    }
+    Ok(Game { fen, moves, url: String::new() })
+}
+
+/// Searches the position to `depth`, and returns every root move with its
+/// score, from the point of view of the side to move, best first.
+fn root_moves(board: &mut Board, depth: i32) -> Vec<RootMove> {
+    let mut info = SearchInfo {
+        depth: Depth::new(depth),
+        output_format: OutputFormat::Silent,
+        root_report: true,
+        ..SearchInfo::default()
+    };
+    board.search_position(&mut info);
+    let mut root_moves = info.root_moves;
+    root_moves.sort_by_key(|rm| std::cmp::Reverse(rm.score));
+    root_moves
+}
+
+/// The only move that wins, if there is one: it scores at least `win`,
+/// and every other move scores below `win`, by at least `margin` less.
+fn only_winning_move(root_moves: &[RootMove], settings: &Settings) -> Option<RootMove> {
+    let win = Score::new(centipawns(settings.win));
+    match root_moves {
+        [best, rest @ ..] if best.score >= win => rest
+            .first()
+            .is_none_or(|second| {
+                second.score < win && best.score - second.score >= centipawns(settings.margin)
+            })
+            .then_some(*best),
+        _ => None,
+    }
+}
+
+/// A puzzle: the opponent's mistake, followed by the solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Puzzle {
+    /// The position before the mistake, in FEN.
+    fen: String,
+    key: u64,
+    moves: Vec<Move>,
+    themes: Vec<String>,
+    url: String,
+}
+
+impl Puzzle {
+    fn csv_row(&self) -> String {
+        let moves = self.moves.iter().map(Move::to_string).collect::<Vec<_>>();
+        format!(
+            "{:016x},{},{},,,,,{},{},",
+            self.key,
+            self.fen,
+            moves.join(" "),
+            self.themes.join(" "),
+            self.url
+        )
+    }
+}
+
+/// Finds the solution from the current position, at the verification depth. Each move
+/// of the solver must be the only winning one, and the line ends with the last of them.
+fn solve(board: &mut Board, settings: &Settings) -> (Vec<Move>, Score) {
+    let mut line = Vec::new();
+    let mut first_score = Score::DRAW;
+    let mut solver_moves = 0;
+    while solver_moves < settings.max_moves {
+        let Some(best) = only_winning_move(&root_moves(board, settings.verify_depth), settings)
+        else {
+            break;
+        };
+        if line.is_empty() {
+            first_score = best.score;
+        }
+        line.push(best.m);
+        solver_moves += 1;
+        board.make_move(best.m);
+        board.zero_height();
+        if solver_moves == settings.max_moves {
+            break;
+        }
+        let Some(reply) = root_moves(board, settings.verify_depth).first().map(|rm| rm.m) else {
+            break;
+        };
+        line.push(reply);
+        board.make_move(reply);
+        board.zero_height();
+    }
+    // the opponent's last reply is only part of the puzzle if the solver has a move to answer it.
+    if line.len() % 2 == 0 {
+        line.pop();
+    }
+    (line, first_score)
+}
+
+fn themes(score: Score, solver_moves: usize) -> Vec<String> {
+    let mut themes = Vec::new();
+    if score.is_mate() {
+        themes.push("mate".to_string());
+        themes.push(format!("mateIn{}", (score.plies_to_mate() + 1) / 2));
+    } else if score >= Score::new(centipawns(600)) {
+        themes.push("crushing".to_string());
+    } else {
+        themes.push("advantage".to_string());
+    }
+    let length = match solver_moves {
+        1 => "oneMove",
+        2 => "short",
+        3 => "long",
+        _ => "veryLong",
+    };
+    themes.push(length.to_string());
+    themes
+}
+
+/// Scans every position of `game` for puzzles. A position is a puzzle if the side
+/// to move has a single winning move, found at the scan depth and confirmed at the
+/// verification depth, and wasn't already winning before the opponent's last move.
+fn find_puzzles(board: &mut Board, game: &Game, settings: &Settings) -> Vec<Puzzle> {
+    let mut puzzles = Vec::new();
+    let win = Score::new(centipawns(settings.win));
+    // the best score in the previous position, for the side to move there.
+    let mut previous_best = None;
+    for ply in 0..=game.moves.len() {
+        game.set_up(board, ply);
+        let root_moves = root_moves(board, settings.depth);
+        let best = root_moves.first().map(|rm| rm.score);
+        let was_winning = previous_best.is_none_or(|score: Score| -score >= win);
+        previous_best = best;
+        if ply == 0 || was_winning {
+            continue;
+        }
+        let Some(candidate) = only_winning_move(&root_moves, settings) else {
+            continue;
+        };
+        let (solution, score) = solve(board, settings);
+        if solution.first() != Some(&candidate.m) {
+            continue;
+        }
+        game.set_up(board, ply - 1);
+        let mut moves = vec![game.moves[ply - 1]];
+        let themes = themes(score, solution.len().div_ceil(2));
+        moves.extend(solution);
+        puzzles.push(Puzzle {
+            fen: board.fen(),
+            key: board.hashkey(),
+            moves,
+            themes,
+            url: game.url.clone(),
+        });
+    }
+    puzzles
+}
+
+/// Runs the puzzle search, with the arguments after `puzzles`: a PGN file to
+/// scan, or `--selfplay N` to play N games and scan those, with `--depth D`,
+/// `--verify-depth D`, `--win CP`, `--margin CP` and `--max-moves N`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut settings = Settings::default();
+    let mut verify_depth = None;
+    let mut path = None;
+    let mut self_play_games = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("no value given after {arg}"))
+        };
+        match arg.as_str() {
+            "--depth" => {
+                settings.depth = value()?.parse().map_err(|e| format!("bad --depth: {e}"))?;
+            }
+            "--verify-depth" => {
+                verify_depth =
+                    Some(value()?.parse().map_err(|e| format!("bad --verify-depth: {e}"))?);
+            }
+            "--win" => settings.win = value()?.parse().map_err(|e| format!("bad --win: {e}"))?,
+            "--margin" => {
+                settings.margin = value()?.parse().map_err(|e| format!("bad --margin: {e}"))?;
+            }
+            "--max-moves" => {
+                settings.max_moves =
+                    value()?.parse().map_err(|e| format!("bad --max-moves: {e}"))?;
+            }
+            "--selfplay" => {
+                self_play_games =
+                    Some(value()?.parse::<usize>().map_err(|e| format!("bad --selfplay: {e}"))?);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+            _ => path = Some(arg.clone()),
+        }
+    }
+    settings.verify_depth = verify_depth.unwrap_or(settings.depth + VERIFY_EXTRA_DEPTH);
+    if path.is_some() == self_play_games.is_some() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut board = Board::new();
+    board.reset_tables();
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "{CSV_HEADER}").map_err(|e| e.to_string())?;
+    let mut scan = |game: &Game, board: &mut Board| -> Result<(), String> {
+        for puzzle in find_puzzles(board, game, &settings) {
+            writeln!(out, "{}", puzzle.csv_row()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    };
+    match (path, self_play_games) {
+        (Some(path), None) => {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("couldn't read {path}: {e}"))?;
+            for (number, pgn) in read_pgn(&text).iter().enumerate() {
+                match Game::from_pgn(pgn, &mut board) {
+                    Ok(game) => scan(&game, &mut board)?,
+                    Err(e) => eprintln!("Warning: skipping game {}: {e}", number + 1),
+                }
+            }
+        }
+        (None, Some(games)) => {
+            let openings = Opening::builtin();
+            for opening in openings.iter().cycle().take(games) {
+                board.clear_tt();
+                board.reset_tables();
+                let game = play_self_play_game(&mut board, opening, settings.depth)?;
+                scan(&game, &mut board)?;
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+mod tests {
+    #[test]
+    fn pgn_games_are_read() {
+        use super::read_pgn;
+
+        let pgn = "[Event \"casual\"]\n[Site \"https://lichess.org/abcdefgh\"]\n\n\
+                   1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 a6 1/2-1/2\n\n\
+                   [FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n\n1.O-O-O+ Kf7 ; resigns\n*\n";
+        let games = read_pgn(pgn);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Site"), Some("https://lichess.org/abcdefgh"));
+        assert_eq!(games[0].sans, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(games[1].tag("FEN"), Some("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"));
+        assert_eq!(games[1].sans, ["O-O-O+", "Kf7"]);
+    }
+
+    #[test]
+    fn a_blunder_becomes_a_puzzle() {
+        use super::{find_puzzles, read_pgn, Game, Settings};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut board = Board::new();
+        board.reset_tables();
+        let pgn = read_pgn("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0");
+        let game = Game::from_pgn(&pgn[0], &mut board).unwrap();
+        let settings = Settings {
+            depth: 3,
+            verify_depth: 4,
+            ..Settings::default()
+        };
+        let puzzles = find_puzzles(&mut board, &game, &settings);
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(
+            puzzles[0].csv_row(),
+            format!(
+                "{:016x},r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3,g8f6 h5f7,,,,,mate mateIn1 oneMove,,",
+                puzzles[0].key
+            )
+        );
+    }
+}
@@ -23,7 +23,7 @@ impl XorShiftState {
     }
 
     /// Generates the next random number in the sequence.
-    pub fn next(&mut self) -> u64 {
+    pub const fn next(&mut self) -> u64 {
         let mut x = self.state;
         x ^= x >> 12;
         x ^= x << 25;
@@ -36,7 +36,7 @@ impl XorShiftState {
 
     /// Generates a random number with only a few bits set.
     /// This will advance the generator by three steps.
-    pub fn random_few_bits(&mut self) -> u64 {
+    pub const fn random_few_bits(&mut self) -> u64 {
         let first = self.next();
         let second = self.next();
         let third = self.next();
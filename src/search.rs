@@ -1,16 +1,21 @@
+pub mod mate;
+pub mod trace;
+
 use crate::{
     board::movegen::MoveList,
     board::{
-        evaluation::{DRAW_SCORE, MATE_SCORE, is_mate_score},
-        movegen::TT_MOVE_SCORE,
+        evaluation::centipawns,
+        movegen::{is_underpromotion, TT_MOVE_SCORE},
         Board,
     },
     chessmove::Move,
-    definitions::{Depth, INFINITY, MAX_DEPTH},
-    searchinfo::SearchInfo,
-    transpositiontable::{HFlag, ProbeResult},
+    definitions::{Depth, Score, MAX_DEPTH, ZERO_PLY},
+    searchinfo::{RootMove, SearchInfo},
+    transpositiontable::{HFlag, ProbeResult, TTHit},
 };
 
+use trace::NodeExit;
+
 // In alpha-beta search, there are three classes of node to be aware of:
 // 1. PV-nodes: nodes that end up being within the alpha-beta window,
 // i.e. a call to alpha_beta(PVNODE, a, b) returns a value v where v is within the window [a, b].
@@ -23,14 +28,31 @@ use crate::{
 // Every move at an All-node is searched, and the score returned is an upper bound, so the exact score might be lower.
 
 impl Board {
-    pub fn quiescence(pos: &mut Self, info: &mut SearchInfo, mut alpha: i32, beta: i32) -> i32 {
+    pub fn quiescence(pos: &mut Self, info: &mut SearchInfo, alpha: Score, beta: Score) -> Score {
+        if info.trace.is_none() {
+            return Self::quiescence_node(pos, info, alpha, beta).0;
+        }
+        let ply = pos.height();
+        info.trace_enter(ply, "qs", ZERO_PLY, alpha, beta);
+        let (score, exit) = Self::quiescence_node(pos, info, alpha, beta);
+        info.trace_exit(ply, score, exit);
+        score
+    }
+
+    /// Quiescence search, which also reports why the node returned.
+    fn quiescence_node(
+        pos: &mut Self,
+        info: &mut SearchInfo,
+        mut alpha: Score,
+        beta: Score,
+    ) -> (Score, NodeExit) {
         #[cfg(debug_assertions)]
-        pos.check_validity().unwrap();
+        pos.validate().unwrap();
 
-        if info.nodes.trailing_zeros() >= 12 {
+        if info.check_up_due() {
             info.check_up();
             if info.stopped {
-                return 0;
+                return (Score::DRAW, NodeExit::Stopped);
             }
         }
 
@@ -38,23 +60,43 @@ impl Board {
         info.nodes += 1;
         info.seldepth = info.seldepth.max(height.into());
 
+        if pos.is_variant_loss() {
+            return (Score::mated_in(pos.height()), NodeExit::VariantLoss);
+        }
+
         // check draw
         if pos.is_draw() {
             // score fuzzing apparently helps with threefolds.
-            return 1 - (info.nodes & 2) as i32;
+            return (info.draw_score(pos.height()) + (1 - (info.nodes & 2) as i32), NodeExit::Draw);
         }
 
         // are we too deep?
         if height > (MAX_DEPTH - 1).round() {
-            return pos.evaluate();
+            return (pos.evaluate(), NodeExit::MaxDepth);
         }
 
-        let stand_pat = pos.evaluate();
+        // quiescence entries are stored at ZERO_PLY, so any entry for this
+        // position is deep enough to be used here.
+        let tt_move = match pos.tt_probe(alpha, beta, ZERO_PLY) {
+            ProbeResult::Cutoff(s) => {
+                return (s, NodeExit::TtCutoff);
+            }
+            ProbeResult::BestMove(tt_move) => Some(tt_move),
+            ProbeResult::Nothing => None,
+        };
+
+        let stand_pat = if info.lazy_eval {
+            pos.lazy_evaluate(alpha, beta)
+        } else {
+            pos.evaluate()
+        };
 
         if stand_pat >= beta {
-            return beta;
+            pos.tt_store(Move::NULL, beta, HFlag::Beta, ZERO_PLY);
+            return (beta, NodeExit::StandPat);
         }
 
+        let original_alpha = alpha;
         if stand_pat > alpha {
             alpha = stand_pat;
         }
@@ -62,7 +104,14 @@ impl Board {
         let mut move_list = MoveList::new();
         pos.generate_captures(&mut move_list);
 
+        if let Some(tt_move) = tt_move {
+            if let Some(movelist_entry) = move_list.lookup_by_move(tt_move) {
+                movelist_entry.score = TT_MOVE_SCORE;
+            }
+        }
+
         let mut moves_made = 0;
+        let mut best_move = Move::NULL;
 
         for m in move_list {
             if !pos.make_move(m) {
@@ -73,41 +122,85 @@ impl Board {
             let score = -Self::quiescence(pos, info, -beta, -alpha);
             pos.unmake_move();
 
+            if info.stopped {
+                return (Score::DRAW, NodeExit::Stopped);
+            }
+
             if score > alpha {
+                best_move = m;
                 if score >= beta {
                     if moves_made == 1 {
                         info.failhigh_first += 1.0;
                     }
                     info.failhigh += 1.0;
-                    return beta;
+                    pos.tt_store(best_move, beta, HFlag::Beta, ZERO_PLY);
+                    return (beta, NodeExit::BetaCutoff(best_move));
                 }
                 alpha = score;
             }
         }
 
-        alpha
+        if alpha > original_alpha {
+            // either the stand-pat or a capture raised alpha,
+            // so this is the exact quiescent value of the node.
+            pos.tt_store(best_move, alpha, HFlag::Exact, ZERO_PLY);
+            if best_move.is_null() {
+                (alpha, NodeExit::StandPat)
+            } else {
+                (alpha, NodeExit::PvNode(best_move))
+            }
+        } else {
+            pos.tt_store(best_move, alpha, HFlag::Alpha, ZERO_PLY);
+            (alpha, NodeExit::AllNode(best_move))
+        }
+    }
+
+    pub fn alpha_beta<const PV: bool>(
+        &mut self,
+        info: &mut SearchInfo,
+        depth: Depth,
+        alpha: Score,
+        beta: Score,
+        cut_node: bool,
+    ) -> Score {
+        if depth <= 0.into() {
+            return Self::quiescence(self, info, alpha, beta);
+        }
+        if info.trace.is_none() {
+            return self.alpha_beta_node::<PV>(info, depth, alpha, beta, cut_node).0;
+        }
+        let ply = self.height();
+        let kind = if PV { "pv" } else if cut_node { "cut" } else { "all" };
+        info.trace_enter(ply, kind, depth, alpha, beta);
+        let (score, exit) = self.alpha_beta_node::<PV>(info, depth, alpha, beta, cut_node);
+        info.trace_exit(ply, score, exit);
+        score
     }
 
+    /// The main search, which also reports why the node returned.
+    /// `cut_node` is set when a zero-window node is expected to fail high.
 #[rustfmt::skip]
 #[allow(clippy::too_many_lines, clippy::cognitive_complexity, clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
-    pub fn alpha_beta<const PV: bool>(&mut self, info: &mut SearchInfo, depth: Depth, mut alpha: i32, beta: i32) -> i32 {
+    fn alpha_beta_node<const PV: bool>(&mut self, info: &mut SearchInfo, mut depth: Depth, mut alpha: Score, beta: Score, cut_node: bool) -> (Score, NodeExit) {
     #[cfg(debug_assertions)]
-    self.check_validity().unwrap();
+    self.validate().unwrap();
 
-    if depth <= 0.into() {
-        return Self::quiescence(self, info, alpha, beta);
-    }
-
-    if info.nodes.trailing_zeros() >= 12 {
+    if info.check_up_due() {
         info.check_up();
         if info.stopped {
-            return 0;
+            return (Score::DRAW, NodeExit::Stopped);
+        }
+        if info.currline_due() {
+            self.print_currline(info);
         }
     }
 
     let height: i32 = self.height().try_into().unwrap();
 
     let root_node = height == 0;
+    if root_node {
+        info.root_moves.clear();
+    }
 
     info.nodes += 1;
     info.seldepth = if root_node { 0.into() } else { info.seldepth.max(height.into()) };
@@ -115,51 +208,88 @@ impl Board {
     let static_eval = self.evaluate();
 
     if !root_node {
+        // the variant's win condition ends the game before any draw can be claimed.
+        if self.is_variant_loss() {
+            return (Score::mated_in(self.height()), NodeExit::VariantLoss);
+        }
+
         // check draw
         if self.is_draw() {
             // score fuzzing apparently helps with threefolds.
-            return 1 - (info.nodes & 2) as i32;
+            return (info.draw_score(self.height()) + (1 - (info.nodes & 2) as i32), NodeExit::Draw);
         }
 
         // are we too deep?
         if height > MAX_DEPTH.round() - 1 {
-            return static_eval;
+            return (static_eval, NodeExit::MaxDepth);
         }
 
         // mate-distance pruning.
         // doesn't actually add strength, but it makes viri better at solving puzzles.
         // approach taken from Ethereal.
-        let r_alpha = if alpha > -MATE_SCORE + height     { alpha } else { -MATE_SCORE + height };
-        let r_beta  = if  beta <  MATE_SCORE - height - 1 {  beta } else {  MATE_SCORE - height - 1 };
-        if r_alpha >= r_beta { return r_alpha; }
+        let r_alpha = alpha.max(Score::mated_in(self.height()));
+        let r_beta  = beta.min(Score::mate_in(self.height() + 1));
+        if r_alpha >= r_beta { return (r_alpha, NodeExit::MateDistance); }
     }
 
     debug_assert_eq!(PV, beta - alpha > 1, "PV must be true if the alpha-beta window is larger than 1");
+    debug_assert!(!(PV && cut_node), "PV-nodes can't be expected to fail high");
+
+    // if we're verifying a singular extension, the TT move is
+    // excluded from this node, and move generation will skip it.
+    let in_singular_search = !self.excluded[self.height()].is_null();
+    let scores_root_moves = root_node && self.scores_root_moves(info);
 
     let tt_move = match self.tt_probe(alpha, beta, depth) {
-        ProbeResult::Cutoff(s) => {
-            return s;
+        // the TT entry for this position was made without excluding a move,
+        // so it can't be used to cut off a singular verification search.
+        // nor can the root cut off when every root move needs a score.
+        ProbeResult::Cutoff(s) if !(in_singular_search || scores_root_moves) => {
+            return (s, NodeExit::TtCutoff);
         }
         ProbeResult::BestMove(tt_move) => {
             Some(tt_move)
         }
-        ProbeResult::Nothing => {
+        ProbeResult::Cutoff(_) | ProbeResult::Nothing => {
             None
         }
     };
 
+    // internal iterative reduction:
+    // a node we expect to matter with no TT move was probably
+    // not searched before, so its move ordering will be poor,
+    // and it is cheaper to search it shallower first.
+    if (PV || cut_node)
+        && tt_move.is_none()
+        && !in_singular_search
+        && depth >= self.search_params.iir_depth
+    {
+        depth -= 1;
+    }
+
     let in_check = self.in_check::<{ Self::US }>();
 
     // TEST: pv nullmove pruning
-    if !PV && !in_check && !root_node && depth >= 3.into() && self.zugzwang_unlikely() {
+    // we don't pass if one of our major pieces is en prise, as
+    // the opponent's reply would just be to take it.
+    if !PV
+        && !in_check
+        && !root_node
+        && !in_singular_search
+        && depth >= 3.into()
+        && self.zugzwang_unlikely()
+        && !self.has_threatened_major_piece()
+    {
         self.make_nullmove();
-        let score = -self.alpha_beta::<PV>(info, depth - 3, -beta, -alpha);
+        // the opponent's reply to a pass is expected to fail high where we were expected to fail low.
+        let reduced_depth = depth - self.search_params.null_move_reduction;
+        let score = -self.alpha_beta::<PV>(info, reduced_depth, -beta, -alpha, !cut_node);
         self.unmake_nullmove();
         if info.stopped {
-            return 0;
+            return (Score::DRAW, NodeExit::Stopped);
         }
         if score >= beta {
-            return beta;
+            return (beta, NodeExit::NullMove);
         }
     }
 
@@ -171,7 +301,7 @@ impl Board {
     let original_alpha = alpha;
     let mut moves_made = 0;
     let mut best_move = Move::NULL;
-    let mut best_score = -INFINITY;
+    let mut best_score = -Score::INFINITY;
 
     if let Some(tt_move) = tt_move {
         if let Some(movelist_entry) = move_list.lookup_by_move(tt_move) {
@@ -179,35 +309,102 @@ impl Board {
         }
     }
 
+    // only look up the full TT entry if we might want to do a singular extension.
+    let tt_hit = if !root_node && !in_singular_search && depth >= self.search_params.singularity_depth {
+        self.tt_probe_for_singularity()
+    } else {
+        None
+    };
+
     for m in move_list {
+        let singular_extension = match tt_hit {
+            Some(tt_hit) if tt_hit.m == m => {
+                match self.singularity(info, m, tt_hit, depth, beta, cut_node) {
+                    Singularity::MultiCut(score) => return (score, NodeExit::MultiCut(m)),
+                    Singularity::Extend(extension) => extension,
+                }
+            }
+            _ => ZERO_PLY,
+        };
+        if info.stopped {
+            return (Score::DRAW, NodeExit::Stopped);
+        }
+
+        // the history tables are indexed by the moved piece,
+        // so we need to look it up before making the move.
+        let is_capture = self.is_capture(m);
+        let is_quiet = !is_capture && !m.is_promo();
+        let hangs_to_pawn = is_quiet && self.hangs_to_pawn(m);
+        let quiet_history = if !PV && is_quiet && depth <= self.search_params.history_pruning_depth {
+            self.history_score(m) + 2 * self.followup_history_score(m)
+        } else {
+            0
+        };
+
+        let nodes_before = info.nodes;
         if !self.make_move(m) {
             continue;
         }
         moves_made += 1;
 
-        let is_capture = m.is_capture();
         let gives_check = self.in_check::<{ Self::US }>();
-        let is_promotion = m.is_promo();
+        // underpromotions are left to late move reductions and pruning,
+        // unless they capture or give check.
+        let is_promotion = m.is_promo() && !is_underpromotion(m);
 
         let is_interesting = is_capture || is_promotion || gives_check || in_check;
 
+        // late move pruning:
+        // at low depth, quiet moves late in the ordering are very unlikely to be good.
+        // quiet moves that hang a piece to a pawn are pruned earlier.
+        if !PV && is_late_move_prunable(&self.search_params, depth, moves_made, is_interesting, hangs_to_pawn, alpha, beta) {
+            info.lmp_prunes += 1;
+            self.unmake_move();
+            continue;
+        }
+
+        // history pruning:
+        // at low depth, quiet moves that have never caused a cutoff
+        // at this depth or deeper are very unlikely to be good.
+        if !PV && is_history_prunable(&self.search_params, depth, moves_made, is_interesting, quiet_history, alpha, beta) {
+            info.history_prunes += 1;
+            self.unmake_move();
+            continue;
+        }
+
         // futility pruning (worth 32 +/- 44 elo)
         // if the static eval is too low, we might just skip the move.
-        if !PV && is_move_futile(depth, moves_made, is_interesting, static_eval, alpha, beta) {
+        if !PV
+            && is_move_futile(
+                &self.search_params,
+                depth,
+                moves_made,
+                is_interesting,
+                static_eval,
+                alpha,
+                beta,
+            )
+        {
             self.unmake_move();
             continue;
         }
 
         let extension: Depth = if gives_check {
-            0.8.into()
+            singular_extension.max(0.8.into())
         } else {
-            0.into()
+            singular_extension
         };
+        let is_double_extension = extension > Depth::new(1);
+        if is_double_extension {
+            self.double_extensions += 1;
+        }
 
         let mut score;
         if moves_made == 1 {
             // first move (presumably the PV-move)
-            score = -self.alpha_beta::<PV>(info, depth + extension - 1, -beta, -alpha);
+            // node types alternate along the line we expect to be best:
+            // the children of cut-nodes are all-nodes, and vice versa.
+            score = -self.alpha_beta::<PV>(info, depth + extension - 1, -beta, -alpha, !PV && !cut_node);
         } else {
             // nullwindow searches to prove PV.
             // we only do late move reductions when a set of conditions are true:
@@ -226,22 +423,56 @@ impl Board {
             let r = if can_reduce {
                 let mut r = self.lmr_table.get(depth, moves_made);
                 r += i32::from(!PV);
+                // reduce more where we expect to fail high anyway,
+                // as some earlier or later move should produce the cutoff.
+                r += i32::from(cut_node);
+                // reduce quiet moves that hang a piece to a pawn more.
+                r += i32::from(hangs_to_pawn);
                 Depth::new(r).clamp(1.into(), depth - 1)
             } else {
                 1.into()
             };
-            // perform a zero-window search
-            score = -self.alpha_beta::<false>(info, depth + extension - r, -alpha - 1, -alpha);
+            // perform a zero-window search.
+            // we expect the moves after the first to fail low,
+            // i.e. for the opponent to refute them with a cutoff.
+            score = -self.alpha_beta::<false>(info, depth + extension - r, -alpha - 1, -alpha, true);
+            if info.verify_scout && score > alpha {
+                self.verify_scout(info, depth + extension - r, alpha, score);
+            }
             // if we failed, then full window search
             if score > alpha && score < beta {
                 // this is a new best move, so it *is* PV.
-                score = -self.alpha_beta::<PV>(info, depth + extension - 1, -beta, -alpha);
+                score = -self.alpha_beta::<PV>(info, depth + extension - 1, -beta, -alpha, !PV && !cut_node);
             }
         }
         self.unmake_move();
 
+        if is_double_extension {
+            self.double_extensions -= 1;
+        }
+
         if info.stopped {
-            return 0;
+            return (Score::DRAW, NodeExit::Stopped);
+        }
+
+        if scores_root_moves {
+            // a move that failed low only has a bound on its score, so it's searched
+            // again, with a window below alpha, to get its exact score. This costs
+            // time, so it is only done for the root report and root move sampling.
+            let exact_score = if moves_made > 1 && score <= alpha {
+                // the move was made above, so it is legal.
+                let legal = self.make_move(m);
+                debug_assert!(legal, "{m} was legal a moment ago");
+                let exact_score = -self.alpha_beta::<true>(info, depth + extension - 1, -alpha - 1, Score::INFINITY, false);
+                self.unmake_move();
+                if info.stopped {
+                    return (Score::DRAW, NodeExit::Stopped);
+                }
+                exact_score
+            } else {
+                score
+            };
+            info.root_moves.push(RootMove { m, score: exact_score, nodes: info.nodes - nodes_before });
         }
 
         if score > best_score {
@@ -249,6 +480,9 @@ impl Board {
             best_move = m;
             if score > alpha {
                 alpha = score;
+                if root_node && moves_made > 1 {
+                    self.print_new_best_root_move(info, depth, m, score, beta);
+                }
                 if score >= beta {
                     // we failed high, so this is a cut-node
                     if moves_made == 1 {
@@ -256,18 +490,21 @@ impl Board {
                     }
                     info.failhigh += 1.0;
 
-                    if !is_capture {
-                        // IDEA (todo): if the cutoff move wasn't the first, i.e.
-                        // moves_made != 1, then we should decrease the history
-                        // scores of the moves that we've already searched.
-                        self.insert_killer(best_move);
-                        self.insert_countermove(best_move);
-                        self.update_history_metrics(best_move, history_score);
+                    // as below, a cutoff with the excluded move removed
+                    // mustn't go into the TT or the histories.
+                    if !in_singular_search {
+                        if !is_capture {
+                            // IDEA (todo): if the cutoff move wasn't the first, i.e.
+                            // moves_made != 1, then we should decrease the history
+                            // scores of the moves that we've already searched.
+                            self.record_killer(best_move, score);
+                            self.insert_countermove(best_move);
+                            self.update_history_metrics(best_move, history_score);
+                        }
+                        self.tt_store(best_move, beta, HFlag::Beta, depth);
                     }
 
-                    self.tt_store(best_move, beta, HFlag::Beta, depth);
-
-                    return beta;
+                    return (beta, NodeExit::BetaCutoff(best_move));
                 }
                 assert_eq!(alpha, score);
                 assert_eq!(best_move, m);
@@ -276,78 +513,325 @@ impl Board {
     }
 
     if moves_made == 0 {
+        if in_singular_search {
+            // the excluded move was the only legal move,
+            // so it is certainly singular.
+            return (alpha, NodeExit::OnlyMoveExcluded);
+        }
         if in_check {
-            return -MATE_SCORE + height;
+            return (Score::mated_in(self.height()), NodeExit::Checkmate);
         }
-        return DRAW_SCORE;
+        return (info.draw_score(self.height()), NodeExit::Stalemate);
     }
 
-    if alpha == original_alpha {
+    if in_singular_search {
+        // the result of this search is only valid with the excluded
+        // move removed, so it mustn't go into the TT or the histories.
+    } else if alpha == original_alpha {
         // we didn't raise alpha, so this is an all-node
         self.tt_store(best_move, alpha, HFlag::Alpha, depth);
     } else {
         // we raised alpha, and didn't raise beta
         // as if we had, we would have returned early, 
         // so this is a PV-node
-        self.insert_killer(best_move);
+        self.record_killer(best_move, best_score);
         self.insert_countermove(best_move);
         self.update_history_metrics(best_move, history_score);
         self.tt_store(best_move, best_score, HFlag::Exact, depth);
     }
 
-    alpha
+    if alpha == original_alpha {
+        (alpha, NodeExit::AllNode(best_move))
+    } else {
+        (alpha, NodeExit::PvNode(best_move))
+    }
 }
 
+    /// Tests whether the TT move `m` is better than every other move
+    /// by a margin, by searching the position with `m` excluded at a
+    /// reduced depth and a window just below the TT score.
+    /// - If every other move fails low, `m` is singular and is extended,
+    ///   by two plies if the others fail low by a wide margin.
+    /// - If another move beats the window, and the window is above beta,
+    ///   there are multiple moves that beat beta, and we can cut the node.
+    /// - Otherwise, if the TT score alone beats beta, `m` is probably
+    ///   not the only good move, so it is searched at a reduced depth.
+    fn singularity(
+        &mut self,
+        info: &mut SearchInfo,
+        m: Move,
+        tt_hit: TTHit,
+        depth: Depth,
+        beta: Score,
+        cut_node: bool,
+    ) -> Singularity {
+        let config = &self.search_params;
+        if tt_hit.bound == HFlag::Alpha
+            || tt_hit.depth < depth - config.singularity_depth_margin
+            || tt_hit.value.is_mate()
+        {
+            return Singularity::Extend(ZERO_PLY);
+        }
+
+        let r_beta = tt_hit.value - config.singularity_margin * depth.round();
+        let r_depth = (depth - 1) / 2;
+        let double_extension_margin = config.double_extension_margin;
+        let can_double_extend = self.double_extensions < config.double_extension_limit;
+
+        let height = self.height();
+        self.excluded[height] = m;
+        let value = self.alpha_beta::<false>(info, r_depth, r_beta - 1, r_beta, cut_node);
+        self.excluded[height] = Move::NULL;
+
+        if value < r_beta {
+            if can_double_extend && value < r_beta - double_extension_margin {
+                Singularity::Extend(Depth::new(2))
+            } else {
+                Singularity::Extend(Depth::new(1))
+            }
+        } else if r_beta >= beta {
+            Singularity::MultiCut(r_beta)
+        } else if tt_hit.value >= beta {
+            Singularity::Extend(Depth::new(-1))
+        } else {
+            Singularity::Extend(ZERO_PLY)
+        }
+    }
+
+    /// Records a quiet move that raised alpha as a killer,
+    /// or as the mate killer if it leads to mate.
+    fn record_killer(&mut self, m: Move, score: Score) {
+        if score >= Score::IS_MATE {
+            self.insert_mate_killer(m);
+        } else {
+            self.insert_killer(m);
+        }
+    }
+
     fn update_history_metrics(&mut self, best_move: Move, history_score: i32) {
         self.add_history(best_move, history_score);
         self.add_followup_history(best_move, history_score);
     }
 }
 
+enum Singularity {
+    /// Search the TT move with this extension, which may be negative.
+    Extend(Depth),
+    /// More than one move beats beta, so return this score.
+    MultiCut(Score),
+}
+
 fn is_move_futile(
+    config: &Config,
     depth: Depth,
     moves_made: usize,
     interesting: bool,
-    static_eval: i32,
-    a: i32,
-    b: i32,
+    static_eval: Score,
+    a: Score,
+    b: Score,
 ) -> bool {
     if !(1.into()..=4.into()).contains(&depth) || interesting || moves_made == 1 {
         return false;
     }
-    if is_mate_score(a) || is_mate_score(b) {
+    if a.is_mate() || b.is_mate() {
         return false;
     }
-    let threshold = FUTILITY_PRUNING_MARGINS[depth.ply_to_horizon()];
+    let threshold = config.futility_gradient * depth.round() + config.futility_intercept;
     static_eval + threshold < a
 }
 
-static FUTILITY_PRUNING_MARGINS: [i32; 5] = [
-    100, // 0 moves to the horizon
-    150, // 1 move to the horizon
-    250, // 2 moves to the horizon
-    400, // 3 moves to the horizon
-    600, // 4 moves to the horizon
-];
+fn is_late_move_prunable(
+    config: &Config,
+    depth: Depth,
+    moves_made: usize,
+    interesting: bool,
+    hangs_to_pawn: bool,
+    a: Score,
+    b: Score,
+) -> bool {
+    #![allow(clippy::cast_sign_loss)]
+    if depth > config.lmp_depth || interesting || moves_made == 1 {
+        return false;
+    }
+    if a.is_mate() || b.is_mate() {
+        return false;
+    }
+    let plies = depth.round().max(1) as usize;
+    let limit = config.lmp_base + plies * plies;
+    if hangs_to_pawn {
+        moves_made > limit / 2
+    } else {
+        moves_made > limit
+    }
+}
+
+fn is_history_prunable(
+    config: &Config,
+    depth: Depth,
+    moves_made: usize,
+    interesting: bool,
+    history: i32,
+    a: Score,
+    b: Score,
+) -> bool {
+    if depth > config.history_pruning_depth || interesting || moves_made == 1 {
+        return false;
+    }
+    if a.is_mate() || b.is_mate() {
+        return false;
+    }
+    let plies = depth.round().max(1);
+    history < config.history_pruning_margin * plies * plies
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
+    /// How much shallower the search after a null move is.
     pub null_move_reduction: Depth,
+    /// Moves are futile if the static eval is below alpha by more than
+    /// `futility_gradient * depth + futility_intercept`, in evaluation units.
     pub futility_gradient: i32,
     pub futility_intercept: i32,
     pub lmr_base: f64,
     pub lmr_division: f64,
+    /// The minimum depth at which the TT move is tested for singularity.
+    pub singularity_depth: Depth,
+    /// How far below the current depth the TT entry may have been searched
+    /// and still be trusted for a singularity test.
+    pub singularity_depth_margin: Depth,
+    /// The singular window is lowered by this much per ply, in evaluation units.
+    pub singularity_margin: i32,
+    /// How far the other moves must fail low by for a double extension.
+    pub double_extension_margin: i32,
+    /// The maximum number of double extensions on a single line.
+    pub double_extension_limit: usize,
+    /// The maximum depth at which late move pruning is done.
+    pub lmp_depth: Depth,
+    /// Quiet moves after the first `lmp_base + depth^2` are pruned.
+    pub lmp_base: usize,
+    /// The maximum depth at which history pruning is done.
+    pub history_pruning_depth: Depth,
+    /// Quiet moves with history below `history_pruning_margin * depth^2` are pruned.
+    pub history_pruning_margin: i32,
+    /// The half-width of the aspiration window, in evaluation units.
+    pub aspiration_window: i32,
+    /// The minimum iteration depth at which aspiration windows are used.
+    pub aspiration_depth: Depth,
+    /// The minimum depth at which PV- and cut-nodes without a TT move are reduced.
+    pub iir_depth: Depth,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             null_move_reduction: 3.into(),
-            futility_gradient: 41,
-            futility_intercept: 51,
+            futility_gradient: centipawns(41),
+            futility_intercept: centipawns(51),
             lmr_base: 0.75,
             lmr_division: 2.25,
+            singularity_depth: 8.into(),
+            singularity_depth_margin: 3.into(),
+            singularity_margin: centipawns(3),
+            double_extension_margin: centipawns(20),
+            double_extension_limit: 6,
+            lmp_depth: 3.into(),
+            lmp_base: 3,
+            history_pruning_depth: 2.into(),
+            history_pruning_margin: 1,
+            aspiration_window: centipawns(25),
+            aspiration_depth: 5.into(),
+            iir_depth: 4.into(),
+        }
+    }
+}
+
+impl Config {
+    /// The name and current value of every parameter, in the format read by [`Self::set`].
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("null_move_reduction", self.null_move_reduction.to_string()),
+            ("futility_gradient", self.futility_gradient.to_string()),
+            ("futility_intercept", self.futility_intercept.to_string()),
+            ("lmr_base", self.lmr_base.to_string()),
+            ("lmr_division", self.lmr_division.to_string()),
+            ("singularity_depth", self.singularity_depth.to_string()),
+            ("singularity_depth_margin", self.singularity_depth_margin.to_string()),
+            ("singularity_margin", self.singularity_margin.to_string()),
+            ("double_extension_margin", self.double_extension_margin.to_string()),
+            ("double_extension_limit", self.double_extension_limit.to_string()),
+            ("lmp_depth", self.lmp_depth.to_string()),
+            ("lmp_base", self.lmp_base.to_string()),
+            ("history_pruning_depth", self.history_pruning_depth.to_string()),
+            ("history_pruning_margin", self.history_pruning_margin.to_string()),
+            ("aspiration_window", self.aspiration_window.to_string()),
+            ("aspiration_depth", self.aspiration_depth.to_string()),
+            ("iir_depth", self.iir_depth.to_string()),
+        ]
+    }
+
+    /// Sets the parameter called `name`, as listed by [`Self::values`].
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.parse().map_err(|e| format!("bad value \"{value}\" for {name}: {e}"))
+        }
+        match name {
+            "null_move_reduction" => self.null_move_reduction = parse(name, value)?,
+            "futility_gradient" => self.futility_gradient = parse(name, value)?,
+            "futility_intercept" => self.futility_intercept = parse(name, value)?,
+            "lmr_base" => self.lmr_base = parse(name, value)?,
+            "lmr_division" => self.lmr_division = parse(name, value)?,
+            "singularity_depth" => self.singularity_depth = parse(name, value)?,
+            "singularity_depth_margin" => self.singularity_depth_margin = parse(name, value)?,
+            "singularity_margin" => self.singularity_margin = parse(name, value)?,
+            "double_extension_margin" => self.double_extension_margin = parse(name, value)?,
+            "double_extension_limit" => self.double_extension_limit = parse(name, value)?,
+            "lmp_depth" => self.lmp_depth = parse(name, value)?,
+            "lmp_base" => self.lmp_base = parse(name, value)?,
+            "history_pruning_depth" => self.history_pruning_depth = parse(name, value)?,
+            "history_pruning_margin" => self.history_pruning_margin = parse(name, value)?,
+            "aspiration_window" => self.aspiration_window = parse(name, value)?,
+            "aspiration_depth" => self.aspiration_depth = parse(name, value)?,
+            "iir_depth" => self.iir_depth = parse(name, value)?,
+            _ => return Err(format!("unknown search parameter {name}")),
+        }
+        Ok(())
+    }
+
+    /// Writes out the parameters as a TOML file that [`Self::from_toml`] can read.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("# virtue search parameters.\n");
+        out.push_str("# parameters missing from the file keep their built-in values.\n");
+        for (name, value) in self.values() {
+            out.push_str(name);
+            out.push_str(" = ");
+            out.push_str(&value);
+            out.push('\n');
         }
+        out
+    }
+
+    /// Reads `name = value` lines, as written by [`Self::to_toml`].
+    /// Parameters missing from the text keep their built-in values.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let mut out = Self::default();
+        for line in text.lines().map(|line| line.split('#').next().unwrap_or("").trim()) {
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected \"name = value\", got \"{line}\""))?;
+            out.set(name.trim(), value.trim())?;
+        }
+        Ok(out)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::from_toml(&text)?)
     }
 }
 
@@ -376,4 +860,384 @@ impl LMRTable {
         let played = moves_made.min(63);
         self.table[depth][played]
     }
-}
\ No newline at end of file
+}
+
+mod tests {
+    #[test]
+    fn config_round_trip() {
+        use super::Config;
+
+        let mut config = Config {
+            lmr_base: 1.5,
+            lmp_depth: 4.into(),
+            aspiration_window: 40,
+            ..Config::default()
+        };
+        let loaded = Config::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(loaded.values(), config.values());
+        // every listed parameter can be set by name.
+        for (name, value) in Config::default().values() {
+            assert!(config.set(name, &value).is_ok(), "couldn't set {name} to {value}");
+        }
+        assert_eq!(config.values(), Config::default().values());
+
+        assert!(Config::from_toml("lmr_base = fast").is_err());
+        assert!(Config::from_toml("no_such_parameter = 1").is_err());
+        assert_eq!(Config::from_toml("# nothing\n").unwrap().values(), Config::default().values());
+    }
+
+    #[test]
+    fn pruning_parameters_change_the_search() {
+        use super::Config;
+        use crate::{board::Board, definitions::Depth, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let nodes_with = |config: Config| {
+            let mut pos = Board::default();
+            pos.set_search_config(config);
+            pos.reset_tables();
+            let mut info = SearchInfo {
+                depth: Depth::new(6),
+                output_format: OutputFormat::Silent,
+                ..SearchInfo::default()
+            };
+            pos.search_position(&mut info);
+            info.nodes
+        };
+        let default_nodes = nodes_with(Config::default());
+        for (name, value) in [
+            ("null_move_reduction", "2"),
+            ("futility_gradient", "0"),
+            ("futility_intercept", "1000"),
+        ] {
+            let mut config = Config::default();
+            config.set(name, value).unwrap();
+            assert_ne!(nodes_with(config), default_nodes, "{name} isn't used");
+        }
+    }
+
+    #[test]
+    fn trace_has_an_exit_for_every_entry() {
+        use crate::{board::Board, definitions::Depth, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let path = std::env::temp_dir().join(format!("virtue-trace-{}.jsonl", std::process::id()));
+        let mut pos = Board::default();
+        let mut info = SearchInfo {
+            depth: Depth::new(4),
+            output_format: OutputFormat::Silent,
+            debug: true,
+            trace_file: Some(path.to_str().unwrap().to_string()),
+            trace_node_limit: 1_000_000,
+            ..SearchInfo::default()
+        };
+        info.start_trace().unwrap();
+        pos.search_position(&mut info);
+        info.finish_trace().unwrap();
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let count = |event: &str| trace.lines().filter(|line| line.contains(event)).count();
+        assert!(count("\"event\":\"enter\"") > 0);
+        assert_eq!(count("\"event\":\"enter\""), count("\"event\":\"exit\""));
+        assert_eq!(count("\"event\":\"truncated\""), 0);
+        // zero-window nodes are labelled with the type we expect them to have.
+        assert!(count("\"kind\":\"cut\"") > 0);
+        assert!(count("\"kind\":\"all\"") > 0);
+
+        // outside of debug mode, nothing is traced.
+        info.debug = false;
+        info.start_trace().unwrap();
+        assert!(info.trace.is_none());
+    }
+
+    #[test]
+    fn root_report_scores_every_root_move() {
+        use crate::{
+            board::Board,
+            definitions::{Depth, Score},
+            searchinfo::{MoveQuality, SearchInfo},
+            uci::OutputFormat,
+        };
+
+        crate::magic::initialise();
+        let mut pos = Board::default();
+        let mut info = SearchInfo {
+            depth: Depth::new(4),
+            output_format: OutputFormat::Silent,
+            root_report: true,
+            ..SearchInfo::default()
+        };
+        pos.search_position(&mut info);
+        assert_eq!(info.root_moves.len(), 20);
+
+        let (mate, draw) = (Score::mate_in(3), Score::DRAW);
+        assert_eq!(MoveQuality::classify(draw, draw), MoveQuality::Best);
+        assert_eq!(
+            MoveQuality::classify(draw, draw - 80),
+            MoveQuality::Inaccuracy
+        );
+        assert_eq!(
+            MoveQuality::classify(Score::mate_in(1), mate),
+            MoveQuality::Good
+        );
+        assert_eq!(MoveQuality::classify(mate, draw), MoveQuality::Blunder);
+    }
+
+    #[test]
+    fn root_temperature_varies_the_opening() {
+        use crate::{board::Board, definitions::Depth, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let mut pos = Board::default();
+        let mut info = SearchInfo {
+            depth: Depth::new(3),
+            output_format: OutputFormat::Silent,
+            root_temperature: 1000,
+            root_temperature_margin: 1000,
+            ..SearchInfo::default()
+        };
+        let best_move = pos.search_position(&mut info).1;
+        assert!((0..16).any(|_| pos.search_position(&mut info).1 != best_move));
+
+        // after the first few moves, the best move is always played.
+        info.root_temperature_moves = 0;
+        let best_move = pos.search_position(&mut info).1;
+        assert!((0..4).all(|_| pos.search_position(&mut info).1 == best_move));
+    }
+
+    #[test]
+    fn root_temperature_keeps_to_the_node_limit() {
+        use crate::{board::Board, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let mut pos = Board::default();
+        let mut info = SearchInfo {
+            node_limit: 20_000,
+            output_format: OutputFormat::Silent,
+            root_temperature: 1000,
+            root_temperature_margin: 1000,
+            ..SearchInfo::default()
+        };
+        pos.search_position(&mut info);
+        // the limit is checked every 2048 nodes.
+        assert!(info.nodes <= 20_000 + 2048, "{} nodes", info.nodes);
+    }
+
+    #[test]
+    fn tiny_time_budgets_still_find_a_real_move() {
+        use crate::{board::Board, searchinfo::SearchInfo, uci::OutputFormat};
+
+        // white can take a loose queen, which the first legal move doesn't do.
+        const FEN: &str = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+        crate::magic::initialise();
+        let mut pos = Board::from_fen(FEN).unwrap();
+
+        // no time at all still finishes the first iteration.
+        let mut info = SearchInfo {
+            output_format: OutputFormat::Silent,
+            time_set: true,
+            ..SearchInfo::default()
+        };
+        info.set_time_window(0);
+        assert_eq!(pos.search_position(&mut info).1.to_string(), "d2d5");
+
+        // whenever the search is cut off, the move comes from a finished
+        // iteration, or from one that beat it before being cut off.
+        for node_limit in [1, 100, 4096, 5000, 10_000, 50_000] {
+            let mut pos = Board::from_fen(FEN).unwrap();
+            let mut info = SearchInfo {
+                output_format: OutputFormat::Silent,
+                node_limit,
+                ..SearchInfo::default()
+            };
+            let (_, best_move) = pos.search_position(&mut info);
+            assert_eq!(best_move.to_string(), "d2d5", "node limit {node_limit}");
+        }
+    }
+
+    #[test]
+    fn single_legal_moves_are_played_at_once() {
+        use crate::{board::Board, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let mut pos = Board::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let mut info = SearchInfo {
+            output_format: OutputFormat::Silent,
+            time_set: true,
+            ..SearchInfo::default()
+        };
+        info.set_time_window(60_000);
+        let (_, best_move) = pos.search_position(&mut info);
+        assert_eq!(pos.legal_move_count(), 1);
+        assert_eq!(best_move.to_string(), "a8b8");
+        assert!(info.start_time.elapsed().as_secs() < 10);
+    }
+
+    #[test]
+    fn blunder_check_finds_the_refutation() {
+        use crate::{board::Board, definitions::Depth};
+
+        crate::magic::initialise();
+        let mut pos = Board::default();
+        pos.set_from_fen("4k3/8/8/3q4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        pos.reset_tables();
+        let fen = pos.fen();
+        let hang = pos.parse_san("d2d4").unwrap();
+        let check = pos.blunder_check(hang, Depth::new(4)).unwrap();
+        assert_eq!(pos.fen(), fen);
+        assert_eq!(check.best_move.to_string(), "d2d5");
+        assert!(check.loss.unwrap() > 1000);
+        assert_eq!(check.refutation.first().map(ToString::to_string).as_deref(), Some("d5d4"));
+
+        // the best move loses nothing.
+        let check = pos.blunder_check(check.best_move, Depth::new(4)).unwrap();
+        assert_eq!(check.loss, Some(0));
+    }
+
+    #[test]
+    fn background_analysis_leaves_the_position_alone() {
+        use std::sync::Arc;
+
+        use crate::{
+            board::Board,
+            chessmove::Move,
+            definitions::{
+                Square::{A1, A8},
+                PIECE_EMPTY,
+            },
+            searchinfo::SearchControl,
+        };
+
+        crate::magic::initialise();
+        let control = Arc::new(SearchControl::default());
+        let mut pos = Board::default();
+        pos.reset_tables();
+        // a move the analysis won't come across, whose history it mustn't age.
+        let rook_lift = Move::new(A1, A8, PIECE_EMPTY, 0);
+        pos.add_history(rook_lift, 400);
+        let history = pos.history_score(rook_lift);
+        assert!(history > 0);
+        let fen = pos.fen();
+        let m = pos.parse_san("e2e4").unwrap();
+        let stopper = {
+            let control = Arc::clone(&control);
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                control.stop();
+            })
+        };
+        pos.analyse_after(m, Some(control));
+        stopper.join().unwrap();
+        assert_eq!(pos.fen(), fen);
+        assert_eq!(pos.height(), 0);
+        assert_eq!(pos.history_score(rook_lift), history);
+        // the reply was searched, and left in the TT.
+        assert!(pos.make_move(m));
+        pos.zero_height();
+        assert!(pos.tt_probe_for_singularity().is_some());
+    }
+
+    #[test]
+    fn control_stops_a_search_from_another_thread() {
+        use std::sync::Arc;
+
+        use crate::{
+            board::Board,
+            searchinfo::{SearchControl, SearchInfo},
+            uci::OutputFormat,
+        };
+
+        crate::magic::initialise();
+        let control = Arc::new(SearchControl::default());
+        let mut pos = Board::default();
+        let mut info = SearchInfo {
+            output_format: OutputFormat::Silent,
+            control: Some(Arc::clone(&control)),
+            ..SearchInfo::default()
+        };
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            control.stop();
+        });
+        // without a stop, this search would never finish.
+        let (_, best_move) = pos.search_position(&mut info);
+        stopper.join().unwrap();
+        assert!(!best_move.is_null());
+        assert!(info.stopped);
+    }
+
+    #[test]
+    fn throttled_searches_stop_at_once() {
+        use std::{
+            sync::Arc,
+            time::{Duration, Instant},
+        };
+
+        use crate::searchinfo::{SearchControl, SearchInfo, MIN_NPS_LIMIT};
+
+        let control = Arc::new(SearchControl::default());
+        // five seconds ahead of the node budget.
+        let mut info = SearchInfo {
+            control: Some(Arc::clone(&control)),
+            nps_limit: MIN_NPS_LIMIT,
+            nodes: 5 * MIN_NPS_LIMIT,
+            ..SearchInfo::default()
+        };
+        let start = Instant::now();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            control.stop();
+        });
+        info.check_up();
+        stopper.join().unwrap();
+        assert!(info.stopped);
+        assert!(start.elapsed() < Duration::from_secs(1), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn colliding_tt_moves_are_never_played() {
+        use crate::{
+            board::{movegen::MoveList, Board},
+            chessmove::Move,
+            definitions::{Depth, Score, ZERO_PLY},
+            rng::XorShiftState,
+            searchinfo::SearchInfo,
+            testpositions::TEST_POSITIONS,
+            transpositiontable::HFlag,
+            uci::OutputFormat,
+        };
+
+        crate::magic::initialise();
+        let mut rng = XorShiftState::new();
+        #[allow(clippy::cast_possible_truncation)]
+        let mut random_move = || Move { data: rng.next() as u16 };
+        // the table is shared between positions, which only adds to the garbage in it.
+        let mut pos = Board::new();
+        for position in &TEST_POSITIONS {
+            pos.set_from_fen(position.fen).unwrap();
+            pos.reset_tables();
+            if pos.legal_move_count() == 0 {
+                continue;
+            }
+            // plant garbage, as if from key collisions, at the root and a ply below it.
+            pos.tt_store(random_move(), Score::DRAW, HFlag::Exact, ZERO_PLY);
+            let mut moves = MoveList::new();
+            pos.generate_moves(&mut moves);
+            for m in moves {
+                if pos.make_move(m) {
+                    pos.tt_store(random_move(), Score::DRAW, HFlag::Exact, ZERO_PLY);
+                    pos.unmake_move();
+                }
+            }
+            // making an illegal move trips the assertions in make_move.
+            let mut info = SearchInfo {
+                output_format: OutputFormat::Silent,
+                depth: Depth::new(3),
+                ..SearchInfo::default()
+            };
+            let (_, best_move) = pos.search_position(&mut info);
+            assert!(pos.is_legal(best_move), "{best_move} in {}", position.name);
+        }
+    }
+}
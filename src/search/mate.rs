@@ -0,0 +1,142 @@
+// An exhaustive search for forced mates, for `go mate N`.
+// Unlike the main search, nothing is pruned or reduced, so a mate
+// that this finds is proven, and the line it prints is the proof.
+
+use crate::{
+    board::{movegen::MoveList, Board},
+    chessmove::Move,
+    definitions::{Score, MAX_DEPTH},
+    searchinfo::SearchInfo,
+    uci::{self, SearchReport},
+};
+
+impl Board {
+    /// Looks for a forced mate in at most `moves` moves, trying each mate length in turn
+    /// so that the first mate found is the shortest. Prints the mating line, and returns it.
+    pub fn prove_mate(&mut self, info: &mut SearchInfo, moves: usize) -> Option<Vec<Move>> {
+        let moves = moves.min((usize::try_from(MAX_DEPTH.round()).unwrap() - 1) / 2);
+        for n in 1..=moves {
+            let line = self.mating_line(info, n);
+            if info.stopped {
+                return None;
+            }
+            if let Some(line) = line {
+                let report = SearchReport {
+                    score: Score::mate_in(2 * n - 1),
+                    bound: None,
+                    depth: i32::try_from(2 * n - 1).unwrap(),
+                    seldepth: line.len(),
+                    nodes: info.nodes,
+                    time: info.start_time.elapsed().as_millis(),
+                    pv: &line,
+                };
+                uci::print_search_report(info.output_format, self.turn(), &report);
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// The legal moves in the position, with checks first, then captures.
+    fn ordered_legal_moves(&mut self) -> Vec<(Move, bool)> {
+        let mut move_list = MoveList::new();
+        self.generate_moves(&mut move_list);
+        let mut moves = Vec::new();
+        for m in move_list {
+            let is_capture = self.is_capture(m);
+            if self.make_move(m) {
+                let gives_check = self.in_check::<{ Self::US }>();
+                self.unmake_move();
+                moves.push((m, gives_check, is_capture));
+            }
+        }
+        moves.sort_by_key(|&(_, gives_check, is_capture)| (!gives_check, !is_capture));
+        moves
+            .into_iter()
+            .map(|(m, gives_check, _)| (m, gives_check))
+            .collect()
+    }
+
+    /// A line in which the side to move mates within `moves` moves, whatever the other side plays.
+    fn mating_line(&mut self, info: &mut SearchInfo, moves: usize) -> Option<Vec<Move>> {
+        for (m, gives_check) in self.ordered_legal_moves() {
+            // only a check can mate, so the last move of the mate must be one.
+            if moves == 1 && !gives_check {
+                continue;
+            }
+            self.make_move(m);
+            let defence = self.longest_defence(info, moves);
+            self.unmake_move();
+            if info.stopped {
+                return None;
+            }
+            if let Some(mut line) = defence {
+                line.insert(0, m);
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// The longest line the side to move can put up against a mate, when the other
+    /// side has `moves` moves to mate in, counting the one just made. `None` if there's a defence.
+    fn longest_defence(&mut self, info: &mut SearchInfo, moves: usize) -> Option<Vec<Move>> {
+        info.nodes += 1;
+        if info.nodes.trailing_zeros() >= 12 {
+            info.check_up();
+        }
+        if info.stopped || self.is_draw() {
+            return None;
+        }
+        let replies = self.ordered_legal_moves();
+        if replies.is_empty() {
+            return self.in_check::<{ Self::US }>().then(Vec::new);
+        }
+        if moves == 1 {
+            return None;
+        }
+        let mut longest = Vec::new();
+        for (reply, _) in replies {
+            self.make_move(reply);
+            let line = self.mating_line(info, moves - 1);
+            self.unmake_move();
+            let mut line = line?;
+            if line.len() + 1 > longest.len() {
+                line.insert(0, reply);
+                longest = line;
+            }
+        }
+        Some(longest)
+    }
+}
+
+mod tests {
+    #[test]
+    fn mates_are_proven_at_the_shortest_length() {
+        use crate::{board::Board, searchinfo::SearchInfo, uci::OutputFormat};
+
+        crate::magic::initialise();
+        let mut info = SearchInfo {
+            output_format: OutputFormat::Silent,
+            ..SearchInfo::default()
+        };
+
+        // back-rank mate in one.
+        let mut pos = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        pos.reset_tables();
+        let line = pos.prove_mate(&mut info, 3).unwrap();
+        assert_eq!(line.len(), 1);
+        assert_eq!(line[0].to_string(), "a1a8");
+
+        // two rooks against a bare king mate in two, but not in one.
+        let mut pos = Board::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        pos.reset_tables();
+        assert!(pos.prove_mate(&mut info, 1).is_none());
+        assert_eq!(pos.prove_mate(&mut info, 2).unwrap().len(), 3);
+
+        // a stalemate isn't a mate.
+        let mut pos = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        pos.reset_tables();
+        assert!(pos.prove_mate(&mut info, 2).is_none());
+    }
+}
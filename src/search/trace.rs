@@ -0,0 +1,139 @@
+// Node-by-node traces of small searches, written when `debug on` is set,
+// so that pruning bugs can be replayed and inspected offline.
+// Each line of the trace is a JSON object for one node entry or exit.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::{
+    chessmove::Move,
+    definitions::{Depth, Score},
+};
+
+/// Why a node returned the score it did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeExit {
+    /// The search ran out of time or was told to stop.
+    Stopped,
+    Draw,
+    /// The search reached the maximum ply.
+    MaxDepth,
+    /// No mate could improve on the window, given the distance from the root.
+    MateDistance,
+    TtCutoff,
+    NullMove,
+    /// The singular search found several moves that beat beta.
+    MultiCut(Move),
+    /// Quiescence search stood pat on the static evaluation.
+    StandPat,
+    BetaCutoff(Move),
+    /// No move raised alpha.
+    AllNode(Move),
+    /// A move raised alpha but none beat beta.
+    PvNode(Move),
+    Checkmate,
+    Stalemate,
+    /// The previous move won the game under the rules of the variant.
+    VariantLoss,
+    /// The excluded move of a singular search was the only legal move.
+    OnlyMoveExcluded,
+}
+
+impl NodeExit {
+    /// The name of the reason, and the move chosen at the node, if any.
+    const fn parts(self) -> (&'static str, Option<Move>) {
+        match self {
+            Self::Stopped => ("stopped", None),
+            Self::Draw => ("draw", None),
+            Self::MaxDepth => ("max_depth", None),
+            Self::MateDistance => ("mate_distance", None),
+            Self::TtCutoff => ("tt_cutoff", None),
+            Self::NullMove => ("null_move", None),
+            Self::MultiCut(m) => ("multi_cut", Some(m)),
+            Self::StandPat => ("stand_pat", None),
+            Self::BetaCutoff(m) => ("beta_cutoff", Some(m)),
+            Self::AllNode(m) => ("all_node", Some(m)),
+            Self::PvNode(m) => ("pv_node", Some(m)),
+            Self::Checkmate => ("checkmate", None),
+            Self::VariantLoss => ("variant_loss", None),
+            Self::Stalemate => ("stalemate", None),
+            Self::OnlyMoveExcluded => ("only_move_excluded", None),
+        }
+    }
+}
+
+/// A trace being written to a file. Once the search has gone past
+/// `node_limit` nodes, a final `truncated` line is written and the
+/// trace stops, so nodes that are still open at that point have no exit.
+pub struct SearchTrace {
+    out: BufWriter<File>,
+    node_limit: u64,
+    done: bool,
+}
+
+impl SearchTrace {
+    pub fn create(path: &str, node_limit: u64) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            node_limit,
+            done: false,
+        })
+    }
+
+    /// Whether the node count is still within the limit, writing the
+    /// `truncated` line the first time it isn't.
+    fn active(&mut self, nodes: u64) -> bool {
+        if self.done {
+            return false;
+        }
+        if nodes > self.node_limit {
+            self.write(format_args!(
+                "{{\"event\":\"truncated\",\"nodes\":{nodes}}}"
+            ));
+            self.done = true;
+        }
+        !self.done
+    }
+
+    /// Writes a line, giving up on the trace if the file can't be written to.
+    fn write(&mut self, line: std::fmt::Arguments) {
+        if writeln!(self.out, "{line}").is_err() {
+            self.done = true;
+        }
+    }
+
+    /// Records entering a node. `kind` is "pv", "cut", "all" or "qs".
+    pub fn enter(
+        &mut self,
+        nodes: u64,
+        ply: usize,
+        kind: &str,
+        depth: Depth,
+        alpha: Score,
+        beta: Score,
+    ) {
+        if self.active(nodes) {
+            self.write(format_args!(
+                "{{\"event\":\"enter\",\"nodes\":{nodes},\"ply\":{ply},\"kind\":\"{kind}\",\"depth\":{depth},\"alpha\":{alpha},\"beta\":{beta}}}"
+            ));
+        }
+    }
+
+    pub fn exit(&mut self, nodes: u64, ply: usize, score: Score, exit: NodeExit) {
+        if self.active(nodes) {
+            let (reason, m) = exit.parts();
+            let m = m
+                .filter(|m| !m.is_null())
+                .map_or_else(|| "null".to_string(), |m| format!("\"{m}\""));
+            self.write(format_args!(
+                "{{\"event\":\"exit\",\"nodes\":{nodes},\"ply\":{ply},\"score\":{score},\"reason\":\"{reason}\",\"move\":{m}}}"
+            ));
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -1,9 +1,282 @@
-use std::{sync::mpsc, time::Instant};
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
 
-use crate::definitions::Depth;
+use crate::{
+    board::{
+        evaluation::{centipawns, to_centipawns},
+        variant::Variant,
+    },
+    chessmove::Move,
+    definitions::{Depth, Score},
+    search::trace::{NodeExit, SearchTrace},
+    uci::OutputFormat,
+};
+
+/// Roughly the playing strength of the engine, for judging opponents by their rating.
+const ENGINE_RATING: i32 = 2800;
+/// With `AutoContempt`, the contempt rises by a centipawn for every this much
+/// Elo that the opponent is rated below the engine...
+const AUTO_CONTEMPT_ELO_PER_CENTIPAWN: i32 = 20;
+/// ...up to this many centipawns.
+const MAX_AUTO_CONTEMPT: i32 = 50;
+
+/// The opponent, as described by the GUI with the `UCI_Opponent` option:
+/// `<title> <rating> <computer|human> <name>`, where an unknown title or rating is `none`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opponent {
+    pub title: Option<String>,
+    pub rating: Option<i32>,
+    pub computer: bool,
+    pub name: String,
+}
+
+impl Opponent {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split_whitespace();
+        let title = match parts.next()? {
+            "none" => None,
+            title => Some(title.to_string()),
+        };
+        let rating = match parts.next()? {
+            "none" => None,
+            rating => Some(rating.parse().ok()?),
+        };
+        let computer = match parts.next()? {
+            "computer" => true,
+            "human" => false,
+            _ => return None,
+        };
+        let name = parts.collect::<Vec<_>>().join(" ");
+        Some(Self { title, rating, computer, name })
+    }
+}
+
+/// The number of nodes searched between calls to [`SearchInfo::check_up`],
+/// which reads the clock and locks the search signals, so is too slow for every node.
+/// This must be a power of two.
+const CHECK_UP_INTERVAL: u64 = 2048;
+
+/// The lowest `nps_limit` other than no limit. Any slower, and the search
+/// would go seconds between check-ups.
+pub const MIN_NPS_LIMIT: u64 = 1000;
+
+/// How much worse a move is than the best move, for annotating games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveQuality {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveQuality {
+    /// Classifies a move that scores `score` when the best move scores `best`.
+    pub fn classify(best: Score, score: Score) -> Self {
+        if score >= best {
+            return Self::Best;
+        }
+        if score.is_mate() && score > Score::DRAW {
+            // a slower mate still wins.
+            return Self::Good;
+        }
+        if best.is_mate() || score.is_mate() {
+            return Self::Blunder;
+        }
+        match to_centipawns(best - score) {
+            ..=30 => Self::Good,
+            31..=100 => Self::Inaccuracy,
+            101..=300 => Self::Mistake,
+            _ => Self::Blunder,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Best => "best",
+            Self::Good => "good",
+            Self::Inaccuracy => "inaccuracy",
+            Self::Mistake => "mistake",
+            Self::Blunder => "blunder",
+        }
+    }
+}
+
+/// What the engine would do about the game, alongside playing its move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Play,
+    AcceptDraw,
+    Resign,
+}
+
+impl Verdict {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Play => "play",
+            Self::AcceptDraw => "draw",
+            Self::Resign => "resign",
+        }
+    }
+}
+
+/// Decides when the engine would resign or accept a draw, once its own
+/// score has stayed past a threshold for enough consecutive searches.
+/// A rule with a move count of zero is turned off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verdicts {
+    /// Resign once the score is at most minus this many centipawns...
+    pub resign_score: i32,
+    /// ...for this many searches in a row.
+    pub resign_moves: usize,
+    /// Accept a draw once the score is within this many centipawns of zero...
+    pub draw_score: i32,
+    /// ...for this many searches in a row...
+    pub draw_moves: usize,
+    /// ...and the game is at least this many full moves long.
+    pub draw_min_moves: usize,
+    resign_streak: usize,
+    draw_streak: usize,
+    last: Option<Verdict>,
+}
+
+impl Verdicts {
+    /// Records the `score` of a search, in centipawns from the point of view
+    /// of the side to move, made `full_moves` into the game.
+    pub const fn update(&mut self, score: i32, full_moves: usize) -> Verdict {
+        self.resign_streak = if score <= -self.resign_score {
+            self.resign_streak + 1
+        } else {
+            0
+        };
+        self.draw_streak = if score.abs() <= self.draw_score && full_moves >= self.draw_min_moves {
+            self.draw_streak + 1
+        } else {
+            0
+        };
+        let verdict = if self.resign_moves != 0 && self.resign_streak >= self.resign_moves {
+            Verdict::Resign
+        } else if self.draw_moves != 0 && self.draw_streak >= self.draw_moves {
+            Verdict::AcceptDraw
+        } else {
+            Verdict::Play
+        };
+        self.last = Some(verdict);
+        verdict
+    }
+
+    /// The verdict after the most recent search, if there has been one this game.
+    pub const fn last(&self) -> Option<Verdict> {
+        self.last
+    }
+
+    /// Forgets the scores of the last game.
+    pub const fn new_game(&mut self) {
+        self.resign_streak = 0;
+        self.draw_streak = 0;
+        self.last = None;
+    }
+}
+
+/// The result of searching one move at the root.
+#[derive(Debug, Clone, Copy)]
+pub struct RootMove {
+    pub m: Move,
+    /// The score of the move, from the point of view of the side to move.
+    pub score: Score,
+    /// The number of nodes spent searching the move.
+    pub nodes: u64,
+}
+
+impl RootMove {
+    /// The expected game result for the side to move after this move, between 0 and 1.
+    pub fn expected_score(&self) -> f64 {
+        if self.score.is_mate() {
+            return if self.score > Score::DRAW { 1.0 } else { 0.0 };
+        }
+        let cp = f64::from(to_centipawns(self.score.raw()));
+        1.0 / (1.0 + 10f64.powf(-cp / 400.0))
+    }
+}
+
+/// The commands from the GUI that a running search needs to act on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Signals {
+    pub stop: bool,
+    pub ponderhit: bool,
+    pub quit: bool,
+}
+
+/// Passes commands from the thread reading UCI input to the search thread.
+#[derive(Debug, Default)]
+pub struct SearchControl {
+    signals: Mutex<Signals>,
+    signalled: Condvar,
+}
+
+impl SearchControl {
+    fn send(&self, f: impl FnOnce(&mut Signals)) {
+        f(&mut self.signals.lock().unwrap());
+        self.signalled.notify_all();
+    }
+
+    pub fn stop(&self) {
+        self.send(|signals| signals.stop = true);
+    }
+
+    pub fn ponderhit(&self) {
+        self.send(|signals| signals.ponderhit = true);
+    }
+
+    pub fn quit(&self) {
+        self.send(|signals| {
+            signals.stop = true;
+            signals.quit = true;
+        });
+    }
+
+    /// Forgets the signals sent to the last search.
+    pub fn reset(&self) {
+        *self.signals.lock().unwrap() = Signals::default();
+    }
+
+    /// The signals sent so far. A `ponderhit` is only reported once.
+    fn take(&self) -> Signals {
+        let mut signals = self.signals.lock().unwrap();
+        let out = *signals;
+        signals.ponderhit = false;
+        out
+    }
+
+    /// Blocks until a signal is sent or `timeout` has passed, leaving the
+    /// signals for the next `take`.
+    fn wait_timeout(&self, timeout: std::time::Duration) {
+        let (signals, _) = self
+            .signalled
+            .wait_timeout_while(self.signals.lock().unwrap(), timeout, |s| {
+                !(s.stop || s.ponderhit || s.quit)
+            })
+            .unwrap();
+        drop(signals);
+    }
+
+    /// Blocks until a signal is sent, and returns it as `take` would.
+    fn wait(&self) -> Signals {
+        let guard = self.signals.lock().unwrap();
+        let mut signals = self
+            .signalled
+            .wait_while(guard, |s| !(s.stop || s.ponderhit || s.quit))
+            .unwrap();
+        let out = *signals;
+        signals.ponderhit = false;
+        out
+    }
+}
 
 #[allow(clippy::struct_excessive_bools)]
-pub struct SearchInfo<'a> {
+pub struct SearchInfo {
     /// The starting time of the search.
     pub start_time: Instant,
     /// The ending time of the search.
@@ -11,11 +284,63 @@ pub struct SearchInfo<'a> {
 
     /// The maximum depth of the search.
     pub depth: Depth,
+    /// If set, the search looks for a forced mate in at most this many moves,
+    /// before falling back to a normal search.
+    pub mate_moves: Option<usize>,
 
     pub time_set: bool,
+    #[allow(dead_code)]
     pub moves_to_go: usize,
     pub infinite: bool,
+    /// The size of the transposition table asked for with the `Hash` option, in megabytes.
+    /// The table is the built-in default size until this is set.
+    pub hash_megabytes: Option<usize>,
+    /// Whether the transposition table is backed by huge pages, from the `UseLargePages` option.
+    pub large_pages: bool,
+    /// The evaluation parameter file asked for with the `EvalFile` option,
+    /// or `None` for the built-in parameters.
+    pub eval_file: Option<String>,
+    /// Whether the search is pondering. Time limits are ignored until `ponderhit`.
+    pub pondering: bool,
+    /// Whether to suggest a move to ponder on alongside the best move.
+    pub ponder: bool,
+    /// Whether to keep analysing the game while waiting for the opponent,
+    /// for GUIs that never send `go ponder`.
+    pub background_analysis: bool,
+    /// The variant chosen with `UCI_Variant`, which the board is switched to.
+    pub variant: Variant,
     pub nodes: u64,
+    /// If non-zero, the search stops once it has searched this many nodes.
+    pub node_limit: u64,
+    /// If non-zero, the search is slowed down to at most this many nodes per second,
+    /// which is at least `MIN_NPS_LIMIT`.
+    pub nps_limit: u64,
+    /// If non-zero, no search goes deeper than this many plies, as a handicap.
+    pub depth_limit: i32,
+    /// Every time window is divided by this, as a handicap.
+    pub time_odds: u64,
+    /// Whether to avoid anything that depends on the wall clock, so that
+    /// the same commands always give the same output.
+    pub deterministic: bool,
+
+    /// If non-zero, the temperature in centipawns for picking
+    /// the root move at random in the opening.
+    pub root_temperature: i32,
+    /// The number of full moves into the game for which the root move is picked at random.
+    pub root_temperature_moves: usize,
+    /// How far in centipawns below the best move a root move may score and still be picked.
+    pub root_temperature_margin: i32,
+
+    /// How many centipawns worse than equal a draw is for the side to move at the root.
+    pub contempt: i32,
+    /// Whether the contempt is worked out from the opponent's rating, when the GUI sends one.
+    pub auto_contempt: bool,
+    /// The opponent, if the GUI has said who it is.
+    pub opponent: Option<Opponent>,
+
+    /// Whether `check_up` ignores limits and commands, so that the
+    /// first iteration always finishes and there is a move to play.
+    pub limits_deferred: bool,
 
     /// Signal to quit the search.
     pub quit: bool,
@@ -26,43 +351,175 @@ pub struct SearchInfo<'a> {
     pub failhigh: f32,
     /// The number of fail-highs that occured on the first move searched.
     pub failhigh_first: f32,
+    /// The number of quiet moves skipped by late move pruning.
+    pub lmp_prunes: u64,
+    /// The number of quiet moves skipped by history pruning.
+    pub history_prunes: u64,
     /// The highest depth reached (selective depth).
     pub seldepth: Depth,
 
-    /// A handle to a receiver for stdin.
-    pub stdin_rx: Option<&'a mpsc::Receiver<String>>,
+    /// Whether quiescence search may use lazy evaluation. The tuner
+    /// turns this off, as it needs the full evaluation of every position.
+    pub lazy_eval: bool,
+
+    /// Whether to re-search every zero-window fail-high with a full window,
+    /// and report the lines where the two searches disagree.
+    pub verify_scout: bool,
+
+    /// Whether to periodically print the line currently being searched.
+    pub print_currline: bool,
+    /// If non-zero, reported PVs are cut down to this many moves.
+    pub max_pv_length: usize,
+    /// The format of `info` and `bestmove` output.
+    pub output_format: OutputFormat,
+    /// The last time the current line was printed.
+    pub last_currline_time: Instant,
+
+    /// Where commands from the GUI arrive during a search, if anywhere.
+    pub control: Option<Arc<SearchControl>>,
+
+    /// Whether the GUI has asked for debug output with `debug on`.
+    pub debug: bool,
+    /// Where to write a trace of each search in debug mode, if anywhere.
+    pub trace_file: Option<String>,
+    /// The number of nodes after which a trace is cut off.
+    pub trace_node_limit: u64,
+    /// The trace of the current search.
+    pub trace: Option<SearchTrace>,
+
+    /// Whether to report the score of every root move after the search.
+    pub root_report: bool,
+    /// The root moves searched so far in the current iteration, with their exact
+    /// scores, when `root_report` is set.
+    pub root_moves: Vec<RootMove>,
+
+    /// When to resign or accept a draw, and the scores leading up to it.
+    pub verdicts: Verdicts,
 }
 
-impl Default for SearchInfo<'_> {
+impl Default for SearchInfo {
     fn default() -> Self {
         Self {
             start_time: Instant::now(),
             stop_time: Instant::now() + std::time::Duration::from_secs(1),
             depth: 60.into(),
+            mate_moves: None,
             time_set: false,
             moves_to_go: 0,
             infinite: false,
+            hash_megabytes: None,
+            large_pages: false,
+            eval_file: None,
+            pondering: false,
+            ponder: false,
+            background_analysis: false,
+            variant: Variant::Standard,
             nodes: 0,
+            node_limit: 0,
+            nps_limit: 0,
+            depth_limit: 0,
+            time_odds: 1,
+            deterministic: false,
+            root_temperature: 0,
+            root_temperature_moves: 8,
+            root_temperature_margin: 50,
+            contempt: 0,
+            auto_contempt: false,
+            opponent: None,
+            limits_deferred: false,
             quit: false,
             stopped: false,
             failhigh: 0.0,
             failhigh_first: 0.0,
+            lmp_prunes: 0,
+            history_prunes: 0,
             seldepth: 0.into(),
-            stdin_rx: None,
+            lazy_eval: true,
+            verify_scout: false,
+            print_currline: false,
+            max_pv_length: 0,
+            output_format: OutputFormat::Uci,
+            last_currline_time: Instant::now(),
+            control: None,
+            debug: false,
+            trace_file: None,
+            trace_node_limit: 100_000,
+            trace: None,
+            root_report: false,
+            root_moves: Vec::new(),
+            verdicts: Verdicts {
+                resign_score: 1000,
+                resign_moves: 0,
+                draw_score: 10,
+                draw_moves: 0,
+                draw_min_moves: 40,
+                ..Verdicts::default()
+            },
         }
     }
 }
 
-impl<'a> SearchInfo<'a> {
-    pub fn clear_for_search(&mut self) {
+impl SearchInfo {
+    /// Whether enough nodes have been searched since the last [`SearchInfo::check_up`]
+    /// that it is due again.
+    pub const fn check_up_due(&self) -> bool {
+        self.nodes & (CHECK_UP_INTERVAL - 1) == 0
+    }
+
+    /// The contempt in centipawns for this game. With `auto_contempt`, the engine
+    /// plays on against weaker opponents, but never avoids draws against stronger ones.
+    pub fn effective_contempt(&self) -> i32 {
+        match self.opponent.as_ref().and_then(|opponent| opponent.rating) {
+            Some(rating) if self.auto_contempt => ((ENGINE_RATING - rating)
+                / AUTO_CONTEMPT_ELO_PER_CENTIPAWN)
+                .clamp(0, MAX_AUTO_CONTEMPT),
+            _ => self.contempt,
+        }
+    }
+
+    /// The score of a draw for the side to move at `height`, before any fuzzing.
+    pub fn draw_score(&self, height: usize) -> Score {
+        let contempt = centipawns(self.effective_contempt());
+        if height.is_multiple_of(2) {
+            Score::new(-contempt)
+        } else {
+            Score::new(contempt)
+        }
+    }
+
+    pub const fn clear_for_search(&mut self) {
         self.stopped = false;
         self.nodes = 0;
         self.failhigh = 0.0;
         self.failhigh_first = 0.0;
+        self.lmp_prunes = 0;
+        self.history_prunes = 0;
+    }
+
+    /// Starts tracing the next search to `trace_file`, if in debug mode.
+    pub fn start_trace(&mut self) -> std::io::Result<()> {
+        self.trace = match &self.trace_file {
+            Some(path) if self.debug => Some(SearchTrace::create(path, self.trace_node_limit)?),
+            _ => None,
+        };
+        Ok(())
     }
 
-    pub fn set_stdin(&mut self, stdin_rx: &'a mpsc::Receiver<String>) {
-        self.stdin_rx = Some(stdin_rx);
+    /// Flushes the trace of the last search, if there is one.
+    pub fn finish_trace(&mut self) -> std::io::Result<()> {
+        self.trace.take().map_or(Ok(()), SearchTrace::finish)
+    }
+
+    pub fn trace_enter(&mut self, ply: usize, kind: &str, depth: Depth, alpha: Score, beta: Score) {
+        if let Some(trace) = &mut self.trace {
+            trace.enter(self.nodes, ply, kind, depth, alpha, beta);
+        }
+    }
+
+    pub fn trace_exit(&mut self, ply: usize, score: Score, exit: NodeExit) {
+        if let Some(trace) = &mut self.trace {
+            trace.exit(self.nodes, ply, score, exit);
+        }
     }
 
     pub fn set_time_window(&mut self, millis: u64) {
@@ -70,20 +527,85 @@ impl<'a> SearchInfo<'a> {
         self.stop_time = self.start_time + std::time::Duration::from_millis(millis);
     }
 
+    /// Whether it's time to print the current line again, at most once a second.
+    pub fn currline_due(&mut self) -> bool {
+        if !self.print_currline || self.last_currline_time.elapsed().as_secs() < 1 {
+            return false;
+        }
+        self.last_currline_time = Instant::now();
+        true
+    }
+
+    /// Switches from pondering to a normal search, with the time window
+    /// restarting from now, as the opponent has played the expected move.
+    fn ponderhit(&mut self) {
+        self.pondering = false;
+        let window = self.stop_time.saturating_duration_since(self.start_time);
+        self.start_time = Instant::now();
+        self.stop_time = self.start_time + window;
+    }
+
+    /// Blocks until the GUI ends a ponder search that finished early,
+    /// as a pondering engine mustn't send its best move before `ponderhit` or `stop`.
+    pub fn wait_for_ponder_end(&mut self) {
+        if !self.pondering {
+            return;
+        }
+        self.pondering = false;
+        if let Some(control) = &self.control {
+            self.quit |= control.wait().quit;
+        }
+    }
+
+    /// Sleeps for as long as the search is ahead of the `nps_limit` budget,
+    /// but never past the end of the time window, and wakes early for a signal
+    /// from the GUI, so that `stop` and `ponderhit` are answered at once.
+    fn throttle(&self) {
+        if self.nps_limit == 0 || self.deterministic {
+            return;
+        }
+        let budget = std::time::Duration::from_micros(
+            self.nodes.saturating_mul(1_000_000) / self.nps_limit,
+        );
+        let mut wake_time = self.start_time + budget;
+        if self.time_set && !self.pondering {
+            wake_time = wake_time.min(self.stop_time);
+        }
+        let now = Instant::now();
+        if wake_time > now {
+            match &self.control {
+                Some(control) => control.wait_timeout(wake_time - now),
+                None => std::thread::sleep(wake_time - now),
+            }
+        }
+    }
+
     pub fn check_up(&mut self) {
+        if self.limits_deferred {
+            return;
+        }
+        self.throttle();
+        if self.node_limit != 0 && self.nodes >= self.node_limit {
+            self.stopped = true;
+        }
         if self.time_set
+            && !self.pondering
             && Instant::now()
                 .checked_duration_since(self.stop_time)
                 .is_some()
         {
             self.stopped = true;
         }
-        if let Some(Ok(cmd)) = self.stdin_rx.map(mpsc::Receiver::try_recv) {
-            self.stopped = true;
-            let cmd = cmd.trim();
-            if cmd == "quit" {
-                self.quit = true;
-            }
+        let Some(signals) = self.control.as_ref().map(|control| control.take()) else {
+            return;
         };
+        if signals.ponderhit && self.pondering {
+            self.ponderhit();
+        }
+        if signals.stop {
+            self.stopped = true;
+            self.pondering = false;
+            self.quit |= signals.quit;
+        }
     }
 }
@@ -0,0 +1,257 @@
+// Serves the UCI interface over TCP or a Unix socket, for deployments where a
+// front-end connects to the engine over the network rather than owning its
+// stdin and stdout. Each connection gets an engine process of its own, running
+// `uci` with the same arguments, so clients can't disturb each other's searches.
+//
+// Clients aren't authenticated, and each can use as much CPU and memory as its
+// engine allows, so the address should stay on loopback (or a Unix socket) unless
+// something in front of the server controls who reaches it. The engines are run
+// with `--no-file-options`, so that a client can't have them read or write files.
+
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// The command sent to each engine before the client's, when `--json` is given.
+const JSON_OUTPUT: &str = "setoption name OutputFormat value json\n";
+
+/// Sent to a client that connects while the server is full, before it is disconnected.
+const SERVER_FULL: &str = "info string too many connections, try again later\n";
+
+/// A connection to a client.
+trait Connection: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    /// Closes the connection in both directions, which also ends any blocked read of it.
+    fn close(&self) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Self::try_clone(self)
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Self::try_clone(self)
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+/// How to start the engine for each connection.
+#[derive(Debug, Clone)]
+struct EngineCommand {
+    program: PathBuf,
+    args: Vec<String>,
+    /// Sent to the engine before anything from the client.
+    preamble: String,
+}
+
+/// Copies everything from `from` to `to` as it arrives, until `from` ends.
+/// This isn't `io::copy`, which on Linux may move the data with `splice`,
+/// and was found to hold a client's lines back from the engine.
+fn forward(from: &mut impl Read, to: &mut impl Write) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+    loop {
+        let read = match from.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        to.write_all(&buffer[..read])?;
+        to.flush()?;
+    }
+}
+
+/// Connects the client to a new engine process, passing lines in both directions
+/// until one of them closes the connection or the engine quits.
+fn serve_connection(mut stream: impl Connection, engine: &EngineCommand) -> io::Result<()> {
+    let mut child = Command::new(&engine.program)
+        .args(&engine.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut engine_input = child.stdin.take().expect("engine stdin is piped");
+    let mut engine_output = child.stdout.take().expect("engine stdout is piped");
+
+    engine_input.write_all(engine.preamble.as_bytes())?;
+    let mut from_client = stream.try_clone()?;
+    // when the client hangs up, the engine's stdin is closed with it, and the engine quits.
+    let forwarding = std::thread::spawn(move || forward(&mut from_client, &mut engine_input));
+    let copied = forward(&mut engine_output, &mut stream);
+    // the engine has quit, so the client is disconnected, which also ends the forwarding thread.
+    let _ = stream.close();
+    let _ = forwarding.join();
+    child.wait()?;
+    copied
+}
+
+/// Accepts connections until accepting one fails, serving each on a thread of its own.
+/// Connections past `max_connections` at once are turned away, as each one runs an engine.
+fn accept_all<C: Connection>(
+    connections: impl Iterator<Item = io::Result<C>>,
+    engine: &EngineCommand,
+    max_connections: usize,
+) -> Result<(), String> {
+    // only this thread adds to the count, so it can't go past the limit.
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in connections {
+        let mut stream = stream.map_err(|e| format!("couldn't accept a connection: {e}"))?;
+        if active.load(Ordering::SeqCst) >= max_connections {
+            let _ = stream.write_all(SERVER_FULL.as_bytes());
+            let _ = stream.close();
+            continue;
+        }
+        active.fetch_add(1, Ordering::SeqCst);
+        let (engine, active) = (engine.clone(), Arc::clone(&active));
+        std::thread::spawn(move || {
+            if let Err(e) = serve_connection(stream, &engine) {
+                eprintln!("Warning: connection ended with an error: {e}");
+            }
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+/// Runs the server, with the arguments after `serve`: `--listen ADDRESS`, where the
+/// address is `HOST:PORT` for TCP or `unix:PATH` for a Unix socket, then `--json`
+/// to report searches as JSON, `--max-connections N` to serve at most N clients at
+/// once, by default one for each logical core, and any arguments for `uci`, such as
+/// `--eval-params PATH`. The options that name files are always taken away from
+/// clients, and the address should be a loopback one.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut address = None;
+    let mut max_connections = num_cpus::get();
+    let mut engine = EngineCommand {
+        program: std::env::current_exe()
+            .map_err(|e| format!("couldn't find the engine executable: {e}"))?,
+        args: vec!["uci".to_string(), "--no-file-options".to_string()],
+        preamble: String::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                address = Some(
+                    args.next()
+                        .ok_or_else(|| "no address given after --listen".to_string())?,
+                );
+            }
+            "--json" => engine.preamble.push_str(JSON_OUTPUT),
+            "--max-connections" => {
+                max_connections = args
+                    .next()
+                    .ok_or_else(|| "no number given after --max-connections".to_string())?
+                    .parse()
+                    .map_err(|e| format!("bad --max-connections: {e}"))?;
+            }
+            _ => engine.args.push(arg.clone()),
+        }
+    }
+    let Some(address) = address else {
+        return Err(
+            "usage: serve --listen (HOST:PORT | unix:PATH) [--json] [--max-connections N] [UCI ARGS...]"
+                .to_string(),
+        );
+    };
+
+    if let Some(path) = address.strip_prefix("unix:") {
+        #[cfg(unix)]
+        {
+            let listener = std::os::unix::net::UnixListener::bind(path)
+                .map_err(|e| format!("couldn't listen on {path}: {e}"))?;
+            eprintln!("listening on {path}");
+            return accept_all(listener.incoming(), &engine, max_connections);
+        }
+        #[cfg(not(unix))]
+        return Err(format!("can't listen on {path}: Unix sockets aren't supported here"));
+    }
+    let listener =
+        TcpListener::bind(address).map_err(|e| format!("couldn't listen on {address}: {e}"))?;
+    eprintln!("listening on {address}");
+    accept_all(listener.incoming(), &engine, max_connections)
+}
+
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn connections_reach_the_engine() {
+        use super::{serve_connection, EngineCommand};
+        use std::{
+            io::{Read, Write},
+            net::{Shutdown, TcpListener, TcpStream},
+        };
+
+        // `cat` stands in for the engine, echoing each line back after the preamble.
+        let engine = EngineCommand {
+            program: "cat".into(),
+            args: Vec::new(),
+            preamble: "uci\n".to_string(),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream, &engine).unwrap();
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"isready\n").unwrap();
+        // hanging up closes the engine's input, so it quits and the connection ends.
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "uci\nisready\n");
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn connections_past_the_limit_are_turned_away() {
+        use super::{accept_all, EngineCommand, SERVER_FULL};
+        use std::{
+            io::{Read, Write},
+            net::{Shutdown, TcpListener, TcpStream},
+        };
+
+        let engine = EngineCommand {
+            program: "cat".into(),
+            args: Vec::new(),
+            preamble: String::new(),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || accept_all(listener.incoming().take(2), &engine, 1));
+
+        let mut first = TcpStream::connect(address).unwrap();
+        let mut second = TcpStream::connect(address).unwrap();
+        let mut received = String::new();
+        second.read_to_string(&mut received).unwrap();
+        assert_eq!(received, SERVER_FULL);
+
+        // the first client is still served.
+        first.write_all(b"isready\n").unwrap();
+        first.shutdown(Shutdown::Write).unwrap();
+        let mut received = String::new();
+        first.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "isready\n");
+        server.join().unwrap().unwrap();
+    }
+}
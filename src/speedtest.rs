@@ -0,0 +1,89 @@
+// A fixed workload that exercises each part of the engine in turn, and reports
+// how fast each part runs, for comparing hardware and as a training run for PGO builds.
+
+use std::{hint::black_box, time::Instant};
+
+use crate::{
+    board::{movegen::MoveList, Board},
+    definitions::Depth,
+    perft,
+    searchinfo::SearchInfo,
+    uci::OutputFormat,
+};
+
+/// A mix of opening, middlegame and endgame positions.
+const POSITIONS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+const MOVEGEN_REPEATS: u64 = 200_000;
+const EVAL_REPEATS: u64 = 200_000;
+const PERFT_DEPTH: usize = 4;
+const SEARCH_DEPTH: i32 = 10;
+
+/// Prints the throughput for one part of the workload.
+fn report(component: &str, work: u64, unit: &str, start: Instant) {
+    #![allow(clippy::cast_precision_loss)]
+    let seconds = start.elapsed().as_secs_f64();
+    let rate = work as f64 / seconds;
+    println!("{component:<12} {work:>10} {unit:<10} {seconds:>7.2}s {rate:>14.0} {unit}/s");
+}
+
+pub fn run() {
+    let mut pos = Board::new();
+    pos.reset_tables();
+    println!("speedtest: {} positions", POSITIONS.len());
+
+    let start = Instant::now();
+    for fen in POSITIONS {
+        pos.set_from_fen(fen).unwrap();
+        for _ in 0..MOVEGEN_REPEATS {
+            let mut move_list = MoveList::new();
+            black_box(&pos).generate_moves(&mut move_list);
+            black_box(move_list);
+        }
+    }
+    let calls = MOVEGEN_REPEATS * POSITIONS.len() as u64;
+    report("movegen", calls, "calls", start);
+
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in POSITIONS {
+        pos.set_from_fen(fen).unwrap();
+        nodes += perft::perft(&mut pos, PERFT_DEPTH);
+    }
+    report("make/unmake", nodes, "nodes", start);
+
+    let start = Instant::now();
+    let mut total = 0;
+    for fen in POSITIONS {
+        pos.set_from_fen(fen).unwrap();
+        for _ in 0..EVAL_REPEATS {
+            total += black_box(&pos).evaluate().raw();
+        }
+    }
+    black_box(total);
+    let calls = EVAL_REPEATS * POSITIONS.len() as u64;
+    report("evaluation", calls, "calls", start);
+
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in POSITIONS {
+        let mut info = SearchInfo {
+            depth: Depth::new(SEARCH_DEPTH),
+            output_format: OutputFormat::Silent,
+            ..SearchInfo::default()
+        };
+        pos.set_from_fen(fen).unwrap();
+        // each position is searched from scratch, so that every run searches the same trees.
+        pos.clear_tt();
+        pos.reset_tables();
+        pos.search_position(&mut info);
+        nodes += info.nodes;
+    }
+    report("search", nodes, "nodes", start);
+}
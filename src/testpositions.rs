@@ -0,0 +1,125 @@
+// A curated set of positions, each with a name, so that bug reports and
+// benchmarks can refer to exactly the same position with `position test NAME`.
+
+/// A named position, with a note on what it's good for.
+pub struct TestPosition {
+    pub name: &'static str,
+    pub fen: &'static str,
+    pub description: &'static str,
+}
+
+pub const TEST_POSITIONS: [TestPosition; 14] = [
+    TestPosition {
+        name: "startpos",
+        fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        description: "the starting position",
+    },
+    TestPosition {
+        name: "kiwipete",
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        description: "perft position 2: castling, en passant, pins and promotions",
+    },
+    TestPosition {
+        name: "perft3",
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        description: "perft position 3: en passant discovered checks in a rook endgame",
+    },
+    TestPosition {
+        name: "perft4",
+        fen: "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        description: "perft position 4: promotions, and castling through attacked squares",
+    },
+    TestPosition {
+        name: "perft5",
+        fen: "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        description: "perft position 5: promotions with capture",
+    },
+    TestPosition {
+        name: "perft6",
+        fen: "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        description: "perft position 6: a symmetrical middlegame",
+    },
+    TestPosition {
+        name: "promotions",
+        fen: "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        description: "promotions and underpromotions with and without capture, for both sides",
+    },
+    TestPosition {
+        name: "maxmoves",
+        fen: "R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1",
+        description: "218 legal moves, the most known in a legal position",
+    },
+    TestPosition {
+        name: "wac1",
+        fen: "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PP3PPP/R4RK1 w - - 0 1",
+        description: "Win at Chess #1: Qg6 wins",
+    },
+    TestPosition {
+        name: "colle",
+        fen: "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1",
+        description: "a quiet, closed middlegame with no tactics",
+    },
+    TestPosition {
+        name: "lasker",
+        fen: "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1",
+        description: "Lasker-Reichhelm: Kb1 wins, which only a working TT finds quickly",
+    },
+    TestPosition {
+        name: "zugzwang",
+        fen: "8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1",
+        description: "Rf1 wins by zugzwang, which null move pruning can miss",
+    },
+    TestPosition {
+        name: "stalemate",
+        fen: "k7/8/1Q6/8/8/8/8/7K b - - 0 1",
+        description: "the side to move has no legal moves and is not in check",
+    },
+    TestPosition {
+        name: "mated",
+        fen: "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        description: "the side to move is checkmated (fool's mate)",
+    },
+];
+
+/// Finds the test position called `name`, ignoring case.
+pub fn by_name(name: &str) -> Option<&'static TestPosition> {
+    TEST_POSITIONS
+        .iter()
+        .find(|position| position.name.eq_ignore_ascii_case(name))
+}
+
+/// Prints the name, FEN and description of every test position.
+pub fn list() {
+    for position in &TEST_POSITIONS {
+        println!("{:<10} {}", position.name, position.fen);
+        println!("{:<10} {}", "", position.description);
+    }
+}
+
+mod tests {
+    #[test]
+    fn test_positions_are_valid() {
+        use super::{by_name, TEST_POSITIONS};
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        for (i, position) in TEST_POSITIONS.iter().enumerate() {
+            let name = position.name;
+            assert!(
+                TEST_POSITIONS[..i].iter().all(|other| other.name != name),
+                "{name} is used twice"
+            );
+            let mut board = Board::from_fen(position.fen).unwrap_or_else(|e| panic!("{name}: {e}"));
+            board.reset_tables();
+            assert_eq!(board.fen(), position.fen, "{name}");
+            let moves = board.legal_move_count();
+            match name {
+                "maxmoves" => assert_eq!(moves, 218),
+                "stalemate" | "mated" => assert_eq!(moves, 0, "{name}"),
+                _ => assert!(moves > 0, "{name}"),
+            }
+        }
+        assert_eq!(by_name("KiwiPete").map(|p| p.name), Some("kiwipete"));
+        assert!(by_name("nonsense").is_none());
+    }
+}
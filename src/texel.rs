@@ -11,8 +11,12 @@ use rand::{prelude::SliceRandom, Rng};
 use rayon::prelude::*;
 
 use crate::{
-    board::{evaluation::parameters::Parameters, Board},
-    definitions::{INFINITY, WHITE},
+    board::{
+        evaluation::{parameters::Parameters, to_centipawns},
+        Board,
+    },
+    definitions::{Score, WHITE},
+    piecesquaretable::tables::enforce_constraints,
     searchinfo::SearchInfo,
 };
 
@@ -27,24 +31,53 @@ struct TrainingExample {
 }
 
 const DEFAULT_K: f64 = 0.5;
+/// The number of times the search for the best K narrows its grid, each time tenfold.
+const K_PRECISION_STEPS: usize = 4;
+/// The number of epochs without a better validation loss after which tuning stops.
+const PATIENCE: usize = 3;
+/// The number of parts the data is split into to see how much the loss varies across it.
+const FOLDS: usize = 5;
+/// The most epochs that gradient descent runs for, if the validation loss keeps improving.
+const MAX_EPOCHS: usize = 100;
+/// The number of examples used to estimate each gradient.
+const BATCH_SIZE: usize = 16_384;
+/// The size of an `AdamW` step, in evaluation units.
+const LEARNING_RATE: f64 = 0.5;
+const BETA_1: f64 = 0.9;
+const BETA_2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+/// How strongly each parameter is pulled back towards its starting value,
+/// which stops terms that rarely matter from drifting far on noise.
+const WEIGHT_DECAY: f64 = 1e-3;
+/// How far every parameter is nudged, in evaluation units, to estimate the gradient.
+const PERTURBATION: f64 = 2.0;
+/// How far a parameter can move from its starting value, beyond its own size.
+const BOUND_MARGIN: f64 = 50.0;
+/// Where the optimiser state is saved after every epoch, for `tune --resume`.
+const CHECKPOINT_PATH: &str = "params/adamw_checkpoint.txt";
+
 fn sigmoid(s: f64, k: f64) -> f64 {
     1.0 / (1.0 + 10.0f64.powf(-k * s / 400.0))
 }
 
 fn total_squared_error(data: &[TrainingExample], params: &Parameters, k: f64) -> f64 {
     let mut pos = Board::default();
-    let mut info = SearchInfo::default();
+    let mut info = SearchInfo {
+        lazy_eval: false,
+        ..SearchInfo::default()
+    };
     pos.set_eval_params(params.clone());
     data.iter()
         .map(|TrainingExample { fen, outcome }| {
             pos.set_from_fen(fen).unwrap();
-            let pov_score = Board::quiescence(&mut pos, &mut info, -INFINITY, INFINITY);
+            let pov_score =
+                Board::quiescence(&mut pos, &mut info, -Score::INFINITY, Score::INFINITY);
             let score = if pos.turn() == WHITE {
                 pov_score
             } else {
                 -pov_score
             };
-            let prediction = sigmoid(f64::from(score), k);
+            let prediction = sigmoid(f64::from(to_centipawns(score.raw())), k);
             (*outcome - prediction).powi(2)
         })
         .sum::<f64>()
@@ -53,13 +86,290 @@ fn total_squared_error(data: &[TrainingExample], params: &Parameters, k: f64) ->
 fn compute_mse(data: &[TrainingExample], params: &Parameters, k: f64) -> f64 {
     #![allow(clippy::cast_precision_loss)]
     let n: f64 = data.len() as f64;
-    let chunk_size = data.len() / num_cpus::get();
+    let chunk_size = (data.len() / num_cpus::get()).max(1);
     data.par_chunks(chunk_size)
         .map(|chunk| total_squared_error(chunk, params, k))
         .sum::<f64>()
         / n
 }
 
+/// Finds the scaling constant K that best fits the evaluations to the game results,
+/// with a grid search that narrows around the best value so far at each step.
+fn optimise_k(data: &[TrainingExample], params: &Parameters) -> f64 {
+    let mut best_k = DEFAULT_K;
+    let mut best_mse = compute_mse(data, params, best_k);
+    let (mut low, mut high, mut step): (f64, f64, f64) = (0.0, 2.0, 0.1);
+    for _ in 0..K_PRECISION_STEPS {
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let grid = (0..=((high - low) / step).round() as usize).map(|i| (i as f64).mul_add(step, low));
+        for k in grid {
+            let mse = compute_mse(data, params, k);
+            if mse < best_mse {
+                best_mse = mse;
+                best_k = k;
+            }
+        }
+        low = f64::max(best_k - step, 0.0);
+        high = best_k + step;
+        step /= 10.0;
+    }
+    best_k
+}
+
+/// The mean and standard deviation of the loss on each of `folds` parts of the data.
+/// A large deviation means that the data is too small to trust small improvements in the loss.
+fn fold_losses(data: &[TrainingExample], params: &Parameters, k: f64, folds: usize) -> (f64, f64) {
+    #![allow(clippy::cast_precision_loss)]
+    let losses = data
+        .chunks(data.len().div_ceil(folds).max(1))
+        .map(|fold| compute_mse(fold, params, k))
+        .collect::<Vec<_>>();
+    let n = losses.len() as f64;
+    let mean = losses.iter().sum::<f64>() / n;
+    let variance = losses.iter().map(|loss| (loss - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Tracks the validation loss across epochs, so that tuning can stop once
+/// it stops improving, as the training loss is then only falling by overfitting.
+struct EarlyStopping {
+    patience: usize,
+    best_loss: f64,
+    epochs_without_improvement: usize,
+}
+
+impl EarlyStopping {
+    const fn new(patience: usize) -> Self {
+        Self {
+            patience,
+            best_loss: f64::INFINITY,
+            epochs_without_improvement: 0,
+        }
+    }
+
+    /// Records the validation loss after an epoch, and returns whether it is the best so far.
+    fn record(&mut self, loss: f64) -> bool {
+        if loss < self.best_loss {
+            self.best_loss = loss;
+            self.epochs_without_improvement = 0;
+            true
+        } else {
+            self.epochs_without_improvement += 1;
+            false
+        }
+    }
+
+    const fn should_stop(&self) -> bool {
+        self.epochs_without_improvement >= self.patience
+    }
+}
+
+/// The `AdamW` optimiser, over the vectorised evaluation parameters.
+/// The weights are kept as floats, so that steps smaller than one evaluation unit add up.
+#[derive(Debug, Clone, PartialEq)]
+struct AdamW {
+    weights: Vec<f64>,
+    /// The starting values, which weight decay pulls towards rather than zero,
+    /// as shrinking piece values or tables towards nothing makes no sense.
+    anchors: Vec<f64>,
+    /// The lowest and highest value of each parameter.
+    bounds: Vec<(f64, f64)>,
+    first_moments: Vec<f64>,
+    second_moments: Vec<f64>,
+    step: i32,
+}
+
+impl AdamW {
+    /// Starts from `params`. Parameters that start at zero are padding, or never
+    /// change the evaluation, so they are held there.
+    fn new(params: &[i32]) -> Self {
+        let weights = params.iter().copied().map(f64::from).collect::<Vec<_>>();
+        let bounds = weights
+            .iter()
+            .map(|&w| {
+                if w == 0.0 {
+                    (0.0, 0.0)
+                } else {
+                    let reach = w.abs() + BOUND_MARGIN;
+                    (w - reach, w + reach)
+                }
+            })
+            .collect();
+        Self {
+            anchors: weights.clone(),
+            bounds,
+            first_moments: vec![0.0; weights.len()],
+            second_moments: vec![0.0; weights.len()],
+            weights,
+            step: 0,
+        }
+    }
+
+    /// The weights, rounded to parameters for the evaluation.
+    fn params(&self) -> Vec<i32> {
+        #![allow(clippy::cast_possible_truncation)]
+        self.weights.iter().map(|w| w.round() as i32).collect()
+    }
+
+    fn is_fixed(&self, index: usize) -> bool {
+        let (low, high) = self.bounds[index];
+        low >= high
+    }
+
+    fn update(&mut self, gradient: &[f64]) {
+        self.step += 1;
+        let first_correction = 1.0 - BETA_1.powi(self.step);
+        let second_correction = 1.0 - BETA_2.powi(self.step);
+        for (i, &g) in gradient.iter().enumerate() {
+            let m = BETA_1.mul_add(self.first_moments[i], (1.0 - BETA_1) * g);
+            let v = BETA_2.mul_add(self.second_moments[i], (1.0 - BETA_2) * g * g);
+            self.first_moments[i] = m;
+            self.second_moments[i] = v;
+            let adam_step = (m / first_correction) / ((v / second_correction).sqrt() + EPSILON);
+            let decay = WEIGHT_DECAY * (self.weights[i] - self.anchors[i]);
+            let (low, high) = self.bounds[i];
+            self.weights[i] = LEARNING_RATE
+                .mul_add(-(adam_step + decay), self.weights[i])
+                .clamp(low, high);
+        }
+    }
+
+    /// Estimates the gradient of `cost_function` from just two evaluations, by nudging
+    /// every free parameter up or down at random at once (simultaneous perturbation).
+    /// Each estimate is noisy, but costs the same however many parameters there are.
+    fn estimate_gradient<F: Fn(&[i32]) -> f64>(
+        &self,
+        rng: &mut impl Rng,
+        cost_function: F,
+    ) -> Vec<f64> {
+        #![allow(clippy::cast_possible_truncation)]
+        let directions = (0..self.weights.len())
+            .map(|i| {
+                if self.is_fixed(i) {
+                    0.0
+                } else if rng.gen() {
+                    1.0
+                } else {
+                    -1.0
+                }
+            })
+            .collect::<Vec<f64>>();
+        let nudged = |sign: f64| {
+            self.weights
+                .iter()
+                .zip(&directions)
+                .map(|(w, d)| (sign * PERTURBATION).mul_add(*d, *w).round() as i32)
+                .collect::<Vec<_>>()
+        };
+        let difference = cost_function(&nudged(1.0)) - cost_function(&nudged(-1.0));
+        directions
+            .iter()
+            .map(|d| difference / (2.0 * PERTURBATION) * d)
+            .collect()
+    }
+
+    /// Writes out the whole optimiser state, one parameter per line.
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        use std::fmt::Write;
+        let mut out = format!("step {}\n", self.step);
+        for i in 0..self.weights.len() {
+            let (low, high) = self.bounds[i];
+            writeln!(
+                out,
+                "{} {} {low} {high} {} {}",
+                self.weights[i], self.anchors[i], self.first_moments[i], self.second_moments[i]
+            )
+            .unwrap();
+        }
+        std::fs::write(path, out)
+    }
+
+    /// Reads back a state written by [`Self::save`].
+    fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {path}: {e}"))?;
+        let mut lines = text.lines();
+        let step = lines
+            .next()
+            .and_then(|line| line.strip_prefix("step "))
+            .and_then(|step| step.parse().ok())
+            .ok_or_else(|| format!("{path} doesn't start with the step count"))?;
+        let mut state = Self {
+            weights: Vec::new(),
+            anchors: Vec::new(),
+            bounds: Vec::new(),
+            first_moments: Vec::new(),
+            second_moments: Vec::new(),
+            step,
+        };
+        for (number, line) in lines.enumerate() {
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f64>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("{path}, line {}: {e}", number + 2))?;
+            let &[weight, anchor, low, high, m, v] = values.as_slice() else {
+                return Err(format!("{path}, line {}: expected six values", number + 2));
+            };
+            state.weights.push(weight);
+            state.anchors.push(anchor);
+            state.bounds.push((low, high));
+            state.first_moments.push(m);
+            state.second_moments.push(v);
+        }
+        Ok(state)
+    }
+}
+
+/// Tunes with `AdamW` on mini-batches of the training set, reporting the training
+/// and validation losses after every epoch, and saving a checkpoint to resume from.
+/// Returns the parameters with the best validation loss, and that loss.
+fn adamw_optimise<F: Fn(&[i32]) -> f64>(
+    mut optimiser: AdamW,
+    train_set: &mut [TrainingExample],
+    validation_function: F,
+    k: f64,
+) -> (Vec<i32>, f64) {
+    let mut rng = rand::thread_rng();
+    let mut early_stopping = EarlyStopping::new(PATIENCE);
+    early_stopping.record(validation_function(&optimiser.params()));
+    let mut best_params = optimiser.params();
+    for epoch in 1..=MAX_EPOCHS {
+        let epoch_start = Instant::now();
+        train_set.shuffle(&mut rng);
+        for batch in train_set.chunks(BATCH_SIZE) {
+            let gradient = optimiser.estimate_gradient(&mut rng, |pvec| {
+                compute_mse(batch, &Parameters::devectorise(pvec), k)
+            });
+            optimiser.update(&gradient);
+        }
+        let params = optimiser.params();
+        let train_err = compute_mse(train_set, &Parameters::devectorise(&params), k);
+        let validation_err = validation_function(&params);
+        println!(
+            "epoch {epoch}: training mse {train_err:.6}, validation mse {validation_err:.6}, took {:.1}s",
+            epoch_start.elapsed().as_secs_f64()
+        );
+        if let Err(e) = optimiser.save(CHECKPOINT_PATH) {
+            println!("{CONTROL_RED}couldn't save a checkpoint to {CHECKPOINT_PATH}: {e}{CONTROL_RESET}");
+        }
+        if early_stopping.record(validation_err) {
+            Parameters::save_param_vec(&params, &format!("params/adamw{epoch}.txt"));
+            best_params = params;
+        }
+        if early_stopping.should_stop() {
+            println!(
+                "{CONTROL_RED}no validation improvement for {PATIENCE} epochs, stopping.{CONTROL_RESET}"
+            );
+            break;
+        }
+    }
+    (best_params, early_stopping.best_loss)
+}
+
 fn generate_particles(
     starting_point: Vec<i32>,
     n_particles: usize,
@@ -107,7 +417,7 @@ fn initialise<F1: Fn(&[i32]) -> f64 + Sync>(
     particle_distance: i32,
     velocity_distance: i32,
 ) -> (Vec<Particle>, Vec<i32>, f64) {
-    #![allow(clippy::cast_precision_loss)]
+    #![allow(clippy::cast_precision_loss, clippy::assigning_clones)]
     // initialize the particles' positions with a uniformly distributed random vector
     println!("generating particle locations...");
     let particles = generate_particles(starting_point, n_particles, particle_distance);
@@ -128,8 +438,8 @@ fn initialise<F1: Fn(&[i32]) -> f64 + Sync>(
     let start_time = Instant::now();
     let mut best_cost = cost_function(&best_loc);
     let time_taken = start_time.elapsed().as_secs_f64();
-    println!("cost of the default parameters: {}", best_cost);
-    println!("costing the first particle took {:.1}s", time_taken);
+    println!("cost of the default parameters: {best_cost}");
+    println!("costing the first particle took {time_taken:.1}s");
     println!(
         "for {0} particles and {1} threads, it can be expected that we will need {0} * {2:.1} / {1} = ~{3:.1}s to compute an iteration.", 
         n_particles,
@@ -149,13 +459,13 @@ fn initialise<F1: Fn(&[i32]) -> f64 + Sync>(
         }
     });
     let time_taken = start_time.elapsed().as_secs_f64();
-    println!("finding the lowest-energy particle took {:.1}s", time_taken);
+    println!("finding the lowest-energy particle took {time_taken:.1}s");
     // generate the initial velocity of each particle
     println!("generating particle velocities...");
     let velocities = generate_velocities(n_params, n_particles, velocity_distance);
     particles
         .iter_mut()
-        .zip(velocities.into_iter())
+        .zip(velocities)
         .for_each(|(particle, velocity)| {
             particle.velocity = velocity;
         });
@@ -178,7 +488,9 @@ fn particle_swarm_optimise<F1: Fn(&[i32]) -> f64 + Sync, F2: Fn(&[i32]) -> f64 +
         clippy::cast_possible_truncation,
         clippy::needless_range_loop,
         clippy::too_many_arguments,
-        clippy::too_many_lines
+        clippy::too_many_lines,
+        clippy::assigning_clones,
+        clippy::significant_drop_tightening
     )]
     let n_params = starting_point.len();
     let (mut particles, mut best_loc, mut best_cost) = initialise(
@@ -194,7 +506,7 @@ fn particle_swarm_optimise<F1: Fn(&[i32]) -> f64 + Sync, F2: Fn(&[i32]) -> f64 +
     println!("optimising...");
     let mut iterations_without_progress = 0;
     for iteration in 1.. {
-        println!("iteration {}", iteration);
+        println!("iteration {iteration}");
         let it_start = Instant::now();
         let cost_before = **best_cost_mutex.lock().unwrap();
         println!("computing new velocities...");
@@ -283,15 +595,22 @@ fn particle_swarm_optimise<F1: Fn(&[i32]) -> f64 + Sync, F2: Fn(&[i32]) -> f64 +
     (best_loc, best_cost)
 }
 
-fn local_search_optimise<F1: Fn(&[i32]) -> f64 + Sync>(
+/// Nudges each parameter in turn while that lowers the loss on the training set,
+/// reporting the training and validation losses after every epoch. Returns the
+/// parameters with the best validation loss, and that loss.
+fn local_search_optimise<F1: Fn(&[i32]) -> f64 + Sync, F2: Fn(&[i32]) -> f64>(
     starting_point: &[i32],
     cost_function: F1,
+    validation_function: F2,
     step_size: i32,
 ) -> (Vec<i32>, f64) {
     let init_start_time = Instant::now();
     let n_params = starting_point.len();
     let mut best_params = starting_point.to_vec();
     let mut best_err = cost_function(&best_params);
+    let mut early_stopping = EarlyStopping::new(PATIENCE);
+    early_stopping.record(validation_function(&best_params));
+    let mut best_validated_params = best_params.clone();
     let mut improved = true;
     let mut iteration = 1;
     println!(
@@ -330,24 +649,43 @@ fn local_search_optimise<F1: Fn(&[i32]) -> f64 + Sync>(
             }
         }
         Parameters::save_param_vec(&best_params, &format!("params/localsearch{iteration}.txt"));
+        let validation_err = validation_function(&best_params);
+        println!("epoch {iteration}: training mse {best_err:.6}, validation mse {validation_err:.6}");
+        if early_stopping.record(validation_err) {
+            best_validated_params.clone_from(&best_params);
+        }
+        if early_stopping.should_stop() {
+            println!(
+                "{CONTROL_RED}no validation improvement for {PATIENCE} epochs, stopping.{CONTROL_RESET}"
+            );
+            break;
+        }
         iteration += 1;
     }
-    (best_params, best_err)
+    (best_validated_params, early_stopping.best_loss)
 }
 
-pub fn tune() {
+/// Tunes the evaluation parameters on `../texel_data.txt`. With `--resume PATH`,
+/// the optimiser carries on from a checkpoint instead of the built-in parameters.
+/// The piece tables are made symmetric between mirrored files before they are
+/// saved, unless `--asymmetric` is given.
+pub fn tune(args: &[String]) -> Result<(), String> {
     // hyperparameters
     let train = 12_000_000; // 8 million is recommended.
     let test = 100_000; // validation set.
 
-    // let n_particles = 100; // No idea what a good value is.
-    // let inertia_weight = 0.8; // the inertia of a particle
-    // let cognitive_coeff = 1.7; // how much particles get drawn towards their best known position
-    // let social_coeff = 1.7; // how much particles get drawn towards the best position of the whole swarm
-    // let particle_distance = 10; // how far away from the default parameters a particle can be
-    // let velocity_distance = 3; // how far away from the zero velocity a particle can begin
+    let mut resume = None;
+    let mut mirror_files = true;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--resume" => resume = Some(args.next().ok_or("no path given after --resume")?),
+            "--asymmetric" => mirror_files = false,
+            _ => return Err("usage: tune [--resume CHECKPOINT] [--asymmetric]".to_string()),
+        }
+    }
 
-    let data = File::open("../texel_data.txt").unwrap();
+    let data = File::open("../texel_data.txt").map_err(|e| format!("couldn't open ../texel_data.txt: {e}"))?;
 
     println!("Parsing tuning data...");
     let start_time = Instant::now();
@@ -380,31 +718,115 @@ pub fn tune() {
 
     assert!(train + test <= data.len(), "not enough data for training and testing, requested train = {train}, test = {test}, but data has {} examples", data.len());
     data.truncate(train + test);
-    let (train_set, _test_set) = data.split_at(train);
+    let (train_set, test_set) = data.split_at_mut(train);
 
-    let params = Parameters::default();
+    let optimiser = match resume {
+        Some(path) => AdamW::load(path)?,
+        None => AdamW::new(&Parameters::default().vectorise()),
+    };
+    let params = Parameters::devectorise(&optimiser.params());
+
+    println!("Optimising K...");
+    let start_time = Instant::now();
+    let k = optimise_k(train_set, &params);
+    println!("K = {k:.4}, found in {:.1}s", start_time.elapsed().as_secs_f32());
+    let (mean, deviation) = fold_losses(train_set, &params, k, FOLDS);
+    println!("Initial loss over {FOLDS} folds: {mean:.6} ± {deviation:.6}");
 
     println!("Optimising...");
     let start_time = Instant::now();
-    // let (best_params, best_loss) = particle_swarm_optimise(
-    //     params.vectorise(),
-    //     |pvec| compute_mse(&train_set, &Parameters::devectorise(pvec), DEFAULT_K),
-    //     |pvec| compute_mse(&test_set, &Parameters::devectorise(pvec), DEFAULT_K),
-    //     inertia_weight,
-    //     cognitive_coeff,
-    //     social_coeff,
-    //     n_particles,
-    //     particle_distance,
-    //     velocity_distance,
-    // );
-    let (best_params, best_loss) = local_search_optimise(
-        &params.vectorise(),
-        |pvec| compute_mse(train_set, &Parameters::devectorise(pvec), DEFAULT_K),
-        1,
+    let (best_params, best_loss) = adamw_optimise(
+        optimiser,
+        train_set,
+        |pvec| compute_mse(test_set, &Parameters::devectorise(pvec), k),
+        k,
     );
     println!("Optimised in {:.1}s", start_time.elapsed().as_secs_f32());
 
-    println!("Best loss: {best_loss:.6}");
+    println!("Best validation loss: {best_loss:.6}");
+    let (mean, deviation) = fold_losses(train_set, &Parameters::devectorise(&best_params), k, FOLDS);
+    println!("Final loss over {FOLDS} folds: {mean:.6} ± {deviation:.6}");
     println!("Saving best parameters...");
-    Parameters::save_param_vec(&best_params, "params/localsearchfinal.txt");
+    let mut best_params = Parameters::devectorise(&best_params);
+    enforce_constraints(&mut best_params.piece_square_tables, mirror_files);
+    Parameters::save_param_vec(&best_params.vectorise(), "params/adamwfinal.txt");
+    Ok(())
+}
+
+mod tests {
+    #[test]
+    fn early_stopping_waits_for_patience() {
+        use super::EarlyStopping;
+
+        let mut early_stopping = EarlyStopping::new(2);
+        assert!(early_stopping.record(0.10));
+        assert!(!early_stopping.record(0.11));
+        assert!(!early_stopping.should_stop());
+        // a new best resets the count.
+        assert!(early_stopping.record(0.09));
+        assert!(!early_stopping.record(0.09));
+        assert!(!early_stopping.record(0.12));
+        assert!(early_stopping.should_stop());
+        assert!((early_stopping.best_loss - 0.09).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn k_fits_the_results() {
+        use super::{compute_mse, optimise_k, TrainingExample, DEFAULT_K};
+        use crate::board::evaluation::parameters::Parameters;
+
+        crate::magic::initialise();
+        // white is a rook up, and wins three games out of four.
+        let data = [1.0, 0.0, 1.0, 1.0].map(|outcome| TrainingExample {
+            fen: "4k3/8/8/8/8/8/8/3RK3 w - - 0 1".to_string(),
+            outcome,
+        });
+        let params = Parameters::default();
+        let k = optimise_k(&data, &params);
+        let mse = |k| compute_mse(&data, &params, k);
+        assert!(mse(k) < mse(DEFAULT_K));
+        assert!(mse(k) <= mse(k - 0.001) && mse(k) <= mse(k + 0.001), "K = {k}");
+    }
+
+    #[test]
+    fn adamw_descends_within_bounds() {
+        use super::AdamW;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        // a bowl with its lowest point at (30, 30, 100), but the second parameter
+        // is held at zero, and the third can go no higher than -1 + 1 + BOUND_MARGIN.
+        let cost = |pvec: &[i32]| {
+            pvec.iter()
+                .zip([30, 30, 100])
+                .map(|(&x, target)| f64::from(x - target).powi(2))
+                .sum::<f64>()
+        };
+        let mut optimiser = AdamW::new(&[10, 0, -1]);
+        let start = cost(&optimiser.params());
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..2000 {
+            let gradient = optimiser.estimate_gradient(&mut rng, cost);
+            optimiser.update(&gradient);
+        }
+        let params = optimiser.params();
+        assert!(cost(&params) < start);
+        // the gradient estimates are noisy, so the first parameter only gets close.
+        assert!((params[0] - 30).abs() <= 10, "{params:?}");
+        assert_eq!(params[1], 0);
+        assert_eq!(params[2], 50);
+    }
+
+    #[test]
+    fn adamw_checkpoints_round_trip() {
+        use super::AdamW;
+
+        let mut optimiser = AdamW::new(&[10, 0, -7, 300]);
+        optimiser.update(&[0.5, 0.0, -0.25, 1e-9]);
+        let path = std::env::temp_dir().join(format!("virtue-adamw-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        optimiser.save(path).unwrap();
+        let loaded = AdamW::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, Ok(optimiser));
+    }
 }
@@ -5,9 +5,8 @@
 )]
 
 use crate::{
-    board::evaluation::IS_MATE_SCORE,
     chessmove::Move,
-    definitions::{CompactDepthStorage, Depth, INFINITY, MAX_DEPTH},
+    definitions::{CompactDepthStorage, Depth, Score, MAX_DEPTH, ZERO_PLY},
     macros,
 };
 
@@ -23,7 +22,7 @@ pub enum HFlag {
 pub struct TTEntry {
     pub key: u64,
     pub m: Move,
-    pub score: i32,
+    pub score: Score,
     pub depth: CompactDepthStorage,
     pub flag: HFlag,
 }
@@ -32,7 +31,7 @@ impl TTEntry {
     pub const NULL: Self = Self {
         key: 0,
         m: Move::NULL,
-        score: 0,
+        score: Score::DRAW,
         depth: CompactDepthStorage::NULL,
         flag: HFlag::None,
     };
@@ -51,54 +50,166 @@ impl Bucket {
     };
 }
 
-const TASTY_PRIME_NUMBER: usize = 12_582_917;
-
-const MEGABYTE: usize = 1024 * 1024;
+pub const MEGABYTE: usize = 1024 * 1024;
 const TT_ENTRY_SIZE: usize = std::mem::size_of::<Bucket>();
 
+/// The largest power of two that is no greater than `n`, which must be nonzero.
+const fn floor_power_of_two(n: usize) -> usize {
+    1 << (usize::BITS - 1 - n.leading_zeros())
+}
+
 /// One option is to use 4MB of memory for the hashtable,
 /// as my i5 has 6mb of L3 cache, so this endeavours to keep the
 /// entire hashtable in L3 cache.
-pub const IN_CACHE_TABLE_SIZE: usize = MEGABYTE * 4 / TT_ENTRY_SIZE;
+pub const IN_CACHE_TABLE_SIZE: usize = floor_power_of_two(MEGABYTE * 4 / TT_ENTRY_SIZE);
 /// Another option is just to use a ton of memory,
 /// wahoooooooo
-pub const BIG_TABLE_SIZE: usize = MEGABYTE * 4096 / TT_ENTRY_SIZE;
+pub const BIG_TABLE_SIZE: usize = floor_power_of_two(MEGABYTE * 4096 / TT_ENTRY_SIZE);
 /// Middle-ground between the two.
-pub const MEDIUM_TABLE_SIZE: usize = MEGABYTE * 512 / TT_ENTRY_SIZE;
-/// Prime sized table that's around 256-512 megabytes.
-pub const PRIME_TABLE_SIZE: usize = TASTY_PRIME_NUMBER;
+pub const MEDIUM_TABLE_SIZE: usize = floor_power_of_two(MEGABYTE * 512 / TT_ENTRY_SIZE);
 
-pub const DEFAULT_TABLE_SIZE: usize = PRIME_TABLE_SIZE;
+/// The number of buckets the table has until the Hash option resizes it.
+pub const DEFAULT_TABLE_SIZE: usize = MEDIUM_TABLE_SIZE;
+/// The memory the default table takes up, rounded down to the megabyte.
+pub const DEFAULT_TABLE_MEGABYTES: usize = DEFAULT_TABLE_SIZE * TT_ENTRY_SIZE / MEGABYTE;
 
 #[derive(Debug)]
 pub struct TranspositionTable<const SIZE: usize> {
     table: Vec<Bucket>,
+    /// The number of buckets to allocate when the table is first used.
+    /// This is `SIZE` until the table is resized.
+    buckets: usize,
+    /// Whether to ask for the table to be backed by huge pages.
+    large_pages: bool,
 }
 
 pub type DefaultTT = TranspositionTable<DEFAULT_TABLE_SIZE>;
 
+/// The size of the huge pages that `advise_huge_pages` asks for.
+const HUGE_PAGE_SIZE: usize = 2 * MEGABYTE;
+
+/// Asks the kernel to back the memory reserved for `table` with transparent huge
+/// pages, which only works for the parts aligned to a huge page. This has to be
+/// done before the memory is first written, as that maps it with small pages.
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(table: &mut Vec<Bucket>) {
+    let start = table.as_mut_ptr().cast::<u8>();
+    let end = start.addr() + table.capacity() * TT_ENTRY_SIZE;
+    let offset = start.addr().next_multiple_of(HUGE_PAGE_SIZE) - start.addr();
+    let length = (end - start.addr()).saturating_sub(offset) / HUGE_PAGE_SIZE * HUGE_PAGE_SIZE;
+    if length > 0 {
+        // SAFETY: the range lies within the table's allocation, and the advice
+        // doesn't change its contents. If the kernel can't give huge pages,
+        // this fails, and the table just uses small pages.
+        unsafe {
+            libc::madvise(start.wrapping_add(offset).cast(), length, libc::MADV_HUGEPAGE);
+        }
+    }
+}
+
+/// Huge pages are only asked for on Linux, so elsewhere the table uses small pages.
+#[cfg(not(target_os = "linux"))]
+const fn advise_huge_pages(_: &mut Vec<Bucket>) {}
+
 pub enum ProbeResult {
-    Cutoff(i32),
+    Cutoff(Score),
     BestMove(Move),
     Nothing,
 }
 
+/// The contents of a TT entry, with the score adjusted for the
+/// current ply, as needed when testing whether a move is singular.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TTHit {
+    pub m: Move,
+    pub depth: Depth,
+    pub bound: HFlag,
+    pub value: Score,
+}
+
 impl<const SIZE: usize> TranspositionTable<SIZE> {
     pub const fn new() -> Self {
-        Self { table: Vec::new() }
+        Self {
+            table: Vec::new(),
+            buckets: floor_power_of_two(SIZE),
+            large_pages: false,
+        }
+    }
+
+    /// Allocates `buckets` empty buckets, or returns `None` if there isn't enough memory.
+    fn allocate(&self, buckets: usize) -> Option<Vec<Bucket>> {
+        let mut table = Vec::new();
+        table.try_reserve_exact(buckets).ok()?;
+        if self.large_pages {
+            advise_huge_pages(&mut table);
+        }
+        table.resize(buckets, Bucket::NULL);
+        Some(table)
+    }
+
+    /// Sets whether the table is backed by 2MB huge pages, where the OS supports them.
+    /// They make probing a big table faster, as fewer pages means fewer TLB misses.
+    /// A table that has already been allocated is allocated again, and so emptied.
+    pub fn set_large_pages(&mut self, large_pages: bool) {
+        self.large_pages = large_pages;
+        if !self.table.is_empty() {
+            self.table = Vec::new();
+            self.clear();
+        }
     }
 
     pub fn clear(&mut self) {
         if self.table.is_empty() {
-            self.table.resize(SIZE, Bucket::NULL);
+            self.table = self
+                .allocate(self.buckets)
+                .expect("couldn't allocate the transposition table");
         } else {
             self.table.fill(Bucket::NULL);
         }
     }
 
+    /// Replaces the table with an empty one of as many buckets as fit in `megabytes`
+    /// of memory. The multiply-shift indexing needs no particular number of them.
+    /// If that much can't be allocated, the largest power of two megabytes that
+    /// can be is used instead. Returns the number of megabytes used, which is
+    /// zero if not even one megabyte was available.
+    pub fn resize(&mut self, megabytes: usize) -> usize {
+        // free the old table first, so that its memory can be reused.
+        self.table = Vec::new();
+        let mut megabytes = megabytes.max(1);
+        loop {
+            let buckets = megabytes
+                .checked_mul(MEGABYTE)
+                .map(|bytes| bytes / TT_ENTRY_SIZE);
+            if let Some((buckets, table)) =
+                buckets.and_then(|n| self.allocate(n).map(|table| (n, table)))
+            {
+                self.table = table;
+                self.buckets = buckets;
+                return megabytes;
+            }
+            if megabytes == 1 {
+                self.buckets = 0;
+                return 0;
+            }
+            megabytes = floor_power_of_two(megabytes - 1);
+        }
+    }
+
+    /// The bucket a key hashes to. This takes the high bits of the key
+    /// with a multiply-shift, which avoids a division on every access.
+    fn index(&self, key: u64) -> usize {
+        ((u128::from(key) * self.table.len() as u128) >> 64) as usize
+    }
+
+    /// The memory allocated for the table, in bytes.
+    pub const fn size_in_bytes(&self) -> usize {
+        self.table.len() * std::mem::size_of::<Bucket>()
+    }
+
     pub fn clear_for_search(&mut self) {
         if self.table.is_empty() {
-            self.table.resize(SIZE, Bucket::NULL);
+            self.clear();
         } else {
             // do nothing.
         }
@@ -109,22 +220,23 @@ impl<const SIZE: usize> TranspositionTable<SIZE> {
         key: u64,
         ply: usize,
         best_move: Move,
-        score: i32,
+        score: Score,
         flag: HFlag,
         depth: Depth,
     ) {
-        let index = (key % SIZE as u64) as usize;
+        // the table is allocated lazily, and the tuner runs quiescence
+        // search on boards that never allocate it.
+        if self.table.is_empty() {
+            return;
+        }
 
-        debug_assert!((0i32.into()..=MAX_DEPTH).contains(&depth), "depth: {depth}");
-        debug_assert!(score >= -INFINITY);
+        let index = self.index(key);
+
+        debug_assert!((ZERO_PLY..=MAX_DEPTH).contains(&depth), "depth: {depth}");
+        debug_assert!(score >= -Score::INFINITY);
         debug_assert!((0..=MAX_DEPTH.ply_to_horizon()).contains(&ply));
 
-        let mut score = score;
-        if score > IS_MATE_SCORE {
-            score += ply as i32;
-        } else if score < -IS_MATE_SCORE {
-            score -= ply as i32;
-        }
+        let score = score.to_tt(ply);
 
         let slot = &mut self.table[index];
 
@@ -132,7 +244,7 @@ impl<const SIZE: usize> TranspositionTable<SIZE> {
             key,
             m: best_move,
             score,
-            depth: depth.try_into().unwrap(),
+            depth: depth.into(),
             flag,
         };
 
@@ -144,19 +256,23 @@ impl<const SIZE: usize> TranspositionTable<SIZE> {
     }
 
     pub fn probe(
-        &mut self,
+        &self,
         key: u64,
         ply: usize,
-        alpha: i32,
-        beta: i32,
+        alpha: Score,
+        beta: Score,
         depth: Depth,
     ) -> ProbeResult {
-        let index = (key % (SIZE as u64)) as usize;
+        if self.table.is_empty() {
+            return ProbeResult::Nothing;
+        }
+
+        let index = self.index(key);
 
-        debug_assert!((0i32.into()..=MAX_DEPTH).contains(&depth), "depth: {depth}");
+        debug_assert!((ZERO_PLY..=MAX_DEPTH).contains(&depth), "depth: {depth}");
         debug_assert!(alpha < beta);
-        debug_assert!(alpha >= -INFINITY);
-        debug_assert!(beta >= -INFINITY);
+        debug_assert!(alpha >= -Score::INFINITY);
+        debug_assert!(beta >= -Score::INFINITY);
         debug_assert!((0..=MAX_DEPTH.ply_to_horizon()).contains(&ply));
 
         let slot = &self.table[index];
@@ -168,18 +284,13 @@ impl<const SIZE: usize> TranspositionTable<SIZE> {
             let m = entry.m;
             let e_depth = entry.depth.into();
             if e_depth >= depth {
-                debug_assert!((0i32.into()..=MAX_DEPTH).contains(&e_depth), "depth: {}", e_depth);
+                debug_assert!((ZERO_PLY..=MAX_DEPTH).contains(&e_depth), "depth: {e_depth}");
 
                 // we can't store the score in a tagged union,
                 // because we need to do mate score preprocessing.
-                let mut score = entry.score;
-                if score > IS_MATE_SCORE {
-                    score -= ply as i32;
-                } else if score < -IS_MATE_SCORE {
-                    score += ply as i32;
-                }
+                let score = entry.score.from_tt(ply);
 
-                debug_assert!(score >= -INFINITY);
+                debug_assert!(score >= -Score::INFINITY);
                 match entry.flag {
                     HFlag::None => unsafe { macros::inconceivable!() },
                     HFlag::Alpha => {
@@ -202,4 +313,167 @@ impl<const SIZE: usize> TranspositionTable<SIZE> {
 
         ProbeResult::Nothing
     }
+
+    pub fn probe_for_singularity(&self, key: u64, ply: usize) -> Option<TTHit> {
+        if self.table.is_empty() {
+            return None;
+        }
+
+        let index = self.index(key);
+
+        let slot = &self.table[index];
+        let entry = if slot.depth_preferred.key == key {
+            &slot.depth_preferred
+        } else if slot.always_replace.key == key {
+            &slot.always_replace
+        } else {
+            return None;
+        };
+
+        Some(TTHit {
+            m: entry.m,
+            depth: entry.depth.into(),
+            bound: entry.flag,
+            value: entry.score.from_tt(ply),
+        })
+    }
+}
+
+mod tests {
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_node() {
+        use super::{HFlag, ProbeResult, TranspositionTable};
+        use crate::{
+            chessmove::Move,
+            definitions::{Depth, Score, MAX_DEPTH},
+        };
+
+        let max_ply = MAX_DEPTH.ply_to_horizon();
+        let mut tt = TranspositionTable::<1024>::new();
+        tt.clear();
+        let depth = Depth::new(5);
+        // (ply the entry is stored at, plies from the root to the mate)
+        for (key, (store_ply, mate_ply)) in (1..).zip([
+            (0, 1),
+            (3, 7),
+            (10, 10),
+            (20, 63),
+            (0, max_ply),
+            (max_ply, max_ply),
+        ]) {
+            for sign in [1, -1] {
+                let in_plies = |ply| {
+                    if sign > 0 {
+                        Score::mate_in(ply)
+                    } else {
+                        Score::mated_in(ply)
+                    }
+                };
+                let score = in_plies(mate_ply);
+                assert!(score.is_mate());
+                tt.store(key, store_ply, Move::NULL, score, HFlag::Exact, depth);
+                for probe_ply in [0, store_ply, mate_ply, max_ply] {
+                    // the mate is as far from the node as it was when stored.
+                    let expected = in_plies(mate_ply - store_ply + probe_ply);
+                    let hit = tt.probe_for_singularity(key, probe_ply).unwrap();
+                    assert_eq!(
+                        hit.value, expected,
+                        "stored at {store_ply}, probed at {probe_ply}"
+                    );
+                    let ProbeResult::Cutoff(cutoff) =
+                        tt.probe(key, probe_ply, -Score::MATE, Score::MATE, depth)
+                    else {
+                        panic!("exact hit did not cut off");
+                    };
+                    assert_eq!(cutoff, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mate_bounds_cut_off_at_the_adjusted_score() {
+        use super::{HFlag, ProbeResult, TranspositionTable};
+        use crate::{
+            chessmove::Move,
+            definitions::{Depth, Score},
+        };
+
+        let mut tt = TranspositionTable::<1024>::new();
+        tt.clear();
+        let depth = Depth::new(5);
+
+        // a lower bound of mate in five plies from the root, found at ply 2.
+        tt.store(1, 2, Move::NULL, Score::mate_in(5), HFlag::Beta, depth);
+        // from the root, that is mate in seven, which fails high over mate in eight...
+        let beta = Score::mate_in(8);
+        assert!(
+            matches!(tt.probe(1, 4, beta - 1, beta, depth), ProbeResult::Cutoff(b) if b == beta)
+        );
+        // ...but not over mate in six.
+        let beta = Score::mate_in(6);
+        assert!(matches!(
+            tt.probe(1, 4, beta - 1, beta, depth),
+            ProbeResult::BestMove(_)
+        ));
+
+        // an upper bound of being mated in five plies from the root, found at ply 2.
+        tt.store(2, 2, Move::NULL, Score::mated_in(5), HFlag::Alpha, depth);
+        let alpha = Score::mated_in(8);
+        assert!(
+            matches!(tt.probe(2, 4, alpha, alpha + 1, depth), ProbeResult::Cutoff(a) if a == alpha)
+        );
+        let alpha = Score::mated_in(6);
+        assert!(matches!(
+            tt.probe(2, 4, alpha, alpha + 1, depth),
+            ProbeResult::BestMove(_)
+        ));
+
+        // infinite bounds are not mate scores, and are not shifted.
+        for score in [Score::INFINITY, -Score::INFINITY] {
+            tt.store(3, 10, Move::NULL, score, HFlag::Alpha, depth);
+            assert_eq!(tt.probe_for_singularity(3, 0).unwrap().value, score);
+            assert_eq!(tt.probe_for_singularity(3, 20).unwrap().value, score);
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_the_table_usable() {
+        use super::{HFlag, TranspositionTable, MEGABYTE, TT_ENTRY_SIZE};
+        use crate::{
+            chessmove::Move,
+            definitions::{Depth, Score},
+        };
+
+        let mut tt = TranspositionTable::<1024>::new();
+        assert_eq!(tt.resize(0), 1);
+        assert_eq!(tt.resize(1), 1);
+        assert_eq!(tt.resize(3), 3);
+        tt.clear();
+        assert_eq!(tt.size_in_bytes(), 3 * MEGABYTE / TT_ENTRY_SIZE * TT_ENTRY_SIZE);
+        let key = u64::MAX;
+        tt.store(key, 0, Move::NULL, Score::DRAW, HFlag::Exact, Depth::new(3));
+        assert_eq!(tt.probe_for_singularity(key, 0).unwrap().value, Score::DRAW);
+    }
+
+    #[test]
+    fn large_pages_keep_the_table_usable() {
+        use super::{HFlag, TranspositionTable, MEGABYTE, TT_ENTRY_SIZE};
+        use crate::{
+            chessmove::Move,
+            definitions::{Depth, Score},
+        };
+
+        let mut tt = TranspositionTable::<1024>::new();
+        tt.set_large_pages(true);
+        assert_eq!(tt.resize(8), 8);
+        assert_eq!(tt.size_in_bytes(), 8 * MEGABYTE / TT_ENTRY_SIZE * TT_ENTRY_SIZE);
+        let key = u64::MAX / 3;
+        tt.store(key, 0, Move::NULL, Score::DRAW, HFlag::Exact, Depth::new(3));
+        assert_eq!(tt.probe_for_singularity(key, 0).unwrap().value, Score::DRAW);
+        // switching them off allocates the table again, which empties it.
+        tt.set_large_pages(false);
+        assert_eq!(tt.size_in_bytes(), 8 * MEGABYTE / TT_ENTRY_SIZE * TT_ENTRY_SIZE);
+        assert!(tt.probe_for_singularity(key, 0).is_none());
+    }
 }
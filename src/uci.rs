@@ -1,26 +1,32 @@
 use std::{
     fmt::Display,
-    io::Write,
+    io::{self, BufRead, Write},
     num::{ParseFloatError, ParseIntError},
+    str::ParseBoolError,
     sync::{
         atomic::{self, AtomicBool},
-        mpsc,
+        mpsc, Arc, Condvar, Mutex, MutexGuard,
     },
 };
 
 use crate::{
     board::{
-        evaluation::{is_mate_score, parameters::Parameters, MATE_SCORE},
+        evaluation::{parameters::Parameters, to_centipawns},
         Board,
     },
-    definitions::{BLACK, MAX_DEPTH, WHITE},
+    chessmove::Move,
+    definitions::{Depth, Score, BLACK, MAX_DEPTH, WHITE},
     errors::{FenParseError, MoveParseError},
-    search,
-    searchinfo::SearchInfo,
-    NAME,
+    historytable::HeatmapFormat,
+    macros, search,
+    searchinfo::{MoveQuality, RootMove, SearchControl, SearchInfo, Verdict},
+    testpositions, NAME,
 };
 
-enum UciError {
+mod crash;
+pub mod options;
+
+pub enum UciError {
     ParseGo(String),
     ParseOption(String),
     ParseFen(FenParseError),
@@ -48,6 +54,12 @@ impl From<ParseFloatError> for UciError {
     }
 }
 
+impl From<ParseBoolError> for UciError {
+    fn from(pbe: ParseBoolError) -> Self {
+        Self::ParseOption(pbe.to_string())
+    }
+}
+
 impl From<ParseIntError> for UciError {
     fn from(pie: ParseIntError) -> Self {
         Self::ParseOption(pie.to_string())
@@ -57,21 +69,22 @@ impl From<ParseIntError> for UciError {
 impl Display for UciError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            UciError::ParseGo(s) => write!(f, "ParseGo: {}", s),
-            UciError::ParseOption(s) => write!(f, "ParseOption: {}", s),
-            UciError::ParseFen(s) => write!(f, "ParseFen: {}", s),
-            UciError::ParseMove(s) => write!(f, "ParseMove: {}", s),
-            UciError::UnexpectedCommandTermination(s) => {
-                write!(f, "UnexpectedCommandTermination: {}", s)
+            Self::ParseGo(s) => write!(f, "ParseGo: {s}"),
+            Self::ParseOption(s) => write!(f, "ParseOption: {s}"),
+            Self::ParseFen(s) => write!(f, "ParseFen: {s}"),
+            Self::ParseMove(s) => write!(f, "ParseMove: {s}"),
+            Self::UnexpectedCommandTermination(s) => {
+                write!(f, "UnexpectedCommandTermination: {s}")
             }
-            UciError::InvalidFormat(s) => write!(f, "InvalidFormat: {}", s),
-            UciError::UnknownCommand(s) => write!(f, "UnknownCommand: {}", s),
+            Self::InvalidFormat(s) => write!(f, "InvalidFormat: {s}"),
+            Self::UnknownCommand(s) => write!(f, "UnknownCommand: {s}"),
         }
     }
 }
 
 // position fen
 // position startpos
+// position test kiwipete
 // ... moves e2e4 e7e5 b7b8q
 fn parse_position(text: &str, pos: &mut Board) -> Result<(), UciError> {
     let mut parts = text.split_ascii_whitespace();
@@ -84,6 +97,7 @@ fn parse_position(text: &str, pos: &mut Board) -> Result<(), UciError> {
     let determiner = parts.next().ok_or_else(|| {
         UciError::UnexpectedCommandTermination("No determiner after \"position\"".into())
     })?;
+    let previous_key = pos.hashkey();
     if determiner == "startpos" {
         pos.set_startpos();
         let moves = parts.next(); // skip "moves"
@@ -92,6 +106,24 @@ fn parse_position(text: &str, pos: &mut Board) -> Result<(), UciError> {
                 "Expected either \"moves\" or no content to follow \"startpos\".".into(),
             ));
         }
+    } else if determiner == "test" {
+        let name = parts.next().ok_or_else(|| {
+            UciError::UnexpectedCommandTermination("No name after \"position test\"".into())
+        })?;
+        let position = testpositions::by_name(name).ok_or_else(|| {
+            let names = testpositions::TEST_POSITIONS.map(|position| position.name);
+            UciError::InvalidFormat(format!(
+                "Unknown test position \"{name}\", expected one of: {}",
+                names.join(", ")
+            ))
+        })?;
+        pos.set_from_fen(position.fen)?;
+        let moves = parts.next();
+        if !(matches!(moves, Some("moves") | None)) {
+            return Err(UciError::InvalidFormat(
+                "Expected either \"moves\" or no content to follow the test position name.".into(),
+            ));
+        }
     } else {
         if determiner != "fen" {
             return Err(UciError::InvalidFormat(format!(
@@ -108,24 +140,32 @@ fn parse_position(text: &str, pos: &mut Board) -> Result<(), UciError> {
         }
         pos.set_from_fen(&fen)?;
     }
-    for san in parts {
-        pos.zero_height(); // stuff breaks really hard without this lmao
-        let m = pos.parse_san(san)?;
-        pos.make_move(m);
-    }
-    pos.zero_height();
+    pos.zero_height(); // stuff breaks really hard without this lmao
+    pos.make_uci_moves(&parts.collect::<Vec<_>>().join(" "))?;
+    pos.detect_game_continuation(previous_key);
     // eprintln!("{}", pos);
     Ok(())
 }
 
-fn parse_go(text: &str, info: &mut SearchInfo, pos: &mut Board) -> Result<(), UciError> {
+/// In deterministic mode, time limits are converted to node limits at this rate.
+const DETERMINISTIC_NODES_PER_MS: u64 = 1000;
+/// The depth `blundercheck` searches to, unless it's given one.
+const BLUNDER_CHECK_DEPTH: i32 = 10;
+/// The nice value that background analysis runs at, on Linux.
+#[cfg(target_os = "linux")]
+const ANALYSIS_NICENESS: i32 = 10;
+
+fn parse_go(text: &str, info: &mut SearchInfo, pos: &Board) -> Result<(), UciError> {
     #![allow(clippy::too_many_lines)]
     let mut depth: Option<i32> = None;
+    let mut nodes: Option<u64> = None;
     let mut moves_to_go: Option<u64> = None;
     let mut movetime: Option<u64> = None;
     let mut time: Option<u64> = None;
     let mut inc: Option<u64> = None;
+    let mut mate: Option<usize> = None;
     info.time_set = false;
+    info.pondering = false;
 
     let mut parts = text.split_ascii_whitespace();
     let command = parts
@@ -138,6 +178,8 @@ fn parse_go(text: &str, info: &mut SearchInfo, pos: &mut Board) -> Result<(), Uc
     while let Some(part) = parts.next() {
         match part {
             "depth" => depth = Some(part_parse("depth", parts.next())?),
+            "nodes" => nodes = Some(part_parse("nodes", parts.next())?),
+            "mate" => mate = Some(part_parse("mate", parts.next())?),
             "movestogo" => moves_to_go = Some(part_parse("movestogo", parts.next())?),
             "movetime" => movetime = Some(part_parse("movetime", parts.next())?),
             "wtime" if pos.turn() == WHITE => time = Some(part_parse("wtime", parts.next())?),
@@ -145,6 +187,7 @@ fn parse_go(text: &str, info: &mut SearchInfo, pos: &mut Board) -> Result<(), Uc
             "winc" if pos.turn() == WHITE => inc = Some(part_parse("winc", parts.next())?),
             "binc" if pos.turn() == BLACK => inc = Some(part_parse("binc", parts.next())?),
             "infinite" => info.infinite = true,
+            "ponder" => info.pondering = true,
             _ => (), //eprintln!("ignoring term in parse_go: {}", part),
         }
     }
@@ -155,22 +198,19 @@ fn parse_go(text: &str, info: &mut SearchInfo, pos: &mut Board) -> Result<(), Uc
             time = Some(movetime);
             movetime
         }
-        None => match time {
-            Some(t) => {
-                info.time_set = true;
-                let time = t / moves_to_go.unwrap_or(30) + inc.unwrap_or(0);
-                let time = time.saturating_sub(30);
-                time.min(t)
-            }
-            None => {
-                info.time_set = false;
-                0
-            }
+        None => if let Some(t) = time {
+            info.time_set = true;
+            let time = t / moves_to_go.unwrap_or(30) + inc.unwrap_or(0);
+            let time = time.saturating_sub(30);
+            time.min(t)
+        } else {
+            info.time_set = false;
+            0
         },
     };
 
     let is_computed_time_window_valid =
-        !info.time_set || search_time_window <= time.unwrap() as u64;
+        !info.time_set || search_time_window <= time.unwrap();
     if !is_computed_time_window_valid {
         let time = time.unwrap();
         return Err(UciError::ParseGo(format!(
@@ -178,13 +218,31 @@ fn parse_go(text: &str, info: &mut SearchInfo, pos: &mut Board) -> Result<(), Uc
         )));
     }
 
+    // a handicapped engine thinks for a fraction of the time it's given.
+    let search_time_window = search_time_window / info.time_odds.max(1);
     info.set_time_window(search_time_window);
 
+    info.node_limit = nodes.unwrap_or(0);
+    if info.deterministic && info.time_set {
+        // searching to a node count rather than a time makes the result independent of the machine.
+        info.time_set = false;
+        let budget = search_time_window.saturating_mul(DETERMINISTIC_NODES_PER_MS).max(1);
+        info.node_limit = nodes.map_or(budget, |nodes| nodes.min(budget));
+    }
+
+    info.mate_moves = mate.filter(|&mate| mate > 0);
     if let Some(depth) = depth {
         info.depth = depth.into();
+    } else if let Some(mate) = info.mate_moves {
+        // if no mate is found, the normal search looks as far as the mate would have been.
+        let plies = i32::try_from(mate.saturating_mul(2) - 1);
+        info.depth = plies.map_or(MAX_DEPTH, Depth::new).min(MAX_DEPTH);
     } else {
         info.depth = MAX_DEPTH;
     }
+    if info.depth_limit > 0 {
+        info.depth = info.depth.min(Depth::new(info.depth_limit));
+    }
 
     // println!(
     //     "time: {}, depth: {}, timeset: {}",
@@ -212,39 +270,86 @@ where
         })
 }
 
-fn parse_setoption(text: &str, _info: &mut SearchInfo) -> Result<search::Config, UciError> {
+// history [grid|csv]
+// dumps the history tables left by the last search.
+fn parse_history(text: &str, pos: &Board) -> Result<(), UciError> {
+    let format = match text.split_whitespace().nth(1) {
+        None | Some("grid") => HeatmapFormat::Grid,
+        Some("csv") => HeatmapFormat::Csv,
+        Some(other) => {
+            return Err(UciError::InvalidFormat(format!(
+                "history format must be grid or csv, got \"{other}\""
+            )))
+        }
+    };
+    write_stdout(|out| pos.write_history_heatmaps(out, format));
+    Ok(())
+}
+
+// blundercheck [depth <n>] <fen|startpos> <move>
+// compares a move to the best move in the position, which is left on the board.
+fn parse_blundercheck(text: &str, pos: &mut Board, format: OutputFormat) -> Result<(), UciError> {
+    let mut parts = text.split_whitespace().skip(1).collect::<Vec<_>>();
+    let depth = if parts.first() == Some(&"depth") {
+        let depth = part_parse("depth", parts.get(1).copied())?;
+        parts.drain(..2);
+        depth
+    } else {
+        BLUNDER_CHECK_DEPTH
+    };
+    let (&move_text, fen) = parts.split_last().ok_or_else(|| {
+        UciError::UnexpectedCommandTermination("no position or move after blundercheck".into())
+    })?;
+    match fen {
+        [] => return Err(UciError::UnexpectedCommandTermination("no position before the move".into())),
+        ["startpos"] => pos.set_startpos(),
+        fen => pos.set_from_fen(&fen.join(" "))?,
+    }
+    let m = pos.parse_san(move_text)?;
+    let check = pos
+        .blunder_check(m, Depth::new(depth))
+        .ok_or_else(|| UciError::ParseMove(MoveParseError::IllegalMove(move_text.to_string())))?;
+    print_blunder_check(format, pos.turn(), &check);
+    Ok(())
+}
+
+// setoption name <name> value <value>
+// both the name and the value may contain spaces.
+fn parse_setoption(
+    text: &str,
+    info: &mut SearchInfo,
+    config: &mut search::Config,
+    file_options: bool,
+) -> Result<(), UciError> {
     use UciError::UnexpectedCommandTermination;
-    let mut parts = text.split_ascii_whitespace();
-    parts.next().unwrap();
-    parts
-        .next()
-        .map(|s| {
-            assert!(
-                s == "name",
-                "unexpected character after \"setoption\", expected \"name\", got {}",
-                s
-            );
-        })
+    let rest = text
+        .trim()
+        .strip_prefix("setoption")
+        .ok_or_else(|| UciError::InvalidFormat("Expected \"setoption\"".into()))?
+        .trim_start()
+        .strip_prefix("name ")
         .ok_or_else(|| UnexpectedCommandTermination("no name after setoption".into()))?;
-    let opt_name = parts.next().ok_or_else(|| {
-        UnexpectedCommandTermination("no option name given after \"setoption name\"".into())
-    })?;
-    parts.next().map_or_else(|| panic!("no value after \"setoption name {opt_name}\""), |s| assert!(s == "value", "unexpected character after \"setoption name {opt_name}\", expected \"value\", got {}", s));
-    let opt_value = parts.next().ok_or_else(|| {
-        UnexpectedCommandTermination(format!(
-            "no option value given after \"setoption name {opt_name} value\""
-        ))
+    let (opt_name, opt_value) = rest.split_once(" value ").ok_or_else(|| {
+        UnexpectedCommandTermination(format!("no value after \"setoption name {rest}\""))
     })?;
-    let mut config = search::Config::default();
-    match opt_name {
-        "LMRBASE" => config.lmr_base = opt_value.parse()?,
-        "LMRDIVISION" => config.lmr_division = opt_value.parse()?,
-        "FUTILITY_GRADIENT" => config.futility_gradient = opt_value.parse()?,
-        "FUTILITY_INTERCEPT" => config.futility_intercept = opt_value.parse()?,
-        "NULL_MOVE_REDUCTION" => config.null_move_reduction = opt_value.parse()?,
-        _ => eprintln!("ignoring option {}", opt_name),
+    let (opt_name, opt_value) = (opt_name.trim(), opt_value.trim());
+    if !file_options && is_file_option(opt_name) {
+        return Err(UciError::ParseOption(format!("{opt_name} is disabled for this engine")));
     }
-    Ok(config)
+    set_option(opt_name, opt_value, info, config)?;
+    crash::record_option(opt_name, opt_value);
+    Ok(())
+}
+
+/// Sets the option `opt_name` to `opt_value`, in either the search configuration or
+/// the search info, as appropriate.
+pub fn set_option(
+    opt_name: &str,
+    opt_value: &str,
+    info: &mut SearchInfo,
+    config: &mut search::Config,
+) -> Result<(), UciError> {
+    options::set(opt_name, opt_value, info, config)
 }
 
 static KEEP_RUNNING: AtomicBool = AtomicBool::new(true);
@@ -253,14 +358,17 @@ fn stdin_reader() -> mpsc::Receiver<String> {
     let (sender, reciever) = mpsc::channel();
     std::thread::Builder::new()
         .name("stdin-reader".into())
-        .spawn(|| stdin_reader_worker(sender))
+        .spawn(|| input_reader_worker(std::io::stdin().lock(), sender))
         .expect("Couldn't start stdin reader worker thread");
     reciever
 }
 
-fn stdin_reader_worker(sender: mpsc::Sender<String>) {
+/// Sends each non-empty line of `input` down `sender`, then hangs up once
+/// the input ends or can't be read, so the main loop knows to quit.
+fn input_reader_worker(mut input: impl BufRead, sender: mpsc::Sender<String>) {
     let mut linebuf = String::with_capacity(128);
-    while std::io::stdin().read_line(&mut linebuf).is_ok() {
+    // reading nothing means that stdin was closed, which ends the session as quit does.
+    while input.read_line(&mut linebuf).is_ok_and(|read| read != 0) {
         let cmd = linebuf.trim();
         if cmd.is_empty() {
             linebuf.clear();
@@ -277,74 +385,691 @@ fn stdin_reader_worker(sender: mpsc::Sender<String>) {
     std::mem::drop(sender);
 }
 
-pub fn format_score(score: i32, turn: u8) -> String {
+/// The format in which search progress and results are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Plain UCI `info` and `bestmove` lines.
+    #[default]
+    Uci,
+    /// One JSON object per line, for consumption by scripts.
+    Json,
+    /// No output at all, for when the engine is driven internally.
+    Silent,
+}
+
+/// A snapshot of the progress of a search, as reported in an `info` line.
+pub struct SearchReport<'a> {
+    pub score: Score,
+    /// "lowerbound" or "upperbound", if the score isn't exact.
+    pub bound: Option<&'static str>,
+    pub depth: i32,
+    pub seldepth: usize,
+    pub nodes: u64,
+    pub time: u128,
+    pub pv: &'a [Move],
+}
+
+fn format_score_json(score: Score) -> String {
+    if score.is_mate() {
+        let moves_to_mate = (score.plies_to_mate() + 1) / 2;
+        if score > Score::DRAW {
+            format!("{{\"mate\":{moves_to_mate}}}")
+        } else {
+            format!("{{\"mate\":-{moves_to_mate}}}")
+        }
+    } else {
+        format!("{{\"cp\":{}}}", to_centipawns(score.raw()))
+    }
+}
+
+fn format_move_array(moves: impl IntoIterator<Item = Move>) -> String {
+    let moves = moves
+        .into_iter()
+        .map(|m| if m.is_null() { "\"0000\"".to_string() } else { format!("\"{m}\"") })
+        .collect::<Vec<_>>();
+    format!("[{}]", moves.join(","))
+}
+
+/// How a proposed move compares to the best move, from `blundercheck`.
+/// Scores are from the point of view of the side making the move.
+pub struct BlunderCheck {
+    pub m: Move,
+    pub move_score: Score,
+    pub best_move: Move,
+    pub best_score: Score,
+    /// How many centipawns the move loses, unless either score is a mate.
+    pub loss: Option<i32>,
+    /// The principal variation after the move, starting with the reply.
+    pub refutation: Vec<Move>,
+}
+
+/// Writes to stdout with `write`. If whatever reads stdout has closed it, the output is
+/// dropped, so that a GUI going away doesn't make the engine panic, and the main loop
+/// quits once its next flush fails. Other errors panic, as they would with `println!`.
+pub fn write_stdout(write: impl FnOnce(&mut io::StdoutLock<'static>) -> io::Result<()>) {
+    ignore_closed_pipe(write(&mut io::stdout().lock()));
+}
+
+fn ignore_closed_pipe(result: io::Result<()>) {
+    if let Err(e) = result {
+        assert!(e.kind() == io::ErrorKind::BrokenPipe, "failed printing to stdout: {e}");
+    }
+}
+
+pub fn print_search_report(format: OutputFormat, turn: u8, report: &SearchReport) {
+    write_stdout(|out| {
+        match format {
+            OutputFormat::Uci => {
+                write!(out, "info score {}", format_score(report.score, turn))?;
+                if let Some(bound) = report.bound {
+                    write!(out, " {bound}")?;
+                }
+                write!(
+                    out,
+                    " depth {} seldepth {} nodes {} time {} pv",
+                    report.depth, report.seldepth, report.nodes, report.time
+                )?;
+                for m in report.pv {
+                    write!(out, " {m}")?;
+                }
+                writeln!(out)?;
+            }
+            OutputFormat::Silent => (),
+            OutputFormat::Json => {
+                write!(out, "{{\"score\":{}", format_score_json(report.score))?;
+                if let Some(bound) = report.bound {
+                    write!(out, ",\"bound\":\"{bound}\"")?;
+                }
+                writeln!(
+                    out,
+                    ",\"depth\":{},\"seldepth\":{},\"nodes\":{},\"time\":{},\"pv\":{}}}",
+                    report.depth,
+                    report.seldepth,
+                    report.nodes,
+                    report.time,
+                    format_move_array(report.pv.iter().copied())
+                )?;
+            }
+        }
+        Ok(())
+    });
+}
+
+pub fn print_currline(format: OutputFormat, line: impl IntoIterator<Item = Move>) {
+    write_stdout(|out| {
+        match format {
+            OutputFormat::Uci => {
+                write!(out, "info currline")?;
+                for m in line {
+                    if m.is_null() {
+                        write!(out, " 0000")?;
+                    } else {
+                        write!(out, " {m}")?;
+                    }
+                }
+                writeln!(out)?;
+            }
+            OutputFormat::Json => {
+                writeln!(out, "{{\"currline\":{}}}", format_move_array(line))?;
+            }
+            OutputFormat::Silent => (),
+        }
+        Ok(())
+    });
+}
+
+/// Reports a line whose zero-window search failed high against `alpha`,
+/// but whose full-window search scored no better than it.
+pub fn print_scout_discrepancy(
+    format: OutputFormat,
+    line: impl IntoIterator<Item = Move>,
+    alpha: Score,
+    scout: Score,
+    full: Score,
+) {
+    write_stdout(|out| {
+        match format {
+            OutputFormat::Uci => {
+                write!(
+                    out,
+                    "info string scout discrepancy alpha {alpha} scout {scout} full {full} line"
+                )?;
+                for m in line {
+                    if m.is_null() {
+                        write!(out, " 0000")?;
+                    } else {
+                        write!(out, " {m}")?;
+                    }
+                }
+                writeln!(out)?;
+            }
+            OutputFormat::Json => {
+                writeln!(
+                    out,
+                    "{{\"scout_discrepancy\":{{\"alpha\":{alpha},\"scout\":{scout},\"full\":{full},\"line\":{}}}}}",
+                    format_move_array(line)
+                )?;
+            }
+            OutputFormat::Silent => (),
+        }
+        Ok(())
+    });
+}
+
+/// Reports the score of every root move from the last completed iteration, best first,
+/// with the expected result after each move and how it compares to the best.
+pub fn print_root_report(format: OutputFormat, turn: u8, root_moves: &mut [RootMove]) {
+    root_moves.sort_by_key(|rm| std::cmp::Reverse(rm.score));
+    let Some(best) = root_moves.first().map(|rm| rm.score) else {
+        return;
+    };
+    write_stdout(|out| {
+        match format {
+            OutputFormat::Uci => {
+                for rm in root_moves.iter() {
+                    writeln!(
+                        out,
+                        "info string rootmove {} score {} nodes {} expected {:.3} class {}",
+                        rm.m,
+                        format_score(rm.score, turn),
+                        rm.nodes,
+                        rm.expected_score(),
+                        MoveQuality::classify(best, rm.score).as_str()
+                    )?;
+                }
+            }
+            OutputFormat::Json => {
+                let entries = root_moves
+                    .iter()
+                    .map(|rm| {
+                        format!(
+                            "{{\"move\":\"{}\",\"score\":{},\"nodes\":{},\"expected\":{:.3},\"class\":\"{}\"}}",
+                            rm.m,
+                            format_score_json(rm.score),
+                            rm.nodes,
+                            rm.expected_score(),
+                            MoveQuality::classify(best, rm.score).as_str()
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(out, "{{\"rootmoves\":[{}]}}", entries.join(","))?;
+            }
+            OutputFormat::Silent => (),
+        }
+        Ok(())
+    });
+}
+
+pub fn print_blunder_check(format: OutputFormat, turn: u8, check: &BlunderCheck) {
+    let class = MoveQuality::classify(check.best_score, check.move_score).as_str();
+    write_stdout(|out| {
+        match format {
+            OutputFormat::Uci => {
+                write!(
+                    out,
+                    "info string blundercheck move {} score {} best {} bestscore {}",
+                    check.m,
+                    format_score(check.move_score, turn),
+                    check.best_move,
+                    format_score(check.best_score, turn)
+                )?;
+                if let Some(loss) = check.loss {
+                    write!(out, " loss {loss}")?;
+                }
+                write!(out, " class {class} refutation")?;
+                for m in &check.refutation {
+                    write!(out, " {m}")?;
+                }
+                writeln!(out)?;
+            }
+            OutputFormat::Json => {
+                let loss = check.loss.map_or_else(|| "null".to_string(), |loss| loss.to_string());
+                writeln!(
+                    out,
+                    "{{\"blundercheck\":{{\"move\":\"{}\",\"score\":{},\"best\":\"{}\",\"bestscore\":{},\"loss\":{loss},\"class\":\"{class}\",\"refutation\":{}}}}}",
+                    check.m,
+                    format_score_json(check.move_score),
+                    check.best_move,
+                    format_score_json(check.best_score),
+                    format_move_array(check.refutation.iter().copied())
+                )?;
+            }
+            OutputFormat::Silent => (),
+        }
+        Ok(())
+    });
+}
+
+/// Tells the GUI that the engine would resign or accept a draw. Nothing is printed
+/// while the engine would play on.
+pub fn print_verdict(format: OutputFormat, verdict: Verdict) {
+    match (format, verdict) {
+        (_, Verdict::Play) | (OutputFormat::Silent, _) => (),
+        (OutputFormat::Uci, _) => macros::send!("info string verdict {}", verdict.as_str()),
+        (OutputFormat::Json, _) => macros::send!("{{\"verdict\":\"{}\"}}", verdict.as_str()),
+    }
+}
+
+pub fn print_bestmove(format: OutputFormat, m: Move, ponder: Option<Move>) {
+    match (format, ponder) {
+        (OutputFormat::Uci, None) => macros::send!("bestmove {m}"),
+        (OutputFormat::Uci, Some(ponder)) => macros::send!("bestmove {m} ponder {ponder}"),
+        (OutputFormat::Json, None) => macros::send!("{{\"bestmove\":\"{m}\"}}"),
+        (OutputFormat::Json, Some(ponder)) => {
+            macros::send!("{{\"bestmove\":\"{m}\",\"ponder\":\"{ponder}\"}}");
+        }
+        (OutputFormat::Silent, _) => (),
+    }
+}
+
+pub fn format_score(score: Score, turn: u8) -> String {
     assert!(turn == WHITE || turn == BLACK);
-    if is_mate_score(score) {
-        let plies_to_mate = MATE_SCORE - score.abs();
-        let moves_to_mate = (plies_to_mate + 1) / 2;
-        if score > 0 {
+    if score.is_mate() {
+        let moves_to_mate = (score.plies_to_mate() + 1) / 2;
+        if score > Score::DRAW {
             format!("mate {moves_to_mate}")
         } else {
             format!("mate -{moves_to_mate}")
         }
     } else {
-        format!("cp {score}")
+        format!("cp {}", to_centipawns(score.raw()))
     }
 }
 
-pub fn main_loop(evaluation_parameters: Parameters) {
-    println!("id name {NAME}");
-    println!("id author Cosmo");
-    println!("uciok");
+fn is_file_option(name: &str) -> bool {
+    options::FILE_OPTIONS.iter().any(|option| option.eq_ignore_ascii_case(name))
+}
 
-    let mut pos = Board::new();
+fn print_uci_response(file_options: bool) {
+    macros::send!("id name {NAME}");
+    macros::send!("id author Cosmo");
+    for option in options::registry() {
+        if file_options || !is_file_option(option.name) {
+            macros::send!("{option}");
+        }
+    }
+    macros::send!("uciok");
+}
 
-    pos.reset_tables();
+/// The CPU features the engine can make use of that this machine supports.
+fn cpu_features() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut features = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::is_x86_feature_detected!("popcnt") {
+            features.push("popcnt");
+        }
+        if std::is_x86_feature_detected!("lzcnt") {
+            features.push("lzcnt");
+        }
+        if std::is_x86_feature_detected!("bmi1") {
+            features.push("bmi1");
+        }
+        if std::is_x86_feature_detected!("bmi2") {
+            features.push("bmi2");
+        }
+        if std::is_x86_feature_detected!("avx2") {
+            features.push("avx2");
+        }
+    }
+    features
+}
+
+/// Switches to the evaluation parameters in `path`, or the built-in ones for `None`,
+/// and reports which were loaded. Returns false, keeping the old parameters, if the file can't be read.
+fn load_eval_file(pos: &mut Board, path: Option<&str>) -> bool {
+    let params = match path.map(Parameters::from_file).transpose() {
+        Ok(params) => params.unwrap_or_default(),
+        Err(e) => {
+            macros::send!("info string couldn't load evaluation parameters: {e}");
+            return false;
+        }
+    };
+    let fingerprint = params.fingerprint();
+    pos.set_eval_params(params);
+    macros::send!(
+        "info string evaluation parameters: {} ({fingerprint:016x})",
+        path.unwrap_or("built-in")
+    );
+    true
+}
+
+/// Tells testers how this build is set up on their machine, so that
+/// differences in behaviour between machines are easier to explain.
+fn print_startup_info(pos: &Board) {
+    let features = cpu_features();
+    let features = if features.is_empty() {
+        "none detected".to_string()
+    } else {
+        features.join(" ")
+    };
+    macros::send!(
+        "info string cpu features: {features}, running on {} {}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+    macros::send!(
+        "info string hash: {} MB allocated",
+        pos.tt_size_in_bytes() / (1024 * 1024)
+    );
+    macros::send!(
+        "info string threads: 1 search thread, {} logical cores available",
+        num_cpus::get()
+    );
+    macros::send!(
+        "info string evaluation: hand-crafted, parameters {:016x}",
+        pos.eval_params().fingerprint()
+    );
+    macros::send!("info string tablebases: not supported");
+}
+
+/// The position and search state, shared by the UCI thread and the search thread.
+struct Engine {
+    pos: Board,
+    info: SearchInfo,
+}
+
+/// What the search thread has been asked to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Job {
+    Wait,
+    Search,
+    Searching,
+    /// Analysing in the background after a search, which is as good as waiting.
+    Analysing,
+    Quit,
+}
+
+/// Runs searches on a thread of its own, so that the UCI thread is always free
+/// to answer `isready` and to pass on `stop` and `ponderhit`.
+struct SearchThread {
+    engine: Arc<Mutex<Engine>>,
+    control: Arc<SearchControl>,
+    job: Arc<(Mutex<Job>, Condvar)>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+impl SearchThread {
+    fn spawn(mut engine: Engine) -> Self {
+        let control = Arc::new(SearchControl::default());
+        engine.info.control = Some(Arc::clone(&control));
+        let engine = Arc::new(Mutex::new(engine));
+        let job = Arc::new((Mutex::new(Job::Wait), Condvar::new()));
+        let handle = {
+            let (engine, job) = (Arc::clone(&engine), Arc::clone(&job));
+            std::thread::Builder::new()
+                .name("search".into())
+                .spawn(move || search_worker(&engine, &job))
+                .expect("Couldn't start search thread")
+        };
+        Self {
+            engine,
+            control,
+            job,
+            handle,
+        }
+    }
+
+    fn set_job(&self, job: Job) {
+        let (lock, wakeup) = &*self.job;
+        *lock.lock().unwrap() = job;
+        wakeup.notify_all();
+    }
+
+    fn is_searching(&self) -> bool {
+        matches!(*self.job.0.lock().unwrap(), Job::Search | Job::Searching)
+    }
+
+    /// Locks the engine state. A search holds the lock until it finishes,
+    /// so any running search or background analysis is told to stop first.
+    fn engine(&self) -> MutexGuard<'_, Engine> {
+        if matches!(*self.job.0.lock().unwrap(), Job::Search | Job::Searching | Job::Analysing) {
+            self.control.stop();
+        }
+        self.engine.lock().unwrap()
+    }
+
+    /// Starts a search with the limits already set in the engine state.
+    fn go(&self) {
+        self.control.reset();
+        self.set_job(Job::Search);
+    }
+
+    fn ponderhit(&self) {
+        self.control.ponderhit();
+    }
+
+    fn stop(&self) {
+        self.control.stop();
+    }
+
+    /// Stops any search, and waits for the thread to finish.
+    fn quit(self) {
+        self.control.quit();
+        self.set_job(Job::Quit);
+        self.handle.join().expect("search thread panicked");
+    }
+}
+
+fn search_worker(engine: &Mutex<Engine>, job: &(Mutex<Job>, Condvar)) {
+    let (lock, wakeup) = job;
+    loop {
+        {
+            let mut job = wakeup
+                .wait_while(lock.lock().unwrap(), |job| {
+                    matches!(job, Job::Wait | Job::Searching)
+                })
+                .unwrap();
+            if *job == Job::Quit {
+                return;
+            }
+            *job = Job::Searching;
+        }
+        let mut guard = engine.lock().unwrap();
+        let Engine { pos, info } = &mut *guard;
+        if let Err(e) = info.start_trace() {
+            macros::send!("info string couldn't start search trace: {e}");
+        }
+        let (_, best_move) = pos.search_position(info);
+        if let Err(e) = info.finish_trace() {
+            macros::send!("info string couldn't write search trace: {e}");
+        }
+        // if stdout has been closed, the main loop finds out and quits.
+        let _ = std::io::stdout().flush();
+        if info.background_analysis && !info.quit {
+            // any command that needs the engine state stops the analysis
+            // before it takes the lock.
+            let mut job = lock.lock().unwrap();
+            if *job == Job::Searching {
+                *job = Job::Analysing;
+            }
+            drop(job);
+            let control = info.control.clone();
+            // the analysis gets a thread of its own to lower the priority of,
+            // as an unprivileged thread can't raise its priority back again.
+            std::thread::scope(|scope| {
+                std::thread::Builder::new()
+                    .name("analysis".into())
+                    .spawn_scoped(scope, || {
+                        lower_thread_priority();
+                        pos.analyse_after(best_move, control);
+                    })
+                    .expect("Couldn't start analysis thread");
+            });
+        }
+        drop(guard);
+        let mut job = lock.lock().unwrap();
+        if matches!(*job, Job::Searching | Job::Analysing) {
+            *job = Job::Wait;
+        }
+    }
+}
+
+/// Lowers the priority of the calling thread, so that background analysis
+/// gives way to anything else that wants the CPU.
+#[cfg(target_os = "linux")]
+fn lower_thread_priority() {
+    // SAFETY: this only changes how the thread is scheduled. If it fails,
+    // the analysis just runs at the usual priority.
+    unsafe {
+        libc::setpriority(
+            libc::PRIO_PROCESS,
+            libc::gettid().cast_unsigned(),
+            ANALYSIS_NICENESS,
+        );
+    }
+}
+
+/// Thread priorities are only lowered on Linux, so elsewhere analysis runs at the usual priority.
+#[cfg(not(target_os = "linux"))]
+const fn lower_thread_priority() {}
+
+/// Handles `setoption`, and passes on the options that live in the board.
+fn apply_setoption(text: &str, engine: &mut Engine, file_options: bool) -> Result<(), UciError> {
+    let Engine { pos, info } = engine;
+    let mut config = pos.search_params.clone();
+    let hash_megabytes = info.hash_megabytes;
+    let large_pages = info.large_pages;
+    let eval_file = info.eval_file.clone();
+    parse_setoption(text, info, &mut config, file_options)?;
+    pos.set_search_config(config);
+    if info.large_pages != large_pages {
+        pos.set_large_pages(info.large_pages);
+    }
+    match info.hash_megabytes {
+        Some(megabytes) if info.hash_megabytes != hash_megabytes => {
+            let allocated = pos.resize_tt(megabytes);
+            if allocated < megabytes {
+                macros::send!("info string couldn't allocate {megabytes} MB of hash, using {allocated} MB");
+            } else if allocated > megabytes {
+                macros::send!("info string {megabytes} MB of hash is too little for every table, using {allocated} MB");
+            }
+        }
+        _ => (),
+    }
+    if info.eval_file != eval_file && !load_eval_file(pos, info.eval_file.as_deref()) {
+        info.eval_file = eval_file;
+    }
+    if info.variant != pos.variant() {
+        pos.set_variant(info.variant);
+    }
+    Ok(())
+}
+
+/// Runs the UCI loop on stdin and stdout. Without `file_options`, the options
+/// that name files can't be set, for when the client isn't trusted with the filesystem.
+pub fn main_loop(evaluation_parameters: Parameters, search_config: search::Config, file_options: bool) {
+    #![allow(clippy::too_many_lines)]
+    crash::install_hook();
+
+    print_uci_response(file_options);
 
-    let mut info = SearchInfo::default();
+    // start from the starting position, so that a rejected position command
+    // leaves something that can be searched.
+    let mut pos = Board::default();
+
+    pos.reset_tables();
 
     pos.set_eval_params(evaluation_parameters);
+    pos.set_search_config(search_config);
+
+    print_startup_info(&pos);
 
     let stdin = stdin_reader();
 
-    info.set_stdin(&stdin);
+    let search_thread = SearchThread::spawn(Engine {
+        pos,
+        info: SearchInfo::default(),
+    });
 
     loop {
-        std::io::stdout().flush().unwrap();
-        let line = stdin.recv().expect("Couldn't read from stdin");
+        // the GUI has gone away if its end of stdout is closed.
+        if std::io::stdout().flush().is_err() {
+            break;
+        }
+        let Ok(line) = stdin.recv() else {
+            break;
+        };
         let input = line.trim();
 
         let res = match input {
             "\n" => continue,
             "uci" => {
-                println!("id name {NAME}");
-                println!("id author Cosmo");
-                println!("uciok");
+                print_uci_response(file_options);
+                Ok(())
+            }
+            "memory" => {
+                // the engine can only be looked at by stopping the search, which this shouldn't do,
+                // though background analysis is only using the idle time, so it is stopped.
+                if search_thread.is_searching() {
+                    macros::send!("info string memory can't be measured during a search");
+                } else {
+                    // measured before stdout is locked, as the analysis it stops locks it to report.
+                    let usage = search_thread.engine().pos.memory_usage();
+                    macros::send!("info string memory {usage}");
+                }
                 Ok(())
             }
             "isready" => {
-                println!("readyok");
+                macros::send!("readyok");
                 Ok(())
             }
-            "quit" => {
-                info.quit = true;
-                break;
+            "quit" => break,
+            "stop" => {
+                search_thread.stop();
+                Ok(())
+            }
+            "ponderhit" => {
+                search_thread.ponderhit();
+                Ok(())
             }
             "ucinewgame" => {
-                let res = parse_position("position startpos\n", &mut pos);
-                pos.clear_tt();
+                crash::record_position("position startpos");
+                let mut engine = search_thread.engine();
+                let res = parse_position("position startpos\n", &mut engine.pos);
+                engine.pos.clear_tt();
+                engine.pos.reset_tables();
+                engine.info.verdicts.new_game();
                 res
             }
             input if input.starts_with("setoption") => {
-                parse_setoption(input, &mut info).map(|config| {
-                    pos.set_search_config(config);
-                })
+                apply_setoption(input, &mut search_thread.engine(), file_options)
+            }
+            input if input.starts_with("position") => {
+                crash::record_position(input);
+                let mut engine = search_thread.engine();
+                let res = parse_position(input, &mut engine.pos);
+                if engine.info.debug && engine.pos.continues_game() {
+                    macros::send!("info string game continues, keeping the search tables");
+                }
+                drop(engine);
+                res
+            }
+            input if input.starts_with("blundercheck") => {
+                let mut engine = search_thread.engine();
+                let Engine { pos, info } = &mut *engine;
+                let res = parse_blundercheck(input, pos, info.output_format);
+                drop(engine);
+                res
+            }
+            input if input.starts_with("history") => parse_history(input, &search_thread.engine().pos),
+            "debug on" => {
+                search_thread.engine().info.debug = true;
+                Ok(())
+            }
+            "debug off" => {
+                search_thread.engine().info.debug = false;
+                Ok(())
             }
-            input if input.starts_with("position") => parse_position(input, &mut pos),
             input if input.starts_with("go") => {
-                let res = parse_go(input, &mut info, &mut pos);
+                let mut engine = search_thread.engine();
+                let Engine { pos, info } = &mut *engine;
+                crash::record_go(input, pos.fen());
+                let res = parse_go(input, info, pos);
+                drop(engine);
                 if res.is_ok() {
-                    pos.search_position(&mut info);
+                    search_thread.go();
                 }
                 res
             }
@@ -354,11 +1079,97 @@ pub fn main_loop(evaluation_parameters: Parameters) {
         if let Err(e) = res {
             eprintln!("Error: {e}");
         }
-
-        if info.quit {
-            // quit can be set true in parse_go
-            break;
-        }
     }
+    search_thread.quit();
     KEEP_RUNNING.store(false, atomic::Ordering::SeqCst);
+    let _ = std::io::stdout().flush();
+}
+
+mod tests {
+    #[test]
+    fn file_options_can_be_disabled() {
+        use super::parse_setoption;
+        use crate::{search, searchinfo::SearchInfo};
+
+        let mut info = SearchInfo::default();
+        let mut config = search::Config::default();
+        let mut setoption = |text: &str, file_options: bool| {
+            parse_setoption(text, &mut info, &mut config, file_options).is_ok()
+        };
+        assert!(!setoption("setoption name TraceFile value /tmp/trace.jsonl", false));
+        assert!(!setoption("setoption name evalfile value /etc/passwd", false));
+        assert!(setoption("setoption name Contempt value 10", false));
+        assert!(setoption("setoption name TraceFile value <empty>", true));
+        assert_eq!(info.trace_file, None);
+        assert_eq!(info.eval_file, None);
+    }
+
+    #[test]
+    fn closed_pipes_are_ignored() {
+        use super::ignore_closed_pipe;
+        use std::io::{Error, ErrorKind};
+
+        ignore_closed_pipe(Ok(()));
+        ignore_closed_pipe(Err(Error::from(ErrorKind::BrokenPipe)));
+        let other = std::panic::catch_unwind(|| {
+            ignore_closed_pipe(Err(Error::from(ErrorKind::PermissionDenied)));
+        });
+        assert!(other.is_err());
+    }
+
+    #[test]
+    fn input_ends_at_eof() {
+        use super::input_reader_worker;
+        use std::sync::mpsc;
+
+        let (sender, receiver) = mpsc::channel();
+        input_reader_worker(&b"uci\n\n  isready  \nquit"[..], sender);
+        assert_eq!(receiver.recv().unwrap(), "uci");
+        assert_eq!(receiver.recv().unwrap(), "isready");
+        // a last line without a newline still counts.
+        assert_eq!(receiver.recv().unwrap(), "quit");
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn input_ends_when_the_pipe_closes() {
+        use super::input_reader_worker;
+        use std::{
+            io::{BufReader, Write},
+            sync::mpsc,
+        };
+
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let worker = std::thread::spawn(move || input_reader_worker(BufReader::new(reader), sender));
+        writer.write_all(b"go depth 5\n").unwrap();
+        assert_eq!(receiver.recv().unwrap(), "go depth 5");
+        // the GUI crashing closes its end of the pipe.
+        drop(writer);
+        assert!(receiver.recv().is_err());
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn game_continuations_are_detected() {
+        use super::parse_position;
+        use crate::board::Board;
+
+        crate::magic::initialise();
+        let mut pos = Board::new();
+        pos.reset_tables();
+        parse_position("position startpos moves e2e4", &mut pos).ok();
+        assert!(!pos.continues_game());
+        // our move and the reply, on top of the last position.
+        parse_position("position startpos moves e2e4 e7e5 g1f3", &mut pos).ok();
+        assert!(pos.continues_game());
+        // one move on isn't our turn again.
+        parse_position("position startpos moves e2e4 e7e5 g1f3 b8c6", &mut pos).ok();
+        assert!(!pos.continues_game());
+        // nor is a different game.
+        parse_position("position startpos moves d2d4 d7d5", &mut pos).ok();
+        assert!(!pos.continues_game());
+        parse_position("position startpos moves d2d4 d7d5 c2c4 e7e6", &mut pos).ok();
+        assert!(pos.continues_game());
+    }
 }
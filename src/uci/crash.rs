@@ -0,0 +1,135 @@
+// A panic hook that writes out what the engine was doing when it panicked,
+// so that bug reports come with enough context to reproduce the crash.
+
+use std::{panic::PanicHookInfo, sync::Mutex};
+
+use crate::{macros, NAME};
+
+/// The commands that led to the current search.
+pub struct CrashContext {
+    /// The last `position` command.
+    position: String,
+    /// The position being searched, as a FEN.
+    fen: String,
+    /// The last `go` command.
+    go: String,
+    /// Every option that has been set, with its latest value.
+    options: Vec<(String, String)>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext::new());
+
+impl CrashContext {
+    const fn new() -> Self {
+        Self {
+            position: String::new(),
+            fen: String::new(),
+            go: String::new(),
+            options: Vec::new(),
+        }
+    }
+
+    fn report(&self, panic: &str) -> String {
+        use std::fmt::Write;
+        let mut out = format!("{NAME} crash report\npanic: {panic}\n");
+        writeln!(out, "position command: {}", self.position).unwrap();
+        writeln!(out, "fen: {}", self.fen).unwrap();
+        writeln!(out, "go command: {}", self.go).unwrap();
+        out.push_str("options:\n");
+        for (name, value) in &self.options {
+            writeln!(out, "setoption name {name} value {value}").unwrap();
+        }
+        out
+    }
+}
+
+fn with_context(f: impl FnOnce(&mut CrashContext)) {
+    let mut context = CONTEXT
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    f(&mut context);
+}
+
+pub fn record_position(command: &str) {
+    with_context(|context| command.clone_into(&mut context.position));
+}
+
+pub fn record_go(command: &str, fen: String) {
+    with_context(|context| {
+        command.clone_into(&mut context.go);
+        context.fen = fen;
+    });
+}
+
+pub fn record_option(name: &str, value: &str) {
+    with_context(|context| {
+        context
+            .options
+            .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        context.options.push((name.to_string(), value.to_string()));
+    });
+}
+
+/// Installs a panic hook that writes a crash report to a file in the working directory,
+/// and tells the GUI where it is with an `info string`, before the usual panic message.
+pub fn install_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write_report(info);
+        default_hook(info);
+    }));
+}
+
+fn write_report(info: &PanicHookInfo) {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let panic = info.location().map_or_else(
+        || message.clone(),
+        |location| format!("{message} at {location}"),
+    );
+    // the panic may have happened while the context was locked, so don't wait for it.
+    let report = match CONTEXT.try_lock() {
+        Ok(context) => context.report(&panic),
+        Err(std::sync::TryLockError::Poisoned(context)) => context.into_inner().report(&panic),
+        Err(std::sync::TryLockError::WouldBlock) => CrashContext::new().report(&panic),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = format!("virtue-crash-{timestamp}.txt");
+    // assertion messages span several lines, but an info string has to fit on one.
+    let panic = panic.replace('\n', " ");
+    match std::fs::write(&path, &report) {
+        Ok(()) => macros::send!("info string {NAME} panicked: {panic}, crash report written to {path}"),
+        Err(e) => {
+            macros::send!("info string {NAME} panicked: {panic}, couldn't write crash report: {e}");
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn report_contents() {
+        use super::CrashContext;
+
+        let mut context = CrashContext::new();
+        context.position = "position startpos moves e2e4".to_string();
+        context.fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string();
+        context.go = "go depth 5".to_string();
+        context
+            .options
+            .push(("Ponder".to_string(), "true".to_string()));
+        let report = context.report("oops at src/search.rs:1:1");
+        assert!(report.contains("panic: oops at src/search.rs:1:1\n"));
+        assert!(report.contains("position command: position startpos moves e2e4\n"));
+        assert!(
+            report.contains("fen: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n")
+        );
+        assert!(report.contains("go command: go depth 5\n"));
+        assert!(report.contains("setoption name Ponder value true\n"));
+    }
+}
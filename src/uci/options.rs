@@ -0,0 +1,636 @@
+// The registry of UCI options. Each option knows its type, limits and default,
+// so the same table produces the `option name ...` listing and handles `setoption`.
+
+use std::fmt::Display;
+
+use crate::{
+    board::{variant::Variant, DEFAULT_HASH_MEGABYTES},
+    definitions::MAX_DEPTH,
+    search,
+    searchinfo::{Opponent, SearchInfo, MIN_NPS_LIMIT},
+};
+
+use super::{OutputFormat, UciError};
+
+/// The type of an option, as given after `type` in the `option` listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    Check,
+    Spin { min: i64, max: i64 },
+    Combo { vars: &'static [&'static str] },
+    String,
+}
+
+/// A value that has been checked against the type of its option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(&'static str),
+    String(String),
+}
+
+impl OptionValue {
+    fn int<T: TryFrom<i64>>(&self) -> Result<T, UciError> {
+        match *self {
+            Self::Spin(n) => {
+                T::try_from(n).map_err(|_| UciError::ParseOption(format!("{n} is out of range")))
+            }
+            _ => Err(UciError::ParseOption(format!(
+                "expected a number, got {self:?}"
+            ))),
+        }
+    }
+
+    fn check(&self) -> Result<bool, UciError> {
+        match *self {
+            Self::Check(b) => Ok(b),
+            _ => Err(UciError::ParseOption(format!(
+                "expected true or false, got {self:?}"
+            ))),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Self::Combo(s) => s,
+            Self::String(s) => s,
+            Self::Check(true) => "true",
+            Self::Check(false) => "false",
+            Self::Spin(_) => "",
+        }
+    }
+}
+
+/// Applies a new value for an option.
+type OnChange = fn(&OptionValue, &mut SearchInfo, &mut search::Config) -> Result<(), UciError>;
+
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+    pub default: String,
+    pub on_change: OnChange,
+}
+
+impl UciOption {
+    /// Checks `value` against the type and limits of this option.
+    pub fn parse(&self, value: &str) -> Result<OptionValue, UciError> {
+        let name = self.name;
+        match self.kind {
+            OptionKind::Check => match value {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(UciError::ParseOption(format!(
+                    "{name} must be true or false, got \"{value}\""
+                ))),
+            },
+            OptionKind::Spin { min, max } => {
+                let n: i64 = value.parse()?;
+                if (min..=max).contains(&n) {
+                    Ok(OptionValue::Spin(n))
+                } else {
+                    Err(UciError::ParseOption(format!(
+                        "{name} must be between {min} and {max}, got {n}"
+                    )))
+                }
+            }
+            OptionKind::Combo { vars } => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .map(|&var| OptionValue::Combo(var))
+                .ok_or_else(|| {
+                    UciError::ParseOption(format!(
+                        "{name} must be one of {}, got \"{value}\"",
+                        vars.join(", ")
+                    ))
+                }),
+            OptionKind::String => Ok(OptionValue::String(value.to_string())),
+        }
+    }
+}
+
+impl Display for UciOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { name, default, .. } = self;
+        match self.kind {
+            OptionKind::Check => write!(f, "option name {name} type check default {default}"),
+            OptionKind::Spin { min, max } => write!(
+                f,
+                "option name {name} type spin default {default} min {min} max {max}"
+            ),
+            OptionKind::Combo { vars } => {
+                write!(f, "option name {name} type combo default {default}")?;
+                for var in vars {
+                    write!(f, " var {var}")?;
+                }
+                Ok(())
+            }
+            OptionKind::String => write!(f, "option name {name} type string default {default}"),
+        }
+    }
+}
+
+/// An integer search parameter, as a spin option.
+macro_rules! spin {
+    ($defaults:expr, $name:literal, $field:ident, $min:expr, $max:expr) => {
+        UciOption {
+            name: $name,
+            kind: OptionKind::Spin {
+                min: $min,
+                max: $max,
+            },
+            default: $defaults.$field.to_string(),
+            on_change: |value, _, config| {
+                config.$field = value.int()?;
+                Ok(())
+            },
+        }
+    };
+}
+
+/// A fractional search parameter, as a string option that is parsed by the callback.
+macro_rules! fractional {
+    ($defaults:expr, $name:literal, $field:ident) => {
+        UciOption {
+            name: $name,
+            kind: OptionKind::String,
+            default: $defaults.$field.to_string(),
+            on_change: |value, _, config| {
+                config.$field = value.text().parse()?;
+                Ok(())
+            },
+        }
+    };
+}
+
+/// The options that name a file for the engine to read or write, which an
+/// engine run with `--no-file-options` doesn't offer.
+pub const FILE_OPTIONS: [&str; 2] = ["EvalFile", "TraceFile"];
+
+/// Every option the engine supports, in the order they are listed.
+#[allow(clippy::too_many_lines)]
+pub fn registry() -> Vec<UciOption> {
+    let info = SearchInfo::default();
+    let config = search::Config::default();
+    vec![
+        UciOption {
+            name: "Hash",
+            kind: OptionKind::Spin {
+                min: 1,
+                max: 33_554_432,
+            },
+            default: DEFAULT_HASH_MEGABYTES.to_string(),
+            on_change: |value, info, _| {
+                info.hash_megabytes = Some(value.int()?);
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "UseLargePages",
+            kind: OptionKind::Check,
+            default: info.large_pages.to_string(),
+            on_change: |value, info, _| {
+                info.large_pages = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "EvalFile",
+            kind: OptionKind::String,
+            default: "<empty>".to_string(),
+            on_change: |value, info, _| {
+                info.eval_file = match value.text() {
+                    "" | "<empty>" => None,
+                    path => Some(path.to_string()),
+                };
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "Ponder",
+            kind: OptionKind::Check,
+            default: info.ponder.to_string(),
+            on_change: |value, info, _| {
+                info.ponder = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "BackgroundAnalysis",
+            kind: OptionKind::Check,
+            default: info.background_analysis.to_string(),
+            on_change: |value, info, _| {
+                info.background_analysis = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "NodesPerSecond",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 100_000_000,
+            },
+            default: info.nps_limit.to_string(),
+            on_change: |value, info, _| {
+                let nps_limit = value.int()?;
+                if (1..MIN_NPS_LIMIT).contains(&nps_limit) {
+                    return Err(UciError::ParseOption(format!(
+                        "NodesPerSecond must be 0 for no limit, or at least {MIN_NPS_LIMIT}, got {nps_limit}"
+                    )));
+                }
+                info.nps_limit = nps_limit;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "DepthLimit",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: i64::from(MAX_DEPTH.round()),
+            },
+            default: info.depth_limit.to_string(),
+            on_change: |value, info, _| {
+                info.depth_limit = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "TimeOdds",
+            kind: OptionKind::Spin { min: 1, max: 100 },
+            default: info.time_odds.to_string(),
+            on_change: |value, info, _| {
+                info.time_odds = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "Deterministic",
+            kind: OptionKind::Check,
+            default: info.deterministic.to_string(),
+            on_change: |value, info, _| {
+                info.deterministic = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "RootTemperature",
+            kind: OptionKind::Spin { min: 0, max: 1000 },
+            default: info.root_temperature.to_string(),
+            on_change: |value, info, _| {
+                info.root_temperature = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "RootTemperatureMoves",
+            kind: OptionKind::Spin { min: 0, max: 100 },
+            default: info.root_temperature_moves.to_string(),
+            on_change: |value, info, _| {
+                info.root_temperature_moves = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "RootTemperatureMargin",
+            kind: OptionKind::Spin { min: 0, max: 1000 },
+            default: info.root_temperature_margin.to_string(),
+            on_change: |value, info, _| {
+                info.root_temperature_margin = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "RootReport",
+            kind: OptionKind::Check,
+            default: info.root_report.to_string(),
+            on_change: |value, info, _| {
+                info.root_report = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "VerifyScout",
+            kind: OptionKind::Check,
+            default: info.verify_scout.to_string(),
+            on_change: |value, info, _| {
+                info.verify_scout = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "MaxPVLength",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: i64::from(MAX_DEPTH.round()),
+            },
+            default: info.max_pv_length.to_string(),
+            on_change: |value, info, _| {
+                info.max_pv_length = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "UCI_ShowCurrLine",
+            kind: OptionKind::Check,
+            default: info.print_currline.to_string(),
+            on_change: |value, info, _| {
+                info.print_currline = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "OutputFormat",
+            kind: OptionKind::Combo {
+                vars: &["uci", "json"],
+            },
+            default: "uci".to_string(),
+            on_change: |value, info, _| {
+                info.output_format = match value.text() {
+                    "json" => OutputFormat::Json,
+                    _ => OutputFormat::Uci,
+                };
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "TraceFile",
+            kind: OptionKind::String,
+            default: "<empty>".to_string(),
+            on_change: |value, info, _| {
+                info.trace_file = match value.text() {
+                    "" | "<empty>" => None,
+                    path => Some(path.to_string()),
+                };
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "TraceNodeLimit",
+            kind: OptionKind::Spin {
+                min: 1,
+                max: 100_000_000,
+            },
+            default: info.trace_node_limit.to_string(),
+            on_change: |value, info, _| {
+                info.trace_node_limit = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "ResignScore",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 100_000,
+            },
+            default: info.verdicts.resign_score.to_string(),
+            on_change: |value, info, _| {
+                info.verdicts.resign_score = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "ResignMoves",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 1000,
+            },
+            default: info.verdicts.resign_moves.to_string(),
+            on_change: |value, info, _| {
+                info.verdicts.resign_moves = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "DrawScore",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 100_000,
+            },
+            default: info.verdicts.draw_score.to_string(),
+            on_change: |value, info, _| {
+                info.verdicts.draw_score = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "DrawMoves",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 1000,
+            },
+            default: info.verdicts.draw_moves.to_string(),
+            on_change: |value, info, _| {
+                info.verdicts.draw_moves = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "DrawMinMoves",
+            kind: OptionKind::Spin {
+                min: 0,
+                max: 1000,
+            },
+            default: info.verdicts.draw_min_moves.to_string(),
+            on_change: |value, info, _| {
+                info.verdicts.draw_min_moves = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "Contempt",
+            kind: OptionKind::Spin {
+                min: -100,
+                max: 100,
+            },
+            default: info.contempt.to_string(),
+            on_change: |value, info, _| {
+                info.contempt = value.int()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "AutoContempt",
+            kind: OptionKind::Check,
+            default: info.auto_contempt.to_string(),
+            on_change: |value, info, _| {
+                info.auto_contempt = value.check()?;
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "UCI_Variant",
+            kind: OptionKind::Combo {
+                vars: &Variant::NAMES,
+            },
+            default: info.variant.name().to_string(),
+            on_change: |value, info, _| {
+                info.variant = Variant::from_name(value.text()).unwrap_or_default();
+                Ok(())
+            },
+        },
+        UciOption {
+            name: "UCI_Opponent",
+            kind: OptionKind::String,
+            default: "<empty>".to_string(),
+            on_change: |value, info, _| {
+                info.opponent = match value.text() {
+                    "" | "<empty>" => None,
+                    text => Some(Opponent::parse(text).ok_or_else(|| {
+                        UciError::ParseOption(format!("invalid opponent \"{text}\""))
+                    })?),
+                };
+                Ok(())
+            },
+        },
+        fractional!(config, "LMRBASE", lmr_base),
+        fractional!(config, "LMRDIVISION", lmr_division),
+        spin!(config, "FUTILITY_GRADIENT", futility_gradient, 0, 1000),
+        spin!(config, "FUTILITY_INTERCEPT", futility_intercept, 0, 1000),
+        fractional!(config, "NULL_MOVE_REDUCTION", null_move_reduction),
+        fractional!(config, "SINGULARITY_DEPTH", singularity_depth),
+        fractional!(config, "SINGULARITY_DEPTH_MARGIN", singularity_depth_margin),
+        spin!(config, "SINGULARITY_MARGIN", singularity_margin, 0, 100),
+        spin!(
+            config,
+            "DOUBLE_EXTENSION_MARGIN",
+            double_extension_margin,
+            0,
+            1000
+        ),
+        spin!(
+            config,
+            "DOUBLE_EXTENSION_LIMIT",
+            double_extension_limit,
+            0,
+            64
+        ),
+        fractional!(config, "LMP_DEPTH", lmp_depth),
+        spin!(config, "LMP_BASE", lmp_base, 0, 64),
+        fractional!(config, "HISTORY_PRUNING_DEPTH", history_pruning_depth),
+        spin!(
+            config,
+            "HISTORY_PRUNING_MARGIN",
+            history_pruning_margin,
+            -10_000,
+            10_000
+        ),
+        spin!(config, "ASPIRATION_WINDOW", aspiration_window, 1, 1000),
+        fractional!(config, "ASPIRATION_DEPTH", aspiration_depth),
+        fractional!(config, "IIR_DEPTH", iir_depth),
+    ]
+}
+
+/// Sets the option called `name` (ignoring case, as UCI requires) to `value`.
+pub fn set(
+    name: &str,
+    value: &str,
+    info: &mut SearchInfo,
+    config: &mut search::Config,
+) -> Result<(), UciError> {
+    let option = registry()
+        .into_iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| UciError::ParseOption(format!("unknown option \"{name}\"")))?;
+    let value = option.parse(value)?;
+    (option.on_change)(&value, info, config)
+}
+
+mod tests {
+    #[test]
+    fn defaults_are_valid() {
+        use super::{registry, set};
+        use crate::{search, searchinfo::SearchInfo};
+
+        let mut info = SearchInfo::default();
+        let mut config = search::Config::default();
+        for option in registry() {
+            let name = option.name;
+            let default = &option.default;
+            assert!(
+                option.parse(default).is_ok(),
+                "{name} rejects its default {default}"
+            );
+            assert!(set(name, default, &mut info, &mut config).is_ok());
+        }
+        assert_eq!(config.values(), search::Config::default().values());
+    }
+
+    #[test]
+    fn values_are_checked() {
+        use super::set;
+        use crate::{search, searchinfo::SearchInfo, uci::OutputFormat};
+
+        let mut info = SearchInfo::default();
+        let mut config = search::Config::default();
+        assert!(set("lmp_base", "5", &mut info, &mut config).is_ok());
+        assert_eq!(config.lmp_base, 5);
+        assert!(set("LMP_BASE", "-1", &mut info, &mut config).is_err());
+        assert!(set("LMP_BASE", "many", &mut info, &mut config).is_err());
+        assert_eq!(config.lmp_base, 5);
+        assert!(set("Ponder", "yes", &mut info, &mut config).is_err());
+        assert!(set("OutputFormat", "JSON", &mut info, &mut config).is_ok());
+        assert_eq!(info.output_format, OutputFormat::Json);
+        assert!(set("OutputFormat", "xml", &mut info, &mut config).is_err());
+        assert!(set("NoSuchOption", "1", &mut info, &mut config).is_err());
+        assert!(set("NodesPerSecond", "1", &mut info, &mut config).is_err());
+        assert!(set("NodesPerSecond", "1000", &mut info, &mut config).is_ok());
+        assert!(set("NodesPerSecond", "0", &mut info, &mut config).is_ok());
+        assert_eq!(info.nps_limit, 0);
+    }
+
+    #[test]
+    fn opponents_set_the_contempt() {
+        use super::set;
+        use crate::{search, searchinfo::SearchInfo};
+
+        let mut info = SearchInfo::default();
+        let mut config = search::Config::default();
+        let mut set = |name, value, info: &mut SearchInfo| set(name, value, info, &mut config);
+        assert!(set("Contempt", "10", &mut info).is_ok());
+        assert!(set("UCI_Opponent", "none 1800 human Some Player", &mut info).is_ok());
+        let opponent = info.opponent.clone().unwrap();
+        assert_eq!((opponent.title, opponent.rating), (None, Some(1800)));
+        assert!(!opponent.computer);
+        assert_eq!(opponent.name, "Some Player");
+        // without AutoContempt, the rating makes no difference.
+        assert_eq!(info.effective_contempt(), 10);
+        assert!(set("AutoContempt", "true", &mut info).is_ok());
+        assert_eq!(info.effective_contempt(), 50);
+        assert!(set("UCI_Opponent", "GM 2750 computer Engine", &mut info).is_ok());
+        assert_eq!(info.effective_contempt(), 2);
+        // a stronger opponent gets no contempt, and an unrated one the fixed setting.
+        assert!(set("UCI_Opponent", "none 3500 computer Engine", &mut info).is_ok());
+        assert_eq!(info.effective_contempt(), 0);
+        assert!(set("UCI_Opponent", "none none computer Engine", &mut info).is_ok());
+        assert_eq!(info.effective_contempt(), 10);
+        assert!(set("UCI_Opponent", "GM lots human Someone", &mut info).is_err());
+    }
+
+    #[test]
+    fn handicaps_limit_the_search() {
+        use super::{super::parse_go, set};
+        use crate::{board::Board, definitions::Depth, search, searchinfo::SearchInfo};
+
+        crate::magic::initialise();
+        let pos = Board::default();
+        let mut info = SearchInfo::default();
+        let mut config = search::Config::default();
+        assert!(set("DepthLimit", "6", &mut info, &mut config).is_ok());
+        assert!(set("TimeOdds", "4", &mut info, &mut config).is_ok());
+        assert!(set("TimeOdds", "0", &mut info, &mut config).is_err());
+        let window = |info: &SearchInfo| (info.stop_time - info.start_time).as_millis();
+
+        assert!(parse_go("go movetime 1000", &mut info, &pos).is_ok());
+        assert_eq!(window(&info), 250);
+        assert_eq!(info.depth, Depth::new(6));
+        assert!(parse_go("go depth 3", &mut info, &pos).is_ok());
+        assert_eq!(info.depth, Depth::new(3));
+        assert!(parse_go("go depth 20", &mut info, &pos).is_ok());
+        assert_eq!(info.depth, Depth::new(6));
+
+        assert!(set("DepthLimit", "0", &mut info, &mut config).is_ok());
+        assert!(set("TimeOdds", "1", &mut info, &mut config).is_ok());
+        assert!(parse_go("go movetime 1000", &mut info, &pos).is_ok());
+        assert_eq!(window(&info), 1000);
+    }
+}